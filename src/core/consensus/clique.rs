@@ -0,0 +1,660 @@
+use crate::core::crypto;
+use crate::core::types::block::BlockHeader;
+use ethereum_types::{Address, H256, H64, U256};
+use keccak_hash::{keccak, KECCAK_EMPTY_LIST_RLP};
+use rlp::RlpStream;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Size (in bytes) of the vanity prefix of the extra data.
+pub const EXTRA_VANITY: usize = 32;
+/// Size (in bytes) of the signature suffix of the extra data.
+pub const EXTRA_SEAL: usize = 65;
+/// Default number of blocks after which votes are discarded and the signers checkpointed.
+pub const DEFAULT_EPOCH: u64 = 30_000;
+/// Difficulty of a block signed by the in-turn signer.
+pub const DIFF_IN_TURN: u64 = 2;
+/// Difficulty of a block signed by an out-of-turn signer.
+pub const DIFF_NO_TURN: u64 = 1;
+/// Nonce of a header voting to authorize its beneficiary.
+pub const NONCE_AUTH_VOTE: H64 = H64([0xff; 8]);
+/// Nonce of a header voting to deauthorize its beneficiary.
+pub const NONCE_DROP_VOTE: H64 = H64([0x00; 8]);
+
+/// A reason for a header to be invalid under Clique.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CliqueError {
+    /// The extra data is too short to hold the vanity prefix.
+    MissingVanity,
+    /// The extra data is too short to hold the signature.
+    MissingSignature,
+    /// A checkpoint header lists signers that are not a whole number of addresses, or that
+    /// differ from those of the snapshot.
+    InvalidCheckpointSigners,
+    /// A non-checkpoint header lists signers.
+    ExtraSigners,
+    /// A checkpoint header has a non-zero beneficiary.
+    InvalidCheckpointBeneficiary,
+    /// The nonce is neither an authorize nor a deauthorize vote.
+    InvalidVote,
+    /// A checkpoint header votes.
+    InvalidCheckpointVote,
+    /// The mix hash is non-zero.
+    InvalidMixHash,
+    /// The ommers hash is not that of an empty list.
+    InvalidOmmers,
+    /// The difficulty is neither that of an in-turn nor an out-of-turn block.
+    InvalidDifficulty,
+    /// The difficulty does not match the turn of the signer.
+    WrongDifficulty,
+    /// The signature does not recover to an address.
+    InvalidSignature,
+    /// The signer is not authorized.
+    UnauthorizedSigner,
+    /// The signer signed one of the most recent blocks.
+    RecentlySigned,
+    /// The headers do not extend the snapshot contiguously.
+    InvalidVotingChain,
+}
+
+/// Clique configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CliqueConfig {
+    /// Minimum number of seconds between blocks.
+    period: u64,
+    /// Number of blocks after which votes are discarded and the signers checkpointed.
+    epoch: u64,
+}
+
+impl CliqueConfig {
+    /// Returns a new Clique configuration.
+    pub fn new(period: u64, epoch: u64) -> Self {
+        Self { period, epoch }
+    }
+
+    /// Returns the minimum number of seconds between blocks.
+    pub fn period(&self) -> u64 {
+        self.period
+    }
+
+    /// Returns the number of blocks after which votes are discarded.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+}
+
+impl Default for CliqueConfig {
+    fn default() -> Self {
+        Self::new(15, DEFAULT_EPOCH)
+    }
+}
+
+/// Returns the hash of `header` that its signer signs: the hash of the header with the
+/// signature removed from the extra data.
+pub fn seal_hash(header: &BlockHeader) -> H256 {
+    let extra_data = header.extra_data();
+    let unsealed = &extra_data[..extra_data.len().saturating_sub(EXTRA_SEAL)];
+
+    let optional = [
+        header.base_fee_per_gas().map(|fee| rlp::encode(&fee)),
+        header.withdrawals_root().map(rlp::encode),
+        header.blob_gas_used().map(|gas| rlp::encode(&gas)),
+        header.excess_blob_gas().map(|gas| rlp::encode(&gas)),
+        header.parent_beacon_block_root().map(rlp::encode),
+    ];
+    let optional: Vec<_> = optional.iter().flatten().collect();
+
+    let mut s = RlpStream::new_list(15 + optional.len());
+    s.append(header.parent_hash());
+    s.append(header.ommers_hash());
+    s.append(header.beneficiary());
+    s.append(header.state_root());
+    s.append(header.transactions_root());
+    s.append(header.receipts_root());
+    s.append(header.logs_bloom());
+    s.append(&header.difficulty());
+    s.append(&header.number());
+    s.append(&header.gas_limit());
+    s.append(&header.gas_used());
+    s.append(&header.timestamp());
+    s.append(&unsealed);
+    s.append(header.mix_hash());
+    s.append(header.nonce());
+    for item in optional {
+        s.append_raw(item, 1);
+    }
+    keccak(s.out())
+}
+
+/// Returns the address of the signer of `header`, recovered from the signature at the end of
+/// its extra data.
+pub fn recover_signer(header: &BlockHeader) -> Result<Address, CliqueError> {
+    let extra_data = header.extra_data();
+    if extra_data.len() < EXTRA_SEAL {
+        return Err(CliqueError::MissingSignature);
+    }
+    let signature = &extra_data[extra_data.len() - EXTRA_SEAL..];
+
+    let r = U256::from_big_endian(&signature[..32]);
+    let s = U256::from_big_endian(&signature[32..64]);
+    crypto::recover(&seal_hash(header), signature[64], &r, &s).ok_or(CliqueError::InvalidSignature)
+}
+
+/// Returns the signers listed in the extra data of a checkpoint header.
+pub fn checkpoint_signers(header: &BlockHeader) -> Result<Vec<Address>, CliqueError> {
+    let extra_data = header.extra_data();
+    if extra_data.len() < EXTRA_VANITY {
+        return Err(CliqueError::MissingVanity);
+    }
+    if extra_data.len() < EXTRA_VANITY + EXTRA_SEAL {
+        return Err(CliqueError::MissingSignature);
+    }
+
+    let signers = &extra_data[EXTRA_VANITY..extra_data.len() - EXTRA_SEAL];
+    if !signers.len().is_multiple_of(Address::len_bytes()) {
+        return Err(CliqueError::InvalidCheckpointSigners);
+    }
+    Ok(signers
+        .chunks_exact(Address::len_bytes())
+        .map(Address::from_slice)
+        .collect())
+}
+
+/// Checks the fields of `header` that do not depend on the signers.
+pub fn verify_header(header: &BlockHeader, config: &CliqueConfig) -> Result<(), CliqueError> {
+    let checkpoint = (header.number() % config.epoch).is_zero();
+
+    let signers = checkpoint_signers(header)?;
+    if !checkpoint && !signers.is_empty() {
+        return Err(CliqueError::ExtraSigners);
+    }
+
+    if checkpoint && !header.beneficiary().is_zero() {
+        return Err(CliqueError::InvalidCheckpointBeneficiary);
+    }
+    let nonce = header.nonce();
+    if nonce != &NONCE_AUTH_VOTE && nonce != &NONCE_DROP_VOTE {
+        return Err(CliqueError::InvalidVote);
+    }
+    if checkpoint && nonce != &NONCE_DROP_VOTE {
+        return Err(CliqueError::InvalidCheckpointVote);
+    }
+
+    if !header.mix_hash().is_zero() {
+        return Err(CliqueError::InvalidMixHash);
+    }
+    if header.ommers_hash() != &KECCAK_EMPTY_LIST_RLP {
+        return Err(CliqueError::InvalidOmmers);
+    }
+    let difficulty = header.difficulty();
+    if !header.number().is_zero()
+        && difficulty != U256::from(DIFF_IN_TURN)
+        && difficulty != U256::from(DIFF_NO_TURN)
+    {
+        return Err(CliqueError::InvalidDifficulty);
+    }
+
+    Ok(())
+}
+
+/// A vote cast by a signer to authorize or deauthorize an address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Vote {
+    signer: Address,
+    block: U256,
+    address: Address,
+    authorize: bool,
+}
+
+impl Vote {
+    /// Returns the signer that cast the vote.
+    pub fn signer(&self) -> &Address {
+        &self.signer
+    }
+
+    /// Returns the number of the block the vote was cast in.
+    pub fn block(&self) -> U256 {
+        self.block
+    }
+
+    /// Returns the address voted on.
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Returns whether the vote is to authorize the address.
+    pub fn authorize(&self) -> bool {
+        self.authorize
+    }
+}
+
+/// The running tally of votes on an address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Tally {
+    authorize: bool,
+    votes: usize,
+}
+
+/// The state of the authorized signers as of a block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    number: U256,
+    hash: H256,
+    signers: BTreeSet<Address>,
+    /// Signers of recent blocks, by block number, who may not sign again yet.
+    recents: BTreeMap<U256, Address>,
+    /// Votes cast since the last checkpoint, in order.
+    votes: Vec<Vote>,
+    tally: BTreeMap<Address, Tally>,
+}
+
+impl Snapshot {
+    /// Returns a snapshot with `signers` as of the block numbered `number` with `hash`.
+    pub fn new<I>(number: U256, hash: H256, signers: I) -> Self
+    where
+        I: IntoIterator<Item = Address>,
+    {
+        Self {
+            number,
+            hash,
+            signers: signers.into_iter().collect(),
+            recents: BTreeMap::new(),
+            votes: Vec::new(),
+            tally: BTreeMap::new(),
+        }
+    }
+
+    /// Returns a snapshot as of the checkpoint `header`, with the signers it lists.
+    pub fn from_checkpoint(header: &BlockHeader) -> Result<Self, CliqueError> {
+        Ok(Self::new(
+            header.number(),
+            header.hash(),
+            checkpoint_signers(header)?,
+        ))
+    }
+
+    /// Returns the number of the block of the snapshot.
+    pub fn number(&self) -> U256 {
+        self.number
+    }
+
+    /// Returns the hash of the block of the snapshot.
+    pub fn hash(&self) -> &H256 {
+        &self.hash
+    }
+
+    /// Returns the authorized signers.
+    pub fn signers(&self) -> &BTreeSet<Address> {
+        &self.signers
+    }
+
+    /// Returns the votes cast since the last checkpoint.
+    pub fn votes(&self) -> &Vec<Vote> {
+        &self.votes
+    }
+
+    /// Returns whether `signer` is in turn to sign the block numbered `number`. Signers take
+    /// turns in ascending order of address.
+    pub fn in_turn(&self, number: U256, signer: &Address) -> bool {
+        match self.signers.iter().position(|s| s == signer) {
+            Some(offset) => (number % self.signers.len()).low_u64() as usize == offset,
+            None => false,
+        }
+    }
+
+    /// Returns the number of consecutive blocks within which a signer may sign only once.
+    fn signer_limit(&self) -> usize {
+        self.signers.len() / 2 + 1
+    }
+
+    /// Checks that `header` extends the snapshot and is signed by an authorized signer in turn
+    /// with its difficulty. Returns the signer.
+    pub fn verify_seal(&self, header: &BlockHeader) -> Result<Address, CliqueError> {
+        let signer = recover_signer(header)?;
+        if !self.signers.contains(&signer) {
+            return Err(CliqueError::UnauthorizedSigner);
+        }
+
+        let number = header.number();
+        let limit = U256::from(self.signer_limit());
+        for (&recent, recent_signer) in &self.recents {
+            if recent_signer == &signer && (number < limit || recent > number - limit) {
+                return Err(CliqueError::RecentlySigned);
+            }
+        }
+
+        let expected = if self.in_turn(number, &signer) {
+            DIFF_IN_TURN
+        } else {
+            DIFF_NO_TURN
+        };
+        if header.difficulty() != U256::from(expected) {
+            return Err(CliqueError::WrongDifficulty);
+        }
+
+        Ok(signer)
+    }
+
+    /// Returns the snapshot following `headers`, which must extend the snapshot in order.
+    pub fn apply(
+        &self,
+        headers: &[BlockHeader],
+        config: &CliqueConfig,
+    ) -> Result<Self, CliqueError> {
+        let mut snapshot = self.clone();
+
+        for header in headers {
+            let number = header.number();
+            if number != snapshot.number + 1 {
+                return Err(CliqueError::InvalidVotingChain);
+            }
+
+            // Votes are discarded at each checkpoint, which lists the signers.
+            if (number % config.epoch).is_zero() {
+                let signers = checkpoint_signers(header)?;
+                if signers.len() != snapshot.signers.len()
+                    || signers
+                        .iter()
+                        .any(|signer| !snapshot.signers.contains(signer))
+                {
+                    return Err(CliqueError::InvalidCheckpointSigners);
+                }
+                snapshot.votes.clear();
+                snapshot.tally.clear();
+            }
+
+            // The oldest recent signer may sign again.
+            let limit = U256::from(snapshot.signer_limit());
+            if number >= limit {
+                snapshot.recents.remove(&(number - limit));
+            }
+
+            let signer = recover_signer(header)?;
+            if !snapshot.signers.contains(&signer) {
+                return Err(CliqueError::UnauthorizedSigner);
+            }
+            if snapshot.recents.values().any(|recent| recent == &signer) {
+                return Err(CliqueError::RecentlySigned);
+            }
+            snapshot.recents.insert(number, signer);
+
+            // A new vote by the signer on the same address replaces the old one.
+            let beneficiary = *header.beneficiary();
+            if let Some(i) = snapshot
+                .votes
+                .iter()
+                .position(|vote| vote.signer == signer && vote.address == beneficiary)
+            {
+                let vote = snapshot.votes.remove(i);
+                snapshot.uncast(&vote.address, vote.authorize);
+            }
+
+            let authorize = match header.nonce() {
+                nonce if nonce == &NONCE_AUTH_VOTE => true,
+                nonce if nonce == &NONCE_DROP_VOTE => false,
+                _ => return Err(CliqueError::InvalidVote),
+            };
+            if !beneficiary.is_zero() && snapshot.cast(beneficiary, authorize) {
+                snapshot.votes.push(Vote {
+                    signer,
+                    block: number,
+                    address: beneficiary,
+                    authorize,
+                });
+            }
+
+            // A majority of signers passes the vote.
+            if let Some(tally) = snapshot.tally.get(&beneficiary).copied() {
+                if tally.votes > snapshot.signers.len() / 2 {
+                    snapshot.pass(beneficiary, tally.authorize, number);
+                }
+            }
+
+            snapshot.number = number;
+            snapshot.hash = header.hash();
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Adds a vote on `address` to the tally. Returns `false` if the vote is meaningless:
+    /// authorizing a signer or deauthorizing a non-signer.
+    fn cast(&mut self, address: Address, authorize: bool) -> bool {
+        if self.signers.contains(&address) == authorize {
+            return false;
+        }
+        match self.tally.get_mut(&address) {
+            Some(tally) if tally.authorize != authorize => return false,
+            Some(tally) => tally.votes += 1,
+            None => {
+                self.tally.insert(
+                    address,
+                    Tally {
+                        authorize,
+                        votes: 1,
+                    },
+                );
+            }
+        }
+        true
+    }
+
+    /// Removes a vote on `address` from the tally.
+    fn uncast(&mut self, address: &Address, authorize: bool) {
+        if let Some(tally) = self.tally.get_mut(address) {
+            if tally.authorize == authorize {
+                tally.votes -= 1;
+                if tally.votes == 0 {
+                    self.tally.remove(address);
+                }
+            }
+        }
+    }
+
+    /// Authorizes or deauthorizes `address` following a passed vote in the block numbered
+    /// `number`.
+    fn pass(&mut self, address: Address, authorize: bool, number: U256) {
+        if authorize {
+            self.signers.insert(address);
+        } else {
+            self.signers.remove(&address);
+
+            // The signer limit shrinks, freeing the oldest recent signer.
+            let limit = U256::from(self.signer_limit());
+            if number >= limit {
+                self.recents.remove(&(number - limit));
+            }
+
+            // Votes cast by the deauthorized signer are discarded.
+            let cast: Vec<_> = self
+                .votes
+                .iter()
+                .filter(|vote| vote.signer == address)
+                .cloned()
+                .collect();
+            for vote in cast {
+                self.uncast(&vote.address, vote.authorize);
+            }
+            self.votes.retain(|vote| vote.signer != address);
+        }
+
+        self.votes.retain(|vote| vote.address != address);
+        self.tally.remove(&address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret(i: u8) -> H256 {
+        H256::repeat_byte(i)
+    }
+
+    fn address(i: u8) -> Address {
+        crypto::secret_to_address(&secret(i)).unwrap()
+    }
+
+    /// Returns a header numbered `number` voting on `beneficiary`, signed with `secret(signer)`.
+    fn signed(
+        number: u64,
+        signer: u8,
+        beneficiary: Address,
+        nonce: H64,
+        in_turn: bool,
+    ) -> BlockHeader {
+        let mut header = BlockHeader::default();
+        header.set_number(U256::from(number));
+        header.set_ommers_hash(KECCAK_EMPTY_LIST_RLP);
+        header.set_beneficiary(beneficiary);
+        header.set_nonce(nonce);
+        let difficulty = if in_turn { DIFF_IN_TURN } else { DIFF_NO_TURN };
+        header.set_difficulty(U256::from(difficulty));
+        header.set_extra_data(vec![0; EXTRA_VANITY + EXTRA_SEAL]);
+        seal(&mut header, signer);
+        header
+    }
+
+    fn seal(header: &mut BlockHeader, signer: u8) {
+        let (v, r, s) = crypto::sign(&seal_hash(header), &secret(signer)).unwrap();
+        let mut extra_data = header.extra_data().clone();
+        let len = extra_data.len();
+        let signature = &mut extra_data[len - EXTRA_SEAL..];
+        r.to_big_endian(&mut signature[..32]);
+        s.to_big_endian(&mut signature[32..64]);
+        signature[64] = v;
+        header.set_extra_data(extra_data);
+    }
+
+    #[test]
+    fn test_recover_signer() {
+        let header = signed(1, 1, Address::zero(), NONCE_DROP_VOTE, true);
+        assert_eq!(recover_signer(&header), Ok(address(1)));
+
+        // The seal hash does not commit to the signature.
+        let mut unsealed = header.clone();
+        unsealed.set_extra_data(vec![0; EXTRA_VANITY + EXTRA_SEAL]);
+        assert_eq!(seal_hash(&unsealed), seal_hash(&header));
+
+        let mut short = header;
+        short.set_extra_data(vec![0; EXTRA_SEAL - 1]);
+        assert_eq!(recover_signer(&short), Err(CliqueError::MissingSignature));
+    }
+
+    #[test]
+    fn test_verify_header() {
+        let config = CliqueConfig::new(15, 10);
+        let header = signed(1, 1, Address::repeat_byte(0xaa), NONCE_AUTH_VOTE, true);
+        assert_eq!(verify_header(&header, &config), Ok(()));
+
+        let mut invalid = header.clone();
+        invalid.set_nonce(H64::repeat_byte(0x01));
+        assert_eq!(
+            verify_header(&invalid, &config),
+            Err(CliqueError::InvalidVote)
+        );
+
+        let mut invalid = header.clone();
+        invalid.set_difficulty(U256::from(3));
+        assert_eq!(
+            verify_header(&invalid, &config),
+            Err(CliqueError::InvalidDifficulty)
+        );
+
+        let mut invalid = header;
+        invalid.set_extra_data(vec![0; EXTRA_VANITY + 20 + EXTRA_SEAL]);
+        assert_eq!(
+            verify_header(&invalid, &config),
+            Err(CliqueError::ExtraSigners)
+        );
+
+        // Checkpoints list the signers and do not vote.
+        let mut checkpoint = signed(10, 1, Address::zero(), NONCE_DROP_VOTE, true);
+        let mut extra_data = vec![0; EXTRA_VANITY];
+        extra_data.extend_from_slice(address(1).as_bytes());
+        extra_data.extend_from_slice(&[0; EXTRA_SEAL]);
+        checkpoint.set_extra_data(extra_data);
+        assert_eq!(verify_header(&checkpoint, &config), Ok(()));
+        assert_eq!(checkpoint_signers(&checkpoint), Ok(vec![address(1)]));
+
+        checkpoint.set_beneficiary(Address::repeat_byte(0xaa));
+        assert_eq!(
+            verify_header(&checkpoint, &config),
+            Err(CliqueError::InvalidCheckpointBeneficiary)
+        );
+    }
+
+    #[test]
+    fn test_verify_seal() {
+        let signers = [address(1), address(2)];
+        let snapshot = Snapshot::new(U256::zero(), H256::zero(), signers.iter().copied());
+        let in_turn =
+            |number: u64, signer: u8| snapshot.in_turn(U256::from(number), &address(signer));
+
+        let signer = if in_turn(1, 1) { 1 } else { 2 };
+        let header = signed(1, signer, Address::zero(), NONCE_DROP_VOTE, true);
+        assert_eq!(snapshot.verify_seal(&header), Ok(address(signer)));
+
+        let header = signed(1, signer, Address::zero(), NONCE_DROP_VOTE, false);
+        assert_eq!(
+            snapshot.verify_seal(&header),
+            Err(CliqueError::WrongDifficulty)
+        );
+
+        let header = signed(1, 3, Address::zero(), NONCE_DROP_VOTE, false);
+        assert_eq!(
+            snapshot.verify_seal(&header),
+            Err(CliqueError::UnauthorizedSigner)
+        );
+    }
+
+    #[test]
+    fn test_apply_votes() {
+        let config = CliqueConfig::new(15, 100);
+        let snapshot = Snapshot::new(U256::zero(), H256::zero(), vec![address(1), address(2)]);
+
+        // A single vote of two signers does not pass.
+        let headers = vec![signed(1, 1, address(3), NONCE_AUTH_VOTE, false)];
+        let next = snapshot.apply(&headers, &config).unwrap();
+        assert_eq!(next.signers().len(), 2);
+        assert_eq!(next.votes().len(), 1);
+        assert_eq!(next.number(), U256::one());
+
+        // A second vote passes, and the votes on the new signer are discarded.
+        let headers = vec![
+            signed(1, 1, address(3), NONCE_AUTH_VOTE, false),
+            signed(2, 2, address(3), NONCE_AUTH_VOTE, false),
+        ];
+        let next = snapshot.apply(&headers, &config).unwrap();
+        assert!(next.signers().contains(&address(3)));
+        assert!(next.votes().is_empty());
+
+        // Two of three signers deauthorize the third.
+        let headers = vec![
+            signed(3, 1, address(3), NONCE_DROP_VOTE, false),
+            signed(4, 2, address(3), NONCE_DROP_VOTE, false),
+        ];
+        let last = next.apply(&headers, &config).unwrap();
+        assert_eq!(last.signers().iter().copied().collect::<Vec<_>>(), {
+            let mut signers = vec![address(1), address(2)];
+            signers.sort();
+            signers
+        });
+
+        // A signer may not sign consecutive blocks of two signers.
+        let headers = vec![
+            signed(1, 1, Address::zero(), NONCE_DROP_VOTE, false),
+            signed(2, 1, Address::zero(), NONCE_DROP_VOTE, false),
+        ];
+        assert_eq!(
+            snapshot.apply(&headers, &config),
+            Err(CliqueError::RecentlySigned)
+        );
+
+        // Headers must extend the snapshot.
+        let headers = vec![signed(2, 1, Address::zero(), NONCE_DROP_VOTE, false)];
+        assert_eq!(
+            snapshot.apply(&headers, &config),
+            Err(CliqueError::InvalidVotingChain)
+        );
+    }
+}
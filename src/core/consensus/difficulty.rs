@@ -0,0 +1,237 @@
+use super::header::HeaderError;
+use crate::core::fork::Fork;
+use crate::core::types::block::BlockHeader;
+use ethereum_types::U256;
+use keccak_hash::KECCAK_EMPTY_LIST_RLP;
+
+/// Minimum difficulty of a proof-of-work block.
+pub const MIN_DIFFICULTY: u64 = 131_072;
+/// Bound on the change in difficulty between blocks: the difficulty is adjusted by multiples of
+/// one over this value of the parent's.
+pub const DIFFICULTY_BOUND_DIVISOR: u64 = 2048;
+/// Number of blocks per period of the difficulty bomb, which doubles each period.
+pub const EXP_DIFFICULTY_PERIOD: u64 = 100_000;
+
+/// Returns the number of blocks by which `fork` delays the difficulty bomb.
+pub fn bomb_delay(fork: Fork) -> u64 {
+    match fork {
+        Fork::Frontier
+        | Fork::Homestead
+        | Fork::Dao
+        | Fork::TangerineWhistle
+        | Fork::SpuriousDragon => 0,
+        Fork::Byzantium => 3_000_000,
+        Fork::Constantinople | Fork::Petersburg | Fork::Istanbul => 5_000_000,
+        Fork::MuirGlacier | Fork::Berlin => 9_000_000,
+        Fork::London => 9_700_000,
+        Fork::ArrowGlacier => 10_700_000,
+        Fork::GrayGlacier | Fork::Paris | Fork::Shanghai | Fork::Cancun | Fork::Prague => {
+            11_400_000
+        }
+    }
+}
+
+/// Returns the difficulty of a child of `parent` with `timestamp` under the rules of `fork`.
+/// This is zero since the merge. Fails if the difficulty or the block number overflows.
+pub fn calculate_difficulty(
+    parent: &BlockHeader,
+    timestamp: U256,
+    fork: Fork,
+) -> Result<U256, HeaderError> {
+    if fork >= Fork::Paris {
+        return Ok(U256::zero());
+    }
+
+    let parent_difficulty = parent.difficulty();
+    let step = parent_difficulty / DIFFICULTY_BOUND_DIVISOR;
+    let elapsed = timestamp.saturating_sub(parent.timestamp());
+
+    // The adjustment is a multiple of `step`, positive when blocks come quickly and negative
+    // when they come slowly, bounded below by -99 (EIP-2, EIP-100).
+    let mut difficulty = if fork >= Fork::Byzantium {
+        let base = if parent.ommers_hash() == &KECCAK_EMPTY_LIST_RLP {
+            1
+        } else {
+            2
+        };
+        adjust(parent_difficulty, step, base, elapsed / 9)?
+    } else if fork >= Fork::Homestead {
+        adjust(parent_difficulty, step, 1, elapsed / 10)?
+    } else if elapsed < U256::from(13) {
+        parent_difficulty
+            .checked_add(step)
+            .ok_or(HeaderError::Overflow)?
+    } else {
+        parent_difficulty - step
+    };
+    difficulty = difficulty.max(U256::from(MIN_DIFFICULTY));
+
+    let number = parent
+        .number()
+        .checked_add(U256::one())
+        .ok_or(HeaderError::Overflow)?
+        .saturating_sub(U256::from(bomb_delay(fork)));
+    let period = number / EXP_DIFFICULTY_PERIOD;
+    if period > U256::one() {
+        difficulty = difficulty.saturating_add(U256::one() << (period.low_u64() as usize - 2));
+    }
+
+    Ok(difficulty)
+}
+
+/// Returns `difficulty` adjusted by `max(base - slowness, -99)` multiples of `step`. Fails if
+/// the difficulty overflows.
+fn adjust(difficulty: U256, step: U256, base: u64, slowness: U256) -> Result<U256, HeaderError> {
+    if slowness <= U256::from(base) {
+        step.checked_mul(U256::from(base - slowness.low_u64()))
+            .and_then(|increase| difficulty.checked_add(increase))
+            .ok_or(HeaderError::Overflow)
+    } else {
+        let factor = (slowness - base).min(U256::from(99));
+        Ok(difficulty.saturating_sub(step * factor))
+    }
+}
+
+/// Checks that the difficulty of `header` follows from its `parent` under the rules of `fork`.
+pub fn validate_difficulty(
+    header: &BlockHeader,
+    parent: &BlockHeader,
+    fork: Fork,
+) -> Result<(), HeaderError> {
+    if header.difficulty() != calculate_difficulty(parent, header.timestamp(), fork)? {
+        return Err(HeaderError::DifficultyMismatch);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(number: u64, difficulty: u64) -> BlockHeader {
+        let mut header = BlockHeader::default();
+        header.set_number(U256::from(number));
+        header.set_timestamp(U256::from(1_000_000));
+        header.set_difficulty(U256::from(difficulty));
+        header.set_ommers_hash(KECCAK_EMPTY_LIST_RLP);
+        header
+    }
+
+    #[test]
+    fn test_frontier() {
+        let parent = header(1000, 2_048_000);
+        let at = |elapsed: u64| parent.timestamp() + elapsed;
+        assert_eq!(
+            calculate_difficulty(&parent, at(12), Fork::Frontier),
+            Ok(U256::from(2_049_000))
+        );
+        assert_eq!(
+            calculate_difficulty(&parent, at(13), Fork::Frontier),
+            Ok(U256::from(2_047_000))
+        );
+    }
+
+    #[test]
+    fn test_homestead() {
+        let parent = header(1000, 2_048_000);
+        let at = |elapsed: u64| parent.timestamp() + elapsed;
+        assert_eq!(
+            calculate_difficulty(&parent, at(9), Fork::Homestead),
+            Ok(U256::from(2_049_000))
+        );
+        assert_eq!(
+            calculate_difficulty(&parent, at(15), Fork::Homestead),
+            Ok(U256::from(2_048_000))
+        );
+        assert_eq!(
+            calculate_difficulty(&parent, at(25), Fork::Homestead),
+            Ok(U256::from(2_047_000))
+        );
+        // The adjustment is bounded below by -99 steps.
+        assert_eq!(
+            calculate_difficulty(&parent, at(10_000), Fork::Homestead),
+            Ok(U256::from(2_048_000 - 99 * 1000))
+        );
+    }
+
+    #[test]
+    fn test_byzantium_ommers() {
+        let mut parent = header(1000, 2_048_000);
+        let timestamp = parent.timestamp() + 9;
+        assert_eq!(
+            calculate_difficulty(&parent, timestamp, Fork::Byzantium),
+            Ok(U256::from(2_048_000))
+        );
+
+        parent.set_ommers_hash(Default::default());
+        assert_eq!(
+            calculate_difficulty(&parent, timestamp, Fork::Byzantium),
+            Ok(U256::from(2_049_000))
+        );
+    }
+
+    #[test]
+    fn test_minimum() {
+        let parent = header(1000, MIN_DIFFICULTY);
+        let timestamp = parent.timestamp() + 100;
+        assert_eq!(
+            calculate_difficulty(&parent, timestamp, Fork::Homestead),
+            Ok(U256::from(MIN_DIFFICULTY))
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut parent = header(1000, 0);
+        parent.set_difficulty(U256::MAX);
+        let timestamp = parent.timestamp() + 1;
+        for fork in [Fork::Frontier, Fork::Homestead, Fork::Byzantium] {
+            assert_eq!(
+                calculate_difficulty(&parent, timestamp, fork),
+                Err(HeaderError::Overflow)
+            );
+        }
+
+        let mut last = header(0, 2_048_000);
+        last.set_number(U256::MAX);
+        assert_eq!(
+            calculate_difficulty(&last, timestamp, Fork::Homestead),
+            Err(HeaderError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_bomb() {
+        // Block 3,000,000 falls in period 30, adding 2 ** 28.
+        let parent = header(2_999_999, 2_048_000);
+        let timestamp = parent.timestamp() + 15;
+        assert_eq!(
+            calculate_difficulty(&parent, timestamp, Fork::Homestead),
+            Ok(U256::from(2_048_000 + (1 << 28)))
+        );
+
+        // Byzantium delays the bomb by 3,000,000 blocks, back to period 0.
+        assert_eq!(
+            calculate_difficulty(&parent, timestamp, Fork::Byzantium),
+            Ok(U256::from(2_048_000))
+        );
+
+        // Gray Glacier pushes mainnet block 15,050,000 back to period 36, adding 2 ** 34.
+        let parent = header(15_049_999, 2_048_000);
+        let timestamp = parent.timestamp() + 9;
+        assert_eq!(
+            calculate_difficulty(&parent, timestamp, Fork::GrayGlacier),
+            Ok(U256::from(2_048_000u64 + (1 << 34)))
+        );
+    }
+
+    #[test]
+    fn test_paris() {
+        let parent = header(1000, 2_048_000);
+        let timestamp = parent.timestamp() + 12;
+        assert_eq!(
+            calculate_difficulty(&parent, timestamp, Fork::Paris),
+            Ok(U256::zero())
+        );
+    }
+}
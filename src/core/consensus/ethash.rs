@@ -0,0 +1,508 @@
+use crate::core::types::block::BlockHeader;
+use ethereum_types::{H256, H64, U256, U512};
+use keccak_hash::keccak;
+use rlp::RlpStream;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tiny_keccak::{Hasher, Keccak};
+
+/// Number of blocks per epoch. The cache and dataset are regenerated each epoch.
+pub const EPOCH_LENGTH: u64 = 30_000;
+/// Size (in bytes) of the cache of the first epoch.
+pub const CACHE_BYTES_INIT: u64 = 1 << 24;
+/// Growth (in bytes) of the cache per epoch.
+pub const CACHE_BYTES_GROWTH: u64 = 1 << 17;
+/// Size (in bytes) of the dataset of the first epoch.
+pub const DATASET_BYTES_INIT: u64 = 1 << 30;
+/// Growth (in bytes) of the dataset per epoch.
+pub const DATASET_BYTES_GROWTH: u64 = 1 << 23;
+
+/// Size (in bytes) of a cache or dataset item.
+const HASH_BYTES: u64 = 64;
+/// Size (in bytes) of the mix.
+const MIX_BYTES: u64 = 128;
+/// Number of cache items each dataset item is derived from.
+const DATASET_PARENTS: u32 = 256;
+/// Number of rounds of the memory-hard cache generation.
+const CACHE_ROUNDS: usize = 3;
+/// Number of dataset accesses per hashimoto run.
+const ACCESSES: u32 = 64;
+const FNV_PRIME: u32 = 0x0100_0193;
+/// Number of in-memory caches kept by `Ethash`, which evicts the least recently used.
+const MAX_CACHES: usize = 3;
+
+/// A cache or dataset item of sixteen little-endian words.
+type Node = [u32; 16];
+
+/// A reason for a proof-of-work seal to be invalid.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SealError {
+    /// The mix hash does not match the one computed from the nonce.
+    InvalidMixHash,
+    /// The proof-of-work result exceeds the target set by the difficulty.
+    InsufficientWork,
+    /// The difficulty is zero, which sets no target.
+    ZeroDifficulty,
+}
+
+/// Returns the epoch of the block numbered `number`.
+pub fn epoch(number: u64) -> u64 {
+    number / EPOCH_LENGTH
+}
+
+/// Returns the seed hash of `epoch`: the zero hash hashed once per epoch.
+pub fn seed_hash(epoch: u64) -> H256 {
+    (0..epoch).fold(H256::zero(), |seed, _| keccak(seed))
+}
+
+/// Returns the size (in bytes) of the cache of `epoch`: the largest size below the linear
+/// growth bound whose number of items is prime.
+pub fn cache_size(epoch: u64) -> u64 {
+    let mut size = CACHE_BYTES_INIT + CACHE_BYTES_GROWTH * epoch - HASH_BYTES;
+    while !is_prime(size / HASH_BYTES) {
+        size -= 2 * HASH_BYTES;
+    }
+    size
+}
+
+/// Returns the size (in bytes) of the dataset of `epoch`: the largest size below the linear
+/// growth bound whose number of mixes is prime.
+pub fn dataset_size(epoch: u64) -> u64 {
+    let mut size = DATASET_BYTES_INIT + DATASET_BYTES_GROWTH * epoch - MIX_BYTES;
+    while !is_prime(size / MIX_BYTES) {
+        size -= 2 * MIX_BYTES;
+    }
+    size
+}
+
+fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    let mut i = 2;
+    while i * i <= n {
+        if n.is_multiple_of(i) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Returns the hash of `header` that the seal commits to: the hash of the header without its
+/// mix hash and nonce.
+pub fn seal_hash(header: &BlockHeader) -> H256 {
+    let base_fee = header.base_fee_per_gas();
+    let mut s = RlpStream::new_list(13 + base_fee.is_some() as usize);
+    s.append(header.parent_hash());
+    s.append(header.ommers_hash());
+    s.append(header.beneficiary());
+    s.append(header.state_root());
+    s.append(header.transactions_root());
+    s.append(header.receipts_root());
+    s.append(header.logs_bloom());
+    s.append(&header.difficulty());
+    s.append(&header.number());
+    s.append(&header.gas_limit());
+    s.append(&header.gas_used());
+    s.append(&header.timestamp());
+    s.append(header.extra_data());
+    if let Some(base_fee) = base_fee {
+        s.append(&base_fee);
+    }
+    keccak(s.out())
+}
+
+fn keccak512(data: &[u8]) -> [u8; 64] {
+    let mut keccak = Keccak::v512();
+    let mut output = [0u8; 64];
+    keccak.update(data);
+    keccak.finalize(&mut output);
+    output
+}
+
+fn fnv(a: u32, b: u32) -> u32 {
+    a.wrapping_mul(FNV_PRIME) ^ b
+}
+
+fn node_from_bytes(bytes: &[u8]) -> Node {
+    let mut node = [0u32; 16];
+    for (word, chunk) in node.iter_mut().zip(bytes.chunks_exact(4)) {
+        *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    node
+}
+
+fn node_to_bytes(node: &Node) -> [u8; 64] {
+    let mut bytes = [0u8; 64];
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(node.iter()) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    bytes
+}
+
+fn hash_node(node: &Node) -> Node {
+    node_from_bytes(&keccak512(&node_to_bytes(node)))
+}
+
+/// Returns the `n` items of the cache generated from `seed`.
+fn generate_nodes(seed: &H256, n: usize) -> Vec<Node> {
+    let mut nodes = Vec::with_capacity(n);
+    nodes.push(node_from_bytes(&keccak512(seed.as_bytes())));
+    for i in 1..n {
+        nodes.push(hash_node(&nodes[i - 1]));
+    }
+
+    for _ in 0..CACHE_ROUNDS {
+        for i in 0..n {
+            let v = nodes[i][0] as usize % n;
+            let mut node = nodes[(i + n - 1) % n];
+            for (word, other) in node.iter_mut().zip(nodes[v].iter()) {
+                *word ^= other;
+            }
+            nodes[i] = hash_node(&node);
+        }
+    }
+
+    nodes
+}
+
+/// The cache of an epoch, from which the items of the dataset are derived.
+///
+/// Verifying a seal needs only the cache, which is a few dozen megabytes, rather than the
+/// dataset, which is a few gigabytes.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    epoch: u64,
+    nodes: Vec<Node>,
+}
+
+impl Cache {
+    /// Generates the cache of `epoch`.
+    pub fn generate(epoch: u64) -> Self {
+        let n = (cache_size(epoch) / HASH_BYTES) as usize;
+        Self {
+            epoch,
+            nodes: generate_nodes(&seed_hash(epoch), n),
+        }
+    }
+
+    /// Loads the cache of `epoch` from `dir`, generating and storing it if it is missing or
+    /// malformed.
+    pub fn load_or_generate(dir: &Path, epoch: u64) -> io::Result<Self> {
+        let path = Self::path(dir, epoch);
+        if let Ok(bytes) = fs::read(&path) {
+            if bytes.len() as u64 == cache_size(epoch) {
+                let nodes = bytes.chunks_exact(64).map(node_from_bytes).collect();
+                return Ok(Self { epoch, nodes });
+            }
+        }
+
+        let cache = Self::generate(epoch);
+        fs::create_dir_all(dir)?;
+        let bytes: Vec<u8> = cache.nodes.iter().flat_map(node_to_bytes).collect();
+        // Write to a temporary file first so that a concurrent reader never sees a partial
+        // cache.
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, &path)?;
+        Ok(cache)
+    }
+
+    /// Returns the path of the file holding the cache of `epoch` in `dir`.
+    fn path(dir: &Path, epoch: u64) -> PathBuf {
+        let seed = seed_hash(epoch);
+        dir.join(format!("cache-R23-{:x}", H64::from_slice(&seed[..8])))
+    }
+
+    /// Returns the epoch of the cache.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the size (in bytes) of the cache.
+    pub fn size(&self) -> usize {
+        self.nodes.len() * HASH_BYTES as usize
+    }
+
+    /// Returns the dataset item at `index`.
+    fn dataset_item(&self, index: u32) -> Node {
+        let n = self.nodes.len();
+        let mut mix = self.nodes[index as usize % n];
+        mix[0] ^= index;
+        mix = hash_node(&mix);
+
+        for j in 0..DATASET_PARENTS {
+            let parent = fnv(index ^ j, mix[j as usize % 16]) as usize % n;
+            for (word, other) in mix.iter_mut().zip(self.nodes[parent].iter()) {
+                *word = fnv(*word, *other);
+            }
+        }
+
+        hash_node(&mix)
+    }
+
+    /// Runs hashimoto over the dataset of the epoch, deriving the dataset items from the cache
+    /// as needed. Returns the mix hash and the proof-of-work result for the header with
+    /// `seal_hash` and `nonce`.
+    pub fn hashimoto_light(&self, seal_hash: &H256, nonce: &H64) -> (H256, H256) {
+        let mixes = (dataset_size(self.epoch) / MIX_BYTES) as u32;
+        self.hashimoto(seal_hash, nonce, mixes)
+    }
+
+    /// Runs hashimoto over a dataset of `mixes` mixes derived from the cache.
+    fn hashimoto(&self, seal_hash: &H256, nonce: &H64, mixes: u32) -> (H256, H256) {
+        let mut seed_input = [0u8; 40];
+        seed_input[..32].copy_from_slice(seal_hash.as_bytes());
+        let mut nonce_le = nonce.to_fixed_bytes();
+        nonce_le.reverse();
+        seed_input[32..].copy_from_slice(&nonce_le);
+        let seed = keccak512(&seed_input);
+        let seed_words = node_from_bytes(&seed);
+
+        let mut mix = [0u32; 32];
+        mix[..16].copy_from_slice(&seed_words);
+        mix[16..].copy_from_slice(&seed_words);
+
+        for i in 0..ACCESSES {
+            let p = fnv(i ^ seed_words[0], mix[i as usize % 32]) % mixes * 2;
+            let mut data = [0u32; 32];
+            data[..16].copy_from_slice(&self.dataset_item(p));
+            data[16..].copy_from_slice(&self.dataset_item(p + 1));
+            for (word, other) in mix.iter_mut().zip(data.iter()) {
+                *word = fnv(*word, *other);
+            }
+        }
+
+        let mut compressed = [0u8; 32];
+        for (i, chunk) in mix.chunks_exact(4).enumerate() {
+            let word = fnv(fnv(fnv(chunk[0], chunk[1]), chunk[2]), chunk[3]);
+            compressed[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+
+        let mut result_input = [0u8; 96];
+        result_input[..64].copy_from_slice(&seed);
+        result_input[64..].copy_from_slice(&compressed);
+        (H256(compressed), keccak(&result_input[..]))
+    }
+}
+
+/// Verifies Ethash proof-of-work seals, keeping the caches of recent epochs in memory and,
+/// optionally, on disk.
+#[derive(Debug, Default)]
+pub struct Ethash {
+    cache_dir: Option<PathBuf>,
+    /// The caches in memory, the most recently used last.
+    caches: Vec<Cache>,
+}
+
+impl Ethash {
+    /// Returns a verifier that keeps caches in memory only.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns a verifier that also stores caches in `dir`, so that they are generated once.
+    pub fn with_cache_dir(dir: PathBuf) -> Self {
+        Self {
+            cache_dir: Some(dir),
+            caches: Vec::new(),
+        }
+    }
+
+    /// Returns the cache of `epoch`, loading or generating it if needed.
+    pub fn cache(&mut self, epoch: u64) -> &Cache {
+        let cache = match self.caches.iter().position(|cache| cache.epoch == epoch) {
+            Some(index) => self.caches.remove(index),
+            // The directory only saves regeneration, so fall back to memory if it fails.
+            None => match &self.cache_dir {
+                Some(dir) => {
+                    Cache::load_or_generate(dir, epoch).unwrap_or_else(|_| Cache::generate(epoch))
+                }
+                None => Cache::generate(epoch),
+            },
+        };
+        if self.caches.len() == MAX_CACHES {
+            self.caches.remove(0);
+        }
+        self.caches.push(cache);
+        self.caches.last().expect("caches are not empty")
+    }
+
+    /// Verifies the mix hash and nonce of `header` against its difficulty.
+    pub fn verify_seal(&mut self, header: &BlockHeader) -> Result<(), SealError> {
+        let epoch = epoch(header.number().low_u64());
+        let mixes = (dataset_size(epoch) / MIX_BYTES) as u32;
+        verify_seal_with(self.cache(epoch), mixes, header)
+    }
+}
+
+/// Verifies the seal of `header` against a dataset of `mixes` mixes derived from `cache`.
+fn verify_seal_with(cache: &Cache, mixes: u32, header: &BlockHeader) -> Result<(), SealError> {
+    let difficulty = header.difficulty();
+    if difficulty.is_zero() {
+        return Err(SealError::ZeroDifficulty);
+    }
+
+    let (mix_hash, result) = cache.hashimoto(&seal_hash(header), header.nonce(), mixes);
+    if &mix_hash != header.mix_hash() {
+        return Err(SealError::InvalidMixHash);
+    }
+
+    // The result, as a number, must not exceed 2 ** 256 / difficulty, which any result is
+    // within for a difficulty of one.
+    if difficulty > U256::one() {
+        let target = U512::one() << 256;
+        let target =
+            U256::try_from(target / U512::from(difficulty)).expect("difficulty exceeds one");
+        if U256::from_big_endian(result.as_bytes()) > target {
+            return Err(SealError::InsufficientWork);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_sizes() {
+        assert_eq!(cache_size(0), 16_776_896);
+        assert_eq!(cache_size(1), 16_907_456);
+        assert_eq!(dataset_size(0), 1_073_739_904);
+        assert_eq!(dataset_size(1), 1_082_130_304);
+    }
+
+    #[test]
+    fn test_seed_hash() {
+        assert_eq!(seed_hash(0), H256::zero());
+        assert_eq!(
+            seed_hash(1),
+            H256::from_str("290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e563")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_hashimoto() {
+        // A cache of 1 KiB and a dataset of 32 KiB for the seed of the first epoch.
+        let cache = Cache {
+            epoch: 0,
+            nodes: generate_nodes(&seed_hash(0), 1024 / HASH_BYTES as usize),
+        };
+        let mixes = (32 * 1024 / MIX_BYTES) as u32;
+        let hash =
+            H256::from_str("c9149cc0386e689d789a1c2f3d5d169a61a6218ed30e74414dc736e442ef3d1f")
+                .unwrap();
+        let (mix_hash, result) = cache.hashimoto(&hash, &H64::zero(), mixes);
+        assert_eq!(
+            mix_hash,
+            H256::from_str("e4073cffaef931d37117cefd9afd27ea0f1cad6a981dd2605c4a1ac97c519800")
+                .unwrap()
+        );
+        assert_eq!(
+            result,
+            H256::from_str("d3539235ee2e6f8db665c0a72169f55b7f6c605712330b778ec3944f0eb5a557")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_verify_seal_with() {
+        let cache = Cache {
+            epoch: 0,
+            nodes: generate_nodes(&seed_hash(0), 1024 / HASH_BYTES as usize),
+        };
+        let mixes = (32 * 1024 / MIX_BYTES) as u32;
+        let mut header = BlockHeader::default();
+        header.set_difficulty(U256::one());
+        let (mix_hash, _) = cache.hashimoto(&seal_hash(&header), header.nonce(), mixes);
+        header.set_mix_hash(mix_hash);
+        assert_eq!(verify_seal_with(&cache, mixes, &header), Ok(()));
+
+        // A target of one, which no result meets in practice.
+        header.set_difficulty(U256::MAX);
+        let (mix_hash, _) = cache.hashimoto(&seal_hash(&header), header.nonce(), mixes);
+        header.set_mix_hash(mix_hash);
+        assert_eq!(
+            verify_seal_with(&cache, mixes, &header),
+            Err(SealError::InsufficientWork)
+        );
+
+        header.set_mix_hash(H256::zero());
+        assert_eq!(
+            verify_seal_with(&cache, mixes, &header),
+            Err(SealError::InvalidMixHash)
+        );
+
+        header.set_difficulty(U256::zero());
+        assert_eq!(
+            verify_seal_with(&cache, mixes, &header),
+            Err(SealError::ZeroDifficulty)
+        );
+    }
+
+    fn mainnet_block_1() -> BlockHeader {
+        let mut header = BlockHeader::default();
+        header.set_parent_hash(
+            H256::from_str("d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3")
+                .unwrap(),
+        );
+        header.set_ommers_hash(keccak_hash::KECCAK_EMPTY_LIST_RLP);
+        header.set_beneficiary(
+            ethereum_types::Address::from_str("05a56e2d52c817161883f50c441c3228cfe54d9f").unwrap(),
+        );
+        header.set_state_root(
+            H256::from_str("d67e4d450343046425ae4271474353857ab860dbc0a1dde64b41b5cd3a532bf3")
+                .unwrap(),
+        );
+        header.set_transactions_root(keccak_hash::KECCAK_NULL_RLP);
+        header.set_receipts_root(keccak_hash::KECCAK_NULL_RLP);
+        header.set_difficulty(U256::from(17_171_480_576u64));
+        header.set_number(U256::one());
+        header.set_gas_limit(U256::from(5000));
+        header.set_timestamp(U256::from(1_438_269_988));
+        header.set_extra_data(
+            hex::decode("476574682f76312e302e302f6c696e75782f676f312e342e32").unwrap(),
+        );
+        header.set_mix_hash(
+            H256::from_str("969b900de27b6ac6a67742365dd65f55a0526c41fd18e1b16f1a1215c2e66f59")
+                .unwrap(),
+        );
+        header.set_nonce(H64::from_str("539bd4979fef1ec4").unwrap());
+        header
+    }
+
+    #[test]
+    #[ignore = "generates the epoch 0 cache, which takes a minute in debug builds"]
+    fn test_verify_seal() {
+        let header = mainnet_block_1();
+        assert_eq!(
+            header.hash(),
+            H256::from_str("88e96d4537bea4d9c05d12549907b32561d3bf31f45aae734cdc119f13406cb6")
+                .unwrap()
+        );
+
+        let dir = std::env::temp_dir().join(format!("ethash-test-{}", std::process::id()));
+        let mut ethash = Ethash::with_cache_dir(dir.clone());
+        assert_eq!(ethash.verify_seal(&header), Ok(()));
+
+        let mut invalid = header.clone();
+        invalid.set_nonce(H64::from_low_u64_be(1));
+        assert_eq!(ethash.verify_seal(&invalid), Err(SealError::InvalidMixHash));
+
+        // The stored cache is loaded rather than regenerated.
+        let cache = Cache::load_or_generate(&dir, 0).unwrap();
+        assert_eq!(cache.size() as u64, cache_size(0));
+        assert_eq!(
+            cache.hashimoto_light(&seal_hash(&header), header.nonce()).0,
+            *header.mix_hash()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,426 @@
+use crate::core::fork::Fork;
+use crate::core::spec::BlobParams;
+use crate::core::types::block::{BlockHeader, GAS_PER_BLOB};
+use ethereum_types::{H64, U256, U512};
+use keccak_hash::KECCAK_EMPTY_LIST_RLP;
+use std::convert::TryFrom;
+
+/// Maximum size of the extra data of a header.
+pub const MAX_EXTRA_DATA_SIZE: usize = 32;
+/// Minimum gas limit of a block.
+pub const MIN_GAS_LIMIT: u64 = 5000;
+/// Maximum gas limit of a block, the largest signed 64-bit integer.
+pub const MAX_GAS_LIMIT: u64 = i64::MAX as u64;
+/// Bound on the change in gas limit between blocks: the gas limit must differ from that of the
+/// parent by less than one over this value of the parent's.
+pub const GAS_LIMIT_BOUND_DIVISOR: u64 = 1024;
+/// Bound on the change in base fee between blocks (EIP-1559).
+pub const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
+/// Ratio of the gas limit of a block to the gas it targets (EIP-1559).
+pub const ELASTICITY_MULTIPLIER: u64 = 2;
+/// Base fee (in Wei) of the first London block (EIP-1559).
+pub const INITIAL_BASE_FEE: u64 = 1_000_000_000;
+
+/// A reason for a header to be invalid given its parent.
+#[derive(Debug, PartialEq, Eq)]
+pub enum HeaderError {
+    /// The number is not one more than that of the parent.
+    NumberMismatch,
+    /// The parent hash is not the hash of the parent.
+    ParentHashMismatch,
+    /// The timestamp is not greater than that of the parent.
+    TimestampNotIncreasing,
+    /// The gas limit is less than `MIN_GAS_LIMIT`.
+    GasLimitTooLow,
+    /// The gas limit is more than `MAX_GAS_LIMIT`.
+    GasLimitTooHigh,
+    /// The gas limit changed too much from that of the parent.
+    GasLimitOutOfBounds,
+    /// The gas used is more than the gas limit.
+    GasUsedExceedsLimit,
+    /// The extra data is longer than `MAX_EXTRA_DATA_SIZE`.
+    ExtraDataTooLong,
+    /// A field required by the fork is missing.
+    MissingField,
+    /// A field introduced by a later fork is present.
+    UnexpectedField,
+    /// The header has blob fields, but no blob parameters are in effect (EIP-4844).
+    MissingBlobParams,
+    /// The base fee does not follow from the parent (EIP-1559).
+    BaseFeeMismatch,
+    /// The blob gas used exceeds the maximum or is not a multiple of the blob size (EIP-4844).
+    InvalidBlobGasUsed,
+    /// The excess blob gas does not follow from the parent (EIP-4844).
+    ExcessBlobGasMismatch,
+    /// The difficulty does not follow from the parent.
+    DifficultyMismatch,
+    /// The difficulty is non-zero after the merge.
+    NonZeroDifficulty,
+    /// The ommers hash is not that of an empty list after the merge.
+    NonEmptyOmmers,
+    /// The nonce is non-zero after the merge.
+    NonZeroNonce,
+    /// A value following from the parent overflows.
+    Overflow,
+}
+
+/// Checks `header` against its `parent` under the rules of `fork`, with the blob parameters
+/// in effect, if any.
+///
+/// The proof-of-work or proof-of-authority seal and the difficulty of pre-merge headers are
+/// checked separately, the latter by `difficulty::validate_difficulty` for proof-of-work.
+pub fn validate_header(
+    header: &BlockHeader,
+    parent: &BlockHeader,
+    fork: Fork,
+    blob_params: Option<BlobParams>,
+) -> Result<(), HeaderError> {
+    validate_fields(header, fork)?;
+
+    if parent.number().checked_add(U256::one()) != Some(header.number()) {
+        return Err(HeaderError::NumberMismatch);
+    }
+    if header.parent_hash() != &parent.hash() {
+        return Err(HeaderError::ParentHashMismatch);
+    }
+    if header.timestamp() <= parent.timestamp() {
+        return Err(HeaderError::TimestampNotIncreasing);
+    }
+    if header.extra_data().len() > MAX_EXTRA_DATA_SIZE {
+        return Err(HeaderError::ExtraDataTooLong);
+    }
+    if header.gas_used() > header.gas_limit() {
+        return Err(HeaderError::GasUsedExceedsLimit);
+    }
+
+    // The gas target doubles at the London transition, and the gas limit with it.
+    let mut parent_gas_limit = parent.gas_limit();
+    if fork >= Fork::London && parent.base_fee_per_gas().is_none() {
+        parent_gas_limit = parent_gas_limit
+            .checked_mul(U256::from(ELASTICITY_MULTIPLIER))
+            .ok_or(HeaderError::Overflow)?;
+    }
+    validate_gas_limit(header.gas_limit(), parent_gas_limit)?;
+
+    if let Some(base_fee) = header.base_fee_per_gas() {
+        if base_fee != calculate_base_fee(parent)? {
+            return Err(HeaderError::BaseFeeMismatch);
+        }
+    }
+
+    if let (Some(blob_gas_used), Some(excess_blob_gas)) =
+        (header.blob_gas_used(), header.excess_blob_gas())
+    {
+        let params = blob_params.ok_or(HeaderError::MissingBlobParams)?;
+        if blob_gas_used > U256::from(params.max_blob_gas())
+            || !(blob_gas_used % GAS_PER_BLOB).is_zero()
+        {
+            return Err(HeaderError::InvalidBlobGasUsed);
+        }
+        if excess_blob_gas != calculate_excess_blob_gas(parent, &params)? {
+            return Err(HeaderError::ExcessBlobGasMismatch);
+        }
+    }
+
+    if fork >= Fork::Paris {
+        if !header.difficulty().is_zero() {
+            return Err(HeaderError::NonZeroDifficulty);
+        }
+        if header.ommers_hash() != &KECCAK_EMPTY_LIST_RLP {
+            return Err(HeaderError::NonEmptyOmmers);
+        }
+        if header.nonce() != &H64::zero() {
+            return Err(HeaderError::NonZeroNonce);
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that `header` has exactly the optional fields introduced up to `fork`.
+fn validate_fields(header: &BlockHeader, fork: Fork) -> Result<(), HeaderError> {
+    let fields = [
+        (header.base_fee_per_gas().is_some(), Fork::London),
+        (header.withdrawals_root().is_some(), Fork::Shanghai),
+        (header.blob_gas_used().is_some(), Fork::Cancun),
+        (header.excess_blob_gas().is_some(), Fork::Cancun),
+        (header.parent_beacon_block_root().is_some(), Fork::Cancun),
+    ];
+
+    for (present, introduced) in fields {
+        match (present, fork >= introduced) {
+            (false, true) => return Err(HeaderError::MissingField),
+            (true, false) => return Err(HeaderError::UnexpectedField),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that `gas_limit` is within bounds of the gas limit of the parent.
+fn validate_gas_limit(gas_limit: U256, parent_gas_limit: U256) -> Result<(), HeaderError> {
+    if gas_limit < U256::from(MIN_GAS_LIMIT) {
+        return Err(HeaderError::GasLimitTooLow);
+    }
+    if gas_limit > U256::from(MAX_GAS_LIMIT) {
+        return Err(HeaderError::GasLimitTooHigh);
+    }
+
+    let max_change = parent_gas_limit / GAS_LIMIT_BOUND_DIVISOR;
+    let change = if gas_limit > parent_gas_limit {
+        gas_limit - parent_gas_limit
+    } else {
+        parent_gas_limit - gas_limit
+    };
+    if change >= max_change {
+        return Err(HeaderError::GasLimitOutOfBounds);
+    }
+
+    Ok(())
+}
+
+/// Returns the base fee (in Wei) of the child of `parent` (EIP-1559).
+///
+/// The base fee rises when the parent used more than its gas target, and falls when it used
+/// less, by at most one eighth. Fails if the base fee overflows.
+pub fn calculate_base_fee(parent: &BlockHeader) -> Result<U256, HeaderError> {
+    let parent_base_fee = match parent.base_fee_per_gas() {
+        Some(base_fee) => base_fee,
+        None => return Ok(U256::from(INITIAL_BASE_FEE)),
+    };
+
+    let gas_target = parent.gas_limit() / ELASTICITY_MULTIPLIER;
+    let gas_used = parent.gas_used();
+    if gas_target.is_zero() || gas_used == gas_target {
+        return Ok(parent_base_fee);
+    }
+
+    let delta = |gas_difference: U256| {
+        let delta = parent_base_fee.full_mul(gas_difference)
+            / U512::from(gas_target)
+            / U512::from(BASE_FEE_MAX_CHANGE_DENOMINATOR);
+        U256::try_from(delta).map_err(|_| HeaderError::Overflow)
+    };
+    if gas_used > gas_target {
+        let delta = delta(gas_used - gas_target)?.max(U256::one());
+        parent_base_fee
+            .checked_add(delta)
+            .ok_or(HeaderError::Overflow)
+    } else {
+        Ok(parent_base_fee.saturating_sub(delta(gas_target - gas_used)?))
+    }
+}
+
+/// Returns the excess blob gas of the child of `parent` under `params` (EIP-4844). This is zero
+/// for the child of a block that predates EIP-4844. Fails if the sum of the excess and used blob
+/// gas of the parent overflows.
+pub fn calculate_excess_blob_gas(
+    parent: &BlockHeader,
+    params: &BlobParams,
+) -> Result<U256, HeaderError> {
+    let total = parent
+        .excess_blob_gas()
+        .unwrap_or_default()
+        .checked_add(parent.blob_gas_used().unwrap_or_default())
+        .ok_or(HeaderError::Overflow)?;
+    Ok(total.saturating_sub(U256::from(params.target_blob_gas())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parent() -> BlockHeader {
+        let mut parent = BlockHeader::default();
+        parent.set_number(U256::from(100));
+        parent.set_timestamp(U256::from(1000));
+        parent.set_gas_limit(U256::from(30_000_000));
+        parent.set_gas_used(U256::from(15_000_000));
+        parent.set_base_fee_per_gas(Some(U256::from(INITIAL_BASE_FEE)));
+        parent.set_ommers_hash(KECCAK_EMPTY_LIST_RLP);
+        parent
+    }
+
+    fn child(parent: &BlockHeader) -> BlockHeader {
+        let mut header = parent.clone();
+        header.set_parent_hash(parent.hash());
+        header.set_number(parent.number() + 1);
+        header.set_timestamp(parent.timestamp() + 12);
+        header.set_gas_used(U256::zero());
+        header.set_base_fee_per_gas(Some(calculate_base_fee(parent).unwrap()));
+        header
+    }
+
+    #[test]
+    fn test_calculate_base_fee() {
+        let mut parent = parent();
+        assert_eq!(
+            calculate_base_fee(&parent),
+            Ok(U256::from(INITIAL_BASE_FEE))
+        );
+
+        parent.set_gas_used(U256::from(30_000_000));
+        assert_eq!(calculate_base_fee(&parent), Ok(U256::from(1_125_000_000)));
+
+        parent.set_gas_used(U256::zero());
+        assert_eq!(calculate_base_fee(&parent), Ok(U256::from(875_000_000)));
+
+        parent.set_base_fee_per_gas(Some(U256::from(7)));
+        parent.set_gas_used(U256::from(15_000_001));
+        assert_eq!(calculate_base_fee(&parent), Ok(U256::from(8)));
+
+        parent.set_base_fee_per_gas(Some(U256::MAX - 1));
+        parent.set_gas_used(parent.gas_limit());
+        assert_eq!(calculate_base_fee(&parent), Err(HeaderError::Overflow));
+    }
+
+    #[test]
+    fn test_calculate_excess_blob_gas() {
+        let (cancun, prague) = (BlobParams::cancun(), BlobParams::prague());
+        let mut parent = parent();
+        assert_eq!(
+            calculate_excess_blob_gas(&parent, &cancun),
+            Ok(U256::zero())
+        );
+
+        parent.set_excess_blob_gas(Some(U256::from(3 * GAS_PER_BLOB)));
+        parent.set_blob_gas_used(Some(U256::from(2 * GAS_PER_BLOB)));
+        assert_eq!(
+            calculate_excess_blob_gas(&parent, &cancun),
+            Ok(U256::from(2 * GAS_PER_BLOB))
+        );
+        // Prague targets six blobs rather than three (EIP-7691).
+        assert_eq!(
+            calculate_excess_blob_gas(&parent, &prague),
+            Ok(U256::zero())
+        );
+
+        parent.set_excess_blob_gas(Some(U256::MAX));
+        assert_eq!(
+            calculate_excess_blob_gas(&parent, &cancun),
+            Err(HeaderError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_validate_header() {
+        let parent = parent();
+        let header = child(&parent);
+        assert_eq!(validate_header(&header, &parent, Fork::Paris, None), Ok(()));
+
+        let mut invalid = header.clone();
+        invalid.set_parent_hash(Default::default());
+        assert_eq!(
+            validate_header(&invalid, &parent, Fork::Paris, None),
+            Err(HeaderError::ParentHashMismatch)
+        );
+
+        let mut invalid = header.clone();
+        invalid.set_timestamp(parent.timestamp());
+        assert_eq!(
+            validate_header(&invalid, &parent, Fork::Paris, None),
+            Err(HeaderError::TimestampNotIncreasing)
+        );
+
+        let mut invalid = header.clone();
+        invalid.set_gas_limit(parent.gas_limit() + parent.gas_limit() / 1024);
+        assert_eq!(
+            validate_header(&invalid, &parent, Fork::Paris, None),
+            Err(HeaderError::GasLimitOutOfBounds)
+        );
+
+        let mut parent_at_limit = parent.clone();
+        parent_at_limit.set_gas_limit(U256::from(MAX_GAS_LIMIT));
+        let mut invalid = child(&parent_at_limit);
+        invalid.set_gas_limit(U256::from(MAX_GAS_LIMIT) + 1);
+        assert_eq!(
+            validate_header(&invalid, &parent_at_limit, Fork::Paris, None),
+            Err(HeaderError::GasLimitTooHigh)
+        );
+
+        let mut last = parent.clone();
+        last.set_number(U256::MAX);
+        let mut invalid = child(&parent);
+        invalid.set_parent_hash(last.hash());
+        assert_eq!(
+            validate_header(&invalid, &last, Fork::Paris, None),
+            Err(HeaderError::NumberMismatch)
+        );
+
+        let mut invalid = header.clone();
+        invalid.set_base_fee_per_gas(Some(U256::one()));
+        assert_eq!(
+            validate_header(&invalid, &parent, Fork::Paris, None),
+            Err(HeaderError::BaseFeeMismatch)
+        );
+
+        let mut invalid = header.clone();
+        invalid.set_difficulty(U256::one());
+        assert_eq!(
+            validate_header(&invalid, &parent, Fork::Paris, None),
+            Err(HeaderError::NonZeroDifficulty)
+        );
+
+        assert_eq!(
+            validate_header(&header, &parent, Fork::Shanghai, None),
+            Err(HeaderError::MissingField)
+        );
+        assert_eq!(
+            validate_header(&header, &parent, Fork::Berlin, None),
+            Err(HeaderError::UnexpectedField)
+        );
+    }
+
+    #[test]
+    fn test_validate_london_transition() {
+        let mut parent = parent();
+        parent.set_base_fee_per_gas(None);
+        parent.set_gas_limit(U256::from(15_000_000));
+
+        let mut header = child(&parent);
+        header.set_gas_limit(U256::from(30_000_000));
+        assert_eq!(
+            header.base_fee_per_gas(),
+            Some(U256::from(INITIAL_BASE_FEE))
+        );
+        assert_eq!(
+            validate_header(&header, &parent, Fork::London, None),
+            Ok(())
+        );
+
+        parent.set_gas_limit(U256::MAX);
+        let header = child(&parent);
+        assert_eq!(
+            validate_header(&header, &parent, Fork::London, None),
+            Err(HeaderError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_validate_blob_gas() {
+        let mut parent = parent();
+        parent.set_withdrawals_root(Some(Default::default()));
+        parent.set_blob_gas_used(Some(U256::zero()));
+        parent.set_excess_blob_gas(Some(U256::zero()));
+        parent.set_parent_beacon_block_root(Some(Default::default()));
+
+        // Nine blobs are within the maximum of Prague, but not of Cancun.
+        let mut header = child(&parent);
+        header.set_blob_gas_used(Some(U256::from(9 * GAS_PER_BLOB)));
+        let prague = Some(BlobParams::prague());
+        assert_eq!(
+            validate_header(&header, &parent, Fork::Prague, prague),
+            Ok(())
+        );
+        let cancun = Some(BlobParams::cancun());
+        assert_eq!(
+            validate_header(&header, &parent, Fork::Cancun, cancun),
+            Err(HeaderError::InvalidBlobGasUsed)
+        );
+        assert_eq!(
+            validate_header(&header, &parent, Fork::Cancun, None),
+            Err(HeaderError::MissingBlobParams)
+        );
+    }
+}
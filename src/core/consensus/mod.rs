@@ -0,0 +1,4 @@
+pub mod clique;
+pub mod difficulty;
+pub mod ethash;
+pub mod header;
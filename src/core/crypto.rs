@@ -0,0 +1,69 @@
+use ethereum_types::{Address, H256, U256};
+use keccak_hash::keccak;
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{All, Message, PublicKey, Secp256k1, SecretKey};
+use std::sync::OnceLock;
+
+/// Order of the secp256k1 curve.
+pub const SECP256K1N: U256 = U256([
+    0xbfd25e8cd0364141,
+    0xbaaedce6af48a03b,
+    0xfffffffffffffffe,
+    0xffffffffffffffff,
+]);
+
+/// Returns a shared secp256k1 context.
+fn context() -> &'static Secp256k1<All> {
+    static CONTEXT: OnceLock<Secp256k1<All>> = OnceLock::new();
+    CONTEXT.get_or_init(Secp256k1::new)
+}
+
+/// Returns the address corresponding to an uncompressed public key.
+pub fn public_to_address(public: &PublicKey) -> Address {
+    let serialized = public.serialize_uncompressed();
+    Address::from_slice(&keccak(&serialized[1..])[12..])
+}
+
+/// Returns the address of the account that produced the signature (`recovery_id`, `r`, `s`)
+/// over `hash`, or `None` if no such account can be recovered.
+pub fn recover(hash: &H256, recovery_id: u8, r: &U256, s: &U256) -> Option<Address> {
+    if recovery_id > 1 || r.is_zero() || s.is_zero() || *r >= SECP256K1N || *s >= SECP256K1N {
+        return None;
+    }
+
+    let mut compact = [0u8; 64];
+    r.to_big_endian(&mut compact[..32]);
+    s.to_big_endian(&mut compact[32..]);
+
+    let id = RecoveryId::from_i32(recovery_id as i32).ok()?;
+    let signature = RecoverableSignature::from_compact(&compact, id).ok()?;
+    let message = Message::from_digest_slice(hash.as_bytes()).ok()?;
+    let public = context().recover_ecdsa(&message, &signature).ok()?;
+
+    Some(public_to_address(&public))
+}
+
+/// Signs `hash` with `secret`, returning the recovery id and the `r` and `s` values of the
+/// signature.
+pub fn sign(hash: &H256, secret: &H256) -> Option<(u8, U256, U256)> {
+    let key = SecretKey::from_slice(secret.as_bytes()).ok()?;
+    let message = Message::from_digest_slice(hash.as_bytes()).ok()?;
+    let (id, compact) = context()
+        .sign_ecdsa_recoverable(&message, &key)
+        .serialize_compact();
+
+    Some((
+        id.to_i32() as u8,
+        U256::from_big_endian(&compact[..32]),
+        U256::from_big_endian(&compact[32..]),
+    ))
+}
+
+/// Returns the address corresponding to `secret`, or `None` if `secret` is not a valid key.
+pub fn secret_to_address(secret: &H256) -> Option<Address> {
+    let key = SecretKey::from_slice(secret.as_bytes()).ok()?;
+    Some(public_to_address(&PublicKey::from_secret_key(
+        context(),
+        &key,
+    )))
+}
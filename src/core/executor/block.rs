@@ -0,0 +1,483 @@
+use super::transaction::{execute_transaction_cached, TransactionError};
+use crate::core::fork::Fork;
+use crate::core::spec::{Activation, ChainSpec};
+use crate::core::state::WorldState;
+use crate::core::trie;
+use crate::core::types::block::{Block, BlockHeader};
+use crate::core::types::receipt::Receipt;
+use crate::core::types::transaction::Transaction;
+use crate::core::vm::env::BlockHashes;
+use crate::core::vm::interpreter::{Interpreter, Message};
+use crate::core::vm::jumpdest::CodeAnalysisCache;
+use ethereum_types::{Address, Bloom, H160, H256, U256};
+use keccak_hash::keccak;
+
+/// Address of the beacon roots contract (EIP-4788).
+pub const BEACON_ROOTS_ADDRESS: Address = H160([
+    0x00, 0x0f, 0x3d, 0xf6, 0xd7, 0x32, 0x80, 0x7e, 0xf1, 0x31, 0x9f, 0xb7, 0xb8, 0xbb, 0x85, 0x22,
+    0xd0, 0xbe, 0xac, 0x02,
+]);
+/// Address that system calls are made from (EIP-4788).
+pub const SYSTEM_ADDRESS: Address = H160([
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xff, 0xff, 0xff, 0xfe,
+]);
+/// Gas given to system calls (EIP-4788).
+pub const SYSTEM_CALL_GAS: u64 = 30_000_000;
+/// Number of contracts whose analysis is cached while executing a block.
+pub const CODE_ANALYSIS_CACHE_SIZE: usize = 256;
+/// Maximum number of ommers of a block.
+pub const MAX_OMMERS: usize = 2;
+/// Maximum number of generations between a block and its ommers.
+pub const MAX_OMMER_DEPTH: u64 = 6;
+
+/// A reason for a block to be invalid.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BlockError {
+    /// The transaction at the given index is invalid.
+    Transaction(usize, TransactionError),
+    /// The transactions of the block use more blob gas than allowed (EIP-4844).
+    BlobGasLimitExceeded,
+    /// The hash of the ommers differs from the header.
+    OmmersHashMismatch,
+    /// The block has more ommers than allowed.
+    TooManyOmmers,
+    /// An ommer is not between one and `MAX_OMMER_DEPTH` generations older than the block.
+    InvalidOmmerNumber,
+    /// The root hash of the transactions tree differs from the header.
+    TransactionsRootMismatch,
+    /// The root hash of the withdrawals tree differs from the header (EIP-4895).
+    WithdrawalsRootMismatch,
+    /// The gas used by the transactions differs from the header.
+    GasUsedMismatch,
+    /// The blob gas used by the transactions differs from the header (EIP-4844).
+    BlobGasUsedMismatch,
+    /// The bloom filter of the receipts differs from the header.
+    LogsBloomMismatch,
+    /// The root hash of the receipts tree differs from the header.
+    ReceiptsRootMismatch,
+    /// The root hash of the state tree after execution differs from the header.
+    StateRootMismatch,
+}
+
+/// The result of executing a block.
+#[derive(Clone, Debug)]
+pub struct BlockOutcome {
+    receipts: Vec<Receipt>,
+    logs_bloom: Bloom,
+    gas_used: U256,
+    blob_gas_used: U256,
+    receipts_root: H256,
+    state_root: H256,
+}
+
+impl BlockOutcome {
+    /// Returns the receipts of the transactions of the block.
+    pub fn receipts(&self) -> &Vec<Receipt> {
+        &self.receipts
+    }
+
+    /// Returns the bloom filter composed from the logs of the receipts.
+    pub fn logs_bloom(&self) -> &Bloom {
+        &self.logs_bloom
+    }
+
+    /// Returns the gas used by the transactions of the block.
+    pub fn gas_used(&self) -> U256 {
+        self.gas_used
+    }
+
+    /// Returns the blob gas used by the transactions of the block.
+    pub fn blob_gas_used(&self) -> U256 {
+        self.blob_gas_used
+    }
+
+    /// Returns the root hash of the receipts tree.
+    pub fn receipts_root(&self) -> &H256 {
+        &self.receipts_root
+    }
+
+    /// Returns the root hash of the state tree following the block.
+    pub fn state_root(&self) -> &H256 {
+        &self.state_root
+    }
+
+    /// Checks the outcome against the fields of `header` that commit to it.
+    pub fn validate(&self, header: &BlockHeader) -> Result<(), BlockError> {
+        if self.gas_used != header.gas_used() {
+            return Err(BlockError::GasUsedMismatch);
+        }
+        if let Some(blob_gas_used) = header.blob_gas_used() {
+            if self.blob_gas_used != blob_gas_used {
+                return Err(BlockError::BlobGasUsedMismatch);
+            }
+        }
+        if &self.logs_bloom != header.logs_bloom() {
+            return Err(BlockError::LogsBloomMismatch);
+        }
+        if &self.receipts_root != header.receipts_root() {
+            return Err(BlockError::ReceiptsRootMismatch);
+        }
+        if &self.state_root != header.state_root() {
+            return Err(BlockError::StateRootMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the header of `block` commits to its ommers, transactions and withdrawals.
+pub fn validate_body(block: &Block) -> Result<(), BlockError> {
+    let header = block.header();
+    if block.ommers().len() > MAX_OMMERS {
+        return Err(BlockError::TooManyOmmers);
+    }
+    if header.ommers_hash() != &keccak(rlp::encode_list(block.ommers())) {
+        return Err(BlockError::OmmersHashMismatch);
+    }
+    if !block
+        .ommers()
+        .iter()
+        .all(|ommer| is_ommer_of(ommer, header))
+    {
+        return Err(BlockError::InvalidOmmerNumber);
+    }
+    let transactions_root =
+        trie::ordered_trie_root(block.transactions().iter().map(Transaction::encode));
+    if header.transactions_root() != &transactions_root {
+        return Err(BlockError::TransactionsRootMismatch);
+    }
+    let withdrawals_root = block
+        .withdrawals()
+        .map(|withdrawals| trie::ordered_trie_root(withdrawals.iter().map(rlp::encode)));
+    if header.withdrawals_root() != withdrawals_root.as_ref() {
+        return Err(BlockError::WithdrawalsRootMismatch);
+    }
+    Ok(())
+}
+
+/// Returns whether `ommer` is between one and `MAX_OMMER_DEPTH` generations older than `header`.
+fn is_ommer_of(ommer: &BlockHeader, header: &BlockHeader) -> bool {
+    let depth = header.number().checked_sub(ommer.number());
+    matches!(depth, Some(depth) if !depth.is_zero() && depth <= U256::from(MAX_OMMER_DEPTH))
+}
+
+/// Returns the reward (in Wei) for mining a block under the rules of `fork`.
+pub fn block_reward(fork: Fork) -> U256 {
+    let ether = U256::exp10(18);
+    if fork >= Fork::Paris {
+        U256::zero()
+    } else if fork >= Fork::Constantinople {
+        ether * 2
+    } else if fork >= Fork::Byzantium {
+        ether * 3
+    } else {
+        ether * 5
+    }
+}
+
+/// Executes `block` on top of `state` under the rules of the fork `spec` activates at it,
+/// leaving `state` as of the end of the block.
+///
+/// The result can be checked against the header of the block with `BlockOutcome::validate`.
+pub fn execute_block(
+    state: &mut WorldState,
+    block: &Block,
+    block_hashes: &dyn BlockHashes,
+    spec: &ChainSpec,
+) -> Result<BlockOutcome, BlockError> {
+    let header = block.header();
+    let fork = spec.fork(header);
+    let chain_id = spec.chain_id();
+    let blob_params = spec.blob_params(fork);
+
+    // The balances of the DAO accounts move to the refund contract at the first block of the
+    // DAO fork.
+    if let (Some(Activation::Block(number)), Some(refund)) =
+        (spec.activation(Fork::Dao), spec.dao_refund_contract())
+    {
+        if header.number() == U256::from(number) {
+            for account in spec.dao_accounts() {
+                let balance = state.balance(account);
+                state.sub_balance(account, balance);
+                state.add_balance(refund, balance);
+            }
+        }
+    }
+
+    if fork >= Fork::Cancun {
+        if let Some(root) = header.parent_beacon_block_root() {
+            process_beacon_root(state, header, block_hashes, fork, chain_id, root);
+        }
+    }
+
+    let mut receipts = Vec::with_capacity(block.transactions().len());
+    let mut logs_bloom = Bloom::default();
+    let mut gas_used = U256::zero();
+    let mut blob_gas_used = U256::zero();
+    let mut code_analysis_cache = CodeAnalysisCache::new(CODE_ANALYSIS_CACHE_SIZE);
+
+    for (i, tx) in block.transactions().iter().enumerate() {
+        let gas_remaining = header.gas_limit().saturating_sub(gas_used);
+        let outcome = execute_transaction_cached(
+            state,
+            header,
+            block_hashes,
+            fork,
+            chain_id,
+            tx,
+            gas_remaining,
+            blob_params,
+            &mut code_analysis_cache,
+        )
+        .map_err(|err| BlockError::Transaction(i, err))?;
+
+        gas_used += outcome.gas_used();
+        blob_gas_used += outcome.blob_gas_used();
+        let max_blob_gas = blob_params.map_or(0, |params| params.max_blob_gas());
+        if blob_gas_used > U256::from(max_blob_gas) {
+            return Err(BlockError::BlobGasLimitExceeded);
+        }
+
+        // Receipts carry the intermediate state root before Byzantium, and the status code
+        // since (EIP-658).
+        let (status_code, post_state) = if fork >= Fork::Byzantium {
+            let success = outcome.status().is_success();
+            (U256::from(success as u8), None)
+        } else {
+            (U256::zero(), Some(state.state_root()))
+        };
+        let receipt = Receipt::new(
+            tx.tx_type(),
+            status_code,
+            post_state,
+            gas_used,
+            outcome.logs().clone(),
+        );
+        logs_bloom.accrue_bloom(receipt.logs_bloom());
+        receipts.push(receipt);
+    }
+
+    let reward = block_reward(fork);
+    if !reward.is_zero() {
+        let number = header.number();
+        let mut miner_reward = reward;
+        for ommer in block.ommers() {
+            if !is_ommer_of(ommer, header) {
+                return Err(BlockError::InvalidOmmerNumber);
+            }
+            let ommer_reward = (number + 8 - ommer.number()) * reward / 8;
+            state.add_balance(ommer.beneficiary(), ommer_reward);
+            miner_reward += reward / 32;
+        }
+        state.add_balance(header.beneficiary(), miner_reward);
+    }
+
+    if let Some(withdrawals) = block.withdrawals() {
+        for withdrawal in withdrawals {
+            let address = withdrawal.address();
+            state.add_balance(address, withdrawal.amount_wei());
+            if fork >= Fork::SpuriousDragon && state.is_empty(address) {
+                state.remove_account(address);
+            }
+        }
+    }
+    state.commit();
+
+    Ok(BlockOutcome {
+        receipts_root: trie::ordered_trie_root(receipts.iter().map(Receipt::encode)),
+        receipts,
+        logs_bloom,
+        gas_used,
+        blob_gas_used,
+        state_root: state.state_root(),
+    })
+}
+
+/// Stores the root of the parent beacon block in the beacon roots contract (EIP-4788).
+fn process_beacon_root(
+    state: &mut WorldState,
+    header: &BlockHeader,
+    block_hashes: &dyn BlockHashes,
+    fork: Fork,
+    chain_id: U256,
+    root: &H256,
+) {
+    if state.code(&BEACON_ROOTS_ADDRESS).is_empty() {
+        return;
+    }
+
+    let mut interpreter = Interpreter::new(
+        state,
+        header,
+        block_hashes,
+        fork,
+        chain_id,
+        SYSTEM_ADDRESS,
+        U256::zero(),
+        Vec::new(),
+    );
+    interpreter.execute(Message::call(
+        SYSTEM_ADDRESS,
+        BEACON_ROOTS_ADDRESS,
+        U256::zero(),
+        root.as_bytes().to_vec(),
+        U256::from(SYSTEM_CALL_GAS),
+    ));
+    state.commit();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::crypto;
+    use crate::core::spec::DAO_REFUND_CONTRACT;
+    use crate::core::types::transaction::{TransactionType, TX_GAS};
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    fn secret() -> H256 {
+        H256::from_str("45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8").unwrap()
+    }
+
+    fn london() -> ChainSpec {
+        let mut spec = ChainSpec::new(U256::one());
+        spec.set_fork(Fork::London, Activation::Block(0));
+        spec
+    }
+
+    fn transfer_block(nonce: u64) -> Block {
+        let mut tx = Transaction::new(
+            TransactionType::DynamicFee,
+            Some(U256::one()),
+            U256::from(nonce),
+            U256::from(TX_GAS),
+            Some(Address::repeat_byte(0x22)),
+            U256::from(1000),
+            Vec::new(),
+        );
+        tx.set_gas_price(U256::from(20));
+        tx.set_max_priority_fee_per_gas(U256::from(2));
+        tx.sign(&secret());
+
+        let mut header = BlockHeader::default();
+        header.set_number(U256::one());
+        header.set_gas_limit(U256::from(30_000_000));
+        header.set_beneficiary(Address::repeat_byte(0xcc));
+        header.set_base_fee_per_gas(Some(U256::from(7)));
+        Block::new(header, Vec::new(), vec![tx], None)
+    }
+
+    #[test]
+    fn test_execute_transfer() {
+        let sender = crypto::secret_to_address(&secret()).unwrap();
+        let balance = U256::exp10(18);
+        let mut state = WorldState::new();
+        state.insert_account(sender, U256::zero(), balance, Vec::new(), None);
+
+        let block = transfer_block(0);
+        let outcome = execute_block(&mut state, &block, &HashMap::new(), &london()).unwrap();
+
+        assert_eq!(outcome.gas_used(), U256::from(TX_GAS));
+        assert_eq!(outcome.receipts().len(), 1);
+        assert_eq!(outcome.receipts()[0].status_code(), U256::one());
+        assert_eq!(state.nonce(&sender), U256::one());
+        assert_eq!(
+            state.balance(&sender),
+            balance - 1000 - U256::from(TX_GAS) * 9
+        );
+        assert_eq!(state.balance(&Address::repeat_byte(0x22)), U256::from(1000));
+        assert_eq!(
+            state.balance(&Address::repeat_byte(0xcc)),
+            U256::from(TX_GAS) * 2 + block_reward(Fork::London)
+        );
+
+        let mut header = block.header().clone();
+        header.set_gas_used(outcome.gas_used());
+        header.set_logs_bloom(*outcome.logs_bloom());
+        header.set_receipts_root(*outcome.receipts_root());
+        header.set_state_root(state.state_root());
+        assert_eq!(outcome.validate(&header), Ok(()));
+
+        header.set_gas_used(U256::zero());
+        assert_eq!(outcome.validate(&header), Err(BlockError::GasUsedMismatch));
+    }
+
+    #[test]
+    fn test_validate_body() {
+        let block = transfer_block(0);
+        assert_eq!(validate_body(&block), Err(BlockError::OmmersHashMismatch));
+
+        let mut header = block.header().clone();
+        header.set_ommers_hash(keccak_hash::KECCAK_EMPTY_LIST_RLP);
+        header.set_transactions_root(trie::ordered_trie_root(
+            block.transactions().iter().map(Transaction::encode),
+        ));
+        let valid = Block::new(header, Vec::new(), block.transactions().clone(), None);
+        assert_eq!(validate_body(&valid), Ok(()));
+
+        let withdrawals = Some(Vec::new());
+        let invalid = Block::new(valid.header().clone(), Vec::new(), Vec::new(), withdrawals);
+        assert_eq!(
+            validate_body(&invalid),
+            Err(BlockError::TransactionsRootMismatch)
+        );
+
+        // An ommer must be one to six generations older than the block.
+        let ommers_block = |ommer_number: u64| {
+            let mut ommer = BlockHeader::default();
+            ommer.set_number(U256::from(ommer_number));
+            let ommers = vec![ommer];
+            let mut header = valid.header().clone();
+            header.set_number(U256::from(10));
+            header.set_ommers_hash(keccak(rlp::encode_list(&ommers)));
+            header.set_transactions_root(keccak_hash::KECCAK_NULL_RLP);
+            Block::new(header, ommers, Vec::new(), None)
+        };
+        assert_eq!(validate_body(&ommers_block(4)), Ok(()));
+        assert_eq!(validate_body(&ommers_block(9)), Ok(()));
+        for number in [3, 10, 11, 30] {
+            assert_eq!(
+                validate_body(&ommers_block(number)),
+                Err(BlockError::InvalidOmmerNumber)
+            );
+        }
+
+        // Executing an ommer newer than the block fails rather than underflows.
+        let mut state = WorldState::new();
+        assert_eq!(
+            execute_block(&mut state, &ommers_block(30), &HashMap::new(), &london()).unwrap_err(),
+            BlockError::InvalidOmmerNumber
+        );
+    }
+
+    #[test]
+    fn test_execute_invalid_nonce() {
+        let sender = crypto::secret_to_address(&secret()).unwrap();
+        let mut state = WorldState::new();
+        state.insert_account(sender, U256::zero(), U256::exp10(18), Vec::new(), None);
+
+        let block = transfer_block(1);
+        assert_eq!(
+            execute_block(&mut state, &block, &HashMap::new(), &london()).unwrap_err(),
+            BlockError::Transaction(0, TransactionError::NonceTooHigh)
+        );
+    }
+
+    #[test]
+    fn test_dao_fork() {
+        let dao = Address::repeat_byte(0xda);
+        let mut spec = london();
+        spec.set_fork(Fork::Dao, Activation::Block(1));
+        spec.set_dao_accounts(vec![dao]);
+        spec.set_dao_refund_contract(Some(DAO_REFUND_CONTRACT));
+        let mut header = BlockHeader::default();
+        header.set_number(U256::one());
+        let block = Block::new(header, Vec::new(), Vec::new(), None);
+
+        let mut state = WorldState::new();
+        state.insert_account(dao, U256::zero(), U256::from(1000), Vec::new(), None);
+        execute_block(&mut state, &block, &HashMap::new(), &spec).unwrap();
+        assert_eq!(state.balance(&dao), U256::zero());
+        assert_eq!(state.balance(&DAO_REFUND_CONTRACT), U256::from(1000));
+    }
+}
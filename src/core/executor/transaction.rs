@@ -0,0 +1,471 @@
+use crate::core::fork::Fork;
+use crate::core::spec::BlobParams;
+use crate::core::state::WorldState;
+use crate::core::types::block::{BlockHeader, GAS_PER_BLOB};
+use crate::core::types::log::Log;
+use crate::core::types::transaction::{Transaction, TransactionType};
+use crate::core::vm::env::BlockHashes;
+use crate::core::vm::gas::{Schedule, MAX_INIT_CODE_SIZE};
+use crate::core::vm::inspector::Inspector;
+use crate::core::vm::interpreter::{Interpreter, Message, Status};
+use crate::core::vm::jumpdest::CodeAnalysisCache;
+use crate::core::vm::precompile::{self, VERSIONED_HASH_VERSION_KZG};
+use ethereum_types::{Address, BigEndianHash, U256};
+use keccak_hash::KECCAK_EMPTY;
+
+/// A reason for a transaction to be invalid, and so excluded from a valid block.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TransactionError {
+    /// The transaction type is not supported by the fork.
+    UnsupportedType,
+    /// The transaction is signed for a different chain (EIP-155).
+    InvalidChainId,
+    /// The signature has a high `s` value since Homestead (EIP-2), or the sender cannot be
+    /// recovered from it.
+    InvalidSignature,
+    /// The nonce is below the nonce of the sender.
+    NonceTooLow,
+    /// The nonce is above the nonce of the sender.
+    NonceTooHigh,
+    /// The nonce of the sender cannot be incremented (EIP-2681).
+    NonceOverflow,
+    /// The gas limit does not fit in 64 bits.
+    GasLimitOverflow,
+    /// The gas limit does not cover the intrinsic gas.
+    IntrinsicGasTooLow,
+    /// The gas limit exceeds the gas remaining in the block.
+    BlockGasLimitExceeded,
+    /// The sender has deployed code (EIP-3607).
+    SenderNotEoa,
+    /// The balance of the sender does not cover the value and the maximum fees.
+    InsufficientFunds,
+    /// The maximum fee per gas is below the base fee of the block (EIP-1559).
+    FeeCapTooLow,
+    /// The maximum priority fee per gas exceeds the maximum fee per gas (EIP-1559).
+    PriorityFeeTooHigh,
+    /// The initialization code exceeds the size limit (EIP-3860).
+    InitCodeSizeExceeded,
+    /// A blob transaction creates a contract (EIP-4844).
+    BlobCreate,
+    /// A blob transaction carries no blobs (EIP-4844).
+    NoBlobs,
+    /// A blob transaction carries more blobs than fit in a block (EIP-4844).
+    TooManyBlobs,
+    /// A blob versioned hash has an unknown version (EIP-4844).
+    InvalidBlobVersionedHash,
+    /// The maximum fee per blob gas is below the blob gas price of the block (EIP-4844).
+    BlobFeeCapTooLow,
+}
+
+/// The result of executing a transaction.
+#[derive(Clone, Debug)]
+pub struct TransactionOutcome {
+    status: Status,
+    /// Gas used by the transaction, net of refunds.
+    gas_used: U256,
+    /// Blob gas used by the transaction (EIP-4844).
+    blob_gas_used: U256,
+    logs: Vec<Log>,
+    output: Vec<u8>,
+    created_address: Option<Address>,
+}
+
+impl TransactionOutcome {
+    /// Returns how the execution of the transaction ended.
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// Returns the gas used by the transaction, net of refunds.
+    pub fn gas_used(&self) -> U256 {
+        self.gas_used
+    }
+
+    /// Returns the blob gas used by the transaction.
+    pub fn blob_gas_used(&self) -> U256 {
+        self.blob_gas_used
+    }
+
+    /// Returns the logs created by the transaction.
+    pub fn logs(&self) -> &Vec<Log> {
+        &self.logs
+    }
+
+    /// Returns the data returned by the transaction.
+    pub fn output(&self) -> &Vec<u8> {
+        &self.output
+    }
+
+    /// Returns the address of the contract created by the transaction, if any.
+    pub fn created_address(&self) -> Option<&Address> {
+        self.created_address.as_ref()
+    }
+}
+
+/// Executes `tx` in the block with `header` under the rules of `fork`, with `gas_remaining`
+/// gas left in the block and the blob parameters `blob_params` of the chain, which are `None`
+/// before blobs.
+///
+/// An invalid transaction leaves the state untouched. A valid transaction has its changes
+/// committed to the state, whether or not its execution succeeds.
+#[allow(clippy::too_many_arguments)]
+pub fn execute_transaction(
+    state: &mut WorldState,
+    header: &BlockHeader,
+    block_hashes: &dyn BlockHashes,
+    fork: Fork,
+    chain_id: U256,
+    tx: &Transaction,
+    gas_remaining: U256,
+    blob_params: Option<BlobParams>,
+) -> Result<TransactionOutcome, TransactionError> {
+    execute(
+        state,
+        header,
+        block_hashes,
+        fork,
+        chain_id,
+        tx,
+        gas_remaining,
+        blob_params,
+        None,
+        None,
+    )
+}
+
+/// Executes `tx` as `execute_transaction` does, taking the analysis of deployed code from
+/// `cache`.
+#[allow(clippy::too_many_arguments)]
+pub fn execute_transaction_cached(
+    state: &mut WorldState,
+    header: &BlockHeader,
+    block_hashes: &dyn BlockHashes,
+    fork: Fork,
+    chain_id: U256,
+    tx: &Transaction,
+    gas_remaining: U256,
+    blob_params: Option<BlobParams>,
+    cache: &mut CodeAnalysisCache,
+) -> Result<TransactionOutcome, TransactionError> {
+    execute(
+        state,
+        header,
+        block_hashes,
+        fork,
+        chain_id,
+        tx,
+        gas_remaining,
+        blob_params,
+        None,
+        Some(cache),
+    )
+}
+
+/// Executes `tx` as `execute_transaction` does, calling back `inspector` during execution.
+#[allow(clippy::too_many_arguments)]
+pub fn inspect_transaction(
+    state: &mut WorldState,
+    header: &BlockHeader,
+    block_hashes: &dyn BlockHashes,
+    fork: Fork,
+    chain_id: U256,
+    tx: &Transaction,
+    gas_remaining: U256,
+    blob_params: Option<BlobParams>,
+    inspector: &mut dyn Inspector,
+) -> Result<TransactionOutcome, TransactionError> {
+    execute(
+        state,
+        header,
+        block_hashes,
+        fork,
+        chain_id,
+        tx,
+        gas_remaining,
+        blob_params,
+        Some(inspector),
+        None,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute(
+    state: &mut WorldState,
+    header: &BlockHeader,
+    block_hashes: &dyn BlockHashes,
+    fork: Fork,
+    chain_id: U256,
+    tx: &Transaction,
+    gas_remaining: U256,
+    blob_params: Option<BlobParams>,
+    inspector: Option<&mut dyn Inspector>,
+    cache: Option<&mut CodeAnalysisCache>,
+) -> Result<TransactionOutcome, TransactionError> {
+    let blob_gas_price = blob_params
+        .and_then(|params| header.blob_gas_price(params.update_fraction()))
+        .unwrap_or_default();
+    let sender = validate_transaction(
+        state,
+        header,
+        fork,
+        chain_id,
+        tx,
+        gas_remaining,
+        blob_params,
+        blob_gas_price,
+    )?;
+
+    let base_fee = header.base_fee_per_gas().unwrap_or_default();
+    let gas_price = if fork >= Fork::London {
+        tx.max_fee_per_gas()
+            .min(base_fee + tx.max_priority_fee_per_gas())
+    } else {
+        tx.gas_price()
+    };
+    let blob_gas_used = U256::from(GAS_PER_BLOB) * tx.blob_versioned_hashes().len();
+
+    state.sub_balance(
+        &sender,
+        tx.gas_limit() * gas_price + blob_gas_used * blob_gas_price,
+    );
+    state.increment_nonce(&sender);
+
+    let beneficiary = *header.beneficiary();
+    let mut interpreter = Interpreter::new(
+        state,
+        header,
+        block_hashes,
+        fork,
+        chain_id,
+        sender,
+        gas_price,
+        tx.blob_versioned_hashes().clone(),
+    );
+    interpreter.set_blob_params(blob_params);
+    if let Some(inspector) = inspector {
+        interpreter.set_inspector(inspector);
+    }
+    if let Some(cache) = cache {
+        interpreter.set_code_analysis_cache(cache);
+    }
+
+    if fork >= Fork::Berlin {
+        let substate = interpreter.substate_mut();
+        substate.access_account(sender);
+        if let Some(to) = tx.to() {
+            substate.access_account(*to);
+        }
+        for address in precompile::addresses(fork) {
+            substate.access_account(address);
+        }
+        for item in tx.access_list() {
+            substate.access_account(*item.address());
+            for key in item.storage_keys() {
+                substate.access_storage(*item.address(), key.into_uint());
+            }
+        }
+        // The coinbase is warm since Shanghai (EIP-3651).
+        if fork >= Fork::Shanghai {
+            substate.access_account(beneficiary);
+        }
+    }
+
+    let gas = tx.gas_limit() - tx.intrinsic_gas(fork);
+    let message = match tx.to() {
+        Some(to) => Message::call(sender, *to, tx.value(), tx.input().to_vec(), gas),
+        None => Message::create(sender, tx.value(), tx.input().to_vec(), gas),
+    };
+    let outcome = interpreter.execute(message);
+    let mut substate = interpreter.into_substate();
+
+    let gas_used = tx.gas_limit() - outcome.gas_left();
+    let quotient = Schedule::new(fork).max_refund_quotient;
+    let refund = substate.refund_balance().min(gas_used / quotient);
+    let gas_used = gas_used - refund;
+
+    state.add_balance(&sender, (tx.gas_limit() - gas_used) * gas_price);
+    let priority_fee = if fork >= Fork::London {
+        gas_price - base_fee
+    } else {
+        gas_price
+    };
+    state.add_balance(&beneficiary, gas_used * priority_fee);
+    substate.touch(beneficiary);
+
+    for address in substate.self_destruct_accounts() {
+        state.remove_account(address);
+    }
+    // Touched accounts left empty are deleted since Spurious Dragon (EIP-161).
+    if fork >= Fork::SpuriousDragon {
+        for address in substate.touched_accounts() {
+            if state.is_empty(address) {
+                state.remove_account(address);
+            }
+        }
+    }
+    state.commit();
+
+    Ok(TransactionOutcome {
+        status: outcome.status(),
+        gas_used,
+        blob_gas_used,
+        logs: substate.take_logs(),
+        output: outcome.output().clone(),
+        created_address: outcome.created_address().copied(),
+    })
+}
+
+/// Checks the properties of `tx` that do not depend on the state or the block under the rules
+/// of `fork`, with the blob parameters `blob_params` of the chain, returning its sender.
+pub fn validate_intrinsic(
+    tx: &Transaction,
+    fork: Fork,
+    chain_id: U256,
+    blob_params: Option<BlobParams>,
+) -> Result<Address, TransactionError> {
+    let introduced = match tx.tx_type() {
+        TransactionType::Legacy => Fork::Frontier,
+        TransactionType::AccessList => Fork::Berlin,
+        TransactionType::DynamicFee => Fork::London,
+        TransactionType::Blob => Fork::Cancun,
+    };
+    if fork < introduced {
+        return Err(TransactionError::UnsupportedType);
+    }
+
+    match tx.chain_id() {
+        Some(id) if id != chain_id || fork < Fork::SpuriousDragon => {
+            return Err(TransactionError::InvalidChainId)
+        }
+        _ => {}
+    }
+
+    if fork >= Fork::Homestead && !tx.has_low_s() {
+        return Err(TransactionError::InvalidSignature);
+    }
+    let sender = tx.sender().ok_or(TransactionError::InvalidSignature)?;
+
+    if tx.nonce() >= U256::from(u64::MAX) {
+        return Err(TransactionError::NonceOverflow);
+    }
+    if tx.gas_limit() > U256::from(u64::MAX) {
+        return Err(TransactionError::GasLimitOverflow);
+    }
+    if tx.gas_limit() < tx.intrinsic_gas(fork) {
+        return Err(TransactionError::IntrinsicGasTooLow);
+    }
+    if fork >= Fork::Shanghai && tx.is_create() && tx.input().len() > MAX_INIT_CODE_SIZE {
+        return Err(TransactionError::InitCodeSizeExceeded);
+    }
+    if fork >= Fork::London && tx.max_priority_fee_per_gas() > tx.max_fee_per_gas() {
+        return Err(TransactionError::PriorityFeeTooHigh);
+    }
+
+    if tx.tx_type() == TransactionType::Blob {
+        if tx.is_create() {
+            return Err(TransactionError::BlobCreate);
+        }
+        let hashes = tx.blob_versioned_hashes();
+        if hashes.is_empty() {
+            return Err(TransactionError::NoBlobs);
+        }
+        let max_blob_gas = blob_params.map_or(0, |params| params.max_blob_gas());
+        if U256::from(GAS_PER_BLOB) * hashes.len() > U256::from(max_blob_gas) {
+            return Err(TransactionError::TooManyBlobs);
+        }
+        if hashes
+            .iter()
+            .any(|hash| hash[0] != VERSIONED_HASH_VERSION_KZG)
+        {
+            return Err(TransactionError::InvalidBlobVersionedHash);
+        }
+    }
+
+    Ok(sender)
+}
+
+/// Checks that `tx` can be included in the block with `header`, `blob_params` and
+/// `blob_gas_price`, returning its sender.
+#[allow(clippy::too_many_arguments)]
+fn validate_transaction(
+    state: &WorldState,
+    header: &BlockHeader,
+    fork: Fork,
+    chain_id: U256,
+    tx: &Transaction,
+    gas_remaining: U256,
+    blob_params: Option<BlobParams>,
+    blob_gas_price: U256,
+) -> Result<Address, TransactionError> {
+    let sender = validate_intrinsic(tx, fork, chain_id, blob_params)?;
+
+    match tx.nonce().cmp(&state.nonce(&sender)) {
+        std::cmp::Ordering::Less => return Err(TransactionError::NonceTooLow),
+        std::cmp::Ordering::Greater => return Err(TransactionError::NonceTooHigh),
+        std::cmp::Ordering::Equal => {}
+    }
+    if tx.gas_limit() > gas_remaining {
+        return Err(TransactionError::BlockGasLimitExceeded);
+    }
+    if state.code_hash(&sender) != KECCAK_EMPTY {
+        return Err(TransactionError::SenderNotEoa);
+    }
+    if fork >= Fork::London && tx.max_fee_per_gas() < header.base_fee_per_gas().unwrap_or_default()
+    {
+        return Err(TransactionError::FeeCapTooLow);
+    }
+
+    let mut max_cost = tx
+        .gas_limit()
+        .checked_mul(tx.max_fee_per_gas())
+        .and_then(|cost| cost.checked_add(tx.value()))
+        .ok_or(TransactionError::InsufficientFunds)?;
+
+    if tx.tx_type() == TransactionType::Blob {
+        let max_fee_per_blob_gas = tx.max_fee_per_blob_gas().unwrap_or_default();
+        if max_fee_per_blob_gas < blob_gas_price {
+            return Err(TransactionError::BlobFeeCapTooLow);
+        }
+        let blob_gas = U256::from(GAS_PER_BLOB) * tx.blob_versioned_hashes().len();
+        max_cost = blob_gas
+            .checked_mul(max_fee_per_blob_gas)
+            .and_then(|cost| cost.checked_add(max_cost))
+            .ok_or(TransactionError::InsufficientFunds)?;
+    }
+
+    if state.balance(&sender) < max_cost {
+        return Err(TransactionError::InsufficientFunds);
+    }
+
+    Ok(sender)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::H256;
+
+    #[test]
+    fn test_validate_blob_count() {
+        let mut tx = Transaction::new(
+            TransactionType::Blob,
+            Some(U256::one()),
+            U256::zero(),
+            U256::from(21000),
+            Some(Address::repeat_byte(0xaa)),
+            U256::zero(),
+            Vec::new(),
+        );
+        tx.set_blob_versioned_hashes(vec![H256::repeat_byte(VERSIONED_HASH_VERSION_KZG); 7]);
+        tx.sign(&H256::from_low_u64_be(1));
+
+        // Seven blobs exceed the maximum of Cancun, but not that of a chain allowing nine.
+        let validate = |params| validate_intrinsic(&tx, Fork::Cancun, U256::one(), params);
+        assert_eq!(
+            validate(Some(BlobParams::cancun())),
+            Err(TransactionError::TooManyBlobs)
+        );
+        assert!(validate(Some(BlobParams::new(6, 9, 5_007_716))).is_ok());
+        assert_eq!(validate(None), Err(TransactionError::TooManyBlobs));
+    }
+}
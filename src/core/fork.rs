@@ -0,0 +1,27 @@
+/// An Ethereum protocol upgrade.
+///
+/// Variants are ordered by activation, so a ruleset can be checked with a comparison such as
+/// `fork >= Fork::Byzantium`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Fork {
+    Frontier,
+    Homestead,
+    /// The DAO fork, which moved the funds of the DAO to a refund contract. It changes no rules.
+    Dao,
+    TangerineWhistle,
+    SpuriousDragon,
+    Byzantium,
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    MuirGlacier,
+    Berlin,
+    London,
+    ArrowGlacier,
+    GrayGlacier,
+    Paris,
+    Shanghai,
+    Cancun,
+    /// Prague. Its rules are not yet implemented beyond those of Cancun.
+    Prague,
+}
@@ -0,0 +1,557 @@
+use crate::core::consensus::header::INITIAL_BASE_FEE;
+use crate::core::fork::Fork;
+use crate::core::spec::{
+    mainnet_dao_accounts, Activation, BlobParams, ChainSpec, DAO_REFUND_CONTRACT,
+};
+use crate::core::state::WorldState;
+use crate::core::types::block::{Block, BlockHeader};
+use ethereum_types::{Address, H256, H64, U256};
+use keccak_hash::{KECCAK_EMPTY_LIST_RLP, KECCAK_NULL_RLP};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Default gas limit of a genesis block that does not specify one.
+pub const DEFAULT_GAS_LIMIT: u64 = 4_712_388;
+
+/// Block-numbered fork activations by their name in the chain configuration of a genesis file,
+/// in fork order.
+const BLOCK_FORKS: [(&str, Fork); 13] = [
+    ("homesteadBlock", Fork::Homestead),
+    ("daoForkBlock", Fork::Dao),
+    ("eip150Block", Fork::TangerineWhistle),
+    ("eip158Block", Fork::SpuriousDragon),
+    ("byzantiumBlock", Fork::Byzantium),
+    ("constantinopleBlock", Fork::Constantinople),
+    ("petersburgBlock", Fork::Petersburg),
+    ("istanbulBlock", Fork::Istanbul),
+    ("muirGlacierBlock", Fork::MuirGlacier),
+    ("berlinBlock", Fork::Berlin),
+    ("londonBlock", Fork::London),
+    ("arrowGlacierBlock", Fork::ArrowGlacier),
+    ("grayGlacierBlock", Fork::GrayGlacier),
+];
+/// Timestamped fork activations by their name in the chain configuration of a genesis file, in
+/// fork order.
+const TIMESTAMP_FORKS: [(&str, Fork); 3] = [
+    ("shanghaiTime", Fork::Shanghai),
+    ("cancunTime", Fork::Cancun),
+    ("pragueTime", Fork::Prague),
+];
+
+/// A reason for a genesis file to be invalid.
+#[derive(Debug, PartialEq, Eq)]
+pub enum GenesisError {
+    /// The file is not valid JSON.
+    Json(String),
+    /// The field with the given name is missing or has an invalid value.
+    InvalidField(String),
+}
+
+/// An account allocated in the genesis state.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GenesisAccount {
+    nonce: U256,
+    balance: U256,
+    code: Vec<u8>,
+    storage: BTreeMap<U256, U256>,
+}
+
+impl GenesisAccount {
+    pub fn nonce(&self) -> U256 {
+        self.nonce
+    }
+
+    pub fn balance(&self) -> U256 {
+        self.balance
+    }
+
+    pub fn code(&self) -> &Vec<u8> {
+        &self.code
+    }
+
+    pub fn storage(&self) -> &BTreeMap<U256, U256> {
+        &self.storage
+    }
+}
+
+/// A chain definition in the format of a geth `genesis.json` file: the chain configuration, the
+/// allocated accounts and the fields of the genesis header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Genesis {
+    spec: ChainSpec,
+    alloc: BTreeMap<Address, GenesisAccount>,
+    /// The genesis header, without its state root.
+    header: BlockHeader,
+}
+
+impl Genesis {
+    /// Parses a genesis file.
+    ///
+    /// Numbers may be given as JSON numbers or as decimal or `0x`-prefixed hexadecimal strings.
+    /// The optional header fields of the forks active at genesis take their default values when
+    /// omitted.
+    pub fn from_json(json: &str) -> Result<Self, GenesisError> {
+        let value: Value =
+            serde_json::from_str(json).map_err(|err| GenesisError::Json(err.to_string()))?;
+        let object = value
+            .as_object()
+            .ok_or_else(|| GenesisError::Json(String::from("expected an object")))?;
+
+        let spec = match object.get("config") {
+            Some(config) => parse_config(config)?,
+            None => ChainSpec::new(U256::one()),
+        };
+
+        let alloc = match object.get("alloc") {
+            Some(accounts) => parse_alloc(accounts)?,
+            None => BTreeMap::new(),
+        };
+
+        let header = parse_header(object, &spec)?;
+
+        Ok(Self {
+            spec,
+            alloc,
+            header,
+        })
+    }
+
+    /// Returns the specification of the chain.
+    pub fn spec(&self) -> &ChainSpec {
+        &self.spec
+    }
+
+    /// Returns the accounts allocated in the genesis state.
+    pub fn alloc(&self) -> &BTreeMap<Address, GenesisAccount> {
+        &self.alloc
+    }
+
+    /// Returns the genesis state.
+    pub fn state(&self) -> WorldState {
+        alloc_state(&self.alloc)
+    }
+
+    /// Returns the genesis block, committing to the genesis state.
+    pub fn block(&self) -> Block {
+        let mut header = self.header.clone();
+        header.set_state_root(self.state().state_root());
+        let withdrawals = header.withdrawals_root().map(|_| Vec::new());
+        Block::new(header, Vec::new(), Vec::new(), withdrawals)
+    }
+}
+
+/// Returns the chain specification of the `config` of a genesis file.
+fn parse_config(config: &Value) -> Result<ChainSpec, GenesisError> {
+    let config = config.as_object().ok_or_else(|| invalid("config"))?;
+
+    let chain_id = match config.get("chainId") {
+        Some(value) => parse_number(value, "chainId")?,
+        None => U256::one(),
+    };
+    let mut spec = ChainSpec::new(chain_id);
+
+    let dao_support = config
+        .get("daoForkSupport")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    for (name, fork) in BLOCK_FORKS {
+        if fork == Fork::Dao && !dao_support {
+            continue;
+        }
+        // Older files may give only the EIP-155 block for Spurious Dragon.
+        let value = match (config.get(name), fork) {
+            (None, Fork::SpuriousDragon) => config.get("eip155Block"),
+            (value, _) => value,
+        };
+        if let Some(value) = value.filter(|value| !value.is_null()) {
+            spec.set_fork(fork, Activation::Block(parse_u64(value, name)?));
+        }
+    }
+    // Geth drains the mainnet DAO accounts on any chain that supports the fork.
+    if dao_support {
+        spec.set_dao_accounts(mainnet_dao_accounts());
+        spec.set_dao_refund_contract(Some(DAO_REFUND_CONTRACT));
+    }
+    if let Some(value) = config.get("mergeNetsplitBlock").filter(|v| !v.is_null()) {
+        let block = parse_u64(value, "mergeNetsplitBlock")?;
+        spec.set_fork(Fork::Paris, Activation::Block(block));
+    }
+    for (name, fork) in TIMESTAMP_FORKS {
+        if let Some(value) = config.get(name).filter(|value| !value.is_null()) {
+            spec.set_fork(fork, Activation::Timestamp(parse_u64(value, name)?));
+        }
+    }
+
+    if let Some(value) = config.get("terminalTotalDifficulty") {
+        let ttd = parse_number(value, "terminalTotalDifficulty")?;
+        spec.set_terminal_total_difficulty(Some(ttd));
+    }
+    if let Some(value) = config.get("depositContractAddress") {
+        let address = value
+            .as_str()
+            .ok_or_else(|| invalid("depositContractAddress"))?;
+        spec.set_deposit_contract(Some(parse_address(address, "depositContractAddress")?));
+    }
+
+    if let Some(schedule) = config.get("blobSchedule") {
+        let schedule = schedule
+            .as_object()
+            .ok_or_else(|| invalid("blobSchedule"))?;
+        for (name, fork) in [("cancun", Fork::Cancun), ("prague", Fork::Prague)] {
+            if let Some(params) = schedule.get(name) {
+                spec.set_blob_params(fork, parse_blob_params(params)?);
+            }
+        }
+    }
+
+    Ok(spec)
+}
+
+/// Returns the blob parameters of an entry of the `blobSchedule` of a genesis file.
+fn parse_blob_params(params: &Value) -> Result<BlobParams, GenesisError> {
+    let params = params.as_object().ok_or_else(|| invalid("blobSchedule"))?;
+    let field = |name: &str| match params.get(name) {
+        Some(value) => parse_u64(value, name),
+        None => Err(invalid(name)),
+    };
+    Ok(BlobParams::new(
+        field("target")?,
+        field("max")?,
+        field("baseFeeUpdateFraction")?,
+    ))
+}
+
+/// Returns the world state holding the accounts of `alloc`.
+pub(crate) fn alloc_state(alloc: &BTreeMap<Address, GenesisAccount>) -> WorldState {
+    let mut state = WorldState::new();
+    for (address, account) in alloc {
+        state.insert_account(
+            *address,
+            account.nonce,
+            account.balance,
+            account.code.clone(),
+            account.storage.iter().map(|(key, value)| (*key, *value)),
+        );
+    }
+    state
+}
+
+/// Returns the accounts of an `alloc` object mapping addresses to accounts, the format also used
+/// for the pre- and post-states of the ethereum/tests fixtures.
+pub(crate) fn parse_alloc(
+    accounts: &Value,
+) -> Result<BTreeMap<Address, GenesisAccount>, GenesisError> {
+    let accounts = accounts.as_object().ok_or_else(|| invalid("alloc"))?;
+    let mut alloc = BTreeMap::new();
+    for (address, account) in accounts {
+        alloc.insert(parse_address(address, "alloc")?, parse_account(account)?);
+    }
+    Ok(alloc)
+}
+
+/// Returns an account of the `alloc` of a genesis file.
+fn parse_account(account: &Value) -> Result<GenesisAccount, GenesisError> {
+    let account = account.as_object().ok_or_else(|| invalid("alloc"))?;
+
+    let mut storage = BTreeMap::new();
+    if let Some(entries) = account.get("storage") {
+        let entries = entries.as_object().ok_or_else(|| invalid("storage"))?;
+        for (key, value) in entries {
+            storage.insert(
+                parse_number_str(key, "storage")?,
+                parse_number(value, "storage")?,
+            );
+        }
+    }
+
+    Ok(GenesisAccount {
+        nonce: optional_number(account, "nonce")?.unwrap_or_default(),
+        balance: optional_number(account, "balance")?.unwrap_or_default(),
+        code: optional_bytes(account, "code")?.unwrap_or_default(),
+        storage,
+    })
+}
+
+/// Returns the genesis header described by the top-level fields of a genesis file, without its
+/// state root. The optional fields are set according to the forks of `spec` active at genesis.
+fn parse_header(
+    object: &Map<String, Value>,
+    spec: &ChainSpec,
+) -> Result<BlockHeader, GenesisError> {
+    let mut header = BlockHeader::default();
+    header.set_number(optional_number(object, "number")?.unwrap_or_default());
+    header.set_timestamp(optional_number(object, "timestamp")?.unwrap_or_default());
+    header.set_gas_limit(
+        optional_number(object, "gasLimit")?.unwrap_or_else(|| U256::from(DEFAULT_GAS_LIMIT)),
+    );
+    header.set_gas_used(optional_number(object, "gasUsed")?.unwrap_or_default());
+    header.set_difficulty(optional_number(object, "difficulty")?.unwrap_or_default());
+    header.set_extra_data(optional_bytes(object, "extraData")?.unwrap_or_default());
+    header.set_parent_hash(optional_hash(object, "parentHash")?.unwrap_or_default());
+    header.set_mix_hash(optional_hash(object, "mixHash")?.unwrap_or_default());
+    if let Some(coinbase) = object.get("coinbase") {
+        let coinbase = coinbase.as_str().ok_or_else(|| invalid("coinbase"))?;
+        header.set_beneficiary(parse_address(coinbase, "coinbase")?);
+    }
+    let nonce = optional_number(object, "nonce")?.unwrap_or_default();
+    if nonce.bits() > 64 {
+        return Err(invalid("nonce"));
+    }
+    header.set_nonce(H64::from_low_u64_be(nonce.low_u64()));
+    header.set_ommers_hash(KECCAK_EMPTY_LIST_RLP);
+    header.set_transactions_root(KECCAK_NULL_RLP);
+    header.set_receipts_root(KECCAK_NULL_RLP);
+
+    let fork = spec.fork(&header);
+    if fork >= Fork::London {
+        let base_fee = optional_number(object, "baseFeePerGas")?;
+        header.set_base_fee_per_gas(Some(
+            base_fee.unwrap_or_else(|| U256::from(INITIAL_BASE_FEE)),
+        ));
+    }
+    if fork >= Fork::Shanghai {
+        header.set_withdrawals_root(Some(KECCAK_NULL_RLP));
+    }
+    if fork >= Fork::Cancun {
+        let blob_gas_used = optional_number(object, "blobGasUsed")?;
+        let excess_blob_gas = optional_number(object, "excessBlobGas")?;
+        header.set_blob_gas_used(Some(blob_gas_used.unwrap_or_default()));
+        header.set_excess_blob_gas(Some(excess_blob_gas.unwrap_or_default()));
+        header.set_parent_beacon_block_root(Some(H256::zero()));
+    }
+
+    Ok(header)
+}
+
+/// Returns the error for an invalid field with `name`.
+pub(crate) fn invalid(name: &str) -> GenesisError {
+    GenesisError::InvalidField(String::from(name))
+}
+
+/// Returns the number given as a JSON number or a decimal or hexadecimal string.
+pub(crate) fn parse_number(value: &Value, name: &str) -> Result<U256, GenesisError> {
+    match value {
+        Value::Number(number) => U256::from_dec_str(&number.to_string()).map_err(|_| invalid(name)),
+        Value::String(s) => parse_number_str(s, name),
+        _ => Err(invalid(name)),
+    }
+}
+
+/// Returns the number given as a decimal or hexadecimal string.
+pub(crate) fn parse_number_str(s: &str, name: &str) -> Result<U256, GenesisError> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some("") => Ok(U256::zero()),
+        Some(hex) if hex.len() <= 64 => U256::from_str_radix(hex, 16).map_err(|_| invalid(name)),
+        Some(_) => Err(invalid(name)),
+        None => U256::from_dec_str(s).map_err(|_| invalid(name)),
+    }
+}
+
+/// Returns the number given as by `parse_number`, if it fits in 64 bits.
+pub(crate) fn parse_u64(value: &Value, name: &str) -> Result<u64, GenesisError> {
+    let number = parse_number(value, name)?;
+    if number.bits() > 64 {
+        return Err(invalid(name));
+    }
+    Ok(number.low_u64())
+}
+
+/// Returns the bytes of a hexadecimal string, with or without a `0x` prefix.
+pub(crate) fn parse_bytes(s: &str, name: &str) -> Result<Vec<u8>, GenesisError> {
+    let hex = s.strip_prefix("0x").unwrap_or(s);
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(invalid(name));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid(name)))
+        .collect()
+}
+
+/// Returns the address given as a hexadecimal string, with or without a `0x` prefix.
+pub(crate) fn parse_address(s: &str, name: &str) -> Result<Address, GenesisError> {
+    let bytes = parse_bytes(s, name)?;
+    if bytes.len() != Address::len_bytes() {
+        return Err(invalid(name));
+    }
+    Ok(Address::from_slice(&bytes))
+}
+
+/// Returns the number in the field of `object` with `name`, if present and not null.
+pub(crate) fn optional_number(
+    object: &Map<String, Value>,
+    name: &str,
+) -> Result<Option<U256>, GenesisError> {
+    match object.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => parse_number(value, name).map(Some),
+    }
+}
+
+/// Returns the bytes in the field of `object` with `name`, if present and not null.
+pub(crate) fn optional_bytes(
+    object: &Map<String, Value>,
+    name: &str,
+) -> Result<Option<Vec<u8>>, GenesisError> {
+    match object.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => parse_bytes(s, name).map(Some),
+        Some(_) => Err(invalid(name)),
+    }
+}
+
+/// Returns the hash in the field of `object` with `name`, if present and not null.
+pub(crate) fn optional_hash(
+    object: &Map<String, Value>,
+    name: &str,
+) -> Result<Option<H256>, GenesisError> {
+    match optional_bytes(object, name)? {
+        None => Ok(None),
+        Some(bytes) if bytes.len() == H256::len_bytes() => Ok(Some(H256::from_slice(&bytes))),
+        Some(_) => Err(invalid(name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENESIS: &str = r#"{
+        "config": {
+            "chainId": 1337,
+            "homesteadBlock": 0,
+            "daoForkSupport": false,
+            "daoForkBlock": 0,
+            "eip150Block": 0,
+            "eip155Block": 0,
+            "byzantiumBlock": 0,
+            "constantinopleBlock": 0,
+            "petersburgBlock": 0,
+            "istanbulBlock": 0,
+            "berlinBlock": 0,
+            "londonBlock": 0,
+            "mergeNetsplitBlock": 0,
+            "shanghaiTime": 0,
+            "cancunTime": 100,
+            "terminalTotalDifficulty": 58750000000000000000000,
+            "depositContractAddress": "0x4242424242424242424242424242424242424242",
+            "blobSchedule": {
+                "cancun": { "target": 3, "max": 6, "baseFeeUpdateFraction": 3338477 }
+            }
+        },
+        "nonce": "0x42",
+        "timestamp": "0x0",
+        "extraData": "0x1234",
+        "gasLimit": "0x1c9c380",
+        "difficulty": "0x0",
+        "alloc": {
+            "0x00000000000000000000000000000000000000aa": {
+                "balance": "1000000000000000000",
+                "code": "0x600160015500",
+                "storage": { "0x01": "0x02" }
+            },
+            "00000000000000000000000000000000000000bb": { "balance": "0xff", "nonce": "0x1" }
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_config() {
+        let genesis = Genesis::from_json(GENESIS).unwrap();
+        let spec = genesis.spec();
+        assert_eq!(spec.chain_id(), U256::from(1337));
+        assert_eq!(spec.activation(Fork::Dao), None);
+        assert_eq!(
+            spec.activation(Fork::SpuriousDragon),
+            Some(Activation::Block(0))
+        );
+        assert_eq!(
+            spec.activation(Fork::Cancun),
+            Some(Activation::Timestamp(100))
+        );
+        assert_eq!(
+            spec.terminal_total_difficulty(),
+            Some(U256::from_dec_str("58750000000000000000000").unwrap())
+        );
+        assert_eq!(spec.deposit_contract(), Some(&Address::repeat_byte(0x42)));
+        assert_eq!(spec.blob_params(Fork::Cancun), Some(BlobParams::cancun()));
+
+        // The DAO fork is activated only with support for it, as geth does.
+        let dao = |support: &str| {
+            let json = format!(
+                r#"{{ "config": {{ "daoForkBlock": 1920000{} }} }}"#,
+                support
+            );
+            Genesis::from_json(&json).unwrap().spec().clone()
+        };
+        assert_eq!(dao("").activation(Fork::Dao), None);
+        assert!(dao("").dao_accounts().is_empty());
+        let spec = dao(r#", "daoForkSupport": true"#);
+        assert_eq!(
+            spec.activation(Fork::Dao),
+            Some(Activation::Block(1_920_000))
+        );
+        assert_eq!(spec.dao_accounts(), &mainnet_dao_accounts());
+        assert_eq!(spec.dao_refund_contract(), Some(&DAO_REFUND_CONTRACT));
+    }
+
+    #[test]
+    fn test_parse_alloc() {
+        let genesis = Genesis::from_json(GENESIS).unwrap();
+        let alloc = genesis.alloc();
+        assert_eq!(alloc.len(), 2);
+
+        let account = &alloc[&Address::from_low_u64_be(0xaa)];
+        assert_eq!(account.balance(), U256::exp10(18));
+        assert_eq!(account.code(), &vec![0x60, 0x01, 0x60, 0x01, 0x55, 0x00]);
+        assert_eq!(account.storage()[&U256::one()], U256::from(2));
+
+        let account = &alloc[&Address::from_low_u64_be(0xbb)];
+        assert_eq!(account.balance(), U256::from(0xff));
+        assert_eq!(account.nonce(), U256::one());
+    }
+
+    #[test]
+    fn test_block() {
+        let genesis = Genesis::from_json(GENESIS).unwrap();
+        let block = genesis.block();
+        let header = block.header();
+
+        assert_eq!(header.state_root(), &genesis.state().state_root());
+        assert_eq!(header.nonce(), &H64::from_low_u64_be(0x42));
+        assert_eq!(header.extra_data(), &vec![0x12, 0x34]);
+        assert_eq!(header.gas_limit(), U256::from(30_000_000));
+        // London and Shanghai are active at genesis, but not Cancun.
+        assert_eq!(
+            header.base_fee_per_gas(),
+            Some(U256::from(INITIAL_BASE_FEE))
+        );
+        assert_eq!(header.withdrawals_root(), Some(&KECCAK_NULL_RLP));
+        assert_eq!(header.excess_blob_gas(), None);
+        assert_eq!(block.withdrawals(), Some(&Vec::new()));
+
+        // An empty genesis state commits to the empty tree.
+        let genesis = Genesis::from_json("{}").unwrap();
+        assert_eq!(genesis.block().header().state_root(), &KECCAK_NULL_RLP);
+        assert_eq!(genesis.block().header().base_fee_per_gas(), None);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(matches!(
+            Genesis::from_json("{"),
+            Err(GenesisError::Json(_))
+        ));
+        assert_eq!(
+            Genesis::from_json(r#"{ "gasLimit": "0xzz" }"#),
+            Err(GenesisError::InvalidField(String::from("gasLimit")))
+        );
+        assert_eq!(
+            Genesis::from_json(r#"{ "alloc": { "0x01": {} } }"#),
+            Err(GenesisError::InvalidField(String::from("alloc")))
+        );
+        assert_eq!(
+            Genesis::from_json(r#"{ "extraData": "0x€a" }"#),
+            Err(GenesisError::InvalidField(String::from("extraData")))
+        );
+    }
+}
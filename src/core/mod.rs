@@ -1,2 +1,11 @@
+pub mod consensus;
+pub mod crypto;
+pub mod executor;
+pub mod fork;
+pub mod genesis;
+pub mod spec;
+pub mod state;
+pub mod tracing;
+pub mod trie;
 pub mod types;
 pub mod vm;
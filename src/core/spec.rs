@@ -0,0 +1,539 @@
+use crate::core::fork::Fork;
+use crate::core::types::block::{
+    BlockHeader, BLOB_GAS_PRICE_UPDATE_FRACTION, GAS_PER_BLOB, MAX_BLOB_GAS_PER_BLOCK,
+    TARGET_BLOB_GAS_PER_BLOCK,
+};
+use ethereum_types::{Address, H160, U256};
+
+/// Address of the mainnet deposit contract (EIP-6110).
+pub const MAINNET_DEPOSIT_CONTRACT: Address = H160([
+    0x00, 0x00, 0x00, 0x00, 0x21, 0x9a, 0xb5, 0x40, 0x35, 0x6c, 0xbb, 0x83, 0x9c, 0xbe, 0x05, 0x30,
+    0x3d, 0x77, 0x05, 0xfa,
+]);
+/// Address of the Sepolia deposit contract.
+pub const SEPOLIA_DEPOSIT_CONTRACT: Address = H160([
+    0x7f, 0x02, 0xc3, 0xe3, 0xc9, 0x8b, 0x13, 0x30, 0x55, 0xb8, 0xb3, 0x48, 0xb2, 0xac, 0x62, 0x56,
+    0x69, 0xed, 0x29, 0x5d,
+]);
+/// Address of the Holesky deposit contract.
+pub const HOLESKY_DEPOSIT_CONTRACT: Address = H160([0x42; 20]);
+/// Address of the contract that the balances of the DAO accounts move to at the mainnet DAO
+/// fork.
+pub const DAO_REFUND_CONTRACT: Address = H160([
+    0xbf, 0x4e, 0xd7, 0xb2, 0x7f, 0x1d, 0x66, 0x65, 0x46, 0xe3, 0x0d, 0x74, 0xd5, 0x0d, 0x17, 0x3d,
+    0x20, 0xbc, 0xa7, 0x54,
+]);
+
+/// Accounts of The DAO and its children, each followed by its extra balance contract, whose
+/// balances move to the refund contract at the mainnet DAO fork.
+const MAINNET_DAO_ACCOUNTS: [&str; 116] = [
+    "d4fe7bc31cedb7bfb8a345f31e668033056b2728",
+    "b3fb0e5aba0e20e5c49d252dfd30e102b171a425",
+    "2c19c7f9ae8b751e37aeb2d93a699722395ae18f",
+    "ecd135fa4f61a655311e86238c92adcd779555d2",
+    "1975bd06d486162d5dc297798dfc41edd5d160a7",
+    "a3acf3a1e16b1d7c315e23510fdd7847b48234f6",
+    "319f70bab6845585f412ec7724b744fec6095c85",
+    "06706dd3f2c9abf0a21ddcc6941d9b86f0596936",
+    "5c8536898fbb74fc7445814902fd08422eac56d0",
+    "6966ab0d485353095148a2155858910e0965b6f9",
+    "779543a0491a837ca36ce8c635d6154e3c4911a6",
+    "2a5ed960395e2a49b1c758cef4aa15213cfd874c",
+    "5c6e67ccd5849c0d29219c4f95f1a7a93b3f5dc5",
+    "9c50426be05db97f5d64fc54bf89eff947f0a321",
+    "200450f06520bdd6c527622a273333384d870efb",
+    "be8539bfe837b67d1282b2b1d61c3f723966f049",
+    "6b0c4d41ba9ab8d8cfb5d379c69a612f2ced8ecb",
+    "f1385fb24aad0cd7432824085e42aff90886fef5",
+    "d1ac8b1ef1b69ff51d1d401a476e7e612414f091",
+    "8163e7fb499e90f8544ea62bbf80d21cd26d9efd",
+    "51e0ddd9998364a2eb38588679f0d2c42653e4a6",
+    "627a0a960c079c21c34f7612d5d230e01b4ad4c7",
+    "f0b1aa0eb660754448a7937c022e30aa692fe0c5",
+    "24c4d950dfd4dd1902bbed3508144a54542bba94",
+    "9f27daea7aca0aa0446220b98d028715e3bc803d",
+    "a5dc5acd6a7968a4554d89d65e59b7fd3bff0f90",
+    "d9aef3a1e38a39c16b31d1ace71bca8ef58d315b",
+    "63ed5a272de2f6d968408b4acb9024f4cc208ebf",
+    "6f6704e5a10332af6672e50b3d9754dc460dfa4d",
+    "77ca7b50b6cd7e2f3fa008e24ab793fd56cb15f6",
+    "492ea3bb0f3315521c31f273e565b868fc090f17",
+    "0ff30d6de14a8224aa97b78aea5388d1c51c1f00",
+    "9ea779f907f0b315b364b0cfc39a0fde5b02a416",
+    "ceaeb481747ca6c540a000c1f3641f8cef161fa7",
+    "cc34673c6c40e791051898567a1222daf90be287",
+    "579a80d909f346fbfb1189493f521d7f48d52238",
+    "e308bd1ac5fda103967359b2712dd89deffb7973",
+    "4cb31628079fb14e4bc3cd5e30c2f7489b00960c",
+    "ac1ecab32727358dba8962a0f3b261731aad9723",
+    "4fd6ace747f06ece9c49699c7cabc62d02211f75",
+    "440c59b325d2997a134c2c7c60a8c61611212bad",
+    "4486a3d68fac6967006d7a517b889fd3f98c102b",
+    "9c15b54878ba618f494b38f0ae7443db6af648ba",
+    "27b137a85656544b1ccb5a0f2e561a5703c6a68f",
+    "21c7fdb9ed8d291d79ffd82eb2c4356ec0d81241",
+    "23b75c2f6791eef49c69684db4c6c1f93bf49a50",
+    "1ca6abd14d30affe533b24d7a21bff4c2d5e1f3b",
+    "b9637156d330c0d605a791f1c31ba5890582fe1c",
+    "6131c42fa982e56929107413a9d526fd99405560",
+    "1591fc0f688c81fbeb17f5426a162a7024d430c2",
+    "542a9515200d14b68e934e9830d91645a980dd7a",
+    "c4bbd073882dd2add2424cf47d35213405b01324",
+    "782495b7b3355efb2833d56ecb34dc22ad7dfcc4",
+    "58b95c9a9d5d26825e70a82b6adb139d3fd829eb",
+    "3ba4d81db016dc2890c81f3acec2454bff5aada5",
+    "b52042c8ca3f8aa246fa79c3feaa3d959347c0ab",
+    "e4ae1efdfc53b73893af49113d8694a057b9c0d1",
+    "3c02a7bc0391e86d91b7d144e61c2c01a25a79c5",
+    "0737a6b837f97f46ebade41b9bc3e1c509c85c53",
+    "97f43a37f595ab5dd318fb46e7a155eae057317a",
+    "52c5317c848ba20c7504cb2c8052abd1fde29d03",
+    "4863226780fe7c0356454236d3b1c8792785748d",
+    "5d2b2e6fcbe3b11d26b525e085ff818dae332479",
+    "5f9f3392e9f62f63b8eac0beb55541fc8627f42c",
+    "057b56736d32b86616a10f619859c6cd6f59092a",
+    "9aa008f65de0b923a2a4f02012ad034a5e2e2192",
+    "304a554a310c7e546dfe434669c62820b7d83490",
+    "914d1b8b43e92723e64fd0a06f5bdb8dd9b10c79",
+    "4deb0033bb26bc534b197e61d19e0733e5679784",
+    "07f5c1e1bc2c93e0402f23341973a0e043f7bf8a",
+    "35a051a0010aba705c9008d7a7eff6fb88f6ea7b",
+    "4fa802324e929786dbda3b8820dc7834e9134a2a",
+    "9da397b9e80755301a3b32173283a91c0ef6c87e",
+    "8d9edb3054ce5c5774a420ac37ebae0ac02343c6",
+    "0101f3be8ebb4bbd39a2e3b9a3639d4259832fd9",
+    "5dc28b15dffed94048d73806ce4b7a4612a1d48f",
+    "bcf899e6c7d9d5a215ab1e3444c86806fa854c76",
+    "12e626b0eebfe86a56d633b9864e389b45dcb260",
+    "a2f1ccba9395d7fcb155bba8bc92db9bafaeade7",
+    "ec8e57756626fdc07c63ad2eafbd28d08e7b0ca5",
+    "d164b088bd9108b60d0ca3751da4bceb207b0782",
+    "6231b6d0d5e77fe001c2a460bd9584fee60d409b",
+    "1cba23d343a983e9b5cfd19496b9a9701ada385f",
+    "a82f360a8d3455c5c41366975bde739c37bfeb8a",
+    "9fcd2deaff372a39cc679d5c5e4de7bafb0b1339",
+    "005f5cee7a43331d5a3d3eec71305925a62f34b6",
+    "0e0da70933f4c7849fc0d203f5d1d43b9ae4532d",
+    "d131637d5275fd1a68a3200f4ad25c71a2a9522e",
+    "bc07118b9ac290e4622f5e77a0853539789effbe",
+    "47e7aa56d6bdf3f36be34619660de61275420af8",
+    "acd87e28b0c9d1254e868b81cba4cc20d9a32225",
+    "adf80daec7ba8dcf15392f1ac611fff65d94f880",
+    "5524c55fb03cf21f549444ccbecb664d0acad706",
+    "40b803a9abce16f50f36a77ba41180eb90023925",
+    "fe24cdd8648121a43a7c86d289be4dd2951ed49f",
+    "17802f43a0137c506ba92291391a8a8f207f487d",
+    "253488078a4edf4d6f42f113d1e62836a942cf1a",
+    "86af3e9626fce1957c82e88cbf04ddf3a2ed7915",
+    "b136707642a4ea12fb4bae820f03d2562ebff487",
+    "dbe9b615a3ae8709af8b93336ce9b477e4ac0940",
+    "f14c14075d6c4ed84b86798af0956deef67365b5",
+    "ca544e5c4687d109611d0f8f928b53a25af72448",
+    "aeeb8ff27288bdabc0fa5ebb731b6f409507516c",
+    "cbb9d3703e651b0d496cdefb8b92c25aeb2171f7",
+    "6d87578288b6cb5549d5076a207456a1f6a63dc0",
+    "b2c6f0dfbb716ac562e2d85d6cb2f8d5ee87603e",
+    "accc230e8a6e5be9160b8cdf2864dd2a001c28b6",
+    "2b3455ec7fedf16e646268bf88846bd7a2319bb2",
+    "4613f3bca5c44ea06337a9e439fbc6d42e501d0a",
+    "d343b217de44030afaa275f54d31a9317c7f441e",
+    "84ef4b2357079cd7a7c69fd7a37cd0609a679106",
+    "da2fef9e4a3230988ff17df2165440f37e8b1708",
+    "f4c64518ea10f995918a454158c6b61407ea345c",
+    "7602b46df5390e432ef1c307d4f2c9ff6d65cc97",
+    "bb9bc244d798123fde783fcc1c72d3bb8c189413",
+    "807640a13483f8ac783c557fcdf27be11ea4ac7a",
+];
+
+/// Returns the accounts whose balances move to `DAO_REFUND_CONTRACT` at the mainnet DAO fork.
+pub fn mainnet_dao_accounts() -> Vec<Address> {
+    MAINNET_DAO_ACCOUNTS
+        .iter()
+        .map(|account| account.parse().unwrap())
+        .collect()
+}
+
+/// The condition under which a fork activates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Activation {
+    /// The fork activates at the block with the given number.
+    Block(u64),
+    /// The fork activates at the first block with a timestamp of at least the given value.
+    Timestamp(u64),
+}
+
+/// Parameters of blob gas accounting (EIP-4844).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlobParams {
+    /// Blob gas targeted per block.
+    target_blob_gas: u64,
+    /// Maximum blob gas consumed by the transactions of a block.
+    max_blob_gas: u64,
+    /// Denominator controlling the rate of change of the blob gas price.
+    update_fraction: u64,
+}
+
+impl BlobParams {
+    /// Returns new blob parameters with `target` and `max` blobs per block.
+    pub fn new(target: u64, max: u64, update_fraction: u64) -> Self {
+        Self {
+            target_blob_gas: target * GAS_PER_BLOB,
+            max_blob_gas: max * GAS_PER_BLOB,
+            update_fraction,
+        }
+    }
+
+    /// Returns the parameters introduced by Cancun (EIP-4844).
+    pub fn cancun() -> Self {
+        Self {
+            target_blob_gas: TARGET_BLOB_GAS_PER_BLOCK,
+            max_blob_gas: MAX_BLOB_GAS_PER_BLOCK,
+            update_fraction: BLOB_GAS_PRICE_UPDATE_FRACTION,
+        }
+    }
+
+    /// Returns the parameters introduced by Prague (EIP-7691).
+    pub fn prague() -> Self {
+        Self::new(6, 9, 5_007_716)
+    }
+
+    /// Returns the blob gas targeted per block.
+    pub fn target_blob_gas(&self) -> u64 {
+        self.target_blob_gas
+    }
+
+    /// Returns the maximum blob gas consumed by the transactions of a block.
+    pub fn max_blob_gas(&self) -> u64 {
+        self.max_blob_gas
+    }
+
+    /// Returns the denominator controlling the rate of change of the blob gas price.
+    pub fn update_fraction(&self) -> u64 {
+        self.update_fraction
+    }
+}
+
+/// The specification of a chain: its identifier and the forks it activates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainSpec {
+    chain_id: U256,
+    /// Activations of the forks after Frontier, in fork order.
+    forks: Vec<(Fork, Activation)>,
+    /// Total difficulty at which the chain transitions to proof-of-stake.
+    terminal_total_difficulty: Option<U256>,
+    deposit_contract: Option<Address>,
+    /// Blob parameters by the fork that introduces them, in fork order.
+    blob_schedule: Vec<(Fork, BlobParams)>,
+    /// Accounts whose balances move to the refund contract at the DAO fork.
+    dao_accounts: Vec<Address>,
+    dao_refund_contract: Option<Address>,
+}
+
+impl ChainSpec {
+    /// Returns a new chain specification with only Frontier active.
+    pub fn new(chain_id: U256) -> Self {
+        Self {
+            chain_id,
+            forks: Vec::new(),
+            terminal_total_difficulty: None,
+            deposit_contract: None,
+            blob_schedule: vec![
+                (Fork::Cancun, BlobParams::cancun()),
+                (Fork::Prague, BlobParams::prague()),
+            ],
+            dao_accounts: Vec::new(),
+            dao_refund_contract: None,
+        }
+    }
+
+    /// Returns the specification of mainnet.
+    pub fn mainnet() -> Self {
+        let mut spec = Self::new(U256::one());
+        spec.set_fork(Fork::Homestead, Activation::Block(1_150_000));
+        spec.set_fork(Fork::Dao, Activation::Block(1_920_000));
+        spec.set_fork(Fork::TangerineWhistle, Activation::Block(2_463_000));
+        spec.set_fork(Fork::SpuriousDragon, Activation::Block(2_675_000));
+        spec.set_fork(Fork::Byzantium, Activation::Block(4_370_000));
+        spec.set_fork(Fork::Constantinople, Activation::Block(7_280_000));
+        spec.set_fork(Fork::Petersburg, Activation::Block(7_280_000));
+        spec.set_fork(Fork::Istanbul, Activation::Block(9_069_000));
+        spec.set_fork(Fork::MuirGlacier, Activation::Block(9_200_000));
+        spec.set_fork(Fork::Berlin, Activation::Block(12_244_000));
+        spec.set_fork(Fork::London, Activation::Block(12_965_000));
+        spec.set_fork(Fork::ArrowGlacier, Activation::Block(13_773_000));
+        spec.set_fork(Fork::GrayGlacier, Activation::Block(15_050_000));
+        spec.set_fork(Fork::Paris, Activation::Block(15_537_394));
+        spec.set_fork(Fork::Shanghai, Activation::Timestamp(1_681_338_455));
+        spec.set_fork(Fork::Cancun, Activation::Timestamp(1_710_338_135));
+        spec.set_fork(Fork::Prague, Activation::Timestamp(1_746_612_311));
+        spec.set_terminal_total_difficulty(Some(
+            U256::from_dec_str("58750000000000000000000").unwrap(),
+        ));
+        spec.set_deposit_contract(Some(MAINNET_DEPOSIT_CONTRACT));
+        spec.set_dao_accounts(mainnet_dao_accounts());
+        spec.set_dao_refund_contract(Some(DAO_REFUND_CONTRACT));
+        spec
+    }
+
+    /// Returns the specification of the Sepolia testnet.
+    pub fn sepolia() -> Self {
+        let mut spec = Self::new(U256::from(11_155_111));
+        spec.set_forks_at_genesis();
+        spec.set_fork(Fork::Paris, Activation::Block(1_450_409));
+        spec.set_fork(Fork::Shanghai, Activation::Timestamp(1_677_557_088));
+        spec.set_fork(Fork::Cancun, Activation::Timestamp(1_706_655_072));
+        spec.set_fork(Fork::Prague, Activation::Timestamp(1_741_159_776));
+        spec.set_terminal_total_difficulty(Some(U256::from(17_000_000_000_000_000u64)));
+        spec.set_deposit_contract(Some(SEPOLIA_DEPOSIT_CONTRACT));
+        spec
+    }
+
+    /// Returns the specification of the Holesky testnet.
+    pub fn holesky() -> Self {
+        let mut spec = Self::new(U256::from(17_000));
+        spec.set_forks_at_genesis();
+        spec.set_fork(Fork::Paris, Activation::Block(0));
+        spec.set_fork(Fork::Shanghai, Activation::Timestamp(1_696_000_704));
+        spec.set_fork(Fork::Cancun, Activation::Timestamp(1_707_305_664));
+        spec.set_fork(Fork::Prague, Activation::Timestamp(1_740_434_112));
+        spec.set_terminal_total_difficulty(Some(U256::zero()));
+        spec.set_deposit_contract(Some(HOLESKY_DEPOSIT_CONTRACT));
+        spec
+    }
+
+    /// Activates the forks from Homestead through London at genesis, except the DAO fork.
+    fn set_forks_at_genesis(&mut self) {
+        let forks = [
+            Fork::Homestead,
+            Fork::TangerineWhistle,
+            Fork::SpuriousDragon,
+            Fork::Byzantium,
+            Fork::Constantinople,
+            Fork::Petersburg,
+            Fork::Istanbul,
+            Fork::MuirGlacier,
+            Fork::Berlin,
+            Fork::London,
+        ];
+        for fork in forks {
+            self.set_fork(fork, Activation::Block(0));
+        }
+    }
+
+    /// Returns the chain identifier (EIP-155).
+    pub fn chain_id(&self) -> U256 {
+        self.chain_id
+    }
+
+    /// Returns the activations of the forks after Frontier, in fork order.
+    pub fn forks(&self) -> &Vec<(Fork, Activation)> {
+        &self.forks
+    }
+
+    /// Returns the activation of `fork`, if the chain activates it. Frontier is active from
+    /// genesis.
+    pub fn activation(&self, fork: Fork) -> Option<Activation> {
+        if fork == Fork::Frontier {
+            return Some(Activation::Block(0));
+        }
+        self.forks
+            .iter()
+            .find(|(f, _)| *f == fork)
+            .map(|(_, activation)| *activation)
+    }
+
+    /// Sets the activation of `fork`, replacing any previous activation.
+    pub fn set_fork(&mut self, fork: Fork, activation: Activation) {
+        self.forks.retain(|(f, _)| *f != fork);
+        let index = self.forks.partition_point(|(f, _)| *f < fork);
+        self.forks.insert(index, (fork, activation));
+    }
+
+    /// Returns the total difficulty at which the chain transitions to proof-of-stake.
+    pub fn terminal_total_difficulty(&self) -> Option<U256> {
+        self.terminal_total_difficulty
+    }
+
+    /// Sets the total difficulty at which the chain transitions to proof-of-stake, or `None`
+    /// if it does not.
+    pub fn set_terminal_total_difficulty(&mut self, ttd: Option<U256>) {
+        self.terminal_total_difficulty = ttd;
+    }
+
+    /// Returns the address of the deposit contract of the beacon chain.
+    pub fn deposit_contract(&self) -> Option<&Address> {
+        self.deposit_contract.as_ref()
+    }
+
+    /// Sets the address of the deposit contract of the beacon chain (EIP-6110).
+    pub fn set_deposit_contract(&mut self, address: Option<Address>) {
+        self.deposit_contract = address;
+    }
+
+    /// Returns the accounts whose balances move to the refund contract at the DAO fork.
+    pub fn dao_accounts(&self) -> &Vec<Address> {
+        &self.dao_accounts
+    }
+
+    /// Sets the accounts whose balances move to the refund contract at the DAO fork.
+    pub fn set_dao_accounts(&mut self, accounts: Vec<Address>) {
+        self.dao_accounts = accounts;
+    }
+
+    /// Returns the address of the contract that the balances of the DAO accounts move to.
+    pub fn dao_refund_contract(&self) -> Option<&Address> {
+        self.dao_refund_contract.as_ref()
+    }
+
+    /// Sets the address of the contract that the balances of the DAO accounts move to. Without
+    /// it, the DAO fork moves no balances.
+    pub fn set_dao_refund_contract(&mut self, address: Option<Address>) {
+        self.dao_refund_contract = address;
+    }
+
+    /// Sets the blob parameters introduced by `fork`, replacing any previous parameters.
+    pub fn set_blob_params(&mut self, fork: Fork, params: BlobParams) {
+        self.blob_schedule.retain(|(f, _)| *f != fork);
+        let index = self.blob_schedule.partition_point(|(f, _)| *f < fork);
+        self.blob_schedule.insert(index, (fork, params));
+    }
+
+    /// Returns the blob parameters in effect under `fork`, or `None` before blobs.
+    pub fn blob_params(&self, fork: Fork) -> Option<BlobParams> {
+        self.blob_schedule
+            .iter()
+            .rev()
+            .find(|(f, _)| *f <= fork)
+            .map(|(_, params)| *params)
+    }
+
+    /// Returns the fork active at the block with `number` and `timestamp`.
+    pub fn fork_at(&self, number: U256, timestamp: U256) -> Fork {
+        self.forks
+            .iter()
+            .rev()
+            .find(|(_, activation)| match activation {
+                Activation::Block(block) => number >= U256::from(*block),
+                Activation::Timestamp(time) => timestamp >= U256::from(*time),
+            })
+            .map_or(Fork::Frontier, |(fork, _)| *fork)
+    }
+
+    /// Returns the fork active at `header`.
+    ///
+    /// A header with zero difficulty on a chain with a terminal total difficulty is past the
+    /// merge even if the chain does not schedule Paris at a block.
+    pub fn fork(&self, header: &BlockHeader) -> Fork {
+        let fork = self.fork_at(header.number(), header.timestamp());
+        if fork < Fork::Paris
+            && self.terminal_total_difficulty.is_some()
+            && header.difficulty().is_zero()
+        {
+            return Fork::Paris;
+        }
+        fork
+    }
+
+    /// Returns whether the rules of `fork` are active at `header`. The DAO fork changes no rules
+    /// and is active only on chains that schedule it.
+    pub fn is_active(&self, fork: Fork, header: &BlockHeader) -> bool {
+        if fork == Fork::Dao && self.activation(fork).is_none() {
+            return false;
+        }
+        self.fork(header) >= fork
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(number: u64, timestamp: u64, difficulty: u64) -> BlockHeader {
+        let mut header = BlockHeader::default();
+        header.set_number(U256::from(number));
+        header.set_timestamp(U256::from(timestamp));
+        header.set_difficulty(U256::from(difficulty));
+        header
+    }
+
+    #[test]
+    fn test_mainnet() {
+        let spec = ChainSpec::mainnet();
+        let fork = |number, timestamp| spec.fork(&header(number, timestamp, 1));
+        assert_eq!(fork(0, 0), Fork::Frontier);
+        assert_eq!(fork(1_149_999, 0), Fork::Frontier);
+        assert_eq!(fork(1_150_000, 0), Fork::Homestead);
+        assert_eq!(fork(1_920_000, 0), Fork::Dao);
+        assert_eq!(fork(7_280_000, 0), Fork::Petersburg);
+        assert_eq!(fork(15_050_000, 0), Fork::GrayGlacier);
+        assert_eq!(fork(15_537_394, 1_663_224_179), Fork::Paris);
+        assert_eq!(fork(17_034_870, 1_681_338_455), Fork::Shanghai);
+        assert_eq!(fork(19_426_587, 1_710_338_135), Fork::Cancun);
+        assert_eq!(fork(22_431_084, 1_746_612_311), Fork::Prague);
+
+        assert!(spec.is_active(Fork::Dao, &header(2_000_000, 0, 1)));
+        assert_eq!(spec.deposit_contract(), Some(&MAINNET_DEPOSIT_CONTRACT));
+
+        // The DAO and its children, each with its extra balance contract.
+        let dao_accounts = spec.dao_accounts();
+        assert_eq!(dao_accounts.len(), 116);
+        let the_dao: Address = "bb9bc244d798123fde783fcc1c72d3bb8c189413".parse().unwrap();
+        assert!(dao_accounts.contains(&the_dao));
+        assert_eq!(spec.dao_refund_contract(), Some(&DAO_REFUND_CONTRACT));
+    }
+
+    #[test]
+    fn test_testnets() {
+        let sepolia = ChainSpec::sepolia();
+        assert_eq!(sepolia.fork(&header(0, 0, 1)), Fork::London);
+        assert_eq!(sepolia.activation(Fork::Dao), None);
+        assert!(!sepolia.is_active(Fork::Dao, &header(0, 0, 1)));
+        // Zero difficulty marks a block past the merge.
+        assert_eq!(sepolia.fork(&header(1_700_000, 0, 0)), Fork::Paris);
+        // The merge is at block 1,450,409, well before the netsplit block 1,735,371.
+        assert_eq!(
+            sepolia.fork_at(U256::from(1_450_408), U256::zero()),
+            Fork::London
+        );
+        assert_eq!(
+            sepolia.fork_at(U256::from(1_450_409), U256::zero()),
+            Fork::Paris
+        );
+        assert!(!sepolia.is_active(Fork::Paris, &header(1_450_408, 0, 1)));
+        assert!(sepolia.is_active(Fork::Paris, &header(1_450_409, 0, 0)));
+
+        let holesky = ChainSpec::holesky();
+        assert_eq!(holesky.chain_id(), U256::from(17_000));
+        assert_eq!(holesky.fork(&header(0, 1_695_902_100, 1)), Fork::Paris);
+        assert_eq!(holesky.fork(&header(1, 1_707_305_664, 0)), Fork::Cancun);
+    }
+
+    #[test]
+    fn test_set_fork() {
+        let mut spec = ChainSpec::new(U256::one());
+        spec.set_fork(Fork::London, Activation::Block(10));
+        spec.set_fork(Fork::Berlin, Activation::Block(5));
+        assert_eq!(
+            spec.forks(),
+            &vec![
+                (Fork::Berlin, Activation::Block(5)),
+                (Fork::London, Activation::Block(10))
+            ]
+        );
+        assert_eq!(spec.fork_at(U256::from(7), U256::zero()), Fork::Berlin);
+
+        spec.set_fork(Fork::London, Activation::Block(20));
+        assert_eq!(spec.forks().len(), 2);
+        assert_eq!(spec.fork_at(U256::from(10), U256::zero()), Fork::Berlin);
+    }
+
+    #[test]
+    fn test_blob_params() {
+        let spec = ChainSpec::mainnet();
+        assert_eq!(spec.blob_params(Fork::Shanghai), None);
+        assert_eq!(spec.blob_params(Fork::Cancun), Some(BlobParams::cancun()));
+        let prague = spec.blob_params(Fork::Prague).unwrap();
+        assert_eq!(prague.target_blob_gas(), 6 * GAS_PER_BLOB);
+        assert_eq!(prague.max_blob_gas(), 9 * GAS_PER_BLOB);
+    }
+}
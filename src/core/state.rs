@@ -0,0 +1,445 @@
+use crate::core::trie;
+use crate::core::types::account::Account;
+use ethereum_types::{Address, H256, U256};
+use keccak_hash::{keccak, KECCAK_EMPTY};
+use std::collections::{BTreeMap, HashMap};
+
+/// An account held in the world state, together with its code and storage.
+#[derive(Clone, Debug)]
+struct StateObject {
+    nonce: U256,
+    balance: U256,
+    code: Vec<u8>,
+    code_hash: H256,
+    storage: BTreeMap<U256, U256>,
+}
+
+impl Default for StateObject {
+    fn default() -> Self {
+        Self {
+            nonce: U256::zero(),
+            balance: U256::zero(),
+            code: Vec::new(),
+            code_hash: KECCAK_EMPTY,
+            storage: BTreeMap::new(),
+        }
+    }
+}
+
+impl StateObject {
+    fn is_empty(&self) -> bool {
+        self.nonce.is_zero() && self.balance.is_zero() && self.code_hash == KECCAK_EMPTY
+    }
+
+    fn storage_root(&self) -> H256 {
+        trie::sec_trie_root(self.storage.iter().map(|(key, value)| {
+            let mut slot = [0u8; 32];
+            key.to_big_endian(&mut slot);
+            (slot, rlp::encode(value))
+        }))
+    }
+}
+
+/// A reversible modification of the world state.
+#[derive(Clone, Debug)]
+enum JournalEntry {
+    /// An account was created where none existed.
+    Created(Address),
+    /// An account was replaced or removed.
+    Replaced(Address, StateObject),
+    Nonce(Address, U256),
+    Balance(Address, U256),
+    Code(Address, Vec<u8>, H256),
+    Storage(Address, U256, U256),
+    TransientStorage(Address, U256, U256),
+}
+
+/// A position in the journal of a `WorldState` that modifications can be reverted to.
+pub type Checkpoint = usize;
+
+/// The world state: a mapping between addresses and account states.
+///
+/// Modifications are journaled so that the state can be reverted to a `Checkpoint`, as needed
+/// when a message call or contract creation fails. The journal, the original storage values and
+/// the transient storage are all scoped to a single transaction and are discarded by `commit`.
+#[derive(Clone, Debug, Default)]
+pub struct WorldState {
+    accounts: BTreeMap<Address, StateObject>,
+    journal: Vec<JournalEntry>,
+    /// Storage values as of the start of the current transaction, recorded on first write.
+    original_storage: HashMap<(Address, U256), U256>,
+    /// Transient storage (EIP-1153).
+    transient_storage: HashMap<(Address, U256), U256>,
+}
+
+impl WorldState {
+    /// Returns an empty world state.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Inserts an account, replacing any existing account at `address`. The insertion is not
+    /// journaled.
+    pub fn insert_account<I>(
+        &mut self,
+        address: Address,
+        nonce: U256,
+        balance: U256,
+        code: Vec<u8>,
+        storage: I,
+    ) where
+        I: IntoIterator<Item = (U256, U256)>,
+    {
+        let object = StateObject {
+            nonce,
+            balance,
+            code_hash: keccak(&code),
+            code,
+            storage: storage
+                .into_iter()
+                .filter(|(_, value)| !value.is_zero())
+                .collect(),
+        };
+        self.accounts.insert(address, object);
+    }
+
+    /// Returns the account at `address` with its storage root computed, if any.
+    pub fn account(&self, address: &Address) -> Option<Account> {
+        self.accounts.get(address).map(|object| {
+            Account::new(
+                object.nonce,
+                object.balance,
+                object.storage_root(),
+                object.code_hash,
+            )
+        })
+    }
+
+    /// Returns the addresses of all accounts in the state, in ascending order.
+    pub fn addresses(&self) -> impl Iterator<Item = &Address> {
+        self.accounts.keys()
+    }
+
+    /// Returns whether an account exists at `address`.
+    pub fn exists(&self, address: &Address) -> bool {
+        self.accounts.contains_key(address)
+    }
+
+    /// Returns whether an account exists at `address` and is empty.
+    pub fn is_empty(&self, address: &Address) -> bool {
+        self.accounts
+            .get(address)
+            .is_some_and(|object| object.is_empty())
+    }
+
+    /// Returns whether the account at `address` is dead: either non-existent or empty
+    /// (EIP-161).
+    pub fn is_dead(&self, address: &Address) -> bool {
+        self.accounts
+            .get(address)
+            .is_none_or(|object| object.is_empty())
+    }
+
+    pub fn nonce(&self, address: &Address) -> U256 {
+        self.accounts
+            .get(address)
+            .map_or_else(U256::zero, |object| object.nonce)
+    }
+
+    pub fn balance(&self, address: &Address) -> U256 {
+        self.accounts
+            .get(address)
+            .map_or_else(U256::zero, |object| object.balance)
+    }
+
+    pub fn code(&self, address: &Address) -> &[u8] {
+        self.accounts
+            .get(address)
+            .map_or(&[], |object| object.code.as_slice())
+    }
+
+    /// Returns the hash of the code at `address`, or the hash of empty code if there is no
+    /// account.
+    pub fn code_hash(&self, address: &Address) -> H256 {
+        self.accounts
+            .get(address)
+            .map_or(KECCAK_EMPTY, |object| object.code_hash)
+    }
+
+    pub fn storage(&self, address: &Address, key: &U256) -> U256 {
+        self.accounts
+            .get(address)
+            .and_then(|object| object.storage.get(key).copied())
+            .unwrap_or_default()
+    }
+
+    /// Returns all non-zero storage of the account at `address`.
+    pub fn storage_entries(&self, address: &Address) -> impl Iterator<Item = (&U256, &U256)> {
+        self.accounts
+            .get(address)
+            .into_iter()
+            .flat_map(|object| object.storage.iter())
+    }
+
+    /// Returns whether the account at `address` has any non-zero storage.
+    pub fn has_storage(&self, address: &Address) -> bool {
+        self.accounts
+            .get(address)
+            .is_some_and(|object| !object.storage.is_empty())
+    }
+
+    /// Returns the storage value at `key` as of the start of the current transaction.
+    pub fn original_storage(&self, address: &Address, key: &U256) -> U256 {
+        self.original_storage
+            .get(&(*address, *key))
+            .copied()
+            .unwrap_or_else(|| self.storage(address, key))
+    }
+
+    pub fn transient_storage(&self, address: &Address, key: &U256) -> U256 {
+        self.transient_storage
+            .get(&(*address, *key))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_nonce(&mut self, address: &Address, nonce: U256) {
+        let object = self.object_mut(address);
+        let old = std::mem::replace(&mut object.nonce, nonce);
+        self.journal.push(JournalEntry::Nonce(*address, old));
+    }
+
+    pub fn increment_nonce(&mut self, address: &Address) {
+        let nonce = self.nonce(address);
+        self.set_nonce(address, nonce.saturating_add(U256::one()));
+    }
+
+    /// Sets the balance of `address`, creating the account if it does not exist.
+    pub fn set_balance(&mut self, address: &Address, balance: U256) {
+        let object = self.object_mut(address);
+        let old = std::mem::replace(&mut object.balance, balance);
+        self.journal.push(JournalEntry::Balance(*address, old));
+    }
+
+    /// Adds `value` to the balance of `address`, creating the account if it does not exist.
+    pub fn add_balance(&mut self, address: &Address, value: U256) {
+        let balance = self.balance(address);
+        self.set_balance(address, balance.saturating_add(value));
+    }
+
+    /// Subtracts `value` from the balance of `address`. Returns `false`, leaving the state
+    /// untouched, if the balance is insufficient.
+    pub fn sub_balance(&mut self, address: &Address, value: U256) -> bool {
+        match self.balance(address).checked_sub(value) {
+            Some(balance) => {
+                self.set_balance(address, balance);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Transfers `value` from `from` to `to`, creating `to` if it does not exist and `value` is
+    /// non-zero. Returns `false`, leaving the state untouched, if the balance of `from` is
+    /// insufficient.
+    pub fn transfer(&mut self, from: &Address, to: &Address, value: U256) -> bool {
+        if value.is_zero() {
+            return true;
+        }
+        if !self.sub_balance(from, value) {
+            return false;
+        }
+        self.add_balance(to, value);
+        true
+    }
+
+    pub fn set_code(&mut self, address: &Address, code: Vec<u8>) {
+        let code_hash = keccak(&code);
+        let object = self.object_mut(address);
+        let old_code = std::mem::replace(&mut object.code, code);
+        let old_hash = std::mem::replace(&mut object.code_hash, code_hash);
+        self.journal
+            .push(JournalEntry::Code(*address, old_code, old_hash));
+    }
+
+    pub fn set_storage(&mut self, address: &Address, key: U256, value: U256) {
+        let old = self.storage(address, &key);
+        self.original_storage.entry((*address, key)).or_insert(old);
+
+        let object = self.object_mut(address);
+        if value.is_zero() {
+            object.storage.remove(&key);
+        } else {
+            object.storage.insert(key, value);
+        }
+        self.journal.push(JournalEntry::Storage(*address, key, old));
+    }
+
+    pub fn set_transient_storage(&mut self, address: &Address, key: U256, value: U256) {
+        let old = self
+            .transient_storage
+            .insert((*address, key), value)
+            .unwrap_or_default();
+        self.journal
+            .push(JournalEntry::TransientStorage(*address, key, old));
+    }
+
+    /// Creates an empty account at `address`, carrying over the balance of any account it
+    /// replaces.
+    pub fn create_account(&mut self, address: &Address) {
+        let object = StateObject {
+            balance: self.balance(address),
+            ..Default::default()
+        };
+        match self.accounts.insert(*address, object) {
+            Some(old) => self.journal.push(JournalEntry::Replaced(*address, old)),
+            None => self.journal.push(JournalEntry::Created(*address)),
+        }
+    }
+
+    /// Removes the account at `address`, if any.
+    pub fn remove_account(&mut self, address: &Address) {
+        if let Some(old) = self.accounts.remove(address) {
+            self.journal.push(JournalEntry::Replaced(*address, old));
+        }
+    }
+
+    /// Returns a checkpoint that the state can later be reverted to.
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.journal.len()
+    }
+
+    /// Reverts all modifications made since `checkpoint`.
+    pub fn revert_to(&mut self, checkpoint: Checkpoint) {
+        while self.journal.len() > checkpoint {
+            match self.journal.pop() {
+                Some(JournalEntry::Created(address)) => {
+                    self.accounts.remove(&address);
+                }
+                Some(JournalEntry::Replaced(address, object)) => {
+                    self.accounts.insert(address, object);
+                }
+                Some(JournalEntry::Nonce(address, nonce)) => {
+                    self.object_mut_unjournaled(&address).nonce = nonce;
+                }
+                Some(JournalEntry::Balance(address, balance)) => {
+                    self.object_mut_unjournaled(&address).balance = balance;
+                }
+                Some(JournalEntry::Code(address, code, code_hash)) => {
+                    let object = self.object_mut_unjournaled(&address);
+                    object.code = code;
+                    object.code_hash = code_hash;
+                }
+                Some(JournalEntry::Storage(address, key, value)) => {
+                    let object = self.object_mut_unjournaled(&address);
+                    if value.is_zero() {
+                        object.storage.remove(&key);
+                    } else {
+                        object.storage.insert(key, value);
+                    }
+                }
+                Some(JournalEntry::TransientStorage(address, key, value)) => {
+                    self.transient_storage.insert((address, key), value);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Makes all modifications of the current transaction permanent, discarding the journal,
+    /// the original storage values and the transient storage.
+    pub fn commit(&mut self) {
+        self.journal.clear();
+        self.original_storage.clear();
+        self.transient_storage.clear();
+    }
+
+    /// Returns the root hash of the state tree.
+    pub fn state_root(&self) -> H256 {
+        trie::sec_trie_root(self.accounts.iter().map(|(address, object)| {
+            let account = Account::new(
+                object.nonce,
+                object.balance,
+                object.storage_root(),
+                object.code_hash,
+            );
+            (*address, rlp::encode(&account))
+        }))
+    }
+
+    /// Returns the account at `address`, creating it if it does not exist.
+    fn object_mut(&mut self, address: &Address) -> &mut StateObject {
+        if !self.accounts.contains_key(address) {
+            self.journal.push(JournalEntry::Created(*address));
+        }
+        self.object_mut_unjournaled(address)
+    }
+
+    fn object_mut_unjournaled(&mut self, address: &Address) -> &mut StateObject {
+        self.accounts.entry(*address).or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keccak_hash::KECCAK_NULL_RLP;
+
+    #[test]
+    fn test_empty_state_root() {
+        assert_eq!(WorldState::new().state_root(), KECCAK_NULL_RLP);
+    }
+
+    #[test]
+    fn test_revert_to() {
+        let mut state = WorldState::new();
+        let a = Address::repeat_byte(0xaa);
+        let b = Address::repeat_byte(0xbb);
+        state.insert_account(a, U256::zero(), U256::from(100), Vec::new(), vec![]);
+        let root = state.state_root();
+
+        let checkpoint = state.checkpoint();
+        assert!(state.transfer(&a, &b, U256::from(40)));
+        state.set_storage(&a, U256::one(), U256::from(7));
+        state.set_code(&b, vec![0x00]);
+        state.increment_nonce(&a);
+        assert_eq!(state.balance(&b), U256::from(40));
+        assert_ne!(state.state_root(), root);
+
+        state.revert_to(checkpoint);
+        assert!(!state.exists(&b));
+        assert_eq!(state.balance(&a), U256::from(100));
+        assert_eq!(state.storage(&a, &U256::one()), U256::zero());
+        assert_eq!(state.state_root(), root);
+    }
+
+    #[test]
+    fn test_original_storage() {
+        let mut state = WorldState::new();
+        let a = Address::repeat_byte(0xaa);
+        state.insert_account(
+            a,
+            U256::zero(),
+            U256::zero(),
+            Vec::new(),
+            vec![(U256::one(), U256::from(5))],
+        );
+
+        state.set_storage(&a, U256::one(), U256::from(6));
+        state.set_storage(&a, U256::one(), U256::from(7));
+        assert_eq!(state.original_storage(&a, &U256::one()), U256::from(5));
+
+        state.commit();
+        assert_eq!(state.original_storage(&a, &U256::one()), U256::from(7));
+    }
+
+    #[test]
+    fn test_transfer_insufficient_balance() {
+        let mut state = WorldState::new();
+        let a = Address::repeat_byte(0xaa);
+        let b = Address::repeat_byte(0xbb);
+
+        assert!(!state.transfer(&a, &b, U256::one()));
+        assert!(!state.exists(&b));
+    }
+}
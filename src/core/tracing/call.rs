@@ -0,0 +1,573 @@
+use super::to_hex;
+use crate::core::executor::transaction::{inspect_transaction, TransactionError};
+use crate::core::fork::Fork;
+use crate::core::spec::BlobParams;
+use crate::core::state::WorldState;
+use crate::core::types::block::BlockHeader;
+use crate::core::types::log::Log;
+use crate::core::types::transaction::Transaction;
+use crate::core::vm::env::{BlockHashes, ExecutionEnv};
+use crate::core::vm::inspector::Inspector;
+use crate::core::vm::interpreter::{CallKind, ExceptionalHalt, Message, Outcome, Status};
+use crate::core::vm::machine::MachineState;
+use crate::core::vm::substate::AccruedSubstate;
+use ethereum_types::{Address, U256};
+use serde_json::{json, Map, Value};
+
+/// Opcode of `RETURN`.
+const RETURN: u8 = 0xf3;
+
+/// Selector of `Error(string)`, with which Solidity reverts.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// A log emitted by a message call or contract creation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallLog {
+    log: Log,
+    /// Number of calls made by the emitting frame before the log.
+    position: usize,
+}
+
+impl CallLog {
+    /// Returns the emitted log.
+    pub fn log(&self) -> &Log {
+        &self.log
+    }
+
+    /// Returns the number of calls made by the emitting frame before the log.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+/// A message call, contract creation or self-destruct, with the calls it made.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallFrame {
+    /// `CALL`, `CALLCODE`, `DELEGATECALL`, `STATICCALL`, `CREATE`, `CREATE2` or
+    /// `SELFDESTRUCT`.
+    call_type: &'static str,
+    from: Address,
+    /// Target of the call, or address of the created contract.
+    to: Address,
+    /// Value transferred, absent for delegate and static calls.
+    value: Option<U256>,
+    gas: U256,
+    gas_used: U256,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    error: Option<String>,
+    /// Message of an `Error(string)` revert.
+    revert_reason: Option<String>,
+    calls: Vec<CallFrame>,
+    logs: Vec<CallLog>,
+}
+
+impl CallFrame {
+    fn new(call_type: &'static str, from: Address, to: Address, value: Option<U256>) -> Self {
+        Self {
+            call_type,
+            from,
+            to,
+            value,
+            gas: U256::zero(),
+            gas_used: U256::zero(),
+            input: Vec::new(),
+            output: Vec::new(),
+            error: None,
+            revert_reason: None,
+            calls: Vec::new(),
+            logs: Vec::new(),
+        }
+    }
+
+    /// Returns the type of the frame: `CALL`, `CALLCODE`, `DELEGATECALL`, `STATICCALL`,
+    /// `CREATE`, `CREATE2` or `SELFDESTRUCT`.
+    pub fn call_type(&self) -> &str {
+        self.call_type
+    }
+
+    /// Returns the account making the call or creation, or the self-destructing account.
+    pub fn from(&self) -> &Address {
+        &self.from
+    }
+
+    /// Returns the target of the call, or the address of the created contract.
+    pub fn to(&self) -> &Address {
+        &self.to
+    }
+
+    /// Returns the value transferred, absent for delegate and static calls.
+    pub fn value(&self) -> Option<U256> {
+        self.value
+    }
+
+    /// Returns the gas given to the frame, which for the outermost frame of a transaction
+    /// traced by `trace_calls` is the gas limit of the transaction.
+    pub fn gas(&self) -> U256 {
+        self.gas
+    }
+
+    /// Returns the gas used by the frame, which for the outermost frame of a transaction
+    /// traced by `trace_calls` is the gas used by the transaction, net of refunds.
+    pub fn gas_used(&self) -> U256 {
+        self.gas_used
+    }
+
+    /// Returns the input data of a call, or the initialization code of a creation.
+    pub fn input(&self) -> &Vec<u8> {
+        &self.input
+    }
+
+    /// Returns the output data of a call, or the deployed code of a successful creation.
+    pub fn output(&self) -> &Vec<u8> {
+        &self.output
+    }
+
+    /// Returns why the frame failed, if it did.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Returns the message of an `Error(string)` revert.
+    pub fn revert_reason(&self) -> Option<&str> {
+        self.revert_reason.as_deref()
+    }
+
+    /// Returns the calls made by the frame, in order.
+    pub fn calls(&self) -> &Vec<CallFrame> {
+        &self.calls
+    }
+
+    /// Returns the logs emitted by the frame, in order.
+    pub fn logs(&self) -> &Vec<CallLog> {
+        &self.logs
+    }
+
+    /// Returns the frame in the JSON format of the geth `callTracer`, with logs.
+    pub fn to_json(&self) -> Value {
+        let mut object = Map::new();
+        object.insert(String::from("type"), json!(self.call_type));
+        object.insert(String::from("from"), json!(format!("{:?}", self.from)));
+        object.insert(String::from("to"), json!(format!("{:?}", self.to)));
+        if let Some(value) = self.value {
+            object.insert(String::from("value"), json!(format!("{:#x}", value)));
+        }
+        object.insert(String::from("gas"), json!(format!("{:#x}", self.gas)));
+        object.insert(
+            String::from("gasUsed"),
+            json!(format!("{:#x}", self.gas_used)),
+        );
+        object.insert(String::from("input"), json!(to_hex(&self.input)));
+        if !self.output.is_empty() {
+            object.insert(String::from("output"), json!(to_hex(&self.output)));
+        }
+        if let Some(error) = &self.error {
+            object.insert(String::from("error"), json!(error));
+        }
+        if let Some(reason) = &self.revert_reason {
+            object.insert(String::from("revertReason"), json!(reason));
+        }
+        if !self.logs.is_empty() {
+            let logs = self
+                .logs
+                .iter()
+                .map(|log| {
+                    let topics: Vec<_> = log
+                        .log
+                        .topics()
+                        .iter()
+                        .map(|topic| format!("{:?}", topic))
+                        .collect();
+                    json!({
+                        "address": format!("{:?}", log.log.address()),
+                        "topics": topics,
+                        "data": to_hex(log.log.data()),
+                        "position": format!("{:#x}", log.position),
+                    })
+                })
+                .collect();
+            object.insert(String::from("logs"), Value::Array(logs));
+        }
+        if !self.calls.is_empty() {
+            let calls = self.calls.iter().map(CallFrame::to_json).collect();
+            object.insert(String::from("calls"), Value::Array(calls));
+        }
+        Value::Object(object)
+    }
+
+    /// Removes the logs of the frame and its calls if the frame failed, as failed frames have
+    /// their logs discarded.
+    fn clear_failed_logs(&mut self, parent_failed: bool) {
+        let failed = parent_failed || self.error.is_some();
+        if failed {
+            self.logs.clear();
+        }
+        for call in &mut self.calls {
+            call.clear_failed_logs(failed);
+        }
+    }
+}
+
+/// A tracer recording the message calls and contract creations of a transaction as a tree,
+/// like the geth `callTracer`.
+///
+/// The gas of the outermost frame is that of its message, which excludes the intrinsic gas of
+/// the transaction, until `set_root_gas` sets the gas of the transaction as geth reports it.
+#[derive(Default)]
+pub struct CallTracer {
+    /// Frames of the executing calls, outermost first.
+    stack: Vec<CallFrame>,
+    root: Option<CallFrame>,
+    /// Memory region returned by the executing `RETURN` of a contract creation.
+    return_region: Option<(usize, usize)>,
+}
+
+impl CallTracer {
+    /// Returns a tracer that has recorded no frames.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the gas of the outermost frame to the gas limit `gas` of the transaction, and its
+    /// gas used to `gas_used` by the transaction, which include the intrinsic gas.
+    pub fn set_root_gas(&mut self, gas: U256, gas_used: U256) {
+        if let Some(root) = self.root.as_mut() {
+            root.gas = gas;
+            root.gas_used = gas_used;
+        }
+    }
+
+    /// Returns the outermost frame, once execution has finished.
+    pub fn root(&self) -> Option<&CallFrame> {
+        self.root.as_ref()
+    }
+
+    /// Consumes the tracer, returning the outermost frame once execution has finished.
+    pub fn into_root(self) -> Option<CallFrame> {
+        self.root
+    }
+}
+
+impl Inspector for CallTracer {
+    fn step(&mut self, machine: &mut MachineState, env: &ExecutionEnv, _: &AccruedSubstate) {
+        let is_create = self
+            .stack
+            .last()
+            .is_some_and(|frame| matches!(frame.call_type, "CREATE" | "CREATE2"));
+        let pc = machine.program_counter().low_u64() as usize;
+        if !is_create || env.machine_code().get(pc) != Some(&RETURN) {
+            return;
+        }
+        let stack = machine.stack().as_slice();
+        if let [.., size, offset] = stack {
+            // A larger region cannot be paid for, and fails the `RETURN`.
+            if offset.bits() <= 32 && size.bits() <= 32 {
+                self.return_region = Some((offset.low_u64() as usize, size.low_u64() as usize));
+            }
+        }
+    }
+
+    fn step_end(
+        &mut self,
+        machine: &mut MachineState,
+        _: &ExecutionEnv,
+        _: &AccruedSubstate,
+        error: Option<ExceptionalHalt>,
+    ) {
+        let (offset, size) = match self.return_region.take() {
+            Some(region) if error.is_none() => region,
+            _ => return,
+        };
+        if let (Some(frame), Some(code)) = (
+            self.stack.last_mut(),
+            machine.memory().get(offset..offset + size),
+        ) {
+            frame.output = code.to_vec();
+        }
+    }
+
+    fn call(
+        &mut self,
+        _: Option<&mut MachineState>,
+        _: Option<&ExecutionEnv>,
+        _: &AccruedSubstate,
+        message: &Message,
+    ) -> Option<Outcome> {
+        let (call_type, value) = match message.kind() {
+            CallKind::Call => ("CALL", Some(message.value())),
+            CallKind::CallCode => ("CALLCODE", Some(message.value())),
+            CallKind::DelegateCall => ("DELEGATECALL", None),
+            CallKind::StaticCall => ("STATICCALL", None),
+            CallKind::Create => ("CREATE", Some(message.value())),
+            CallKind::Create2 => ("CREATE2", Some(message.value())),
+        };
+        // A delegate call acts on behalf of the caller of its caller, but is made by its
+        // caller, whose address is the target.
+        let from = if message.kind() == CallKind::DelegateCall {
+            *message.target()
+        } else {
+            *message.caller()
+        };
+        let mut frame = CallFrame::new(call_type, from, *message.code_address(), value);
+        frame.gas = message.gas();
+        frame.input = message.input().clone();
+        self.stack.push(frame);
+        None
+    }
+
+    fn call_end(
+        &mut self,
+        _: Option<&mut MachineState>,
+        _: Option<&ExecutionEnv>,
+        _: &AccruedSubstate,
+        outcome: &mut Outcome,
+    ) {
+        let mut frame = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        frame.gas_used = frame.gas.saturating_sub(outcome.gas_left());
+        // A successful creation deploys the data it returned rather than returning it.
+        let is_create = matches!(frame.call_type, "CREATE" | "CREATE2");
+        if !is_create || outcome.status() != Status::Success {
+            frame.output = outcome.output().clone();
+        }
+        frame.error = error_message(outcome.status()).map(String::from);
+        if outcome.status() == Status::Revert {
+            frame.revert_reason = revert_reason(outcome.output());
+        }
+
+        match self.stack.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => {
+                frame.clear_failed_logs(false);
+                self.root = Some(frame);
+            }
+        }
+    }
+
+    fn log(&mut self, _env: &ExecutionEnv, log: &Log) {
+        if let Some(frame) = self.stack.last_mut() {
+            let position = frame.calls.len();
+            frame.logs.push(CallLog {
+                log: log.clone(),
+                position,
+            });
+        }
+    }
+
+    fn self_destruct(&mut self, address: &Address, beneficiary: &Address, balance: U256) {
+        if let Some(frame) = self.stack.last_mut() {
+            let call = CallFrame::new("SELFDESTRUCT", *address, *beneficiary, Some(balance));
+            frame.calls.push(call);
+        }
+    }
+}
+
+/// Executes `tx` as `execute_transaction` does, returning its outermost frame as the geth
+/// `callTracer` does, with the gas limit and gas used of the transaction.
+#[allow(clippy::too_many_arguments)]
+pub fn trace_calls(
+    state: &mut WorldState,
+    header: &BlockHeader,
+    block_hashes: &dyn BlockHashes,
+    fork: Fork,
+    chain_id: U256,
+    tx: &Transaction,
+    gas_remaining: U256,
+    blob_params: Option<BlobParams>,
+) -> Result<Option<CallFrame>, TransactionError> {
+    let mut tracer = CallTracer::new();
+    let outcome = inspect_transaction(
+        state,
+        header,
+        block_hashes,
+        fork,
+        chain_id,
+        tx,
+        gas_remaining,
+        blob_params,
+        &mut tracer,
+    )?;
+    tracer.set_root_gas(tx.gas_limit(), outcome.gas_used());
+    Ok(tracer.into_root())
+}
+
+/// Returns the message geth gives for an execution ending with `status`, if it failed.
+fn error_message(status: Status) -> Option<&'static str> {
+    use ExceptionalHalt::*;
+    let halt = match status {
+        Status::Success => return None,
+        Status::Revert => return Some("execution reverted"),
+        Status::Halt(halt) => halt,
+    };
+    Some(match halt {
+        OutOfGas => "out of gas",
+        InvalidInstruction => "invalid opcode",
+        StackUnderflow => "stack underflow",
+        StackOverflow => "stack limit reached 1024",
+        BadJumpDestination => "invalid jump destination",
+        StaticStateChange => "write protection",
+        ReturnDataOutOfBounds => "return data out of bounds",
+        CallDepthExceeded => "max call depth exceeded",
+        InsufficientBalance => "insufficient balance for transfer",
+        NonceOverflow => "nonce uint64 overflow",
+        CreateCollision => "contract address collision",
+        CodeSizeExceeded => "max code size exceeded",
+        InitCodeSizeExceeded => "max initcode size exceeded",
+        InvalidCodePrefix => "invalid code: must not begin with 0xef",
+        PrecompileFailure => "precompiled contract failed",
+        AddressOutOfRange => "invalid address",
+        InvalidContainer => "invalid container",
+    })
+}
+
+/// Returns the message of revert data encoding `Error(string)`.
+fn revert_reason(output: &[u8]) -> Option<String> {
+    let data = output.strip_prefix(&ERROR_SELECTOR[..])?;
+    let word = |offset: usize| -> Option<usize> {
+        let word = U256::from_big_endian(data.get(offset..offset.checked_add(32)?)?);
+        (word.bits() <= 32).then(|| word.low_u64() as usize)
+    };
+    let offset = word(0)?;
+    let len = word(offset)?;
+    let start = offset.checked_add(32)?;
+    let bytes = data.get(start..start.checked_add(len)?)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::transaction::TransactionType;
+    use crate::core::vm::testing::{self, TestBlock};
+    use ethereum_types::H256;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_call_tracer() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        let callee = Address::repeat_byte(0xbb);
+        // Emits a log, then calls the callee with 1 wei and 0x2a as input.
+        //
+        // PUSH1 0 PUSH1 0 LOG0 PUSH1 0x2a PUSH1 0 MSTORE8 PUSH1 0 DUP1 PUSH1 1 DUP2 PUSH1 1
+        // PUSH20 callee GAS CALL STOP
+        let code = hex::decode(format!(
+            "60006000a0602a600053600080600181600173{}5af100",
+            hex::encode(callee)
+        ))
+        .unwrap();
+        state.insert_account(contract, U256::zero(), U256::from(10), code, None);
+        // Reverts with `Error("no")`.
+        let revert = format!("08c379a0{:064x}{:064x}{:0<64}", 32, 2, hex::encode("no"));
+        // Stores the revert data in memory and reverts with it.
+        let mut callee_code = Vec::new();
+        for (i, chunk) in hex::decode(&revert).unwrap().chunks(32).enumerate() {
+            callee_code.push(0x7f);
+            let mut word = chunk.to_vec();
+            word.resize(32, 0);
+            callee_code.extend(word);
+            callee_code.extend([0x60, (i * 32) as u8, 0x52]);
+        }
+        callee_code.extend([0x60, 100, 0x60, 0, 0xfd]);
+        state.insert_account(callee, U256::zero(), U256::zero(), callee_code, None);
+
+        let mut tracer = CallTracer::new();
+        let block = TestBlock::default();
+        let mut interpreter = block.interpreter(&mut state, Fork::Cancun);
+        interpreter.set_inspector(&mut tracer);
+        let message = testing::call(contract, vec![1], 100000);
+        let outcome = interpreter.execute(message);
+        assert!(outcome.status().is_success());
+
+        let root = tracer.into_root().unwrap();
+        assert_eq!(root.call_type(), "CALL");
+        assert_eq!(root.from(), &testing::CALLER);
+        assert_eq!(root.to(), &contract);
+        assert_eq!(root.input(), &vec![1]);
+        assert_eq!(root.gas_used(), U256::from(100000) - outcome.gas_left());
+        assert_eq!(root.error(), None);
+        assert_eq!(root.logs().len(), 1);
+        assert_eq!(root.logs()[0].position(), 0);
+
+        let call = &root.calls()[0];
+        assert_eq!(call.from(), &contract);
+        assert_eq!(call.to(), &callee);
+        assert_eq!(call.value(), Some(U256::one()));
+        assert_eq!(call.input(), &vec![0x2a]);
+        assert_eq!(call.error(), Some("execution reverted"));
+        assert_eq!(call.revert_reason(), Some("no"));
+
+        let json = root.to_json();
+        assert_eq!(json["type"], "CALL");
+        assert_eq!(json["input"], "0x01");
+        assert_eq!(json["calls"][0]["value"], "0x1");
+        assert_eq!(json["calls"][0]["revertReason"], "no");
+        assert!(json.get("error").is_none());
+    }
+
+    #[test]
+    fn test_revert_reason() {
+        let output = hex::decode(format!(
+            "08c379a0{:064x}{:064x}{:0<64}",
+            32,
+            5,
+            hex::encode("hello")
+        ))
+        .unwrap();
+        assert_eq!(revert_reason(&output), Some(String::from("hello")));
+        assert_eq!(revert_reason(&output[..40]), None);
+        assert_eq!(revert_reason(&[]), None);
+    }
+
+    #[test]
+    fn test_trace_calls() {
+        let runtime = hex::decode("6001600055").unwrap();
+        // Deploys the runtime code: PUSH5 runtime PUSH0 MSTORE PUSH1 5 PUSH1 27 RETURN
+        let init = hex::decode(format!("64{}5f526005601bf3", hex::encode(&runtime))).unwrap();
+        let mut tx = Transaction::new(
+            TransactionType::Legacy,
+            None,
+            U256::zero(),
+            U256::from(100_000),
+            None,
+            U256::zero(),
+            init.clone(),
+        );
+        tx.sign(&H256::from_low_u64_be(1));
+        let sender = tx.sender().unwrap();
+
+        let mut state = WorldState::new();
+        state.insert_account(sender, U256::zero(), U256::zero(), Vec::new(), None);
+        let mut header = BlockHeader::default();
+        header.set_gas_limit(U256::from(1_000_000));
+        let hashes = HashMap::new();
+        let (gas, fork) = (header.gas_limit(), Fork::Cancun);
+        let blob_params = Some(BlobParams::cancun());
+        let root = trace_calls(
+            &mut state,
+            &header,
+            &hashes,
+            fork,
+            U256::one(),
+            &tx,
+            gas,
+            blob_params,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(root.call_type(), "CREATE");
+        assert_eq!(root.from(), &sender);
+        assert_eq!(root.input(), &init);
+        assert_eq!(root.output(), &runtime);
+        // The intrinsic gas of a creation with 12 non-zero bytes, one zero byte and one word
+        // of initialization code, the execution and the code deposit.
+        assert_eq!(root.gas(), U256::from(100_000));
+        assert_eq!(
+            root.gas_used(),
+            U256::from(21000 + 32000 + 12 * 16 + 4 + 2 + 17 + 5 * 200)
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use super::to_hex;
+use crate::core::fork::Fork;
+use crate::core::vm::env::ExecutionEnv;
+use crate::core::vm::inspector::Inspector;
+use crate::core::vm::instruction::Instruction;
+use crate::core::vm::interpreter::ExceptionalHalt;
+use crate::core::vm::machine::MachineState;
+use crate::core::vm::substate::AccruedSubstate;
+use ethereum_types::{H256, U256};
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// A tracer writing one JSON line per executed instruction in the format of EIP-3155, which
+/// geth and evmone also produce.
+pub struct Eip3155Tracer<W: Write> {
+    writer: W,
+    /// The line of the executing instruction, lacking its gas cost.
+    pending: Option<PendingStep>,
+    /// The first error writing a line, reported by `summary`.
+    error: Option<io::Error>,
+}
+
+/// The state of a frame before executing an instruction.
+struct PendingStep {
+    pc: usize,
+    opcode: u8,
+    gas: U256,
+    memory_size: usize,
+    stack: Vec<U256>,
+    depth: usize,
+    return_data: Vec<u8>,
+    refund: U256,
+}
+
+impl<W: Write> Eip3155Tracer<W> {
+    /// Returns a tracer writing to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            pending: None,
+            error: None,
+        }
+    }
+
+    /// Writes the summary line of a transaction that used `gas_used` gas, returned `output`
+    /// and left the state with `state_root`, executed under the rules of `fork` in `time`.
+    /// Fails if any line could not be written.
+    pub fn summary(
+        &mut self,
+        state_root: H256,
+        output: &[u8],
+        gas_used: U256,
+        pass: bool,
+        fork: Fork,
+        time: Duration,
+    ) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        writeln!(
+            self.writer,
+            concat!(
+                r#"{{"stateRoot":"{:?}","output":"{}","gasUsed":"{:#x}","pass":{},"#,
+                r#""fork":"{:?}","time":{}}}"#,
+            ),
+            state_root,
+            to_hex(output),
+            gas_used,
+            pass,
+            fork,
+            time.as_nanos()
+        )
+    }
+
+    /// Consumes the tracer, returning the writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Inspector for Eip3155Tracer<W> {
+    fn step(&mut self, machine: &mut MachineState, env: &ExecutionEnv, substate: &AccruedSubstate) {
+        let pc = machine.program_counter().low_u64() as usize;
+        self.pending = Some(PendingStep {
+            pc,
+            // Execution stops at the end of the code as if it were followed by `STOP`.
+            opcode: env.machine_code().get(pc).copied().unwrap_or(0),
+            gas: *machine.gas_available(),
+            memory_size: machine.memory().len(),
+            stack: machine.stack().as_slice().to_vec(),
+            depth: env.depth().low_u64() as usize + 1,
+            return_data: machine.return_data().clone(),
+            refund: substate.refund_balance(),
+        });
+    }
+
+    fn step_end(
+        &mut self,
+        machine: &mut MachineState,
+        _env: &ExecutionEnv,
+        _substate: &AccruedSubstate,
+        error: Option<ExceptionalHalt>,
+    ) {
+        let step = match self.pending.take() {
+            Some(step) if self.error.is_none() => step,
+            _ => return,
+        };
+        // The cost includes any gas passed to a message call or contract creation.
+        let gas_cost = step.gas.saturating_sub(*machine.gas_available());
+        let stack = step
+            .stack
+            .iter()
+            .map(|item| format!(r#""{:#x}""#, item))
+            .collect::<Vec<_>>()
+            .join(",");
+        let error = match error {
+            Some(halt) => format!(r#","error":"{:?}""#, halt),
+            None => String::new(),
+        };
+        let result = writeln!(
+            self.writer,
+            concat!(
+                r#"{{"pc":{},"op":{},"gas":"{:#x}","gasCost":"{:#x}","memSize":{},"#,
+                r#""stack":[{}],"depth":{},"returnData":"{}","refund":"{:#x}","opName":"{}"{}}}"#,
+            ),
+            step.pc,
+            step.opcode,
+            step.gas,
+            gas_cost,
+            step.memory_size,
+            stack,
+            step.depth,
+            to_hex(&step.return_data),
+            step.refund,
+            Instruction::from(step.opcode).name(),
+            error
+        );
+        if let Err(err) = result {
+            self.error = Some(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::state::WorldState;
+    use crate::core::vm::testing::{self, TestBlock};
+    use ethereum_types::Address;
+
+    #[test]
+    fn test_eip3155_tracer() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        // PUSH1 0x2a PUSH1 0x00 SSTORE
+        let code = hex::decode("602a600055").unwrap();
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+
+        let mut tracer = Eip3155Tracer::new(Vec::new());
+        let block = TestBlock::default();
+        let mut interpreter = block.interpreter(&mut state, Fork::Cancun);
+        interpreter.set_inspector(&mut tracer);
+        let message = testing::call(contract, Vec::new(), 30000);
+        let outcome = interpreter.execute(message);
+
+        let gas_used = U256::from(30000) - outcome.gas_left();
+        tracer
+            .summary(
+                H256::zero(),
+                outcome.output(),
+                gas_used,
+                true,
+                Fork::Cancun,
+                Duration::from_micros(15),
+            )
+            .unwrap();
+        let trace = String::from_utf8(tracer.into_inner()).unwrap();
+        let lines: Vec<_> = trace.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[0],
+            r#"{"pc":0,"op":96,"gas":"0x7530","gasCost":"0x3","memSize":0,"stack":[],"depth":1,"returnData":"0x","refund":"0x0","opName":"PUSH1"}"#
+        );
+        assert_eq!(
+            lines[2],
+            r#"{"pc":4,"op":85,"gas":"0x752a","gasCost":"0x5654","memSize":0,"stack":["0x2a","0x0"],"depth":1,"returnData":"0x","refund":"0x0","opName":"SSTORE"}"#
+        );
+        // Execution stops at the end of the code.
+        assert!(lines[3].contains(r#""pc":5,"op":0,"#));
+        assert_eq!(
+            lines[4],
+            format!(
+                r#"{{"stateRoot":"{:?}","output":"0x","gasUsed":"0x565a","pass":true,"fork":"Cancun","time":15000}}"#,
+                H256::zero()
+            )
+        );
+    }
+
+    #[test]
+    fn test_eip3155_tracer_error() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        // PUSH1 0x00 JUMP
+        let code = hex::decode("600056").unwrap();
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+
+        let mut tracer = Eip3155Tracer::new(Vec::new());
+        let block = TestBlock::default();
+        let mut interpreter = block.interpreter(&mut state, Fork::Cancun);
+        interpreter.set_inspector(&mut tracer);
+        interpreter.execute(testing::call(contract, Vec::new(), 100));
+
+        let trace = String::from_utf8(tracer.into_inner()).unwrap();
+        let last = trace.lines().last().unwrap();
+        assert!(last.ends_with(r#""opName":"JUMP","error":"BadJumpDestination"}"#));
+    }
+}
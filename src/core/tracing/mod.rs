@@ -0,0 +1,14 @@
+pub mod call;
+pub mod eip3155;
+pub mod parity;
+pub mod prestate;
+
+/// Returns `bytes` as a `0x`-prefixed hexadecimal string.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(2 + bytes.len() * 2);
+    hex.push_str("0x");
+    for byte in bytes {
+        hex.push_str(&format!("{:02x}", byte));
+    }
+    hex
+}
@@ -0,0 +1,699 @@
+use super::prestate::PrestateTracer;
+use super::to_hex;
+use crate::core::executor::transaction::{inspect_transaction, TransactionError};
+use crate::core::fork::Fork;
+use crate::core::spec::BlobParams;
+use crate::core::state::WorldState;
+use crate::core::types::block::BlockHeader;
+use crate::core::types::transaction::Transaction;
+use crate::core::vm::env::{BlockHashes, ExecutionEnv};
+use crate::core::vm::inspector::Inspector;
+use crate::core::vm::instruction::Instruction;
+use crate::core::vm::interpreter::{CallKind, ExceptionalHalt, Message, Outcome, Status};
+use crate::core::vm::machine::MachineState;
+use crate::core::vm::substate::AccruedSubstate;
+use ethereum_types::{Address, BigEndianHash, H256, U256};
+use serde_json::{json, Map, Value};
+
+/// A message call, contract creation or self-destruct, in the flat format of the OpenEthereum
+/// `trace_*` methods.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlatTrace {
+    /// `call`, `create` or `suicide`.
+    trace_type: &'static str,
+    action: Map<String, Value>,
+    /// Result of a call or creation that did not fail.
+    result: Option<Map<String, Value>>,
+    error: Option<&'static str>,
+    /// Number of calls made directly by the traced one.
+    subtraces: usize,
+    /// Position of the trace in the call tree: the index of each call among the calls made
+    /// by its caller, outermost first.
+    trace_address: Vec<usize>,
+}
+
+impl FlatTrace {
+    /// Returns the type of the trace: `call`, `create` or `suicide`.
+    pub fn trace_type(&self) -> &str {
+        self.trace_type
+    }
+
+    /// Returns the fields of the call, creation or self-destruct, such as `from`, `gas` and
+    /// `input`, as OpenEthereum names them.
+    pub fn action(&self) -> &Map<String, Value> {
+        &self.action
+    }
+
+    /// Returns the result of a call or creation that did not fail.
+    pub fn result(&self) -> Option<&Map<String, Value>> {
+        self.result.as_ref()
+    }
+
+    /// Returns why the call or creation failed, if it did.
+    pub fn error(&self) -> Option<&str> {
+        self.error
+    }
+
+    /// Returns the number of calls made directly by the traced one.
+    pub fn subtraces(&self) -> usize {
+        self.subtraces
+    }
+
+    /// Returns the position of the trace in the call tree: the index of each call among the
+    /// calls made by its caller, outermost first.
+    pub fn trace_address(&self) -> &Vec<usize> {
+        &self.trace_address
+    }
+
+    /// Returns the trace in the JSON format of the OpenEthereum `trace` output.
+    pub fn to_json(&self) -> Value {
+        let mut object = Map::new();
+        object.insert(String::from("type"), json!(self.trace_type));
+        object.insert(String::from("action"), Value::Object(self.action.clone()));
+        let result = self.result.clone().map_or(Value::Null, Value::Object);
+        object.insert(String::from("result"), result);
+        if let Some(error) = self.error {
+            object.insert(String::from("error"), json!(error));
+        }
+        object.insert(String::from("subtraces"), json!(self.subtraces));
+        object.insert(String::from("traceAddress"), json!(self.trace_address));
+        Value::Object(object)
+    }
+}
+
+/// The instructions executed by a message call or contract creation, in the `vmTrace` format
+/// of the OpenEthereum `trace_*` methods.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VmTrace {
+    code: Vec<u8>,
+    ops: Vec<VmOperation>,
+}
+
+/// An executed instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct VmOperation {
+    pc: usize,
+    /// Gas consumed, including gas passed to a message call or contract creation.
+    cost: U256,
+    /// Effects of the instruction, absent if it failed.
+    ex: Option<VmExecuted>,
+    /// Trace of the message call or contract creation started by the instruction.
+    sub: Option<VmTrace>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct VmExecuted {
+    /// Gas left after the instruction.
+    used: U256,
+    /// Items pushed onto the stack, bottom first.
+    push: Vec<U256>,
+    /// Memory written: the offset and the bytes.
+    mem: Option<(usize, Vec<u8>)>,
+    /// Storage written: the key and the value.
+    store: Option<(U256, U256)>,
+}
+
+impl VmTrace {
+    /// Returns the executed code.
+    pub fn code(&self) -> &Vec<u8> {
+        &self.code
+    }
+
+    /// Returns the trace in the JSON format of the OpenEthereum `vmTrace` output, with the
+    /// traces of nested calls under the instructions that started them.
+    pub fn to_json(&self) -> Value {
+        let ops: Vec<_> = self.ops.iter().map(VmOperation::to_json).collect();
+        json!({ "code": to_hex(&self.code), "ops": ops })
+    }
+}
+
+impl VmOperation {
+    fn to_json(&self) -> Value {
+        let ex = match &self.ex {
+            Some(ex) => {
+                let push: Vec<_> = ex.push.iter().map(|item| format!("{:#x}", item)).collect();
+                let mem = match &ex.mem {
+                    Some((off, data)) => json!({ "off": off, "data": to_hex(data) }),
+                    None => Value::Null,
+                };
+                let store = match ex.store {
+                    Some((key, val)) => {
+                        json!({ "key": format!("{:#x}", key), "val": format!("{:#x}", val) })
+                    }
+                    None => Value::Null,
+                };
+                json!({ "used": ex.used.low_u64(), "push": push, "mem": mem, "store": store })
+            }
+            None => Value::Null,
+        };
+        let sub = self.sub.as_ref().map_or(Value::Null, VmTrace::to_json);
+        json!({ "pc": self.pc, "cost": self.cost.low_u64(), "ex": ex, "sub": sub })
+    }
+}
+
+/// The instruction executing in a frame, before its effects are known.
+struct PendingOp {
+    pc: usize,
+    instruction: Instruction,
+    gas: U256,
+    /// Memory region the instruction writes, as offset and size.
+    mem: Option<(usize, usize)>,
+    store: Option<(U256, U256)>,
+}
+
+/// A message call or contract creation being traced.
+struct Frame {
+    /// Index of the trace in `ParityTracer::traces`.
+    index: usize,
+    /// Number of calls made so far.
+    subtraces: usize,
+    /// Gas available to the message.
+    gas: U256,
+    vm: VmTrace,
+    pending: Option<PendingOp>,
+    /// The instruction that started a message call or contract creation, whose effects are
+    /// known when the frame resumes.
+    resuming: Option<PendingOp>,
+    /// Data returned by the frame.
+    returned: Vec<u8>,
+}
+
+/// A tracer producing the `trace`, `vmTrace` and `stateDiff` outputs of the OpenEthereum
+/// `trace_replayTransaction` method.
+#[derive(Default)]
+pub struct ParityTracer {
+    traces: Vec<FlatTrace>,
+    frames: Vec<Frame>,
+    vm_trace: Option<VmTrace>,
+    /// Records the touched accounts for the state diff.
+    prestate: PrestateTracer,
+}
+
+impl ParityTracer {
+    /// Returns a tracer that has recorded nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that `address` is touched, as the coinbase is outside of execution.
+    pub fn touch(&mut self, address: Address) {
+        self.prestate.touch(address);
+    }
+
+    /// Returns the traces of the message calls, contract creations and self-destructs, in
+    /// the order they started.
+    pub fn traces(&self) -> &Vec<FlatTrace> {
+        &self.traces
+    }
+
+    /// Returns the trace of the instructions executed, once execution has finished.
+    pub fn vm_trace(&self) -> Option<&VmTrace> {
+        self.vm_trace.as_ref()
+    }
+
+    /// Returns the changes to the touched accounts between `pre` and `post`, in the
+    /// `stateDiff` format.
+    ///
+    /// Each field of a changed account is `"="` if unchanged, and otherwise `{"+": new}` for
+    /// a created account, `{"-": old}` for a deleted account and `{"*": {"from": old, "to":
+    /// new}}` for a modified one. Only changed storage slots are included.
+    pub fn state_diff_json(&self, pre: &WorldState, post: &WorldState) -> Value {
+        let mut accounts = Map::new();
+        for address in self.prestate.accounts() {
+            let (existed, exists) = (pre.exists(address), post.exists(address));
+            if !existed && !exists {
+                continue;
+            }
+            let slots: Vec<_> = self
+                .prestate
+                .storage()
+                .get(address)
+                .into_iter()
+                .flatten()
+                .filter(|key| pre.storage(address, key) != post.storage(address, key))
+                .collect();
+            let fields = [
+                (pre.balance(address), post.balance(address)),
+                (pre.nonce(address), post.nonce(address)),
+            ];
+            let code_changed = pre.code(address) != post.code(address);
+            let unchanged = fields.iter().all(|(from, to)| from == to) && !code_changed;
+            if existed && exists && unchanged && slots.is_empty() {
+                continue;
+            }
+
+            let diff = |from: Value, to: Value| -> Value {
+                match (existed, exists) {
+                    (false, _) => json!({ "+": to }),
+                    (_, false) => json!({ "-": from }),
+                    _ if from == to => json!("="),
+                    _ => json!({ "*": { "from": from, "to": to } }),
+                }
+            };
+            let quantity = |value: U256| json!(format!("{:#x}", value));
+            let word = |value: U256| json!(format!("{:?}", H256::from_uint(&value)));
+
+            let mut storage = Map::new();
+            for key in slots {
+                let (from, to) = (pre.storage(address, key), post.storage(address, key));
+                storage.insert(
+                    format!("{:?}", H256::from_uint(key)),
+                    diff(word(from), word(to)),
+                );
+            }
+            let account = json!({
+                "balance": diff(quantity(fields[0].0), quantity(fields[0].1)),
+                "nonce": diff(quantity(fields[1].0), quantity(fields[1].1)),
+                "code": diff(json!(to_hex(pre.code(address))), json!(to_hex(post.code(address)))),
+                "storage": storage,
+            });
+            accounts.insert(format!("{:?}", address), account);
+        }
+        Value::Object(accounts)
+    }
+
+    /// Completes the instruction of the innermost frame that started a message call or
+    /// contract creation, now that its result is on the stack.
+    fn resume(&mut self, machine: &MachineState) {
+        let frame = match self.frames.last_mut() {
+            Some(frame) => frame,
+            None => return,
+        };
+        if let Some(op) = frame.resuming.take() {
+            let ex = executed(&op, machine, 1);
+            if let Some(last) = frame.vm.ops.last_mut() {
+                last.ex = Some(ex);
+            }
+        }
+    }
+}
+
+impl Inspector for ParityTracer {
+    fn step(&mut self, machine: &mut MachineState, env: &ExecutionEnv, substate: &AccruedSubstate) {
+        self.prestate.step(machine, env, substate);
+        self.resume(machine);
+        let frame = match self.frames.last_mut() {
+            Some(frame) => frame,
+            None => return,
+        };
+        if frame.vm.code.is_empty() {
+            frame.vm.code = env.machine_code().clone();
+        }
+
+        let pc = machine.program_counter().low_u64() as usize;
+        let instruction = match env.machine_code().get(pc) {
+            Some(&opcode) => Instruction::from(opcode),
+            None => return,
+        };
+        let arg = |pos: usize| machine.stack().get(pos).copied().unwrap_or_default();
+        let region = |offset: U256, size: U256| {
+            let fits = offset.bits() <= 32 && size.bits() <= 32;
+            (fits && !size.is_zero()).then(|| (offset.low_u64() as usize, size.low_u64() as usize))
+        };
+        use Instruction::*;
+        let mem = match instruction {
+            MStore => region(arg(0), U256::from(32)),
+            MStore8 => region(arg(0), U256::one()),
+            CallDataCopy | CodeCopy | ReturnDataCopy | MCopy => region(arg(0), arg(2)),
+            ExtCodeCopy => region(arg(1), arg(3)),
+            Call | CallCode => region(arg(5), arg(6)),
+            DelegateCall | StaticCall => region(arg(4), arg(5)),
+            _ => None,
+        };
+        let store = (instruction == SStore).then(|| (arg(0), arg(1)));
+        frame.pending = Some(PendingOp {
+            pc,
+            instruction,
+            gas: *machine.gas_available(),
+            mem,
+            store,
+        });
+    }
+
+    fn step_end(
+        &mut self,
+        machine: &mut MachineState,
+        _: &ExecutionEnv,
+        _: &AccruedSubstate,
+        error: Option<ExceptionalHalt>,
+    ) {
+        let frame = match self.frames.last_mut() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let op = match frame.pending.take() {
+            Some(op) => op,
+            None => return,
+        };
+        let mut operation = VmOperation {
+            pc: op.pc,
+            cost: op.gas.saturating_sub(*machine.gas_available()),
+            ex: None,
+            sub: None,
+        };
+
+        use Instruction::*;
+        match (error, op.instruction) {
+            (Some(_), _) => {}
+            (None, Create | Create2 | Call | CallCode | DelegateCall | StaticCall) => {
+                // The result of the message is pushed when the frame resumes.
+                frame.resuming = Some(op);
+            }
+            (None, Return | Revert) => {
+                let (offset, size) = (machine.stack().get(0), machine.stack().get(1));
+                if let (Some(offset), Some(size)) = (offset, size) {
+                    if !size.is_zero() {
+                        let offset = offset.low_u64() as usize;
+                        let data = &machine.memory()[offset..offset + size.low_u64() as usize];
+                        frame.returned = data.to_vec();
+                    }
+                }
+                operation.ex = Some(executed(&op, machine, 0));
+            }
+            (None, instruction) => {
+                let pushed = instruction.delta_alpha().1 as usize;
+                operation.ex = Some(executed(&op, machine, pushed));
+            }
+        }
+        frame.vm.ops.push(operation);
+    }
+
+    fn call(
+        &mut self,
+        machine: Option<&mut MachineState>,
+        env: Option<&ExecutionEnv>,
+        substate: &AccruedSubstate,
+        message: &Message,
+    ) -> Option<Outcome> {
+        self.prestate.call(machine, env, substate, message);
+
+        let mut trace_address = Vec::new();
+        if let Some(parent) = self.frames.last_mut() {
+            trace_address = self.traces[parent.index].trace_address.clone();
+            trace_address.push(parent.subtraces);
+            parent.subtraces += 1;
+        }
+
+        let gas = json!(format!("{:#x}", message.gas()));
+        let value = json!(format!("{:#x}", message.value()));
+        let (trace_type, action) = if message.kind().is_create() {
+            let action = json!({
+                "from": format!("{:?}", message.caller()),
+                "gas": gas,
+                "init": to_hex(message.input()),
+                "value": value,
+            });
+            ("create", action)
+        } else {
+            let (call_type, from) = match message.kind() {
+                CallKind::CallCode => ("callcode", *message.caller()),
+                // A delegate call is made by the contract it acts on behalf of.
+                CallKind::DelegateCall => ("delegatecall", *message.target()),
+                CallKind::StaticCall => ("staticcall", *message.caller()),
+                _ => ("call", *message.caller()),
+            };
+            let action = json!({
+                "callType": call_type,
+                "from": format!("{:?}", from),
+                "to": format!("{:?}", message.code_address()),
+                "gas": gas,
+                "input": to_hex(message.input()),
+                "value": value,
+            });
+            ("call", action)
+        };
+
+        self.traces.push(FlatTrace {
+            trace_type,
+            action: match action {
+                Value::Object(action) => action,
+                _ => unreachable!("action is an object"),
+            },
+            result: None,
+            error: None,
+            subtraces: 0,
+            trace_address,
+        });
+        self.frames.push(Frame {
+            index: self.traces.len() - 1,
+            subtraces: 0,
+            gas: message.gas(),
+            vm: VmTrace::default(),
+            pending: None,
+            resuming: None,
+            returned: Vec::new(),
+        });
+        None
+    }
+
+    fn call_end(
+        &mut self,
+        _: Option<&mut MachineState>,
+        _: Option<&ExecutionEnv>,
+        _: &AccruedSubstate,
+        outcome: &mut Outcome,
+    ) {
+        let frame = match self.frames.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let trace = &mut self.traces[frame.index];
+        trace.subtraces = frame.subtraces;
+        let gas_used = frame.gas.saturating_sub(outcome.gas_left());
+        let gas_used = json!(format!("{:#x}", gas_used));
+
+        match error_message(outcome.status()) {
+            Some(error) => trace.error = Some(error),
+            None if trace.trace_type == "create" => {
+                let address = outcome.created_address().copied().unwrap_or_default();
+                trace.result = Some(json_object(json!({
+                    "address": format!("{:?}", address),
+                    "code": to_hex(&frame.returned),
+                    "gasUsed": gas_used,
+                })));
+            }
+            None => {
+                trace.result = Some(json_object(json!({
+                    "gasUsed": gas_used,
+                    "output": to_hex(outcome.output()),
+                })));
+            }
+        }
+
+        match self.frames.last_mut() {
+            Some(parent) => {
+                if let Some(last) = parent.vm.ops.last_mut() {
+                    last.sub = Some(frame.vm);
+                }
+            }
+            None => self.vm_trace = Some(frame.vm),
+        }
+    }
+
+    fn self_destruct(&mut self, address: &Address, beneficiary: &Address, balance: U256) {
+        self.prestate.self_destruct(address, beneficiary, balance);
+        let parent = match self.frames.last_mut() {
+            Some(parent) => parent,
+            None => return,
+        };
+        let mut trace_address = self.traces[parent.index].trace_address.clone();
+        trace_address.push(parent.subtraces);
+        parent.subtraces += 1;
+        self.traces.push(FlatTrace {
+            trace_type: "suicide",
+            action: json_object(json!({
+                "address": format!("{:?}", address),
+                "balance": format!("{:#x}", balance),
+                "refundAddress": format!("{:?}", beneficiary),
+            })),
+            result: None,
+            error: None,
+            subtraces: 0,
+            trace_address,
+        });
+    }
+}
+
+/// Executes `tx` as `execute_transaction` does, returning its `output`, `trace`, `vmTrace`
+/// and `stateDiff` as the OpenEthereum `trace_replayTransaction` method does.
+#[allow(clippy::too_many_arguments)]
+pub fn replay_transaction(
+    state: &mut WorldState,
+    header: &BlockHeader,
+    block_hashes: &dyn BlockHashes,
+    fork: Fork,
+    chain_id: U256,
+    tx: &Transaction,
+    gas_remaining: U256,
+    blob_params: Option<BlobParams>,
+) -> Result<Value, TransactionError> {
+    let pre = state.clone();
+    let mut tracer = ParityTracer::new();
+    tracer.touch(*header.beneficiary());
+    let outcome = inspect_transaction(
+        state,
+        header,
+        block_hashes,
+        fork,
+        chain_id,
+        tx,
+        gas_remaining,
+        blob_params,
+        &mut tracer,
+    )?;
+    let traces: Vec<_> = tracer.traces().iter().map(FlatTrace::to_json).collect();
+    Ok(json!({
+        "output": to_hex(outcome.output()),
+        "trace": traces,
+        "vmTrace": tracer.vm_trace().map_or(Value::Null, VmTrace::to_json),
+        "stateDiff": tracer.state_diff_json(&pre, state),
+    }))
+}
+
+/// Returns the effects of the instruction `op` on `machine`, which pushed `pushed` items.
+fn executed(op: &PendingOp, machine: &MachineState, pushed: usize) -> VmExecuted {
+    let stack = machine.stack().as_slice();
+    let push = stack[stack.len().saturating_sub(pushed)..].to_vec();
+    let mem = op.mem.and_then(|(offset, size)| {
+        let data = machine.memory().get(offset..offset + size)?;
+        Some((offset, data.to_vec()))
+    });
+    VmExecuted {
+        used: *machine.gas_available(),
+        push,
+        mem,
+        store: op.store,
+    }
+}
+
+/// Returns the message OpenEthereum gives for an execution ending with `status`, if it
+/// failed.
+fn error_message(status: Status) -> Option<&'static str> {
+    use ExceptionalHalt::*;
+    let halt = match status {
+        Status::Success => return None,
+        Status::Revert => return Some("Reverted"),
+        Status::Halt(halt) => halt,
+    };
+    Some(match halt {
+        OutOfGas | CodeSizeExceeded | InitCodeSizeExceeded => "Out of gas",
+        InvalidInstruction | InvalidCodePrefix | AddressOutOfRange | InvalidContainer => {
+            "Bad instruction"
+        }
+        StackUnderflow => "Stack underflow",
+        StackOverflow => "Out of stack",
+        BadJumpDestination => "Bad jump destination",
+        StaticStateChange => "Mutable Call In Static Context",
+        ReturnDataOutOfBounds => "Out of bounds",
+        CallDepthExceeded | InsufficientBalance | NonceOverflow | CreateCollision => {
+            "Internal error"
+        }
+        PrecompileFailure => "Built-in failed",
+    })
+}
+
+fn json_object(value: Value) -> Map<String, Value> {
+    match value {
+        Value::Object(object) => object,
+        _ => Map::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::transaction::TransactionType;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_replay_transaction() {
+        let secret = H256::from_low_u64_be(1);
+        let contract = Address::repeat_byte(0xcc);
+        let callee = Address::repeat_byte(0xbb);
+        // Stores 0x2a in slot 0, then calls the callee for a 32-byte output.
+        //
+        // PUSH1 0x2a PUSH1 0 SSTORE PUSH1 0x20 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0
+        // PUSH20 callee GAS CALL STOP
+        let code = hex::decode(format!(
+            "602a6000556020600060006000600073{}5af100",
+            hex::encode(callee)
+        ))
+        .unwrap();
+        // PUSH1 1 PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+        let callee_code = hex::decode("600160005260206000f3").unwrap();
+
+        let mut tx = Transaction::new(
+            TransactionType::Legacy,
+            None,
+            U256::zero(),
+            U256::from(100_000),
+            Some(contract),
+            U256::zero(),
+            Vec::new(),
+        );
+        tx.sign(&secret);
+        let sender = tx.sender().unwrap();
+
+        let mut state = WorldState::new();
+        state.insert_account(sender, U256::zero(), U256::zero(), Vec::new(), None);
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+        state.insert_account(callee, U256::zero(), U256::zero(), callee_code, None);
+        let mut header = BlockHeader::default();
+        header.set_gas_limit(U256::from(1_000_000));
+        let hashes = HashMap::new();
+        let gas = header.gas_limit();
+        let replay = replay_transaction(
+            &mut state,
+            &header,
+            &hashes,
+            Fork::Cancun,
+            U256::one(),
+            &tx,
+            gas,
+            Some(BlobParams::cancun()),
+        )
+        .unwrap();
+
+        let word = format!("{:?}", H256::from_low_u64_be(1));
+        let trace = replay["trace"].as_array().unwrap();
+        assert_eq!(trace.len(), 2);
+        assert_eq!(trace[0]["type"], json!("call"));
+        assert_eq!(trace[0]["traceAddress"], json!([]));
+        assert_eq!(trace[0]["subtraces"], json!(1));
+        assert_eq!(trace[0]["action"]["from"], json!(format!("{:?}", sender)));
+        assert_eq!(trace[1]["traceAddress"], json!([0]));
+        assert_eq!(trace[1]["action"]["callType"], json!("call"));
+        assert_eq!(trace[1]["action"]["to"], json!(format!("{:?}", callee)));
+        assert_eq!(trace[1]["result"]["output"], json!(word));
+
+        let ops = replay["vmTrace"]["ops"].as_array().unwrap();
+        assert_eq!(
+            ops[2]["ex"]["store"],
+            json!({ "key": "0x0", "val": "0x2a" })
+        );
+        let call = &ops[10];
+        assert_eq!(call["ex"]["push"], json!(["0x1"]));
+        assert_eq!(call["ex"]["mem"], json!({ "off": 0, "data": word }));
+        let sub = &call["sub"];
+        assert_eq!(sub["code"], json!("0x600160005260206000f3"));
+        assert_eq!(sub["ops"].as_array().unwrap().len(), 6);
+        assert_eq!(
+            sub["ops"][2]["ex"]["mem"],
+            json!({ "off": 0, "data": word })
+        );
+
+        let diff = &replay["stateDiff"];
+        assert_eq!(
+            diff[format!("{:?}", sender)]["nonce"],
+            json!({ "*": { "from": "0x0", "to": "0x1" } })
+        );
+        let contract_diff = &diff[format!("{:?}", contract)];
+        assert_eq!(contract_diff["balance"], json!("="));
+        assert_eq!(
+            contract_diff["storage"][format!("{:?}", H256::zero())],
+            json!({ "*": { "from": format!("{:?}", H256::zero()), "to": format!("{:?}", H256::from_low_u64_be(0x2a)) } })
+        );
+        // The callee is unchanged.
+        assert!(diff.get(format!("{:?}", callee)).is_none());
+    }
+}
@@ -0,0 +1,340 @@
+use super::to_hex;
+use crate::core::executor::transaction::{inspect_transaction, TransactionError};
+use crate::core::fork::Fork;
+use crate::core::spec::BlobParams;
+use crate::core::state::WorldState;
+use crate::core::types::block::BlockHeader;
+use crate::core::types::transaction::Transaction;
+use crate::core::vm::env::{BlockHashes, ExecutionEnv};
+use crate::core::vm::inspector::Inspector;
+use crate::core::vm::instruction::Instruction;
+use crate::core::vm::interpreter::{Message, Outcome};
+use crate::core::vm::machine::MachineState;
+use crate::core::vm::substate::AccruedSubstate;
+use ethereum_types::{Address, BigEndianHash, H256, U256};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A tracer recording the accounts and storage slots touched by a transaction, to report
+/// their values before and after it like the geth `prestateTracer`.
+///
+/// The tracer only sees execution, so the accounts touched outside of it, such as the
+/// coinbase, must be added with `touch`.
+#[derive(Default)]
+pub struct PrestateTracer {
+    accounts: BTreeSet<Address>,
+    storage: BTreeMap<Address, BTreeSet<U256>>,
+}
+
+impl PrestateTracer {
+    /// Returns a tracer that has recorded nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that `address` is touched.
+    pub fn touch(&mut self, address: Address) {
+        self.accounts.insert(address);
+    }
+
+    /// Returns the touched accounts.
+    pub fn accounts(&self) -> &BTreeSet<Address> {
+        &self.accounts
+    }
+
+    /// Returns the touched storage slots, by account.
+    pub fn storage(&self) -> &BTreeMap<Address, BTreeSet<U256>> {
+        &self.storage
+    }
+
+    /// Returns the touched accounts and storage slots with their values in `pre`, in the
+    /// format of the geth `prestateTracer`.
+    pub fn prestate_json(&self, pre: &WorldState) -> Value {
+        let mut accounts = Map::new();
+        for address in &self.accounts {
+            let slots = self
+                .slots(address)
+                .map(|key| (key, pre.storage(address, &key)));
+            let account = account_json(pre, address, true, true, true, slots);
+            accounts.insert(format!("{:?}", address), account);
+        }
+        Value::Object(accounts)
+    }
+
+    /// Returns the touched accounts that changed between `pre` and `post` with their values
+    /// in each, in the format of the geth `prestateTracer` in diff mode.
+    ///
+    /// The pre-state holds every field of the changed accounts that existed, but only their
+    /// changed storage slots. The post-state holds only the changed fields of the accounts
+    /// that still exist. Zero storage values are left out of both.
+    pub fn diff_json(&self, pre: &WorldState, post: &WorldState) -> Value {
+        let mut pre_accounts = Map::new();
+        let mut post_accounts = Map::new();
+        for address in &self.accounts {
+            let changed_slots: Vec<U256> = self
+                .slots(address)
+                .filter(|key| pre.storage(address, key) != post.storage(address, key))
+                .collect();
+            let balance_changed = pre.balance(address) != post.balance(address);
+            let nonce_changed = pre.nonce(address) != post.nonce(address);
+            let code_changed = pre.code(address) != post.code(address);
+            let existence_changed = pre.exists(address) != post.exists(address);
+            if !(balance_changed
+                || nonce_changed
+                || code_changed
+                || existence_changed
+                || !changed_slots.is_empty())
+            {
+                continue;
+            }
+
+            let key = format!("{:?}", address);
+            if pre.exists(address) {
+                let slots = changed_slots
+                    .iter()
+                    .map(|key| (*key, pre.storage(address, key)))
+                    .filter(|(_, value)| !value.is_zero());
+                let account = account_json(pre, address, true, true, true, slots);
+                pre_accounts.insert(key.clone(), account);
+            }
+            if post.exists(address) {
+                let slots = changed_slots
+                    .iter()
+                    .map(|key| (*key, post.storage(address, key)))
+                    .filter(|(_, value)| !value.is_zero());
+                let created = !pre.exists(address);
+                let account = account_json(
+                    post,
+                    address,
+                    balance_changed || created,
+                    nonce_changed || created,
+                    code_changed || created,
+                    slots,
+                );
+                post_accounts.insert(key, account);
+            }
+        }
+        json!({ "pre": pre_accounts, "post": post_accounts })
+    }
+
+    /// Returns the touched storage slots of `address`.
+    fn slots<'a>(&'a self, address: &Address) -> impl Iterator<Item = U256> + 'a {
+        self.storage.get(address).into_iter().flatten().copied()
+    }
+}
+
+impl Inspector for PrestateTracer {
+    fn step(&mut self, machine: &mut MachineState, env: &ExecutionEnv, _: &AccruedSubstate) {
+        let pc = machine.program_counter().low_u64() as usize;
+        let opcode = match env.machine_code().get(pc) {
+            Some(&opcode) => opcode,
+            None => return,
+        };
+        let top = machine.stack().get(0).copied();
+        match (Instruction::from(opcode), top) {
+            (Instruction::SLoad | Instruction::SStore, Some(key)) => {
+                self.accounts.insert(*env.account());
+                self.storage.entry(*env.account()).or_default().insert(key);
+            }
+            (
+                Instruction::Balance
+                | Instruction::ExtCodeSize
+                | Instruction::ExtCodeCopy
+                | Instruction::ExtCodeHash,
+                Some(word),
+            ) => {
+                self.accounts.insert(Address::from(H256::from_uint(&word)));
+            }
+            _ => {}
+        }
+    }
+
+    fn call(
+        &mut self,
+        _: Option<&mut MachineState>,
+        _: Option<&ExecutionEnv>,
+        _: &AccruedSubstate,
+        message: &Message,
+    ) -> Option<Outcome> {
+        self.accounts.insert(*message.caller());
+        self.accounts.insert(*message.target());
+        self.accounts.insert(*message.code_address());
+        None
+    }
+
+    fn self_destruct(&mut self, address: &Address, beneficiary: &Address, _: U256) {
+        self.accounts.insert(*address);
+        self.accounts.insert(*beneficiary);
+    }
+}
+
+/// Executes `tx` as `execute_transaction` does, returning the accounts it touched in the
+/// format of the geth `prestateTracer`, or in its diff mode if `diff`.
+#[allow(clippy::too_many_arguments)]
+pub fn trace_prestate(
+    state: &mut WorldState,
+    header: &BlockHeader,
+    block_hashes: &dyn BlockHashes,
+    fork: Fork,
+    chain_id: U256,
+    tx: &Transaction,
+    gas_remaining: U256,
+    blob_params: Option<BlobParams>,
+    diff: bool,
+) -> Result<Value, TransactionError> {
+    let pre = state.clone();
+    let mut tracer = PrestateTracer::new();
+    tracer.touch(*header.beneficiary());
+    inspect_transaction(
+        state,
+        header,
+        block_hashes,
+        fork,
+        chain_id,
+        tx,
+        gas_remaining,
+        blob_params,
+        &mut tracer,
+    )?;
+    Ok(if diff {
+        tracer.diff_json(&pre, state)
+    } else {
+        tracer.prestate_json(&pre)
+    })
+}
+
+/// Returns the account at `address` in `state` with the requested fields and storage `slots`.
+/// A zero nonce and empty code or storage are left out.
+fn account_json<I>(
+    state: &WorldState,
+    address: &Address,
+    balance: bool,
+    nonce: bool,
+    code: bool,
+    slots: I,
+) -> Value
+where
+    I: IntoIterator<Item = (U256, U256)>,
+{
+    let mut account = Map::new();
+    if balance {
+        let balance = format!("{:#x}", state.balance(address));
+        account.insert(String::from("balance"), json!(balance));
+    }
+    if nonce && !state.nonce(address).is_zero() {
+        account.insert(String::from("nonce"), json!(state.nonce(address).low_u64()));
+    }
+    if code && !state.code(address).is_empty() {
+        account.insert(String::from("code"), json!(to_hex(state.code(address))));
+    }
+    let storage: Map<_, _> = slots
+        .into_iter()
+        .map(|(key, value)| {
+            let key = format!("{:?}", H256::from_uint(&key));
+            (key, json!(format!("{:?}", H256::from_uint(&value))))
+        })
+        .collect();
+    if !storage.is_empty() {
+        account.insert(String::from("storage"), Value::Object(storage));
+    }
+    Value::Object(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::transaction::TransactionType;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_trace_prestate() {
+        let secret = H256::from_low_u64_be(1);
+        let contract = Address::repeat_byte(0xcc);
+        // Increments slot 0, and clears slot 1.
+        //
+        // PUSH1 1 PUSH1 0 SLOAD ADD PUSH1 0 SSTORE PUSH1 0 PUSH1 1 SSTORE
+        let code = hex::decode("600160005401600055600060015500").unwrap();
+        let storage = vec![(U256::zero(), U256::from(5)), (U256::one(), U256::one())];
+
+        let mut tx = Transaction::new(
+            TransactionType::Legacy,
+            None,
+            U256::zero(),
+            U256::from(100_000),
+            Some(contract),
+            U256::from(7),
+            Vec::new(),
+        );
+        tx.sign(&secret);
+        let sender = tx.sender().unwrap();
+
+        let mut header = BlockHeader::default();
+        header.set_gas_limit(U256::from(1_000_000));
+        let new_state = || {
+            let mut state = WorldState::new();
+            state.insert_account(sender, U256::zero(), U256::from(10), Vec::new(), None);
+            state.insert_account(
+                contract,
+                U256::zero(),
+                U256::zero(),
+                code.clone(),
+                storage.clone(),
+            );
+            state
+        };
+        let trace = |diff| {
+            let mut state = new_state();
+            let hashes = HashMap::new();
+            let (gas, fork) = (header.gas_limit(), Fork::Cancun);
+            trace_prestate(
+                &mut state,
+                &header,
+                &hashes,
+                fork,
+                U256::one(),
+                &tx,
+                gas,
+                Some(BlobParams::cancun()),
+                diff,
+            )
+            .unwrap()
+        };
+
+        let sender_key = format!("{:?}", sender);
+        let contract_key = format!("{:?}", contract);
+        let slot = |n: u64| format!("{:?}", H256::from_low_u64_be(n));
+
+        let prestate = trace(false);
+        assert_eq!(prestate[&sender_key], json!({ "balance": "0xa" }));
+        assert_eq!(
+            prestate[&contract_key]["storage"],
+            json!({ slot(0): slot(5), slot(1): slot(1) })
+        );
+        assert_eq!(
+            prestate[&contract_key]["code"],
+            json!(format!("0x{}", hex::encode(&code)))
+        );
+        // The coinbase is touched, although it does not exist.
+        assert_eq!(
+            prestate[&format!("{:?}", Address::zero())],
+            json!({ "balance": "0x0" })
+        );
+
+        let diff = trace(true);
+        assert_eq!(diff["pre"][&sender_key], json!({ "balance": "0xa" }));
+        assert_eq!(
+            diff["post"][&sender_key],
+            json!({ "balance": "0x3", "nonce": 1 })
+        );
+        assert_eq!(
+            diff["pre"][&contract_key]["storage"],
+            json!({ slot(0): slot(5), slot(1): slot(1) })
+        );
+        assert_eq!(
+            diff["post"][&contract_key],
+            json!({ "balance": "0x7", "storage": { slot(0): slot(6) } })
+        );
+        // The coinbase does not exist after the transaction either.
+        assert!(diff["pre"].get(format!("{:?}", Address::zero())).is_none());
+    }
+}
@@ -0,0 +1,209 @@
+use ethereum_types::H256;
+use keccak_hash::keccak;
+use rlp::RlpStream;
+
+/// Returns the root hash of the Merkle Patricia tree containing `items`. If a key occurs more
+/// than once, the last value wins.
+pub fn trie_root<I, K, V>(items: I) -> H256
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    let mut nodes: Vec<(Vec<u8>, Vec<u8>)> = items
+        .into_iter()
+        .map(|(key, value)| (to_nibbles(key.as_ref()), value.as_ref().to_vec()))
+        .collect();
+
+    // A stable sort keeps duplicate keys in insertion order, so the last one is retained.
+    nodes.sort_by(|a, b| a.0.cmp(&b.0));
+    nodes.reverse();
+    nodes.dedup_by(|a, b| a.0 == b.0);
+    nodes.reverse();
+
+    keccak(encode_node(&nodes, 0))
+}
+
+/// Returns the root hash of the Merkle Patricia tree containing `items`, with each key replaced
+/// by its Keccak-256 hash. This is the layout of the state and storage trees.
+pub fn sec_trie_root<I, K, V>(items: I) -> H256
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    trie_root(
+        items
+            .into_iter()
+            .map(|(key, value)| (keccak(key.as_ref()), value)),
+    )
+}
+
+/// Returns the root hash of the Merkle Patricia tree mapping the RLP encoding of each index to
+/// the corresponding item of `values`. This is the layout of the transactions, receipts and
+/// withdrawals trees.
+pub fn ordered_trie_root<I, V>(values: I) -> H256
+where
+    I: IntoIterator<Item = V>,
+    V: AsRef<[u8]>,
+{
+    trie_root(
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| (rlp::encode(&i), value)),
+    )
+}
+
+/// Splits `bytes` into nibbles, high nibble first.
+fn to_nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect()
+}
+
+/// Returns the hex-prefix encoding of `nibbles`.
+fn hex_prefix(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 };
+    let mut out = Vec::with_capacity(nibbles.len() / 2 + 1);
+
+    let rest = if nibbles.len() % 2 == 1 {
+        out.push(((flag + 1) << 4) | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        out.push(flag << 4);
+        nibbles
+    };
+    for pair in rest.chunks(2) {
+        out.push((pair[0] << 4) | pair[1]);
+    }
+
+    out
+}
+
+/// Returns the RLP encoding of the node holding `items`, whose keys all share the first `depth`
+/// nibbles. `items` must be sorted by key and free of duplicates.
+fn encode_node(items: &[(Vec<u8>, Vec<u8>)], depth: usize) -> Vec<u8> {
+    let mut s = RlpStream::new();
+
+    match items {
+        [] => {
+            s.append_empty_data();
+        }
+        [(key, value)] => {
+            s.begin_list(2);
+            s.append(&hex_prefix(&key[depth..], true));
+            s.append(value);
+        }
+        _ => {
+            let first = &items[0].0[depth..];
+            let last = &items[items.len() - 1].0[depth..];
+            let shared = first
+                .iter()
+                .zip(last.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            if shared > 0 {
+                s.begin_list(2);
+                s.append(&hex_prefix(&first[..shared], false));
+                append_reference(&mut s, encode_node(items, depth + shared));
+            } else {
+                s.begin_list(17);
+
+                // Only the first item can end at this depth, since the keys are sorted.
+                let (value, mut rest) = if items[0].0.len() == depth {
+                    (Some(&items[0].1), &items[1..])
+                } else {
+                    (None, items)
+                };
+
+                for nibble in 0..16 {
+                    let len = rest
+                        .iter()
+                        .take_while(|(key, _)| key[depth] == nibble)
+                        .count();
+                    if len == 0 {
+                        s.append_empty_data();
+                    } else {
+                        append_reference(&mut s, encode_node(&rest[..len], depth + 1));
+                    }
+                    rest = &rest[len..];
+                }
+
+                match value {
+                    Some(value) => s.append(value),
+                    None => s.append_empty_data(),
+                };
+            }
+        }
+    }
+
+    s.out().to_vec()
+}
+
+/// Appends a reference to a child node to `s`: the node itself if its encoding is shorter than
+/// 32 bytes, and its hash otherwise.
+fn append_reference(s: &mut RlpStream, node: Vec<u8>) {
+    if node.len() < 32 {
+        s.append_raw(&node, 1);
+    } else {
+        s.append(&keccak(&node));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keccak_hash::KECCAK_NULL_RLP;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_empty() {
+        let empty: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+        assert_eq!(trie_root(empty), KECCAK_NULL_RLP);
+        assert_eq!(ordered_trie_root(Vec::<Vec<u8>>::new()), KECCAK_NULL_RLP);
+    }
+
+    #[test]
+    fn test_single_item() {
+        // "A" => "aaaa..." from the ethereum/tests trie fixtures.
+        let root = trie_root(vec![(
+            b"A".to_vec(),
+            b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_vec(),
+        )]);
+        assert_eq!(
+            root,
+            H256::from_str("d23786fb4a010da3ce639d66d5e904a11dbc02746d1ce25029e53290cabf28ab")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_branch_with_value() {
+        let root = trie_root(vec![
+            (b"do".to_vec(), b"verb".to_vec()),
+            (b"dog".to_vec(), b"puppy".to_vec()),
+            (b"doge".to_vec(), b"coin".to_vec()),
+            (b"horse".to_vec(), b"stallion".to_vec()),
+        ]);
+        assert_eq!(
+            root,
+            H256::from_str("5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_last_duplicate_wins() {
+        let root = trie_root(vec![
+            (b"dog".to_vec(), b"cat".to_vec()),
+            (b"doe".to_vec(), b"reindeer".to_vec()),
+            (b"dogglesworth".to_vec(), b"cat".to_vec()),
+            (b"dog".to_vec(), b"puppy".to_vec()),
+        ]);
+        assert_eq!(
+            root,
+            H256::from_str("8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3")
+                .unwrap()
+        );
+    }
+}
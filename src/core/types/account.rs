@@ -1,7 +1,9 @@
 use ethereum_types::{H256, U256};
-use keccak_hash::KECCAK_EMPTY;
+use keccak_hash::{KECCAK_EMPTY, KECCAK_NULL_RLP};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
 
 /// An Ethereum account.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Account {
     /// The number of transactions sent from this account (non-contract) or the number
     /// of contract creations made by this account.
@@ -16,13 +18,74 @@ pub struct Account {
 }
 
 impl Account {
+    /// Returns a new account.
+    pub fn new(nonce: U256, balance: U256, storage_root: H256, code_hash: H256) -> Self {
+        Self {
+            nonce,
+            balance,
+            storage_root,
+            code_hash,
+        }
+    }
+
+    /// Returns the number of transactions sent or contract creations made by this account.
+    pub fn nonce(&self) -> U256 {
+        self.nonce
+    }
+
+    /// Returns the number of Wei owned by this account.
+    pub fn balance(&self) -> U256 {
+        self.balance
+    }
+
+    /// Returns the hash of the root node of the storage tree of this account.
+    pub fn storage_root(&self) -> &H256 {
+        &self.storage_root
+    }
+
+    /// Returns the hash of the EVM code of this account.
+    pub fn code_hash(&self) -> &H256 {
+        &self.code_hash
+    }
+
     /// Returns whether this account is a contract account.
-    fn is_contract(&self) -> bool {
+    pub fn is_contract(&self) -> bool {
         self.code_hash != KECCAK_EMPTY
     }
 
     /// Returns whether this account is empty.
-    fn is_empty(&self) -> bool {
+    pub fn is_empty(&self) -> bool {
         self.nonce.is_zero() && self.balance.is_zero() && self.code_hash == KECCAK_EMPTY
     }
 }
+
+impl Default for Account {
+    fn default() -> Self {
+        Self::new(U256::zero(), U256::zero(), KECCAK_NULL_RLP, KECCAK_EMPTY)
+    }
+}
+
+impl Encodable for Account {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4);
+        s.append(&self.nonce);
+        s.append(&self.balance);
+        s.append(&self.storage_root);
+        s.append(&self.code_hash);
+    }
+}
+
+impl Decodable for Account {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(Self {
+            nonce: rlp.val_at(0)?,
+            balance: rlp.val_at(1)?,
+            storage_root: rlp.val_at(2)?,
+            code_hash: rlp.val_at(3)?,
+        })
+    }
+}
@@ -1,10 +1,25 @@
 use super::transaction::Transaction;
-use ethereum_types::{Address, Bloom, H256, U256, U64};
+use super::withdrawal::Withdrawal;
+use ethereum_types::{Address, Bloom, H256, H64, U256};
+use keccak_hash::keccak;
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+
+/// Minimum price (in Wei) of a unit of blob gas (EIP-4844).
+pub const MIN_BLOB_GAS_PRICE: u64 = 1;
+/// Denominator controlling the rate of change of the blob gas price (EIP-4844).
+pub const BLOB_GAS_PRICE_UPDATE_FRACTION: u64 = 3338477;
+/// Blob gas consumed per blob (EIP-4844).
+pub const GAS_PER_BLOB: u64 = 1 << 17;
+/// Blob gas targeted per block, above which the blob gas price rises (EIP-4844).
+pub const TARGET_BLOB_GAS_PER_BLOCK: u64 = 3 * GAS_PER_BLOB;
+/// Maximum blob gas consumed by the transactions of a block (EIP-4844).
+pub const MAX_BLOB_GAS_PER_BLOCK: u64 = 6 * GAS_PER_BLOB;
 
 /// An Ethereum block header.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct BlockHeader {
     parent_hash: H256,
-    omners_hash: H256,
+    ommers_hash: H256,
     beneficiary: Address,
     state_root: H256,
     transactions_root: H256,
@@ -15,14 +30,428 @@ pub struct BlockHeader {
     gas_limit: U256,
     gas_used: U256,
     timestamp: U256,
-    extra_data: Option<[u8; 32]>,
+    extra_data: Vec<u8>,
     mix_hash: H256,
-    nonce: U64,
+    nonce: H64,
+    /// Base fee per gas, since London (EIP-1559).
+    base_fee_per_gas: Option<U256>,
+    /// Root of the withdrawals trie, since Shanghai (EIP-4895).
+    withdrawals_root: Option<H256>,
+    /// Total blob gas consumed by the transactions of the block, since Cancun (EIP-4844).
+    blob_gas_used: Option<U256>,
+    /// Running total of blob gas consumed in excess of the target, since Cancun (EIP-4844).
+    excess_blob_gas: Option<U256>,
+    /// Root of the parent beacon block, since Cancun (EIP-4788).
+    parent_beacon_block_root: Option<H256>,
+}
+
+impl BlockHeader {
+    pub fn parent_hash(&self) -> &H256 {
+        &self.parent_hash
+    }
+
+    pub fn ommers_hash(&self) -> &H256 {
+        &self.ommers_hash
+    }
+
+    pub fn beneficiary(&self) -> &Address {
+        &self.beneficiary
+    }
+
+    pub fn state_root(&self) -> &H256 {
+        &self.state_root
+    }
+
+    pub fn transactions_root(&self) -> &H256 {
+        &self.transactions_root
+    }
+
+    pub fn receipts_root(&self) -> &H256 {
+        &self.receipts_root
+    }
+
+    pub fn logs_bloom(&self) -> &Bloom {
+        &self.logs_bloom
+    }
+
+    pub fn difficulty(&self) -> U256 {
+        self.difficulty
+    }
+
+    pub fn number(&self) -> U256 {
+        self.number
+    }
+
+    pub fn gas_limit(&self) -> U256 {
+        self.gas_limit
+    }
+
+    pub fn gas_used(&self) -> U256 {
+        self.gas_used
+    }
+
+    pub fn timestamp(&self) -> U256 {
+        self.timestamp
+    }
+
+    pub fn extra_data(&self) -> &Vec<u8> {
+        &self.extra_data
+    }
+
+    pub fn mix_hash(&self) -> &H256 {
+        &self.mix_hash
+    }
+
+    pub fn nonce(&self) -> &H64 {
+        &self.nonce
+    }
+
+    pub fn base_fee_per_gas(&self) -> Option<U256> {
+        self.base_fee_per_gas
+    }
+
+    pub fn withdrawals_root(&self) -> Option<&H256> {
+        self.withdrawals_root.as_ref()
+    }
+
+    pub fn blob_gas_used(&self) -> Option<U256> {
+        self.blob_gas_used
+    }
+
+    pub fn excess_blob_gas(&self) -> Option<U256> {
+        self.excess_blob_gas
+    }
+
+    pub fn parent_beacon_block_root(&self) -> Option<&H256> {
+        self.parent_beacon_block_root.as_ref()
+    }
+
+    pub fn set_parent_hash(&mut self, parent_hash: H256) {
+        self.parent_hash = parent_hash;
+    }
+
+    pub fn set_ommers_hash(&mut self, ommers_hash: H256) {
+        self.ommers_hash = ommers_hash;
+    }
+
+    pub fn set_beneficiary(&mut self, beneficiary: Address) {
+        self.beneficiary = beneficiary;
+    }
+
+    pub fn set_state_root(&mut self, state_root: H256) {
+        self.state_root = state_root;
+    }
+
+    pub fn set_transactions_root(&mut self, transactions_root: H256) {
+        self.transactions_root = transactions_root;
+    }
+
+    pub fn set_receipts_root(&mut self, receipts_root: H256) {
+        self.receipts_root = receipts_root;
+    }
+
+    pub fn set_logs_bloom(&mut self, logs_bloom: Bloom) {
+        self.logs_bloom = logs_bloom;
+    }
+
+    pub fn set_difficulty(&mut self, difficulty: U256) {
+        self.difficulty = difficulty;
+    }
+
+    pub fn set_number(&mut self, number: U256) {
+        self.number = number;
+    }
+
+    pub fn set_gas_limit(&mut self, gas_limit: U256) {
+        self.gas_limit = gas_limit;
+    }
+
+    pub fn set_gas_used(&mut self, gas_used: U256) {
+        self.gas_used = gas_used;
+    }
+
+    pub fn set_timestamp(&mut self, timestamp: U256) {
+        self.timestamp = timestamp;
+    }
+
+    pub fn set_extra_data(&mut self, extra_data: Vec<u8>) {
+        self.extra_data = extra_data;
+    }
+
+    pub fn set_mix_hash(&mut self, mix_hash: H256) {
+        self.mix_hash = mix_hash;
+    }
+
+    pub fn set_nonce(&mut self, nonce: H64) {
+        self.nonce = nonce;
+    }
+
+    pub fn set_base_fee_per_gas(&mut self, base_fee_per_gas: Option<U256>) {
+        self.base_fee_per_gas = base_fee_per_gas;
+    }
+
+    pub fn set_withdrawals_root(&mut self, withdrawals_root: Option<H256>) {
+        self.withdrawals_root = withdrawals_root;
+    }
+
+    pub fn set_blob_gas_used(&mut self, blob_gas_used: Option<U256>) {
+        self.blob_gas_used = blob_gas_used;
+    }
+
+    pub fn set_excess_blob_gas(&mut self, excess_blob_gas: Option<U256>) {
+        self.excess_blob_gas = excess_blob_gas;
+    }
+
+    pub fn set_parent_beacon_block_root(&mut self, parent_beacon_block_root: Option<H256>) {
+        self.parent_beacon_block_root = parent_beacon_block_root;
+    }
+
+    /// Returns the hash of the header.
+    pub fn hash(&self) -> H256 {
+        keccak(rlp::encode(self))
+    }
+
+    /// Returns the price (in Wei) of a unit of blob gas in the block, whose rate of change is
+    /// controlled by `update_fraction`, or `None` if the header predates EIP-4844.
+    pub fn blob_gas_price(&self, update_fraction: u64) -> Option<U256> {
+        self.excess_blob_gas.map(|excess| {
+            fake_exponential(
+                U256::from(MIN_BLOB_GAS_PRICE),
+                excess,
+                U256::from(update_fraction),
+            )
+        })
+    }
+}
+
+/// Approximates `factor * e ** (numerator / denominator)` using a Taylor expansion (EIP-4844).
+pub fn fake_exponential(factor: U256, numerator: U256, denominator: U256) -> U256 {
+    let mut i = U256::one();
+    let mut output = U256::zero();
+    let mut accumulator = factor.saturating_mul(denominator);
+    while !accumulator.is_zero() {
+        output = output.saturating_add(accumulator);
+        accumulator = accumulator.saturating_mul(numerator) / (denominator.saturating_mul(i));
+        i += U256::one();
+    }
+    output / denominator
+}
+
+impl Encodable for BlockHeader {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let optional = [
+            self.base_fee_per_gas.is_some(),
+            self.withdrawals_root.is_some(),
+            self.blob_gas_used.is_some(),
+            self.excess_blob_gas.is_some(),
+            self.parent_beacon_block_root.is_some(),
+        ];
+        let len = 15
+            + optional
+                .iter()
+                .rposition(|&present| present)
+                .map_or(0, |i| i + 1);
+
+        s.begin_list(len);
+        s.append(&self.parent_hash);
+        s.append(&self.ommers_hash);
+        s.append(&self.beneficiary);
+        s.append(&self.state_root);
+        s.append(&self.transactions_root);
+        s.append(&self.receipts_root);
+        s.append(&self.logs_bloom);
+        s.append(&self.difficulty);
+        s.append(&self.number);
+        s.append(&self.gas_limit);
+        s.append(&self.gas_used);
+        s.append(&self.timestamp);
+        s.append(&self.extra_data);
+        s.append(&self.mix_hash);
+        s.append(&self.nonce);
+
+        if len > 15 {
+            s.append(&self.base_fee_per_gas.unwrap_or_default());
+        }
+        if len > 16 {
+            s.append(&self.withdrawals_root.unwrap_or_default());
+        }
+        if len > 17 {
+            s.append(&self.blob_gas_used.unwrap_or_default());
+        }
+        if len > 18 {
+            s.append(&self.excess_blob_gas.unwrap_or_default());
+        }
+        if len > 19 {
+            s.append(&self.parent_beacon_block_root.unwrap_or_default());
+        }
+    }
+}
+
+impl Decodable for BlockHeader {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        let len = rlp.item_count()?;
+        if !(15..=20).contains(&len) {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(Self {
+            parent_hash: rlp.val_at(0)?,
+            ommers_hash: rlp.val_at(1)?,
+            beneficiary: rlp.val_at(2)?,
+            state_root: rlp.val_at(3)?,
+            transactions_root: rlp.val_at(4)?,
+            receipts_root: rlp.val_at(5)?,
+            logs_bloom: rlp.val_at(6)?,
+            difficulty: rlp.val_at(7)?,
+            number: rlp.val_at(8)?,
+            gas_limit: rlp.val_at(9)?,
+            gas_used: rlp.val_at(10)?,
+            timestamp: rlp.val_at(11)?,
+            extra_data: rlp.val_at(12)?,
+            mix_hash: rlp.val_at(13)?,
+            nonce: rlp.val_at(14)?,
+            base_fee_per_gas: optional_at(rlp, 15)?,
+            withdrawals_root: optional_at(rlp, 16)?,
+            blob_gas_used: optional_at(rlp, 17)?,
+            excess_blob_gas: optional_at(rlp, 18)?,
+            parent_beacon_block_root: optional_at(rlp, 19)?,
+        })
+    }
+}
+
+/// Decodes the item at `index` of `rlp` if the list is long enough to contain it.
+fn optional_at<T: Decodable>(rlp: &Rlp, index: usize) -> Result<Option<T>, DecoderError> {
+    if index < rlp.item_count()? {
+        rlp.val_at(index).map(Some)
+    } else {
+        Ok(None)
+    }
 }
 
 /// An Ethereum block.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Block {
     header: BlockHeader,
-    omners: Vec<BlockHeader>,
+    ommers: Vec<BlockHeader>,
     transactions: Vec<Transaction>,
+    /// Withdrawals, since Shanghai (EIP-4895).
+    withdrawals: Option<Vec<Withdrawal>>,
+}
+
+impl Block {
+    /// Returns a new block.
+    pub fn new(
+        header: BlockHeader,
+        ommers: Vec<BlockHeader>,
+        transactions: Vec<Transaction>,
+        withdrawals: Option<Vec<Withdrawal>>,
+    ) -> Self {
+        Self {
+            header,
+            ommers,
+            transactions,
+            withdrawals,
+        }
+    }
+
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    pub fn ommers(&self) -> &Vec<BlockHeader> {
+        &self.ommers
+    }
+
+    pub fn transactions(&self) -> &Vec<Transaction> {
+        &self.transactions
+    }
+
+    pub fn withdrawals(&self) -> Option<&Vec<Withdrawal>> {
+        self.withdrawals.as_ref()
+    }
+
+    /// Returns the hash of the block, which is the hash of its header.
+    pub fn hash(&self) -> H256 {
+        self.header.hash()
+    }
+}
+
+impl Encodable for Block {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(if self.withdrawals.is_some() { 4 } else { 3 });
+        s.append(&self.header);
+        s.append_list(&self.transactions);
+        s.append_list(&self.ommers);
+        if let Some(withdrawals) = &self.withdrawals {
+            s.append_list(withdrawals);
+        }
+    }
+}
+
+impl Decodable for Block {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        let len = rlp.item_count()?;
+        if len != 3 && len != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(Self {
+            header: rlp.val_at(0)?,
+            transactions: rlp.list_at(1)?,
+            ommers: rlp.list_at(2)?,
+            withdrawals: optional_list_at(rlp, 3)?,
+        })
+    }
+}
+
+/// Decodes the list at `index` of `rlp` if the list is long enough to contain it.
+fn optional_list_at<T: Decodable>(rlp: &Rlp, index: usize) -> Result<Option<Vec<T>>, DecoderError> {
+    if index < rlp.item_count()? {
+        rlp.list_at(index).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fake_exponential() {
+        let cases: [(u64, u64, u64, u64); 7] = [
+            (1, 0, 1, 1),
+            (38493, 0, 1000, 38493),
+            (0, 1234, 2345, 0),
+            (1, 2, 1, 6),
+            (1, 4, 2, 6),
+            (1, 3, 1, 16),
+            (1, 6, 2, 18),
+        ];
+
+        for (factor, numerator, denominator, expected) in cases {
+            assert_eq!(
+                fake_exponential(factor.into(), numerator.into(), denominator.into()),
+                U256::from(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_header_rlp_roundtrip() {
+        let mut header = BlockHeader::default();
+        header.set_number(U256::from(1));
+        header.set_extra_data(vec![0x42; 32]);
+        assert_eq!(
+            rlp::decode::<BlockHeader>(&rlp::encode(&header)).unwrap(),
+            header
+        );
+
+        header.set_base_fee_per_gas(Some(U256::from(7)));
+        header.set_withdrawals_root(Some(H256::repeat_byte(1)));
+        assert_eq!(
+            rlp::decode::<BlockHeader>(&rlp::encode(&header)).unwrap(),
+            header
+        );
+    }
 }
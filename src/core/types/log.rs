@@ -1,6 +1,8 @@
-use ethereum_types::{Address, H256};
+use ethereum_types::{Address, Bloom, BloomInput, H256};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
 
 /// An Ethereum transaction log entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Log {
     /// Address of the logger.
     address: Address,
@@ -9,3 +11,62 @@ pub struct Log {
     /// Data.
     data: Vec<u8>,
 }
+
+impl Log {
+    /// Returns a new log entry.
+    pub fn new(address: Address, topics: Vec<H256>, data: Vec<u8>) -> Self {
+        Self {
+            address,
+            topics,
+            data,
+        }
+    }
+
+    /// Returns the address of the logger.
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Returns the log topics.
+    pub fn topics(&self) -> &Vec<H256> {
+        &self.topics
+    }
+
+    /// Returns the data.
+    pub fn data(&self) -> &Vec<u8> {
+        &self.data
+    }
+
+    /// Returns the bloom filter composed from the address and topics of the log entry.
+    pub fn bloom(&self) -> Bloom {
+        let mut bloom = Bloom::default();
+        bloom.accrue(BloomInput::Raw(self.address.as_bytes()));
+        for topic in &self.topics {
+            bloom.accrue(BloomInput::Raw(topic.as_bytes()));
+        }
+        bloom
+    }
+}
+
+impl Encodable for Log {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3);
+        s.append(&self.address);
+        s.append_list(&self.topics);
+        s.append(&self.data);
+    }
+}
+
+impl Decodable for Log {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(Self {
+            address: rlp.val_at(0)?,
+            topics: rlp.list_at(1)?,
+            data: rlp.val_at(2)?,
+        })
+    }
+}
@@ -3,3 +3,4 @@ pub mod block;
 pub mod log;
 pub mod receipt;
 pub mod transaction;
+pub mod withdrawal;
@@ -1,10 +1,18 @@
 use super::log::Log;
-use ethereum_types::{Bloom, U256};
+use super::transaction::TransactionType;
+use ethereum_types::{Bloom, H256, U256};
+use rlp::RlpStream;
 
 /// An Ethereum transaction receipt.
-struct Receipt {
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Receipt {
+    /// Type of the transaction the receipt belongs to.
+    tx_type: TransactionType,
     /// Status code of the transaction.
     status_code: U256,
+    /// State root following the execution of the transaction. Receipts created before
+    /// Byzantium carry this in place of the status code.
+    post_state: Option<H256>,
     /// Cumulative gas used in the block containing the transaction receipt as of
     /// the completion of the execution of the transaction.
     cumulative_gas_used: U256,
@@ -13,3 +21,78 @@ struct Receipt {
     /// Bloom filter composed from information in `logs`.
     logs_bloom: Bloom,
 }
+
+impl Receipt {
+    /// Returns a new receipt, computing the bloom filter from `logs`.
+    pub fn new(
+        tx_type: TransactionType,
+        status_code: U256,
+        post_state: Option<H256>,
+        cumulative_gas_used: U256,
+        logs: Vec<Log>,
+    ) -> Self {
+        let mut logs_bloom = Bloom::default();
+        for log in &logs {
+            logs_bloom.accrue_bloom(&log.bloom());
+        }
+
+        Self {
+            tx_type,
+            status_code,
+            post_state,
+            cumulative_gas_used,
+            logs,
+            logs_bloom,
+        }
+    }
+
+    /// Returns the type of the transaction the receipt belongs to.
+    pub fn tx_type(&self) -> TransactionType {
+        self.tx_type
+    }
+
+    /// Returns the status code of the transaction.
+    pub fn status_code(&self) -> U256 {
+        self.status_code
+    }
+
+    /// Returns the state root following the execution of the transaction, if any.
+    pub fn post_state(&self) -> Option<H256> {
+        self.post_state
+    }
+
+    /// Returns the cumulative gas used in the block as of the completion of the transaction.
+    pub fn cumulative_gas_used(&self) -> U256 {
+        self.cumulative_gas_used
+    }
+
+    /// Returns the set of logs created through the execution of the transaction.
+    pub fn logs(&self) -> &Vec<Log> {
+        &self.logs
+    }
+
+    /// Returns the bloom filter composed from information in the logs.
+    pub fn logs_bloom(&self) -> &Bloom {
+        &self.logs_bloom
+    }
+
+    /// Returns the EIP-2718 encoding of the receipt: the RLP list for legacy transactions, and the
+    /// transaction type followed by the RLP list otherwise.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut s = RlpStream::new_list(4);
+        match self.post_state {
+            Some(root) => s.append(&root),
+            None => s.append(&self.status_code),
+        };
+        s.append(&self.cumulative_gas_used);
+        s.append(&self.logs_bloom);
+        s.append_list(&self.logs);
+
+        let mut out = Vec::new();
+        if self.tx_type != TransactionType::Legacy {
+            out.push(self.tx_type as u8);
+        }
+        out.extend_from_slice(&s.out());
+        out
+    }
+}
@@ -1,20 +1,185 @@
-use ethereum_types::{Address, U256};
+use crate::core::crypto::{self, SECP256K1N};
+use crate::core::fork::Fork;
+use ethereum_types::{Address, H256, U256};
+use keccak_hash::keccak;
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use std::convert::TryFrom;
+
+/// Gas paid by every transaction.
+pub const TX_GAS: u64 = 21000;
+/// Additional gas paid by contract creation transactions since Homestead.
+pub const TX_CREATE_GAS: u64 = 32000;
+/// Gas paid for each zero byte of transaction input.
+pub const TX_DATA_ZERO_GAS: u64 = 4;
+/// Gas paid for each non-zero byte of transaction input before Istanbul.
+pub const TX_DATA_NON_ZERO_GAS_FRONTIER: u64 = 68;
+/// Gas paid for each non-zero byte of transaction input since Istanbul (EIP-2028).
+pub const TX_DATA_NON_ZERO_GAS_ISTANBUL: u64 = 16;
+/// Gas paid for each address in an access list (EIP-2930).
+pub const TX_ACCESS_LIST_ADDRESS_GAS: u64 = 2400;
+/// Gas paid for each storage key in an access list (EIP-2930).
+pub const TX_ACCESS_LIST_STORAGE_KEY_GAS: u64 = 1900;
+/// Gas paid for each word of contract creation input since Shanghai (EIP-3860).
+pub const TX_INIT_CODE_WORD_GAS: u64 = 2;
+
+/// An EIP-2718 transaction type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TransactionType {
+    /// Untyped transaction, optionally replay protected by EIP-155.
+    Legacy = 0,
+    /// EIP-2930 access list transaction.
+    AccessList = 1,
+    /// EIP-1559 dynamic fee transaction.
+    DynamicFee = 2,
+    /// EIP-4844 blob transaction.
+    Blob = 3,
+}
+
+impl TryFrom<u8> for TransactionType {
+    type Error = DecoderError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(TransactionType::Legacy),
+            1 => Ok(TransactionType::AccessList),
+            2 => Ok(TransactionType::DynamicFee),
+            3 => Ok(TransactionType::Blob),
+            _ => Err(DecoderError::Custom("unknown transaction type")),
+        }
+    }
+}
+
+/// An entry of an EIP-2930 access list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccessListItem {
+    /// Address to be accessed.
+    address: Address,
+    /// Storage keys of `address` to be accessed.
+    storage_keys: Vec<H256>,
+}
+
+impl AccessListItem {
+    /// Returns a new access list entry.
+    pub fn new(address: Address, storage_keys: Vec<H256>) -> Self {
+        Self {
+            address,
+            storage_keys,
+        }
+    }
+
+    /// Returns the address to be accessed.
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Returns the storage keys to be accessed.
+    pub fn storage_keys(&self) -> &Vec<H256> {
+        &self.storage_keys
+    }
+}
+
+impl Encodable for AccessListItem {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2);
+        s.append(&self.address);
+        s.append_list(&self.storage_keys);
+    }
+}
+
+impl Decodable for AccessListItem {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(Self {
+            address: rlp.val_at(0)?,
+            storage_keys: rlp.list_at(1)?,
+        })
+    }
+}
 
 /// An Ethereum transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Transaction {
+    tx_type: TransactionType,
+    /// Chain the transaction is valid on. `None` for legacy transactions without EIP-155
+    /// replay protection.
+    chain_id: Option<U256>,
     nonce: U256,
+    /// Gas price for legacy and access list transactions, and the maximum fee per gas for
+    /// dynamic fee and blob transactions.
     gas_price: U256,
+    max_priority_fee_per_gas: Option<U256>,
     gas_limit: U256,
     to: Option<Address>,
     value: U256,
+    /// Signature recovery value. Either the EIP-155 `v` value for legacy transactions, or the
+    /// y-parity of the signature for typed transactions.
     v: U256,
     r: U256,
     s: U256,
     init: Option<Vec<u8>>,
     data: Option<Vec<u8>>,
+    access_list: Vec<AccessListItem>,
+    max_fee_per_blob_gas: Option<U256>,
+    blob_versioned_hashes: Vec<H256>,
 }
 
 impl Transaction {
+    /// Returns a new unsigned transaction of type `tx_type` with zero fees. `input` is the
+    /// initialization code if `to` is `None`, and the call data otherwise.
+    pub fn new(
+        tx_type: TransactionType,
+        chain_id: Option<U256>,
+        nonce: U256,
+        gas_limit: U256,
+        to: Option<Address>,
+        value: U256,
+        input: Vec<u8>,
+    ) -> Self {
+        let (init, data) = match to {
+            Some(_) => (None, Some(input)),
+            None => (Some(input), None),
+        };
+        let dynamic_fee = matches!(tx_type, TransactionType::DynamicFee | TransactionType::Blob);
+
+        Self {
+            tx_type,
+            chain_id,
+            nonce,
+            gas_price: U256::zero(),
+            max_priority_fee_per_gas: if dynamic_fee {
+                Some(U256::zero())
+            } else {
+                None
+            },
+            gas_limit,
+            to,
+            value,
+            v: U256::zero(),
+            r: U256::zero(),
+            s: U256::zero(),
+            init,
+            data,
+            access_list: Vec::new(),
+            max_fee_per_blob_gas: if tx_type == TransactionType::Blob {
+                Some(U256::zero())
+            } else {
+                None
+            },
+            blob_versioned_hashes: Vec::new(),
+        }
+    }
+
+    pub fn tx_type(&self) -> TransactionType {
+        self.tx_type
+    }
+
+    pub fn chain_id(&self) -> Option<U256> {
+        self.chain_id
+    }
+
     pub fn nonce(&self) -> U256 {
         self.nonce
     }
@@ -23,6 +188,18 @@ impl Transaction {
         self.gas_price
     }
 
+    /// Returns the maximum fee per gas the sender is willing to pay. This is the gas price for
+    /// transactions that predate EIP-1559.
+    pub fn max_fee_per_gas(&self) -> U256 {
+        self.gas_price
+    }
+
+    /// Returns the maximum priority fee per gas the sender is willing to pay. This is the gas
+    /// price for transactions that predate EIP-1559.
+    pub fn max_priority_fee_per_gas(&self) -> U256 {
+        self.max_priority_fee_per_gas.unwrap_or(self.gas_price)
+    }
+
     pub fn gas_limit(&self) -> U256 {
         self.gas_limit
     }
@@ -54,4 +231,441 @@ impl Transaction {
     pub fn data(&self) -> &Option<Vec<u8>> {
         &self.data
     }
+
+    pub fn access_list(&self) -> &Vec<AccessListItem> {
+        &self.access_list
+    }
+
+    pub fn max_fee_per_blob_gas(&self) -> Option<U256> {
+        self.max_fee_per_blob_gas
+    }
+
+    pub fn blob_versioned_hashes(&self) -> &Vec<H256> {
+        &self.blob_versioned_hashes
+    }
+
+    /// Returns the initialization code of a contract creation, or the call data of a message
+    /// call.
+    pub fn input(&self) -> &[u8] {
+        self.init
+            .as_ref()
+            .or(self.data.as_ref())
+            .map(|input| input.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Returns whether the transaction creates a contract.
+    pub fn is_create(&self) -> bool {
+        self.to.is_none()
+    }
+
+    /// Sets the gas price, or the maximum fee per gas for dynamic fee and blob transactions.
+    pub fn set_gas_price(&mut self, gas_price: U256) {
+        self.gas_price = gas_price;
+    }
+
+    /// Sets the maximum priority fee per gas. Has no effect on transactions that predate
+    /// EIP-1559.
+    pub fn set_max_priority_fee_per_gas(&mut self, fee: U256) {
+        if self.max_priority_fee_per_gas.is_some() {
+            self.max_priority_fee_per_gas = Some(fee);
+        }
+    }
+
+    /// Sets the access list.
+    pub fn set_access_list(&mut self, access_list: Vec<AccessListItem>) {
+        self.access_list = access_list;
+    }
+
+    /// Sets the maximum fee per blob gas. Has no effect on non-blob transactions.
+    pub fn set_max_fee_per_blob_gas(&mut self, fee: U256) {
+        if self.max_fee_per_blob_gas.is_some() {
+            self.max_fee_per_blob_gas = Some(fee);
+        }
+    }
+
+    /// Sets the versioned hashes of the blobs carried by the transaction.
+    pub fn set_blob_versioned_hashes(&mut self, hashes: Vec<H256>) {
+        self.blob_versioned_hashes = hashes;
+    }
+
+    /// Sets the signature of the transaction. `v` is either the EIP-155 `v` value for legacy
+    /// transactions, or the y-parity of the signature for typed transactions.
+    pub fn set_signature(&mut self, v: U256, r: U256, s: U256) {
+        self.v = v;
+        self.r = r;
+        self.s = s;
+    }
+
+    /// Signs the transaction with `secret`.
+    pub fn sign(&mut self, secret: &H256) {
+        if let Some((id, r, s)) = crypto::sign(&self.signing_hash(), secret) {
+            let v = match (self.tx_type, self.chain_id) {
+                (TransactionType::Legacy, Some(chain_id)) => chain_id * 2 + 35 + id,
+                (TransactionType::Legacy, None) => U256::from(27 + id),
+                _ => U256::from(id),
+            };
+            self.set_signature(v, r, s);
+        }
+    }
+
+    /// Returns the y-parity of the signature, or `None` if `v` is malformed.
+    pub fn recovery_id(&self) -> Option<u8> {
+        match self.tx_type {
+            TransactionType::Legacy => match self.chain_id {
+                Some(chain_id) => {
+                    let parity = self.v.checked_sub(chain_id * 2 + 35)?;
+                    if parity > U256::one() {
+                        return None;
+                    }
+                    Some(parity.low_u32() as u8)
+                }
+                None if self.v == U256::from(27) || self.v == U256::from(28) => {
+                    Some(self.v.low_u32() as u8 - 27)
+                }
+                None => None,
+            },
+            _ if self.v <= U256::one() => Some(self.v.low_u32() as u8),
+            _ => None,
+        }
+    }
+
+    /// Returns whether `s` lies in the lower half of the curve order, as required since
+    /// Homestead (EIP-2).
+    pub fn has_low_s(&self) -> bool {
+        self.s <= SECP256K1N / 2
+    }
+
+    /// Returns the address of the sender recovered from the signature, or `None` if the
+    /// signature is invalid.
+    pub fn sender(&self) -> Option<Address> {
+        crypto::recover(&self.signing_hash(), self.recovery_id()?, &self.r, &self.s)
+    }
+
+    /// Returns the hash of the transaction.
+    pub fn hash(&self) -> H256 {
+        keccak(self.encode())
+    }
+
+    /// Returns the hash signed by the sender of the transaction.
+    pub fn signing_hash(&self) -> H256 {
+        let mut s = RlpStream::new();
+        self.rlp_append_payload(&mut s, false);
+        keccak(self.envelope(&s.out()))
+    }
+
+    /// Returns the EIP-2718 encoding of the transaction: the RLP list for legacy transactions,
+    /// and the transaction type followed by the RLP list otherwise.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut s = RlpStream::new();
+        self.rlp_append_payload(&mut s, true);
+        self.envelope(&s.out())
+    }
+
+    /// Decodes a transaction from its EIP-2718 encoding.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecoderError> {
+        match bytes.first() {
+            None => Err(DecoderError::RlpIsTooShort),
+            Some(&first) if first >= 0xc0 => Self::decode_legacy(&Rlp::new(bytes)),
+            Some(&first) => Self::decode_typed(TransactionType::try_from(first)?, &bytes[1..]),
+        }
+    }
+
+    /// Returns the intrinsic gas of the transaction under the rules of `fork`. This is the
+    /// amount of gas that must be paid before any code executes.
+    pub fn intrinsic_gas(&self, fork: Fork) -> U256 {
+        let mut gas = TX_GAS;
+
+        if self.is_create() && fork >= Fork::Homestead {
+            gas += TX_CREATE_GAS;
+        }
+
+        let non_zero_gas = if fork >= Fork::Istanbul {
+            TX_DATA_NON_ZERO_GAS_ISTANBUL
+        } else {
+            TX_DATA_NON_ZERO_GAS_FRONTIER
+        };
+        let input = self.input();
+        let zeros = input.iter().filter(|&&byte| byte == 0).count() as u64;
+        let non_zeros = input.len() as u64 - zeros;
+        let mut gas = U256::from(gas)
+            + U256::from(zeros) * TX_DATA_ZERO_GAS
+            + U256::from(non_zeros) * non_zero_gas;
+
+        for item in &self.access_list {
+            gas = gas
+                + TX_ACCESS_LIST_ADDRESS_GAS
+                + U256::from(item.storage_keys.len()) * TX_ACCESS_LIST_STORAGE_KEY_GAS;
+        }
+
+        if self.is_create() && fork >= Fork::Shanghai {
+            let words = (input.len() as u64).div_ceil(32);
+            gas += U256::from(words) * TX_INIT_CODE_WORD_GAS;
+        }
+
+        gas
+    }
+
+    /// Prefixes an encoded payload with the transaction type, if any.
+    fn envelope(&self, payload: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(payload.len() + 1);
+        if self.tx_type != TransactionType::Legacy {
+            out.push(self.tx_type as u8);
+        }
+        out.extend_from_slice(payload);
+        out
+    }
+
+    /// Appends the RLP list of transaction fields to `s`, including the signature if `signed`.
+    fn rlp_append_payload(&self, s: &mut RlpStream, signed: bool) {
+        let chain_id = self.chain_id.unwrap_or_default();
+
+        let len = match self.tx_type {
+            TransactionType::Legacy => 6,
+            TransactionType::AccessList => 8,
+            TransactionType::DynamicFee => 9,
+            TransactionType::Blob => 11,
+        };
+        let legacy_replay_protected =
+            self.tx_type == TransactionType::Legacy && self.chain_id.is_some();
+        let extra = if signed || legacy_replay_protected {
+            3
+        } else {
+            0
+        };
+        s.begin_list(len + extra);
+
+        if self.tx_type != TransactionType::Legacy {
+            s.append(&chain_id);
+        }
+        s.append(&self.nonce);
+        if let Some(max_priority_fee_per_gas) = self.max_priority_fee_per_gas {
+            s.append(&max_priority_fee_per_gas);
+        }
+        s.append(&self.gas_price);
+        s.append(&self.gas_limit);
+        match &self.to {
+            Some(to) => s.append(to),
+            None => s.append_empty_data(),
+        };
+        s.append(&self.value);
+        s.append(&self.input());
+        if self.tx_type != TransactionType::Legacy {
+            s.append_list(&self.access_list);
+        }
+        if let Some(max_fee_per_blob_gas) = self.max_fee_per_blob_gas {
+            s.append(&max_fee_per_blob_gas);
+            s.append_list(&self.blob_versioned_hashes);
+        }
+
+        if signed {
+            s.append(&self.v);
+            s.append(&self.r);
+            s.append(&self.s);
+        } else if legacy_replay_protected {
+            s.append(&chain_id);
+            s.append(&0u8);
+            s.append(&0u8);
+        }
+    }
+
+    /// Decodes the RLP list of a legacy transaction.
+    fn decode_legacy(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 9 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        let v: U256 = rlp.val_at(6)?;
+        let chain_id = if v >= U256::from(35) {
+            Some((v - 35) / 2)
+        } else {
+            None
+        };
+
+        let mut tx = Self::new(
+            TransactionType::Legacy,
+            chain_id,
+            rlp.val_at(0)?,
+            rlp.val_at(2)?,
+            decode_to(&rlp.at(3)?)?,
+            rlp.val_at(4)?,
+            rlp.val_at(5)?,
+        );
+        tx.gas_price = rlp.val_at(1)?;
+        tx.set_signature(v, rlp.val_at(7)?, rlp.val_at(8)?);
+
+        Ok(tx)
+    }
+
+    /// Decodes the RLP list of a typed transaction.
+    fn decode_typed(tx_type: TransactionType, bytes: &[u8]) -> Result<Self, DecoderError> {
+        let rlp = Rlp::new(bytes);
+        if !rlp.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if rlp.payload_info()?.total() != bytes.len() {
+            return Err(DecoderError::RlpInconsistentLengthAndData);
+        }
+
+        let (len, fee_offset) = match tx_type {
+            TransactionType::Legacy => return Err(DecoderError::Custom("untyped transaction")),
+            TransactionType::AccessList => (11, 0),
+            TransactionType::DynamicFee => (12, 1),
+            TransactionType::Blob => (14, 1),
+        };
+        if rlp.item_count()? != len {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        let to = decode_to(&rlp.at(4 + fee_offset)?)?;
+        if tx_type == TransactionType::Blob && to.is_none() {
+            return Err(DecoderError::Custom("blob transaction without recipient"));
+        }
+
+        let mut tx = Self::new(
+            tx_type,
+            Some(rlp.val_at(0)?),
+            rlp.val_at(1)?,
+            rlp.val_at(3 + fee_offset)?,
+            to,
+            rlp.val_at(5 + fee_offset)?,
+            rlp.val_at(6 + fee_offset)?,
+        );
+        if fee_offset == 1 {
+            tx.max_priority_fee_per_gas = Some(rlp.val_at(2)?);
+        }
+        tx.gas_price = rlp.val_at(2 + fee_offset)?;
+        tx.access_list = rlp.list_at(7 + fee_offset)?;
+        if tx_type == TransactionType::Blob {
+            tx.max_fee_per_blob_gas = Some(rlp.val_at(9)?);
+            tx.blob_versioned_hashes = rlp.list_at(10)?;
+        }
+        tx.set_signature(
+            rlp.val_at(len - 3)?,
+            rlp.val_at(len - 2)?,
+            rlp.val_at(len - 1)?,
+        );
+
+        Ok(tx)
+    }
+}
+
+/// Decodes the recipient of a transaction, where the empty string denotes contract creation.
+fn decode_to(rlp: &Rlp) -> Result<Option<Address>, DecoderError> {
+    if rlp.is_empty() {
+        Ok(None)
+    } else {
+        rlp.as_val().map(Some)
+    }
+}
+
+impl Encodable for Transaction {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        match self.tx_type {
+            TransactionType::Legacy => self.rlp_append_payload(s, true),
+            _ => {
+                s.append(&self.encode());
+            }
+        }
+    }
+}
+
+impl Decodable for Transaction {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.is_list() {
+            Self::decode_legacy(rlp)
+        } else {
+            let bytes = rlp.data()?;
+            match bytes.first() {
+                Some(&first) if first < 0x80 => {
+                    Self::decode_typed(TransactionType::try_from(first)?, &bytes[1..])
+                }
+                _ => Err(DecoderError::Custom("invalid transaction envelope")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn secret() -> H256 {
+        H256::from_str("45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8").unwrap()
+    }
+
+    #[test]
+    fn test_decode_legacy_eip155() {
+        // Example transaction from EIP-155.
+        let bytes = hex::decode("f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83").unwrap();
+        let tx = Transaction::decode(&bytes).unwrap();
+
+        assert_eq!(tx.tx_type(), TransactionType::Legacy);
+        assert_eq!(tx.chain_id(), Some(U256::one()));
+        assert_eq!(tx.nonce(), U256::from(9));
+        assert_eq!(
+            tx.sender(),
+            Some(Address::from_str("9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f").unwrap())
+        );
+        assert_eq!(tx.encode(), bytes);
+    }
+
+    #[test]
+    fn test_sign_and_recover() {
+        let sender = crypto::secret_to_address(&secret()).unwrap();
+
+        for tx_type in [
+            TransactionType::Legacy,
+            TransactionType::AccessList,
+            TransactionType::DynamicFee,
+            TransactionType::Blob,
+        ] {
+            let mut tx = Transaction::new(
+                tx_type,
+                Some(U256::one()),
+                U256::zero(),
+                U256::from(TX_GAS),
+                Some(Address::repeat_byte(0x11)),
+                U256::one(),
+                vec![0x00, 0x01],
+            );
+            tx.sign(&secret());
+
+            let decoded = Transaction::decode(&tx.encode()).unwrap();
+            assert_eq!(decoded, tx);
+            assert_eq!(decoded.sender(), Some(sender));
+            assert_eq!(rlp::decode::<Transaction>(&rlp::encode(&tx)).unwrap(), tx);
+        }
+    }
+
+    #[test]
+    fn test_intrinsic_gas() {
+        let mut tx = Transaction::new(
+            TransactionType::AccessList,
+            Some(U256::one()),
+            U256::zero(),
+            U256::zero(),
+            None,
+            U256::zero(),
+            vec![0x00, 0x01],
+        );
+        tx.set_access_list(vec![AccessListItem::new(
+            Address::zero(),
+            vec![H256::zero()],
+        )]);
+
+        assert_eq!(
+            tx.intrinsic_gas(Fork::Frontier),
+            U256::from(21000 + 4 + 68 + 4300)
+        );
+        assert_eq!(
+            tx.intrinsic_gas(Fork::Istanbul),
+            U256::from(53000 + 4 + 16 + 4300)
+        );
+        assert_eq!(
+            tx.intrinsic_gas(Fork::Shanghai),
+            U256::from(53000 + 4 + 16 + 4300 + 2)
+        );
+    }
 }
@@ -0,0 +1,77 @@
+use ethereum_types::{Address, U256};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+
+/// A validator withdrawal from the beacon chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Withdrawal {
+    /// Monotonically increasing identifier of the withdrawal.
+    index: u64,
+    /// Index of the validator the withdrawal originates from.
+    validator_index: u64,
+    /// Address of the account credited by the withdrawal.
+    address: Address,
+    /// Amount (in Gwei) credited by the withdrawal.
+    amount: u64,
+}
+
+impl Withdrawal {
+    /// Returns a new withdrawal.
+    pub fn new(index: u64, validator_index: u64, address: Address, amount: u64) -> Self {
+        Self {
+            index,
+            validator_index,
+            address,
+            amount,
+        }
+    }
+
+    /// Returns the identifier of the withdrawal.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns the index of the validator the withdrawal originates from.
+    pub fn validator_index(&self) -> u64 {
+        self.validator_index
+    }
+
+    /// Returns the address of the account credited by the withdrawal.
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Returns the amount (in Gwei) credited by the withdrawal.
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    /// Returns the amount (in Wei) credited by the withdrawal.
+    pub fn amount_wei(&self) -> U256 {
+        U256::from(self.amount) * U256::exp10(9)
+    }
+}
+
+impl Encodable for Withdrawal {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4);
+        s.append(&self.index);
+        s.append(&self.validator_index);
+        s.append(&self.address);
+        s.append(&self.amount);
+    }
+}
+
+impl Decodable for Withdrawal {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        Ok(Self {
+            index: rlp.val_at(0)?,
+            validator_index: rlp.val_at(1)?,
+            address: rlp.val_at(2)?,
+            amount: rlp.val_at(3)?,
+        })
+    }
+}
@@ -0,0 +1,446 @@
+use super::cfg::ControlFlowGraph;
+use super::disassembler::Disassembler;
+use super::instruction::Instruction;
+use super::stack::MAX_LEN;
+use std::collections::BTreeSet;
+
+/// A way the stack fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackIssueKind {
+    Underflow,
+    Overflow,
+}
+
+/// An instruction at which the stack fails on some path through the control-flow graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StackIssue {
+    kind: StackIssueKind,
+    pc: usize,
+    /// Whether the stack fails on every path found that reaches the instruction.
+    guaranteed: bool,
+}
+
+impl StackIssue {
+    /// Returns whether the stack underflows or overflows.
+    pub fn kind(&self) -> StackIssueKind {
+        self.kind
+    }
+
+    /// Returns the offset of the failing instruction.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Returns whether the stack fails on every path found that reaches the instruction, rather
+    /// than on some of them.
+    pub fn is_guaranteed(&self) -> bool {
+        self.guaranteed
+    }
+}
+
+/// The effect of a basic block on the stack, and its static gas.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockSummary {
+    start: usize,
+    /// The items each instruction needs above the height on entry to the block, with its
+    /// offset.
+    needs: Vec<(usize, usize)>,
+    /// The height after each instruction relative to the height on entry, with its offset.
+    heights: Vec<(usize, isize)>,
+    gas: u64,
+    /// The lowest and highest stack heights the block is entered with, if it is reached.
+    entry_heights: Option<(usize, usize)>,
+}
+
+impl BlockSummary {
+    fn new(code: &[u8], start: usize, end: usize) -> Self {
+        let mut needs = Vec::new();
+        let mut heights = Vec::new();
+        let mut height = 0isize;
+        let mut gas = 0u64;
+        for operation in Disassembler::new(&code[start..end]) {
+            let instruction = operation.instruction();
+            let (delta, alpha) = instruction.delta_alpha();
+            let pc = start + operation.pc();
+            needs.push((pc, (delta as isize - height).max(0) as usize));
+            height += alpha as isize - delta as isize;
+            heights.push((pc, height));
+            gas = gas.saturating_add(instruction.info().base_gas());
+        }
+        Self {
+            start,
+            needs,
+            heights,
+            gas,
+            entry_heights: None,
+        }
+    }
+
+    /// Returns the offset of the first instruction.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the fewest stack items the block must be entered with to not underflow.
+    pub fn required(&self) -> usize {
+        self.needs.iter().map(|(_, need)| *need).max().unwrap_or(0)
+    }
+
+    /// Returns the change in stack height from entry to exit.
+    pub fn delta(&self) -> isize {
+        self.heights.last().map_or(0, |(_, height)| *height)
+    }
+
+    /// Returns the most the stack grows above its height on entry.
+    pub fn max_growth(&self) -> usize {
+        self.heights
+            .iter()
+            .map(|(_, height)| *height)
+            .max()
+            .unwrap_or(0)
+            .max(0) as usize
+    }
+
+    /// Returns the gas charged by the instructions of the block as of the latest fork, before
+    /// any cost depending on operands, memory expansion or accessed state.
+    pub fn gas(&self) -> u64 {
+        self.gas
+    }
+
+    /// Returns the lowest and highest stack heights the block is entered with, or `None` if no
+    /// path found reaches it.
+    pub fn entry_heights(&self) -> Option<(usize, usize)> {
+        self.entry_heights
+    }
+
+    /// Returns the entry heights at which the block runs without the stack failing.
+    fn valid_entry(&self) -> Option<(usize, usize)> {
+        let (low, high) = self.entry_heights?;
+        let low = low.max(self.required());
+        let high = high.min(MAX_LEN.checked_sub(self.max_growth())?);
+        (low <= high).then_some((low, high))
+    }
+}
+
+/// A static analysis of the stack heights and gas of code, over its control-flow graph.
+///
+/// The stack height along any one path is determined by the instructions on it, so an issue
+/// is reported where some path found leads to a failure, although the path may not be feasible
+/// for any input, and is guaranteed where every path found does. An unresolved jump is taken to
+/// lead to every `JUMPDEST`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaticAnalysis {
+    /// The summary of each block, in the order of their starts.
+    blocks: Vec<BlockSummary>,
+    issues: Vec<StackIssue>,
+    max_gas: u64,
+    has_loops: bool,
+}
+
+impl StaticAnalysis {
+    /// Returns the analysis of the code of `cfg`.
+    pub fn new(cfg: &ControlFlowGraph) -> Self {
+        let code = cfg.code();
+        let mut blocks: Vec<_> = cfg
+            .blocks()
+            .iter()
+            .map(|block| BlockSummary::new(code, block.start(), block.end()))
+            .collect();
+        let jump_dests: Vec<usize> = (0..blocks.len())
+            .filter(|&index| code[blocks[index].start] == Instruction::JumpDest as u8)
+            .collect();
+        let successors: Vec<Vec<usize>> = cfg
+            .blocks()
+            .iter()
+            .map(|block| {
+                let mut successors: Vec<_> = block
+                    .successors()
+                    .iter()
+                    .filter_map(|edge| {
+                        cfg.blocks()
+                            .binary_search_by_key(&edge.target(), |b| b.start())
+                            .ok()
+                    })
+                    .collect();
+                if block.has_unresolved_jump() {
+                    successors.extend(jump_dests.iter().copied());
+                    successors.sort_unstable();
+                    successors.dedup();
+                }
+                successors
+            })
+            .collect();
+
+        // Widens the entry heights of each block until none changes. Heights are bounded by
+        // the stack limit, so this terminates even for code whose stack grows in loops.
+        let mut pending = BTreeSet::new();
+        if !blocks.is_empty() {
+            blocks[0].entry_heights = Some((0, 0));
+            pending.insert(0);
+        }
+        while let Some(index) = pending.pop_first() {
+            let (low, high) = match blocks[index].valid_entry() {
+                Some(heights) => heights,
+                None => continue,
+            };
+            let delta = blocks[index].delta();
+            let exit = (
+                (low as isize + delta) as usize,
+                (high as isize + delta) as usize,
+            );
+            for &successor in &successors[index] {
+                let entry = &mut blocks[successor].entry_heights;
+                let widened = match *entry {
+                    Some((low, high)) => (low.min(exit.0), high.max(exit.1)),
+                    None => exit,
+                };
+                if *entry != Some(widened) {
+                    *entry = Some(widened);
+                    pending.insert(successor);
+                }
+            }
+        }
+
+        let mut issues = Vec::new();
+        for block in &blocks {
+            let (low, high) = match block.entry_heights {
+                Some(heights) => heights,
+                None => continue,
+            };
+            // The first instruction failing on some path, and the first failing on every path.
+            let underflows = |entry: usize| {
+                block
+                    .needs
+                    .iter()
+                    .find(|(_, need)| *need > entry)
+                    .map(|(pc, _)| *pc)
+            };
+            let overflows = |entry: usize| {
+                block
+                    .heights
+                    .iter()
+                    .find(|(_, height)| entry as isize + *height > MAX_LEN as isize)
+                    .map(|(pc, _)| *pc)
+            };
+            for (kind, some, every) in [
+                (StackIssueKind::Underflow, underflows(low), underflows(high)),
+                (StackIssueKind::Overflow, overflows(high), overflows(low)),
+            ] {
+                if let Some(pc) = some {
+                    issues.push(StackIssue {
+                        kind,
+                        pc,
+                        guaranteed: every == Some(pc),
+                    });
+                }
+                if let Some(pc) = every.filter(|pc| some != Some(*pc)) {
+                    issues.push(StackIssue {
+                        kind,
+                        pc,
+                        guaranteed: true,
+                    });
+                }
+            }
+        }
+        issues.sort_by_key(StackIssue::pc);
+
+        let (max_gas, has_loops) = max_path_gas(&blocks, &successors);
+        Self {
+            blocks,
+            issues,
+            max_gas,
+            has_loops,
+        }
+    }
+
+    /// Returns the summaries of the blocks, in the order of their starts.
+    pub fn blocks(&self) -> &Vec<BlockSummary> {
+        &self.blocks
+    }
+
+    /// Returns the summary of the block starting at `start`.
+    pub fn block(&self, start: usize) -> Option<&BlockSummary> {
+        self.blocks
+            .binary_search_by_key(&start, |block| block.start)
+            .ok()
+            .map(|i| &self.blocks[i])
+    }
+
+    /// Returns the instructions at which the stack fails on some path, in the order of their
+    /// offsets. Those at which it fails on every path are guaranteed.
+    pub fn issues(&self) -> &Vec<StackIssue> {
+        &self.issues
+    }
+
+    /// Returns the most static gas charged along a path from the entry that does not repeat a
+    /// block. Loops are counted once.
+    pub fn max_gas(&self) -> u64 {
+        self.max_gas
+    }
+
+    /// Returns whether some path repeats a block, so `max_gas` does not bound the gas of every
+    /// execution.
+    pub fn has_loops(&self) -> bool {
+        self.has_loops
+    }
+}
+
+/// Returns the most gas charged along a path from the first block without back edges, and
+/// whether there are back edges, which close loops.
+fn max_path_gas(blocks: &[BlockSummary], successors: &[Vec<usize>]) -> (u64, bool) {
+    if blocks.is_empty() {
+        return (0, false);
+    }
+
+    // A depth-first search yields the blocks in postorder, so each block without a back edge
+    // is visited after its successors.
+    let mut on_path = vec![false; blocks.len()];
+    let mut visited = vec![false; blocks.len()];
+    let mut max_gas = vec![0u64; blocks.len()];
+    let mut has_loops = false;
+    let mut stack = vec![(0, 0)];
+    visited[0] = true;
+    on_path[0] = true;
+    while let Some((index, next)) = stack.pop() {
+        if let Some(&successor) = successors[index].get(next) {
+            stack.push((index, next + 1));
+            if on_path[successor] {
+                has_loops = true;
+            } else if !visited[successor] {
+                visited[successor] = true;
+                on_path[successor] = true;
+                stack.push((successor, 0));
+            }
+            continue;
+        }
+
+        on_path[index] = false;
+        let tail = successors[index]
+            .iter()
+            // The blocks still on the path are the targets of back edges.
+            .filter(|&&successor| !on_path[successor])
+            .map(|&successor| max_gas[successor])
+            .max()
+            .unwrap_or(0);
+        max_gas[index] = blocks[index].gas.saturating_add(tail);
+    }
+    (max_gas[0], has_loops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vm::assembler::assemble;
+
+    fn analyze(source: &str) -> StaticAnalysis {
+        StaticAnalysis::new(&ControlFlowGraph::new(&assemble(source).unwrap()))
+    }
+
+    #[test]
+    fn test_stack_heights() {
+        let analysis = analyze(
+            "
+            PUSH1 0x00
+            CALLDATALOAD
+            PUSH two
+            JUMPI
+            PUSH1 0x01
+            POP
+            STOP
+            two:
+                PUSH1 0x01
+                PUSH1 0x02
+                ADD
+                POP
+                STOP
+            ",
+        );
+        assert!(analysis.issues().is_empty());
+        assert!(!analysis.has_loops());
+        let two = analysis.block(10).unwrap();
+        assert_eq!(two.entry_heights(), Some((0, 0)));
+        assert_eq!(two.max_growth(), 2);
+        assert_eq!(two.delta(), 0);
+        // PUSH1 CALLDATALOAD PUSH1 JUMPI, then JUMPDEST PUSH1 PUSH1 ADD POP STOP.
+        assert_eq!(analysis.max_gas(), 3 + 3 + 3 + 10 + 1 + 3 + 3 + 3 + 2);
+
+        // ADD with one item when branching, which pushes the second on the other path only.
+        let analysis = analyze(
+            "
+            PUSH1 0x01
+            PUSH1 0x00
+            CALLDATALOAD
+            PUSH join
+            JUMPI
+            PUSH1 0x02
+            join:
+                ADD
+                STOP
+            ",
+        );
+        let join = analysis.block(10).unwrap();
+        assert_eq!(join.entry_heights(), Some((1, 2)));
+        assert_eq!(join.required(), 2);
+        assert_eq!(
+            analysis.issues(),
+            &vec![StackIssue {
+                kind: StackIssueKind::Underflow,
+                pc: 11,
+                guaranteed: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_stack_overflow_in_loop() {
+        let analysis = analyze(
+            "
+            start:
+                PUSH0
+                PUSH start
+                JUMP
+            ",
+        );
+        assert!(analysis.has_loops());
+        let issues = analysis.issues();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind(), StackIssueKind::Overflow);
+        // With 1023 items on entry, PUSH1 overflows.
+        assert_eq!(issues[0].pc(), 2);
+        assert!(!issues[0].is_guaranteed());
+        // The loop is counted once: JUMPDEST PUSH0 PUSH1 JUMP.
+        assert_eq!(analysis.max_gas(), 1 + 2 + 3 + 8);
+    }
+
+    #[test]
+    fn test_many_return_addresses() {
+        // An internal function called from more places than the control-flow graph tracks
+        // return addresses for, returning to a POP of an empty stack last.
+        let mut source = String::new();
+        for i in 0..20 {
+            source += &format!("PUSH back{0}\nPUSH function\nJUMP\nback{0}:\n", i);
+        }
+        source += "POP\nSTOP\nfunction:\nJUMP\n";
+        let code = assemble(&source).unwrap();
+        let analysis = StaticAnalysis::new(&ControlFlowGraph::new(&code));
+        let pop = code.len() - 4;
+        assert_eq!(code[pop], Instruction::Pop as u8);
+        let guaranteed: Vec<_> = analysis
+            .issues()
+            .iter()
+            .filter(|issue| issue.is_guaranteed())
+            .collect();
+        assert_eq!(
+            guaranteed,
+            vec![&StackIssue {
+                kind: StackIssueKind::Underflow,
+                pc: pop,
+                guaranteed: true,
+            }]
+        );
+        assert!(analysis
+            .blocks()
+            .iter()
+            .all(|block| block.entry_heights().is_some()));
+    }
+}
@@ -0,0 +1,497 @@
+use super::instruction::Instruction;
+use ethereum_types::U256;
+use std::collections::{HashMap, HashSet};
+
+/// The deepest nesting of macro invocations, which bounds recursive macros.
+const MAX_MACRO_DEPTH: usize = 16;
+
+/// An error assembling source text, with the line it occurred on.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AssemblerError {
+    /// The mnemonic is neither an instruction nor a macro.
+    UnknownInstruction(usize, String),
+    /// The operand is missing, malformed or too large for the instruction.
+    InvalidOperand(usize, String),
+    /// The name is not a defined constant, label or data section.
+    UndefinedName(usize, String),
+    /// The name is already defined.
+    DuplicateName(usize, String),
+    /// The macro is unterminated, nested, too deeply invoked or given the wrong number of
+    /// arguments.
+    InvalidMacro(usize, String),
+    /// The source is the disassembly of an EOF container, which is not assembled back.
+    EofContainer(usize),
+}
+
+/// An item of code, before the operands of pushes are resolved.
+enum Item {
+    /// A label, at a `JUMPDEST`.
+    Label(String),
+    /// A push of an operand, of the given size or the smallest fitting it.
+    Push(Option<usize>, String),
+    Op(u8),
+    Raw(Vec<u8>),
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<(usize, Vec<String>)>,
+}
+
+#[derive(Default)]
+struct Assembler {
+    /// The items of code, with their lines.
+    items: Vec<(usize, Item)>,
+    constants: HashMap<String, U256>,
+    /// The data sections, in the order they follow code.
+    data: Vec<(String, Vec<u8>)>,
+    macros: HashMap<String, Macro>,
+    /// The names of the constants, labels and data sections.
+    names: HashSet<String>,
+    /// The number of macro expansions, which makes the labels of each unique.
+    expansions: usize,
+}
+
+/// Assembles `source` into code.
+///
+/// Each line holds an instruction, such as `PUSH1 0x80` or `MSTORE`, and may be preceded by
+/// labels and followed by a comment starting with `;` or `//`. Mnemonics are case-insensitive.
+///
+/// - `name:` defines a label at a `JUMPDEST`.
+/// - `PUSH operand` pushes with the smallest `PUSHn` fitting the operand, which is a number,
+///   a constant, the offset of a label or data section, or `#name` for the size of a data
+///   section. `PUSHn operand` pushes with the given size.
+/// - `.const NAME value` defines a constant.
+/// - `.data NAME 0x...` defines a data section, which follows code in the order defined.
+/// - `.macro NAME params...` up to `.endmacro` defines a macro, invoked as `NAME args...`
+///   after it is defined. Each `$param` in its body is replaced by the argument, and the labels
+///   defined in its body are local to each expansion.
+///
+/// The output of the disassembler assembles back into the same code: a number leading an
+/// instruction is ignored as its offset, `UNKNOWN 0x0c` is a single byte, and a truncated push
+/// is kept as written. The disassembly of an EOF container is rejected, since its headers are
+/// comments.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    if let Some(i) = source
+        .lines()
+        .position(|line| line.trim_start().starts_with("; code section"))
+    {
+        return Err(AssemblerError::EofContainer(i + 1));
+    }
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, tokenize(line)))
+        .filter(|(_, tokens)| !tokens.is_empty())
+        .collect();
+    let mut assembler = Assembler::default();
+    assembler.parse(lines, 0)?;
+    assembler.emit()
+}
+
+impl Assembler {
+    fn parse(
+        &mut self,
+        lines: Vec<(usize, Vec<String>)>,
+        depth: usize,
+    ) -> Result<(), AssemblerError> {
+        let mut lines = lines.into_iter();
+        while let Some((line, tokens)) = lines.next() {
+            match tokens[0].as_str() {
+                ".macro" => {
+                    let name = match tokens.get(1) {
+                        Some(name) => name.clone(),
+                        None => return Err(AssemblerError::InvalidMacro(line, tokens.join(" "))),
+                    };
+                    let mut body = Vec::new();
+                    loop {
+                        match lines.next() {
+                            Some((_, tokens)) if tokens[0] == ".endmacro" => break,
+                            Some((inner, tokens)) if tokens[0] == ".macro" => {
+                                return Err(AssemblerError::InvalidMacro(inner, tokens.join(" ")))
+                            }
+                            Some(body_line) => body.push(body_line),
+                            None => return Err(AssemblerError::InvalidMacro(line, name)),
+                        }
+                    }
+                    if self.macros.contains_key(&name) {
+                        return Err(AssemblerError::DuplicateName(line, name));
+                    }
+                    let params = tokens[2..].to_vec();
+                    self.macros.insert(name, Macro { params, body });
+                }
+                ".endmacro" => return Err(AssemblerError::InvalidMacro(line, tokens[0].clone())),
+                ".const" => {
+                    let (name, value) = match &tokens[1..] {
+                        [name, value] => (name, value),
+                        _ => return Err(AssemblerError::InvalidOperand(line, tokens.join(" "))),
+                    };
+                    let value = parse_number(value)
+                        .ok_or_else(|| AssemblerError::InvalidOperand(line, value.clone()))?;
+                    self.define(line, name)?;
+                    self.constants.insert(name.clone(), value);
+                }
+                ".data" => {
+                    let (name, bytes) = match &tokens[1..] {
+                        [name, bytes] => (name, bytes),
+                        _ => return Err(AssemblerError::InvalidOperand(line, tokens.join(" "))),
+                    };
+                    let bytes = parse_hex(bytes)
+                        .ok_or_else(|| AssemblerError::InvalidOperand(line, bytes.clone()))?;
+                    self.define(line, name)?;
+                    self.data.push((name.clone(), bytes));
+                }
+                _ => self.parse_instruction(line, &tokens, depth)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_instruction(
+        &mut self,
+        line: usize,
+        mut tokens: &[String],
+        depth: usize,
+    ) -> Result<(), AssemblerError> {
+        while let Some(label) = tokens.first().and_then(|token| token.strip_suffix(':')) {
+            self.define(line, label)?;
+            self.items.push((line, Item::Label(label.to_string())));
+            tokens = &tokens[1..];
+        }
+        // The disassembler leads each instruction with its offset.
+        if tokens.len() > 1 && parse_number(&tokens[0]).is_some() {
+            tokens = &tokens[1..];
+        }
+        let (mnemonic, operands) = match tokens.split_first() {
+            Some(split) => split,
+            None => return Ok(()),
+        };
+
+        if let Some(invoked) = self.macros.get(mnemonic) {
+            if operands.len() != invoked.params.len() || depth >= MAX_MACRO_DEPTH {
+                return Err(AssemblerError::InvalidMacro(line, mnemonic.clone()));
+            }
+            self.expansions += 1;
+            let expansion = self.expansions;
+            let labels: HashSet<&str> = invoked
+                .body
+                .iter()
+                .flat_map(|(_, tokens)| {
+                    let labels = tokens.iter().map_while(|token| token.strip_suffix(':'));
+                    labels.collect::<Vec<_>>()
+                })
+                .collect();
+            let body = invoked
+                .body
+                .iter()
+                .map(|(body_line, tokens)| {
+                    let tokens = tokens
+                        .iter()
+                        .map(|token| {
+                            let param = token.strip_prefix('$');
+                            if let Some(i) =
+                                param.and_then(|p| invoked.params.iter().position(|q| q == p))
+                            {
+                                return operands[i].clone();
+                            }
+                            let label = token.strip_suffix(':').unwrap_or(token);
+                            if labels.contains(label) {
+                                let suffix = &token[label.len()..];
+                                return format!("{}@{}{}", label, expansion, suffix);
+                            }
+                            token.clone()
+                        })
+                        .collect();
+                    (*body_line, tokens)
+                })
+                .collect();
+            return self.parse(body, depth + 1);
+        }
+
+        let item = match (mnemonic.to_uppercase().as_str(), operands) {
+            ("UNKNOWN", [byte]) => match parse_number(byte) {
+                Some(byte) if byte < U256::from(256) => Item::Raw(vec![byte.low_u64() as u8]),
+                _ => return Err(AssemblerError::InvalidOperand(line, byte.clone())),
+            },
+            ("PUSH", [operand]) => Item::Push(None, operand.clone()),
+            (name, operands) => {
+                let instruction = name
+                    .parse::<Instruction>()
+                    .ok()
+                    .filter(|instruction| !instruction.info().is_eof_only())
+                    .ok_or_else(|| AssemblerError::UnknownInstruction(line, mnemonic.clone()))?;
+                let (opcode, size) = (instruction.opcode(), instruction.immediate_size());
+                match operands {
+                    [] if size == 0 => Item::Op(opcode),
+                    [operand] if size > 0 => Item::Push(Some(size), operand.clone()),
+                    [bytes, truncated] if size > 0 && truncated == "(truncated)" => {
+                        let mut raw = vec![opcode];
+                        match parse_hex(bytes) {
+                            Some(bytes) if bytes.len() < size => raw.extend(bytes),
+                            _ => return Err(AssemblerError::InvalidOperand(line, bytes.clone())),
+                        }
+                        Item::Raw(raw)
+                    }
+                    _ => return Err(AssemblerError::InvalidOperand(line, operands.join(" "))),
+                }
+            }
+        };
+        self.items.push((line, item));
+        Ok(())
+    }
+
+    /// Records that `name` is defined on `line`, failing if it already is.
+    fn define(&mut self, line: usize, name: &str) -> Result<(), AssemblerError> {
+        if !self.names.insert(name.to_string()) {
+            return Err(AssemblerError::DuplicateName(line, name.to_string()));
+        }
+        Ok(())
+    }
+
+    fn emit(&self) -> Result<Vec<u8>, AssemblerError> {
+        // The offsets of labels and data sections depend on the sizes of the pushes of them,
+        // which are grown until every operand fits.
+        let mut sizes: Vec<usize> = self
+            .items
+            .iter()
+            .map(|(_, item)| match item {
+                Item::Label(_) | Item::Op(_) => 1,
+                Item::Push(size, _) => 1 + size.unwrap_or(1),
+                Item::Raw(bytes) => bytes.len(),
+            })
+            .collect();
+        let offsets = loop {
+            let offsets = self.offsets(&sizes);
+            let mut changed = false;
+            for (i, (line, item)) in self.items.iter().enumerate() {
+                if let Item::Push(None, operand) = item {
+                    let value = self.resolve(*line, operand, &offsets)?;
+                    let size = 1 + byte_len(value).max(1);
+                    if size > sizes[i] {
+                        sizes[i] = size;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break offsets;
+            }
+        };
+
+        let mut code = Vec::new();
+        for ((line, item), size) in self.items.iter().zip(sizes) {
+            match item {
+                Item::Label(_) => code.push(0x5b),
+                Item::Op(opcode) => code.push(*opcode),
+                Item::Raw(bytes) => code.extend(bytes),
+                Item::Push(_, operand) => {
+                    let value = self.resolve(*line, operand, &offsets)?;
+                    let n = size - 1;
+                    if byte_len(value) > n {
+                        return Err(AssemblerError::InvalidOperand(*line, operand.clone()));
+                    }
+                    let mut word = [0u8; 32];
+                    value.to_big_endian(&mut word);
+                    code.push(0x5f + n as u8);
+                    code.extend(&word[32 - n..]);
+                }
+            }
+        }
+        for (_, bytes) in &self.data {
+            code.extend(bytes);
+        }
+        Ok(code)
+    }
+
+    /// Returns the offsets of the labels and data sections for items of the given sizes.
+    fn offsets(&self, sizes: &[usize]) -> HashMap<&str, usize> {
+        let mut offsets = HashMap::new();
+        let mut offset = 0;
+        for ((_, item), size) in self.items.iter().zip(sizes) {
+            if let Item::Label(name) = item {
+                offsets.insert(name.as_str(), offset);
+            }
+            offset += size;
+        }
+        for (name, bytes) in &self.data {
+            offsets.insert(name.as_str(), offset);
+            offset += bytes.len();
+        }
+        offsets
+    }
+
+    /// Returns the value of the push `operand` on `line`.
+    fn resolve(
+        &self,
+        line: usize,
+        operand: &str,
+        offsets: &HashMap<&str, usize>,
+    ) -> Result<U256, AssemblerError> {
+        if let Some(value) = parse_number(operand) {
+            return Ok(value);
+        }
+        let value = match operand.strip_prefix('#') {
+            Some(name) => self
+                .data
+                .iter()
+                .find(|(data, _)| data == name)
+                .map(|(_, bytes)| U256::from(bytes.len())),
+            None => self
+                .constants
+                .get(operand)
+                .copied()
+                .or_else(|| offsets.get(operand).map(|&offset| U256::from(offset))),
+        };
+        value.ok_or_else(|| AssemblerError::UndefinedName(line, operand.to_string()))
+    }
+}
+
+/// Returns the tokens of `line`, without its comment.
+fn tokenize(line: &str) -> Vec<String> {
+    let end = [line.find(';'), line.find("//")]
+        .iter()
+        .flatten()
+        .min()
+        .copied()
+        .unwrap_or(line.len());
+    line[..end].split_whitespace().map(String::from).collect()
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal number.
+fn parse_number(s: &str) -> Option<U256> {
+    match s.strip_prefix("0x") {
+        Some("") => None,
+        Some(digits) => U256::from_str_radix(digits, 16).ok(),
+        None if s.bytes().all(|b| b.is_ascii_digit()) => U256::from_dec_str(s).ok(),
+        None => None,
+    }
+}
+
+/// Parses `0x`-prefixed hexadecimal bytes.
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    let digits = s.strip_prefix("0x")?;
+    if digits.len() % 2 != 0 || !digits.is_ascii() {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Returns the number of bytes needed to hold `value`.
+fn byte_len(value: U256) -> usize {
+    value.bits().div_ceil(8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vm::disassembler::disassemble;
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            .const SLOT 0x01
+            .data greeting 0x68656c6c6f
+
+            ; Stores `value` in SLOT.
+            .macro store value
+                PUSH $value
+                PUSH SLOT
+                SSTORE
+            .endmacro
+
+            store 0x2a
+            push #greeting      // size
+            PUSH greeting       // offset
+            push 0
+            codecopy
+            PUSH end
+            JUMP
+            INVALID
+            end: STOP
+        ";
+        let code = assemble(source).unwrap();
+        assert_eq!(
+            hex::encode(&code),
+            concat!(
+                "602a600155", // store 0x2a
+                "6005",       // PUSH1 5
+                "6012",       // PUSH1 18, the offset of greeting
+                "6000",       // PUSH1 0
+                "39",         // CODECOPY
+                "6010",       // PUSH1 16, the offset of end
+                "56fe5b00",   // JUMP INVALID JUMPDEST STOP
+                "68656c6c6f", // greeting
+            )
+        );
+    }
+
+    #[test]
+    fn test_assemble_label_size() {
+        // The label is pushed with two bytes once code reaches past 255 bytes.
+        let source = format!("PUSH end\nJUMP\n{}end:", "STOP\n".repeat(300));
+        let code = assemble(&source).unwrap();
+        assert_eq!(&code[..4], &[0x61, 0x01, 0x30, 0x56]);
+        assert_eq!(code[0x130], 0x5b);
+    }
+
+    #[test]
+    fn test_assemble_disassembly() {
+        let code = hex::decode("6080604052340cfe5b61010056630102").unwrap();
+        assert_eq!(assemble(&disassemble(&code)).unwrap(), code);
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        assert_eq!(
+            assemble("PUSH1 0x80\nFOO"),
+            Err(AssemblerError::UnknownInstruction(2, String::from("FOO")))
+        );
+        assert_eq!(
+            assemble("RJUMP 0x0000"),
+            Err(AssemblerError::UnknownInstruction(1, String::from("RJUMP")))
+        );
+        assert_eq!(
+            assemble("PUSH1 0x100"),
+            Err(AssemblerError::InvalidOperand(1, String::from("0x100")))
+        );
+        assert_eq!(
+            assemble("PUSH missing"),
+            Err(AssemblerError::UndefinedName(1, String::from("missing")))
+        );
+        assert_eq!(
+            assemble("a:\na:"),
+            Err(AssemblerError::DuplicateName(2, String::from("a")))
+        );
+        assert_eq!(
+            assemble(".macro loop\nloop\n.endmacro\nloop"),
+            Err(AssemblerError::InvalidMacro(2, String::from("loop")))
+        );
+        // An EOF container with a single STOP.
+        let container = hex::decode("ef00010100040200010001ff0000000080000000").unwrap();
+        assert_eq!(
+            assemble(&disassemble(&container)),
+            Err(AssemblerError::EofContainer(1))
+        );
+    }
+
+    #[test]
+    fn test_assemble_macro_labels() {
+        // Each expansion jumps to its own label.
+        let source = "
+            .macro skip
+                PUSH over
+                JUMP
+                INVALID
+                over:
+            .endmacro
+
+            skip
+            skip
+        ";
+        let code = assemble(source).unwrap();
+        assert_eq!(hex::encode(&code), "600456fe5b600956fe5b");
+    }
+}
@@ -0,0 +1,467 @@
+use super::disassembler::{Disassembler, Operation};
+use super::instruction::{legacy_info, Instruction};
+use super::jumpdest::JumpDests;
+use super::stack::MAX_LEN;
+use ethereum_types::U256;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write;
+
+/// The most stack states at entry to a block that are analyzed, which bounds the analysis of
+/// code whose stack grows in loops. Further states are merged into a stack of unknown values.
+const MAX_ENTRY_STATES: usize = 16;
+/// The most items at the top of the stack tracked across blocks.
+const MAX_TRACKED_DEPTH: usize = 32;
+
+/// How control passes along an edge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EdgeKind {
+    /// Into the next block, by reaching its start or by not taking a `JUMPI`.
+    Fallthrough,
+    /// By a `JUMP`.
+    Jump,
+    /// By taking a `JUMPI`.
+    Branch,
+}
+
+/// An edge to the block starting at `target`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Edge {
+    target: usize,
+    kind: EdgeKind,
+}
+
+impl Edge {
+    /// Returns the start of the block the edge leads to.
+    pub fn target(&self) -> usize {
+        self.target
+    }
+
+    /// Returns how control passes along the edge.
+    pub fn kind(&self) -> EdgeKind {
+        self.kind
+    }
+}
+
+/// A sequence of instructions entered only at its first and left only after its last.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicBlock {
+    start: usize,
+    end: usize,
+    /// The last instruction.
+    terminator: Instruction,
+    successors: BTreeSet<Edge>,
+    /// Whether the block ends with a jump whose target could not be determined.
+    unresolved_jump: bool,
+    reachable: bool,
+}
+
+impl BasicBlock {
+    /// Returns the offset of the first instruction.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the offset following the last instruction and its immediate data.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the last instruction.
+    pub fn terminator(&self) -> Instruction {
+        self.terminator
+    }
+
+    /// Returns the edges to the blocks control may pass to after the block.
+    pub fn successors(&self) -> &BTreeSet<Edge> {
+        &self.successors
+    }
+
+    /// Returns whether the block ends with a jump whose target could not be determined.
+    pub fn has_unresolved_jump(&self) -> bool {
+        self.unresolved_jump
+    }
+
+    /// Returns whether execution can reach the block through the edges found.
+    pub fn is_reachable(&self) -> bool {
+        self.reachable
+    }
+}
+
+/// The control-flow graph of code, recovered without executing it.
+///
+/// Jump targets are resolved by tracking the constants pushed onto the stack, within and
+/// across blocks, so the targets of `PUSH2 0x0040 JUMP` and of the return addresses pushed
+/// by callers of internal functions are found. A jump to a computed target is unresolved, and
+/// a jump to a constant that is not a `JUMPDEST` has no edge, as it always fails. So is a jump
+/// from a block reached with more stacks than are tracked, whose further stacks are unknown.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    code: Vec<u8>,
+    /// The blocks, in the order of their starts.
+    blocks: Vec<BasicBlock>,
+}
+
+/// A value on the stack: a known constant, or unknown.
+type Value = Option<U256>;
+
+impl ControlFlowGraph {
+    /// Returns the control-flow graph of `code`.
+    pub fn new(code: &[u8]) -> Self {
+        let operations: Vec<_> = Disassembler::new(code).collect();
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        let mut first = 0;
+        for (i, operation) in operations.iter().enumerate() {
+            if operation.instruction() == Instruction::JumpDest && i > first {
+                ranges.push((first, i));
+                first = i;
+            }
+            if ends_block(operation) {
+                ranges.push((first, i + 1));
+                first = i + 1;
+            }
+        }
+        if first < operations.len() {
+            ranges.push((first, operations.len()));
+        }
+
+        let mut blocks: Vec<_> = ranges
+            .iter()
+            .map(|&(first, last)| {
+                let terminator = &operations[last - 1];
+                let mut successors = BTreeSet::new();
+                if !halts(terminator) && last < operations.len() {
+                    successors.insert(Edge {
+                        target: operations[last].pc(),
+                        kind: EdgeKind::Fallthrough,
+                    });
+                }
+                BasicBlock {
+                    start: operations[first].pc(),
+                    end: terminator.next_pc().min(code.len()),
+                    terminator: terminator.instruction(),
+                    successors,
+                    unresolved_jump: false,
+                    reachable: false,
+                }
+            })
+            .collect();
+
+        // Propagates the stack at the end of each block to its successors, from the empty
+        // stack at the start of code.
+        let jump_dests = JumpDests::new(code);
+        let mut seen: Vec<HashSet<Vec<Value>>> = vec![HashSet::new(); blocks.len()];
+        // Whether a block has been entered with the stack of unknown values its states are
+        // merged into.
+        let mut merged = vec![false; blocks.len()];
+        let mut pending = Vec::new();
+        if !blocks.is_empty() {
+            seen[0].insert(Vec::new());
+            pending.push((0, Vec::new()));
+        }
+        while let Some((index, mut stack)) = pending.pop() {
+            let (first, last) = ranges[index];
+            blocks[index].reachable = true;
+            let mut target = None;
+            for operation in &operations[first..last] {
+                target = simulate(operation, &mut stack);
+            }
+
+            let block = &mut blocks[index];
+            let jump_kind = match block.terminator {
+                Instruction::Jump => Some(EdgeKind::Jump),
+                Instruction::JumpI => Some(EdgeKind::Branch),
+                _ => None,
+            };
+            if let Some(kind) = jump_kind {
+                match target {
+                    Some(Some(dest)) if dest.bits() <= 64 => {
+                        let dest = dest.low_u64() as usize;
+                        if jump_dests.is_valid(dest) {
+                            block.successors.insert(Edge { target: dest, kind });
+                        }
+                    }
+                    Some(Some(_)) => {}
+                    _ => block.unresolved_jump = true,
+                }
+            }
+
+            let keep = stack.len().saturating_sub(MAX_TRACKED_DEPTH);
+            let stack = stack.split_off(keep);
+            for edge in &blocks[index].successors {
+                let next = match blocks.binary_search_by_key(&edge.target, |block| block.start) {
+                    Ok(next) => next,
+                    Err(_) => continue,
+                };
+                if seen[next].contains(&stack) || merged[next] {
+                    continue;
+                }
+                if seen[next].len() < MAX_ENTRY_STATES {
+                    seen[next].insert(stack.clone());
+                    pending.push((next, stack.clone()));
+                } else {
+                    // An empty stack pops unknown values, so jumps from it are unresolved.
+                    merged[next] = true;
+                    pending.push((next, Vec::new()));
+                }
+            }
+        }
+
+        Self {
+            code: code.to_vec(),
+            blocks,
+        }
+    }
+
+    /// Returns the code the graph was recovered from.
+    pub fn code(&self) -> &Vec<u8> {
+        &self.code
+    }
+
+    /// Returns the blocks, in the order of their starts.
+    pub fn blocks(&self) -> &Vec<BasicBlock> {
+        &self.blocks
+    }
+
+    /// Returns the block starting at `start`.
+    pub fn block(&self, start: usize) -> Option<&BasicBlock> {
+        self.blocks
+            .binary_search_by_key(&start, |block| block.start)
+            .ok()
+            .map(|index| &self.blocks[index])
+    }
+
+    /// Returns the starts of the blocks with an edge to the block starting at `start`.
+    pub fn predecessors(&self, start: usize) -> Vec<usize> {
+        self.blocks
+            .iter()
+            .filter(|block| block.successors.iter().any(|edge| edge.target == start))
+            .map(|block| block.start)
+            .collect()
+    }
+
+    /// Returns the graph in the Graphviz DOT language, with the disassembly of each block.
+    /// Unreachable blocks are grey, and unresolved jumps lead to a node of their own.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box fontname=monospace];\n");
+        let operations: Vec<_> = Disassembler::new(&self.code).collect();
+        let mut unresolved = false;
+        for block in &self.blocks {
+            let mut label = String::new();
+            for operation in operations
+                .iter()
+                .filter(|op| op.pc() >= block.start && op.pc() < block.end)
+            {
+                let _ = write!(label, "{}\\l", operation);
+            }
+            let style = if block.reachable {
+                ""
+            } else {
+                " style=filled fillcolor=lightgrey"
+            };
+            let _ = writeln!(
+                dot,
+                "    block_{} [label=\"{}\"{}];",
+                block.start, label, style
+            );
+            for edge in &block.successors {
+                let label = match (edge.kind, block.terminator) {
+                    (EdgeKind::Branch, _) => " [label=\"true\"]",
+                    (EdgeKind::Fallthrough, Instruction::JumpI) => " [label=\"false\"]",
+                    _ => "",
+                };
+                let _ = writeln!(
+                    dot,
+                    "    block_{} -> block_{}{};",
+                    block.start, edge.target, label
+                );
+            }
+            if block.unresolved_jump {
+                unresolved = true;
+                let _ = writeln!(
+                    dot,
+                    "    block_{} -> unresolved [style=dashed];",
+                    block.start
+                );
+            }
+        }
+        if unresolved {
+            dot.push_str("    unresolved [label=\"?\" shape=circle];\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Returns whether `operation` is the last of its block.
+fn ends_block(operation: &Operation) -> bool {
+    operation.instruction() == Instruction::JumpI || halts(operation)
+}
+
+/// Returns whether execution never continues to the instruction following `operation`.
+fn halts(operation: &Operation) -> bool {
+    use Instruction::*;
+    matches!(
+        operation.instruction(),
+        Stop | Jump | Return | Revert | Invalid | SelfDestruct
+    )
+}
+
+/// Applies `operation` to `stack`, which holds the top of the stack last, returning the target
+/// of a jump.
+fn simulate(operation: &Operation, stack: &mut Vec<Value>) -> Option<Value> {
+    use Instruction::*;
+    let instruction = operation.instruction();
+    match instruction {
+        Push0 => stack.push(Some(U256::zero())),
+        _ if instruction.immediate_size() > 0 => {
+            // Code ending within the immediate data is padded with zeros.
+            let mut word = [0u8; 32];
+            let start = 32 - instruction.immediate_size();
+            word[start..start + operation.immediate().len()].copy_from_slice(operation.immediate());
+            stack.push(Some(U256::from_big_endian(&word)));
+        }
+        Dup1 | Dup2 | Dup3 | Dup4 | Dup5 | Dup6 | Dup7 | Dup8 | Dup9 | Dup10 | Dup11 | Dup12
+        | Dup13 | Dup14 | Dup15 | Dup16 => {
+            let depth = instruction.info().inputs();
+            let value = stack.len().checked_sub(depth).and_then(|i| stack[i]);
+            stack.push(value);
+        }
+        Swap1 | Swap2 | Swap3 | Swap4 | Swap5 | Swap6 | Swap7 | Swap8 | Swap9 | Swap10 | Swap11
+        | Swap12 | Swap13 | Swap14 | Swap15 | Swap16 => {
+            let depth = instruction.info().inputs();
+            if stack.len() < depth {
+                let missing = depth - stack.len();
+                stack.splice(0..0, vec![None; missing]);
+            }
+            let top = stack.len() - 1;
+            stack.swap(top, top + 1 - depth);
+        }
+        Jump => return Some(pop(stack)),
+        JumpI => {
+            let target = pop(stack);
+            pop(stack);
+            return Some(target);
+        }
+        _ => {
+            let info = legacy_info(operation.opcode())?;
+            for _ in 0..info.inputs() {
+                pop(stack);
+            }
+            stack.extend(vec![None; info.outputs()]);
+        }
+    }
+    if stack.len() > MAX_LEN {
+        stack.remove(0);
+    }
+    None
+}
+
+/// Pops the top of `stack`, which is unknown if the stack is.
+fn pop(stack: &mut Vec<Value>) -> Value {
+    stack.pop().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vm::assembler::assemble;
+
+    #[test]
+    fn test_control_flow_graph() {
+        let code = assemble(
+            "
+            PUSH1 0x00
+            CALLDATALOAD
+            PUSH two
+            JUMPI
+            PUSH one
+            JUMP
+            one:
+                STOP
+            two:
+                PUSH 0x2a
+            three:
+                CALLVALUE
+                JUMP
+            ",
+        )
+        .unwrap();
+        let cfg = ControlFlowGraph::new(&code);
+        let starts: Vec<_> = cfg.blocks().iter().map(BasicBlock::start).collect();
+        assert_eq!(starts, vec![0, 6, 9, 11, 14]);
+
+        let entry = cfg.block(0).unwrap();
+        assert_eq!(entry.terminator(), Instruction::JumpI);
+        let edges: Vec<_> = entry
+            .successors()
+            .iter()
+            .map(|e| (e.target(), e.kind()))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![(6, EdgeKind::Fallthrough), (11, EdgeKind::Branch)]
+        );
+        assert_eq!(
+            cfg.block(6).unwrap().successors().iter().next(),
+            Some(&Edge {
+                target: 9,
+                kind: EdgeKind::Jump
+            })
+        );
+        assert!(cfg.block(14).unwrap().has_unresolved_jump());
+        assert_eq!(cfg.predecessors(14), vec![11]);
+        assert!(cfg.blocks().iter().all(BasicBlock::is_reachable));
+
+        let dot = cfg.to_dot();
+        assert!(dot.starts_with("digraph cfg {\n"));
+        assert!(dot.contains("    block_0 -> block_11 [label=\"true\"];\n"));
+        assert!(dot.contains("    block_0 -> block_6 [label=\"false\"];\n"));
+        assert!(dot.contains("    block_14 -> unresolved [style=dashed];\n"));
+        assert!(dot.contains("block_9 [label=\"0x0009 JUMPDEST\\l0x000a STOP\\l\"];"));
+    }
+
+    #[test]
+    fn test_return_address() {
+        // An internal function called from two places returns to the address its caller
+        // pushed.
+        let code = assemble(
+            "
+            PUSH back1
+            PUSH function
+            JUMP
+            back1:
+                PUSH back2
+                PUSH function
+                JUMP
+            back2:
+                STOP
+            function:
+                JUMP
+            dead:
+                STOP
+            ",
+        )
+        .unwrap();
+        let cfg = ControlFlowGraph::new(&code);
+        let function = cfg.block(13).unwrap();
+        let targets: Vec<_> = function.successors().iter().map(Edge::target).collect();
+        assert_eq!(targets, vec![5, 11]);
+        assert!(!function.has_unresolved_jump());
+        assert!(!cfg.blocks().last().unwrap().is_reachable());
+    }
+
+    #[test]
+    fn test_many_return_addresses() {
+        // An internal function called from more places than entry states are analyzed.
+        let mut source = String::new();
+        for i in 0..20 {
+            source += &format!("PUSH back{0}\nPUSH function\nJUMP\nback{0}:\n", i);
+        }
+        source += "STOP\nfunction:\nJUMP\n";
+        let code = assemble(&source).unwrap();
+        let cfg = ControlFlowGraph::new(&code);
+        let function = cfg.blocks().last().unwrap();
+        assert_eq!(function.successors().len(), MAX_ENTRY_STATES);
+        assert!(function.has_unresolved_jump());
+    }
+}
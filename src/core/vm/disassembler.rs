@@ -0,0 +1,234 @@
+use super::eof::{self, EofContainer};
+use super::instruction::{self, Instruction};
+use std::fmt;
+
+/// An instruction in code, with its immediate data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Operation<'a> {
+    pc: usize,
+    opcode: u8,
+    immediate: &'a [u8],
+}
+
+impl<'a> Operation<'a> {
+    /// Returns the offset of the instruction in code.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Returns the byte of the instruction, which may be undefined.
+    pub fn opcode(&self) -> u8 {
+        self.opcode
+    }
+
+    /// Returns the instruction, which is `Invalid` for an opcode undefined in legacy code.
+    pub fn instruction(&self) -> Instruction {
+        instruction::legacy_info(self.opcode)
+            .map_or(Instruction::Invalid, |info| info.instruction())
+    }
+
+    /// Returns the immediate data of the instruction, which is shorter than its immediate size
+    /// if the code ends first.
+    pub fn immediate(&self) -> &'a [u8] {
+        self.immediate
+    }
+
+    /// Returns whether the code ends before the immediate data of the instruction does.
+    pub fn is_truncated(&self) -> bool {
+        self.immediate.len() < self.instruction().immediate_size()
+    }
+
+    /// Returns the offset of the next instruction in code.
+    pub fn next_pc(&self) -> usize {
+        self.pc + 1 + self.instruction().immediate_size()
+    }
+}
+
+impl<'a> fmt::Display for Operation<'a> {
+    /// Formats the operation as its offset, mnemonic and immediate data, such as
+    /// `0x0000 PUSH1 0x80`. An undefined opcode is formatted as `UNKNOWN` with its value.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#06x} ", self.pc)?;
+        if instruction::legacy_info(self.opcode).is_none() {
+            return write!(f, "UNKNOWN {:#04x}", self.opcode);
+        }
+        let instruction = self.instruction();
+        write!(f, "{}", instruction)?;
+        if instruction.immediate_size() > 0 {
+            write!(f, " 0x")?;
+            for byte in self.immediate {
+                write!(f, "{:02x}", byte)?;
+            }
+            if self.is_truncated() {
+                write!(f, " (truncated)")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An iterator over the instructions in code, skipping the immediate data of each.
+#[derive(Clone, Debug)]
+pub struct Disassembler<'a> {
+    code: &'a [u8],
+    pc: usize,
+}
+
+impl<'a> Disassembler<'a> {
+    /// Returns an iterator over the instructions of `code`, from its start.
+    pub fn new(code: &'a [u8]) -> Self {
+        Self { code, pc: 0 }
+    }
+}
+
+impl<'a> Iterator for Disassembler<'a> {
+    type Item = Operation<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let opcode = *self.code.get(self.pc)?;
+        let start = (self.pc + 1).min(self.code.len());
+        let size = instruction::legacy_info(opcode).map_or(0, |info| info.immediate_size());
+        let end = (start + size).min(self.code.len());
+        let operation = Operation {
+            pc: self.pc,
+            opcode,
+            immediate: &self.code[start..end],
+        };
+        self.pc = operation.next_pc();
+        Some(operation)
+    }
+}
+
+/// Returns `code` as text, with one instruction per line. An EOF container is disassembled
+/// section by section, with a comment heading each.
+pub fn disassemble(code: &[u8]) -> String {
+    if let Ok(container) = EofContainer::parse(code) {
+        return disassemble_eof(&container);
+    }
+    Disassembler::new(code)
+        .map(|operation| operation.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns the sections of an EOF `container` as text, with offsets relative to the start of
+/// each code section.
+fn disassemble_eof(container: &EofContainer) -> String {
+    let mut lines = Vec::new();
+    for (index, code) in container.code_sections().iter().enumerate() {
+        let code_type = container.types()[index];
+        let outputs = if code_type.is_returning() {
+            code_type.outputs().to_string()
+        } else {
+            "non-returning".to_string()
+        };
+        lines.push(format!(
+            "; code section {} (inputs {}, outputs {}, max stack increase {})",
+            index,
+            code_type.inputs(),
+            outputs,
+            code_type.max_stack_increase()
+        ));
+        let mut pc = 0;
+        while pc < code.len() {
+            let opcode = code[pc];
+            let Some(name) = eof::mnemonic(opcode) else {
+                lines.push(format!("{:#06x} UNKNOWN {:#04x}", pc, opcode));
+                pc += 1;
+                continue;
+            };
+            let size = eof::immediate_size(code, pc);
+            let end = size.map_or(code.len(), |size| pc + 1 + size);
+            let mut line = format!("{:#06x} {}", pc, name);
+            if end > pc + 1 || size.is_none() {
+                line.push_str(" 0x");
+                for byte in &code[pc + 1..end] {
+                    line.push_str(&format!("{:02x}", byte));
+                }
+            }
+            if size.is_none() {
+                line.push_str(" (truncated)");
+            }
+            lines.push(line);
+            pc = end;
+        }
+    }
+    for (index, container) in container.container_sections().iter().enumerate() {
+        lines.push(format!(
+            "; container section {} ({} bytes)",
+            index,
+            container.len()
+        ));
+    }
+    if !container.data().is_empty() {
+        let data = container.data().iter().map(|b| format!("{:02x}", b));
+        lines.push(format!("; data 0x{}", data.collect::<String>()));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble() {
+        // PUSH1 0x80 PUSH1 0x40 MSTORE CALLVALUE 0x0c INVALID PUSH4 0x0102
+        let code = hex::decode("6080604052340cfe630102").unwrap();
+        let operations: Vec<_> = Disassembler::new(&code).collect();
+        assert_eq!(operations.len(), 7);
+        assert_eq!(operations[1].pc(), 2);
+        assert_eq!(operations[1].instruction(), Instruction::Push1);
+        assert_eq!(operations[1].immediate(), &[0x40]);
+        assert_eq!(operations[6].immediate(), &[0x01, 0x02]);
+        assert!(operations[6].is_truncated());
+        assert_eq!(operations[6].next_pc(), 13);
+
+        assert_eq!(
+            disassemble(&code),
+            [
+                "0x0000 PUSH1 0x80",
+                "0x0002 PUSH1 0x40",
+                "0x0004 MSTORE",
+                "0x0005 CALLVALUE",
+                "0x0006 UNKNOWN 0x0c",
+                "0x0007 INVALID",
+                "0x0008 PUSH4 0x0102 (truncated)",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_disassemble_empty_push() {
+        // PUSH2 at the end of code, and PUSH0.
+        let code = hex::decode("5f61").unwrap();
+        assert_eq!(
+            disassemble(&code),
+            "0x0000 PUSH0\n0x0001 PUSH2 0x (truncated)"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_eof() {
+        // PUSH0 RJUMPI +1 PUSH0 CALLF 1 STOP, and RETF, with data.
+        let code =
+            hex::decode("ef000101000802000200090001ff00010000800001000000005fe100015fe3000100e4aa")
+                .unwrap();
+        assert_eq!(
+            disassemble(&code),
+            [
+                "; code section 0 (inputs 0, outputs non-returning, max stack increase 1)",
+                "0x0000 PUSH0",
+                "0x0001 RJUMPI 0x0001",
+                "0x0004 PUSH0",
+                "0x0005 CALLF 0x0001",
+                "0x0008 STOP",
+                "; code section 1 (inputs 0, outputs 0, max stack increase 0)",
+                "0x0000 RETF",
+                "; data 0xaa",
+            ]
+            .join("\n")
+        );
+    }
+}
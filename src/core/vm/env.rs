@@ -1,7 +1,21 @@
 use crate::core::types::block::BlockHeader;
-use ethereum_types::{Address, U256};
+use ethereum_types::{Address, H256, U256};
+use std::collections::HashMap;
+
+/// A source of the hashes of recent blocks, as read by the `BLOCKHASH` instruction.
+pub trait BlockHashes {
+    /// Returns the hash of the block numbered `number`, if known.
+    fn block_hash(&self, number: U256) -> Option<H256>;
+}
+
+impl BlockHashes for HashMap<U256, H256> {
+    fn block_hash(&self, number: U256) -> Option<H256> {
+        self.get(&number).copied()
+    }
+}
 
 /// EVM execution environment.
+#[derive(Clone)]
 pub struct ExecutionEnv {
     /// Address of the account that owns the code that is executing.
     account: Address,
@@ -27,6 +41,34 @@ pub struct ExecutionEnv {
 }
 
 impl ExecutionEnv {
+    /// Returns a new execution environment.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        account: Address,
+        origin: Address,
+        gas_price: U256,
+        data: Vec<u8>,
+        sender: Address,
+        value: U256,
+        machine_code: Vec<u8>,
+        header: BlockHeader,
+        depth: U256,
+        write_access: bool,
+    ) -> Self {
+        Self {
+            account,
+            origin,
+            gas_price,
+            data,
+            sender,
+            value,
+            machine_code,
+            header,
+            depth,
+            write_access,
+        }
+    }
+
     /// Returns the account that owns the code that is executing.
     pub fn account(&self) -> &Address {
         &self.account
@@ -62,6 +104,11 @@ impl ExecutionEnv {
         &self.machine_code
     }
 
+    /// Sets the machine code to be executed, as when EOF code moves to another code section.
+    pub fn set_machine_code(&mut self, machine_code: Vec<u8>) {
+        self.machine_code = machine_code;
+    }
+
     /// Returns the block header of the current block.
     pub fn header(&self) -> &BlockHeader {
         &self.header
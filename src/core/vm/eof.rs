@@ -0,0 +1,931 @@
+use super::instruction::{Instruction, OpcodeInfo, OPCODES};
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+
+/// Bytes beginning an EOF container (EIP-3540). Code beginning with `0xef` cannot be deployed
+/// as legacy code since London (EIP-3541).
+pub const MAGIC: [u8; 2] = [0xef, 0x00];
+/// The only version of EOF.
+pub const VERSION: u8 = 1;
+
+const KIND_TYPES: u8 = 0x01;
+const KIND_CODE: u8 = 0x02;
+const KIND_CONTAINER: u8 = 0x03;
+const KIND_DATA: u8 = 0xff;
+const TERMINATOR: u8 = 0x00;
+
+/// Maximum number of code sections.
+pub const MAX_CODE_SECTIONS: usize = 1024;
+/// Maximum number of container sections.
+pub const MAX_CONTAINER_SECTIONS: usize = 256;
+/// Maximum height of the stack of a code section (EIP-5450).
+pub const MAX_STACK_HEIGHT: usize = 1023;
+/// The outputs of a code section that never returns to its caller (EIP-6206).
+pub const NON_RETURNING: u8 = 0x80;
+
+/// Opcodes only defined in EOF code.
+pub const DATALOAD: u8 = Instruction::DataLoad as u8;
+pub const DATALOADN: u8 = Instruction::DataLoadN as u8;
+pub const DATASIZE: u8 = Instruction::DataSize as u8;
+pub const DATACOPY: u8 = Instruction::DataCopy as u8;
+pub const RJUMP: u8 = Instruction::RJump as u8;
+pub const RJUMPI: u8 = Instruction::RJumpI as u8;
+pub const RJUMPV: u8 = Instruction::RJumpV as u8;
+pub const CALLF: u8 = Instruction::CallF as u8;
+pub const RETF: u8 = Instruction::RetF as u8;
+pub const JUMPF: u8 = Instruction::JumpF as u8;
+pub const DUPN: u8 = Instruction::DupN as u8;
+pub const SWAPN: u8 = Instruction::SwapN as u8;
+pub const EXCHANGE: u8 = Instruction::Exchange as u8;
+pub const EOFCREATE: u8 = Instruction::EofCreate as u8;
+pub const RETURNCONTRACT: u8 = Instruction::ReturnContract as u8;
+pub const RETURNDATALOAD: u8 = Instruction::ReturnDataLoad as u8;
+pub const EXTCALL: u8 = Instruction::ExtCall as u8;
+pub const EXTDELEGATECALL: u8 = Instruction::ExtDelegateCall as u8;
+pub const EXTSTATICCALL: u8 = Instruction::ExtStaticCall as u8;
+
+/// Legacy opcodes undefined in EOF code, which observe code or gas, or jump dynamically.
+const DEPRECATED: [u8; 16] = [
+    0x38, // CODESIZE
+    0x39, // CODECOPY
+    0x3b, // EXTCODESIZE
+    0x3c, // EXTCODECOPY
+    0x3f, // EXTCODEHASH
+    0x56, // JUMP
+    0x57, // JUMPI
+    0x58, // PC
+    0x5a, // GAS
+    0xf0, // CREATE
+    0xf1, // CALL
+    0xf2, // CALLCODE
+    0xf4, // DELEGATECALL
+    0xf5, // CREATE2
+    0xfa, // STATICCALL
+    0xff, // SELFDESTRUCT
+];
+
+/// A reason for an EOF container to be invalid.
+#[derive(Debug, PartialEq, Eq)]
+pub enum EofError {
+    /// The container does not begin with the magic.
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    /// The header is truncated, or its sections are missing or out of order.
+    InvalidHeader,
+    /// A section has a size of zero or too large for the header, or there are too many
+    /// sections.
+    InvalidSectionSize,
+    /// The size of the types section does not match the number of code sections.
+    InvalidTypesSize,
+    /// The body is shorter or longer than the header declares.
+    InvalidBodySize,
+    /// The type of the code section with the given index is invalid. The first must take no
+    /// inputs and never return.
+    InvalidType(usize),
+    /// The instruction in the given code section and at the given offset is undefined.
+    UndefinedInstruction(usize, usize),
+    /// The immediate data of the instruction in the given code section and at the given
+    /// offset runs past its end.
+    TruncatedImmediate(usize, usize),
+    /// The relative jump in the given code section and at the given offset leads outside of
+    /// the section or into immediate data.
+    InvalidJumpTarget(usize, usize),
+    /// The instruction in the given code section and at the given offset refers to a code
+    /// section, container section or data that does not exist, or to a code section it
+    /// cannot call.
+    InvalidReference(usize, usize),
+    /// The code section with the given index does not end with a terminating instruction.
+    MissingTerminator(usize),
+    /// The instruction in the given code section and at the given offset cannot be reached.
+    UnreachableCode(usize, usize),
+    /// The code section or container section with the given index is never referred to.
+    UnreferencedSection(usize),
+    /// The instruction in the given code section and at the given offset needs more stack
+    /// items than there are.
+    StackUnderflow(usize, usize),
+    /// The instruction in the given code section and at the given offset grows the stack
+    /// past its maximum.
+    StackOverflow(usize, usize),
+    /// The instruction in the given code section and at the given offset is reached with
+    /// different stack heights where they must be the same.
+    StackHeightMismatch(usize, usize),
+    /// The maximum stack increase declared for the code section with the given index is not
+    /// the one computed.
+    InvalidMaxStackIncrease(usize),
+    /// Whether the code section with the given index returns does not match its type.
+    InvalidReturning(usize),
+    /// The container has instructions not allowed for its kind, or a container section is
+    /// referred to both as initcode and as runtime code.
+    IncompatibleContainerKind,
+}
+
+/// What a container is deployed as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContainerKind {
+    /// Code executed to create a contract, which ends with `RETURNCONTRACT` rather than `STOP`
+    /// or `RETURN`.
+    InitCode,
+    /// Code of a deployed contract, without `RETURNCONTRACT`.
+    Runtime,
+}
+
+/// The type of a code section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CodeType {
+    inputs: u8,
+    outputs: u8,
+    max_stack_increase: u16,
+}
+
+impl CodeType {
+    pub fn new(inputs: u8, outputs: u8, max_stack_increase: u16) -> Self {
+        Self {
+            inputs,
+            outputs,
+            max_stack_increase,
+        }
+    }
+
+    /// Returns the number of stack items the section takes.
+    pub fn inputs(&self) -> usize {
+        self.inputs as usize
+    }
+
+    /// Returns the number of stack items the section returns, or `NON_RETURNING`.
+    pub fn outputs(&self) -> usize {
+        self.outputs as usize
+    }
+
+    /// Returns how much the section grows the stack above its inputs at most.
+    pub fn max_stack_increase(&self) -> usize {
+        self.max_stack_increase as usize
+    }
+
+    /// Returns the height the stack reaches at most, counting the inputs.
+    pub fn max_stack_height(&self) -> usize {
+        self.inputs() + self.max_stack_increase()
+    }
+
+    /// Returns whether the section returns to its caller.
+    pub fn is_returning(&self) -> bool {
+        self.outputs != NON_RETURNING
+    }
+}
+
+/// An EOF container (EIP-3540): the code of a contract, split into functions and data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EofContainer {
+    types: Vec<CodeType>,
+    code_sections: Vec<Vec<u8>>,
+    /// Encoded containers, which may be deployed by the code.
+    container_sections: Vec<Vec<u8>>,
+    data: Vec<u8>,
+    /// The size of the data declared in the header, which `data` may fall short of in a
+    /// container not yet deployed.
+    data_size: u16,
+}
+
+/// Returns whether `code` is an EOF container, judging by its magic.
+pub fn is_eof(code: &[u8]) -> bool {
+    code.starts_with(&MAGIC)
+}
+
+impl EofContainer {
+    /// Returns a container of the given sections, without validating their contents. Fails if
+    /// the number or sizes of the sections do not fit in the header.
+    pub fn new(
+        types: Vec<CodeType>,
+        code_sections: Vec<Vec<u8>>,
+        container_sections: Vec<Vec<u8>>,
+        data: Vec<u8>,
+    ) -> Result<Self, EofError> {
+        let max = u16::MAX as usize;
+        if 4 * types.len() > max
+            || code_sections.len() > max
+            || container_sections.len() > max
+            || code_sections.iter().any(|code| code.len() > max)
+            || container_sections
+                .iter()
+                .any(|container| container.len() > u32::MAX as usize)
+        {
+            return Err(EofError::InvalidSectionSize);
+        }
+        let data_size = u16::try_from(data.len()).map_err(|_| EofError::InvalidBodySize)?;
+        Ok(Self {
+            types,
+            code_sections,
+            container_sections,
+            data,
+            data_size,
+        })
+    }
+
+    /// Parses the sections of the container `bytes`, without validating their contents. The
+    /// data section must be complete.
+    pub fn parse(bytes: &[u8]) -> Result<Self, EofError> {
+        Self::parse_sections(bytes, false)
+    }
+
+    /// Parses the sections of the container `bytes`, which may fall short of the data the header
+    /// declares, as a container not yet deployed.
+    pub fn parse_undeployed(bytes: &[u8]) -> Result<Self, EofError> {
+        Self::parse_sections(bytes, true)
+    }
+
+    /// Parses `bytes` as a container deployed as `kind`, validating its code and the
+    /// containers within it.
+    pub fn validate(bytes: &[u8], kind: ContainerKind) -> Result<Self, EofError> {
+        Self::validate_sections(bytes, kind, false)
+    }
+
+    fn validate_sections(
+        bytes: &[u8],
+        kind: ContainerKind,
+        truncated_data: bool,
+    ) -> Result<Self, EofError> {
+        let container = Self::parse_sections(bytes, truncated_data)?;
+        if truncated_data && kind == ContainerKind::InitCode {
+            container.check_data_complete()?;
+        }
+
+        let mut references = References::default();
+        for index in 0..container.code_sections.len() {
+            container.validate_code(index, kind, &mut references)?;
+        }
+
+        // Every code section must be reachable from the first through calls and jumps.
+        let mut reached = BTreeSet::from([0]);
+        let mut pending = vec![0];
+        while let Some(index) = pending.pop() {
+            for &target in &references.sections[index] {
+                if reached.insert(target) {
+                    pending.push(target);
+                }
+            }
+        }
+        if let Some(index) = (0..container.code_sections.len()).find(|i| !reached.contains(i)) {
+            return Err(EofError::UnreferencedSection(index));
+        }
+
+        for (index, bytes) in container.container_sections.iter().enumerate() {
+            let initcode = references.eofcreate.contains(&index);
+            let runtime = references.returncontract.contains(&index);
+            let kind = match (initcode, runtime) {
+                (true, false) => ContainerKind::InitCode,
+                (false, true) => ContainerKind::Runtime,
+                (true, true) => return Err(EofError::IncompatibleContainerKind),
+                (false, false) => return Err(EofError::UnreferencedSection(index)),
+            };
+            Self::validate_sections(bytes, kind, kind == ContainerKind::Runtime)?;
+        }
+        Ok(container)
+    }
+
+    fn parse_sections(bytes: &[u8], truncated_data: bool) -> Result<Self, EofError> {
+        if !is_eof(bytes) {
+            return Err(EofError::InvalidMagic);
+        }
+        let mut reader = Reader { bytes, pos: 2 };
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(EofError::UnsupportedVersion(version));
+        }
+
+        reader.expect(KIND_TYPES)?;
+        let types_size = reader.u16()? as usize;
+        reader.expect(KIND_CODE)?;
+        let num_code_sections = reader.u16()? as usize;
+        if num_code_sections == 0 || num_code_sections > MAX_CODE_SECTIONS {
+            return Err(EofError::InvalidSectionSize);
+        }
+        if types_size != 4 * num_code_sections {
+            return Err(EofError::InvalidTypesSize);
+        }
+        let code_sizes = (0..num_code_sections)
+            .map(|_| reader.u16().map(usize::from))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut container_sizes = Vec::new();
+        let mut kind = reader.u8()?;
+        if kind == KIND_CONTAINER {
+            let num_container_sections = reader.u16()? as usize;
+            if num_container_sections == 0 || num_container_sections > MAX_CONTAINER_SECTIONS {
+                return Err(EofError::InvalidSectionSize);
+            }
+            for _ in 0..num_container_sections {
+                container_sizes.push(reader.u32()? as usize);
+            }
+            kind = reader.u8()?;
+        }
+        if kind != KIND_DATA {
+            return Err(EofError::InvalidHeader);
+        }
+        let data_size = reader.u16()?;
+        reader.expect(TERMINATOR)?;
+        if code_sizes.contains(&0) || container_sizes.contains(&0) {
+            return Err(EofError::InvalidSectionSize);
+        }
+
+        let mut types = Vec::with_capacity(num_code_sections);
+        for index in 0..num_code_sections {
+            let body = reader.take(4).ok_or(EofError::InvalidBodySize)?;
+            let max_stack_increase = u16::from_be_bytes([body[2], body[3]]);
+            let code_type = CodeType::new(body[0], body[1], max_stack_increase);
+            if code_type.inputs > 127
+                || (code_type.outputs > 127 && code_type.is_returning())
+                || code_type.max_stack_height() > MAX_STACK_HEIGHT
+            {
+                return Err(EofError::InvalidType(index));
+            }
+            types.push(code_type);
+        }
+        if types[0].inputs != 0 || types[0].is_returning() {
+            return Err(EofError::InvalidType(0));
+        }
+
+        let mut section = |size| {
+            reader
+                .take(size)
+                .map(<[u8]>::to_vec)
+                .ok_or(EofError::InvalidBodySize)
+        };
+        let code_sections = code_sizes
+            .into_iter()
+            .map(&mut section)
+            .collect::<Result<Vec<_>, _>>()?;
+        let container_sections = container_sizes
+            .into_iter()
+            .map(&mut section)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let data = reader.rest().to_vec();
+        let truncated = data.len() < data_size as usize;
+        if data.len() != data_size as usize && !(truncated_data && truncated) {
+            return Err(EofError::InvalidBodySize);
+        }
+        Ok(Self {
+            types,
+            code_sections,
+            container_sections,
+            data,
+            data_size,
+        })
+    }
+
+    /// Returns the container encoded as bytes. The number and sizes of its sections fit in the
+    /// header, as `new` and parsing check.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        out.push(KIND_TYPES);
+        out.extend(&(4 * self.types.len() as u16).to_be_bytes());
+        out.push(KIND_CODE);
+        out.extend(&(self.code_sections.len() as u16).to_be_bytes());
+        for code in &self.code_sections {
+            out.extend(&(code.len() as u16).to_be_bytes());
+        }
+        if !self.container_sections.is_empty() {
+            out.push(KIND_CONTAINER);
+            out.extend(&(self.container_sections.len() as u16).to_be_bytes());
+            for container in &self.container_sections {
+                out.extend(&(container.len() as u32).to_be_bytes());
+            }
+        }
+        out.push(KIND_DATA);
+        out.extend(&self.data_size.to_be_bytes());
+        out.push(TERMINATOR);
+        for code_type in &self.types {
+            out.push(code_type.inputs);
+            out.push(code_type.outputs);
+            out.extend(&code_type.max_stack_increase.to_be_bytes());
+        }
+        for code in &self.code_sections {
+            out.extend(code);
+        }
+        for container in &self.container_sections {
+            out.extend(container);
+        }
+        out.extend(&self.data);
+        out
+    }
+
+    pub fn types(&self) -> &Vec<CodeType> {
+        &self.types
+    }
+
+    pub fn code_sections(&self) -> &Vec<Vec<u8>> {
+        &self.code_sections
+    }
+
+    /// Returns the encoded containers, which may be deployed by the code.
+    pub fn container_sections(&self) -> &Vec<Vec<u8>> {
+        &self.container_sections
+    }
+
+    pub fn data(&self) -> &Vec<u8> {
+        &self.data
+    }
+
+    /// Returns the size of the data declared in the header, which the data of a container not
+    /// yet deployed may fall short of.
+    pub fn data_size(&self) -> usize {
+        self.data_size as usize
+    }
+
+    /// Appends `aux_data` to the data, as on deployment, and declares the data complete.
+    /// Fails if the result is still short of the declared size or does not fit in the header.
+    pub fn append_data(&mut self, aux_data: &[u8]) -> Result<(), EofError> {
+        let size = self.data.len() + aux_data.len();
+        if size < self.data_size as usize || size > u16::MAX as usize {
+            return Err(EofError::InvalidBodySize);
+        }
+        self.data.extend_from_slice(aux_data);
+        self.data_size = size as u16;
+        Ok(())
+    }
+
+    fn check_data_complete(&self) -> Result<(), EofError> {
+        if self.data.len() != self.data_size as usize {
+            return Err(EofError::InvalidBodySize);
+        }
+        Ok(())
+    }
+
+    /// Validates the instructions and stack heights of the code section with `index`,
+    /// recording the sections it refers to.
+    fn validate_code(
+        &self,
+        index: usize,
+        kind: ContainerKind,
+        references: &mut References,
+    ) -> Result<(), EofError> {
+        let code = &self.code_sections[index];
+        let code_type = self.types[index];
+        let mut starts = vec![false; code.len()];
+        let mut jumps = Vec::new();
+        let mut sections = BTreeSet::new();
+        let mut returns = false;
+
+        let mut pc = 0;
+        let mut opcode = 0;
+        while pc < code.len() {
+            opcode = code[pc];
+            starts[pc] = true;
+            if eof_stack_io(opcode).is_none() {
+                return Err(EofError::UndefinedInstruction(index, pc));
+            }
+            let size = immediate_size(code, pc).ok_or(EofError::TruncatedImmediate(index, pc))?;
+            let immediate = &code[pc + 1..pc + 1 + size];
+            let next = pc + 1 + size;
+            let invalid_reference = EofError::InvalidReference(index, pc);
+
+            match opcode {
+                RJUMP | RJUMPI => jumps.push((pc, relative_target(next, immediate))),
+                RJUMPV => {
+                    for offset in immediate[1..].chunks(2) {
+                        jumps.push((pc, relative_target(next, offset)));
+                    }
+                }
+                CALLF | JUMPF => {
+                    let target = u16::from_be_bytes([immediate[0], immediate[1]]) as usize;
+                    let target_type = self.types.get(target).ok_or(invalid_reference)?;
+                    if opcode == CALLF && !target_type.is_returning() {
+                        return Err(EofError::InvalidReference(index, pc));
+                    }
+                    if opcode == JUMPF && target_type.is_returning() {
+                        returns = true;
+                    }
+                    sections.insert(target);
+                }
+                RETF => returns = true,
+                DATALOADN => {
+                    let offset = u16::from_be_bytes([immediate[0], immediate[1]]) as usize;
+                    if offset + 32 > self.data_size as usize {
+                        return Err(invalid_reference);
+                    }
+                }
+                EOFCREATE | RETURNCONTRACT => {
+                    let container = immediate[0] as usize;
+                    if container >= self.container_sections.len() {
+                        return Err(invalid_reference);
+                    }
+                    if opcode == EOFCREATE {
+                        references.eofcreate.insert(container);
+                    } else if kind == ContainerKind::InitCode {
+                        references.returncontract.insert(container);
+                    } else {
+                        return Err(EofError::IncompatibleContainerKind);
+                    }
+                }
+                0x00 | 0xf3 if kind == ContainerKind::InitCode => {
+                    return Err(EofError::IncompatibleContainerKind)
+                }
+                _ => {}
+            }
+            pc = next;
+        }
+        if !is_terminating(opcode) && opcode != RJUMP {
+            return Err(EofError::MissingTerminator(index));
+        }
+        if returns != code_type.is_returning() {
+            return Err(EofError::InvalidReturning(index));
+        }
+        for (pc, target) in jumps {
+            match target {
+                Some(target) if target < code.len() && starts[target] => {}
+                _ => return Err(EofError::InvalidJumpTarget(index, pc)),
+            }
+        }
+
+        self.validate_stack(index)?;
+        references.sections.push(sections);
+        Ok(())
+    }
+
+    /// Validates the stack heights of the code section with `index`, whose instructions are
+    /// valid (EIP-5450).
+    ///
+    /// Each instruction is reached with a range of stack heights. Instructions are visited in
+    /// order, so all but backward jumps have been seen at the time each is visited, and a
+    /// backward jump must reach its target with the range it was visited with.
+    fn validate_stack(&self, index: usize) -> Result<(), EofError> {
+        let code = &self.code_sections[index];
+        let code_type = self.types[index];
+        let mut heights: Vec<Option<(usize, usize)>> = vec![None; code.len()];
+        heights[0] = Some((code_type.inputs(), code_type.inputs()));
+        let mut max_height = code_type.inputs();
+
+        let mut pc = 0;
+        while pc < code.len() {
+            let opcode = code[pc];
+            let (min, max) = heights[pc].ok_or(EofError::UnreachableCode(index, pc))?;
+            let size = immediate_size(code, pc).unwrap_or_default();
+            let immediate = &code[pc + 1..pc + 1 + size];
+            let next = pc + 1 + size;
+
+            let (inputs, outputs) = match opcode {
+                CALLF | JUMPF => {
+                    let target = u16::from_be_bytes([immediate[0], immediate[1]]) as usize;
+                    let target_type = self.types[target];
+                    if max + target_type.max_stack_increase() > MAX_STACK_HEIGHT {
+                        return Err(EofError::StackOverflow(index, pc));
+                    }
+                    if opcode == JUMPF && target_type.is_returning() {
+                        // The target returns to the caller of this section.
+                        let height = (code_type.outputs() + target_type.inputs())
+                            .checked_sub(target_type.outputs());
+                        if height != Some(min) || min != max {
+                            return Err(EofError::StackHeightMismatch(index, pc));
+                        }
+                    }
+                    let outputs = if target_type.is_returning() {
+                        target_type.outputs()
+                    } else {
+                        0
+                    };
+                    (target_type.inputs(), outputs)
+                }
+                RETF => {
+                    if min != max || min != code_type.outputs() {
+                        return Err(EofError::StackHeightMismatch(index, pc));
+                    }
+                    (code_type.outputs(), 0)
+                }
+                DUPN => (immediate[0] as usize + 1, immediate[0] as usize + 2),
+                SWAPN => (immediate[0] as usize + 2, immediate[0] as usize + 2),
+                EXCHANGE => {
+                    let n = (immediate[0] >> 4) as usize + 1;
+                    let m = (immediate[0] & 0x0f) as usize + 1;
+                    (n + m + 1, n + m + 1)
+                }
+                _ => eof_stack_io(opcode).unwrap_or_default(),
+            };
+            if min < inputs {
+                return Err(EofError::StackUnderflow(index, pc));
+            }
+            let (min, max) = (min - inputs + outputs, max - inputs + outputs);
+            max_height = max_height.max(max);
+            if max_height > MAX_STACK_HEIGHT {
+                return Err(EofError::StackOverflow(index, pc));
+            }
+
+            let mut successors = Vec::new();
+            if !is_terminating(opcode) && opcode != RJUMP {
+                successors.push(next);
+            }
+            match opcode {
+                RJUMP | RJUMPI => successors.extend(relative_target(next, immediate)),
+                RJUMPV => {
+                    for offset in immediate[1..].chunks(2) {
+                        successors.extend(relative_target(next, offset));
+                    }
+                }
+                _ => {}
+            }
+            for successor in successors {
+                if successor >= code.len() {
+                    return Err(EofError::MissingTerminator(index));
+                }
+                if successor > pc {
+                    heights[successor] = Some(match heights[successor] {
+                        Some((lo, hi)) => (lo.min(min), hi.max(max)),
+                        None => (min, max),
+                    });
+                } else if heights[successor] != Some((min, max)) {
+                    return Err(EofError::StackHeightMismatch(index, pc));
+                }
+            }
+            pc = next;
+        }
+
+        if max_height - code_type.inputs() != code_type.max_stack_increase() {
+            return Err(EofError::InvalidMaxStackIncrease(index));
+        }
+        Ok(())
+    }
+}
+
+/// The sections referred to by the code of a container.
+#[derive(Default)]
+struct References {
+    /// The code sections called or jumped to by each code section.
+    sections: Vec<BTreeSet<usize>>,
+    /// The container sections created by `EOFCREATE`.
+    eofcreate: BTreeSet<usize>,
+    /// The container sections returned by `RETURNCONTRACT`.
+    returncontract: BTreeSet<usize>,
+}
+
+/// A cursor over the bytes of a container.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, size: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos + size)?;
+        self.pos += size;
+        Some(bytes)
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let bytes = &self.bytes[self.pos..];
+        self.pos = self.bytes.len();
+        bytes
+    }
+
+    fn u8(&mut self) -> Result<u8, EofError> {
+        self.take(1).map(|b| b[0]).ok_or(EofError::InvalidHeader)
+    }
+
+    fn u16(&mut self) -> Result<u16, EofError> {
+        let b = self.take(2).ok_or(EofError::InvalidHeader)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, EofError> {
+        let b = self.take(4).ok_or(EofError::InvalidHeader)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), EofError> {
+        if self.u8()? != byte {
+            return Err(EofError::InvalidHeader);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the metadata of `opcode` in EOF code, or `None` if it is undefined there.
+fn eof_info(opcode: u8) -> Option<&'static OpcodeInfo> {
+    if DEPRECATED.contains(&opcode) {
+        return None;
+    }
+    OPCODES[opcode as usize].as_ref()
+}
+
+/// Returns the number of stack items removed and added by `opcode` in EOF code, or `None` if
+/// it is undefined there. The items of instructions depending on their immediate data or on
+/// code sections are computed during validation.
+pub fn eof_stack_io(opcode: u8) -> Option<(usize, usize)> {
+    eof_info(opcode).map(|info| (info.inputs(), info.outputs()))
+}
+
+/// Returns the size of the immediate data of the instruction at `pc` in EOF `code`, or `None`
+/// if it runs past the end of code.
+pub fn immediate_size(code: &[u8], pc: usize) -> Option<usize> {
+    let size = match code[pc] {
+        RJUMPV => 1 + 2 * (*code.get(pc + 1)? as usize + 1),
+        opcode => OPCODES[opcode as usize].map_or(0, |info| info.immediate_size()),
+    };
+    if pc + size >= code.len() {
+        return None;
+    }
+    Some(size)
+}
+
+/// Returns the mnemonic of `opcode` in EOF code, or `None` if it is undefined there.
+pub fn mnemonic(opcode: u8) -> Option<&'static str> {
+    eof_info(opcode).map(|info| info.name())
+}
+
+/// Returns whether `opcode` ends execution of its code section.
+pub fn is_terminating(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        0x00 | 0xf3 | 0xfd | 0xfe | RETF | JUMPF | RETURNCONTRACT
+    )
+}
+
+/// Returns the target of a relative jump by the signed `offset` from `next`, the offset of the
+/// following instruction.
+pub fn relative_target(next: usize, offset: &[u8]) -> Option<usize> {
+    let offset = i16::from_be_bytes([offset[0], offset[1]]) as isize;
+    let target = next as isize + offset;
+    (target >= 0).then_some(target as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a container with `code` sections of the given types and `data`.
+    fn container(types: &[(u8, u8, u16)], code: &[&str], data: &[u8]) -> Vec<u8> {
+        let types = types
+            .iter()
+            .map(|&(inputs, outputs, max)| CodeType::new(inputs, outputs, max))
+            .collect();
+        let code = code.iter().map(|code| hex::decode(code).unwrap()).collect();
+        EofContainer::new(types, code, Vec::new(), data.to_vec())
+            .unwrap()
+            .encode()
+    }
+
+    fn validate(bytes: &[u8]) -> Result<EofContainer, EofError> {
+        EofContainer::validate(bytes, ContainerKind::Runtime)
+    }
+
+    #[test]
+    fn test_validate() {
+        // PUSH1 1 PUSH1 2 CALLF 1 POP STOP, and ADD RETF.
+        let bytes = container(
+            &[(0, 0x80, 2), (2, 1, 0)],
+            &["60016002e300015000", "01e4"],
+            &[0xaa, 0xbb],
+        );
+        assert_eq!(
+            hex::encode(&bytes[..22]),
+            "ef000101000802000200090002ff0002000080000202"
+        );
+        let container = validate(&bytes).unwrap();
+        assert_eq!(container.code_sections().len(), 2);
+        assert_eq!(container.types()[1].max_stack_height(), 2);
+        assert_eq!(container.data(), &vec![0xaa, 0xbb]);
+        assert_eq!(container.encode(), bytes);
+    }
+
+    #[test]
+    fn test_validate_header() {
+        let bytes = container(&[(0, 0x80, 0)], &["00"], &[]);
+        assert_eq!(validate(&bytes[1..]), Err(EofError::InvalidMagic));
+        let mut version = bytes.clone();
+        version[2] = 2;
+        assert_eq!(validate(&version), Err(EofError::UnsupportedVersion(2)));
+        assert_eq!(
+            validate(&bytes[..bytes.len() - 1]),
+            Err(EofError::InvalidBodySize)
+        );
+        assert_eq!(
+            validate(&[bytes.clone(), vec![0]].concat()),
+            Err(EofError::InvalidBodySize)
+        );
+        assert_eq!(validate(&bytes[..8]), Err(EofError::InvalidHeader));
+        let returning = container(&[(0, 0, 0)], &["00"], &[]);
+        assert_eq!(validate(&returning), Err(EofError::InvalidType(0)));
+    }
+
+    #[test]
+    fn test_validate_code() {
+        let invalid = |types: &[(u8, u8, u16)], code: &[&str]| {
+            validate(&container(types, code, &[])).unwrap_err()
+        };
+        let main = (0, 0x80, 0);
+        // JUMP is deprecated.
+        assert_eq!(
+            invalid(&[(0, 0x80, 1)], &["600056"]),
+            EofError::UndefinedInstruction(0, 2)
+        );
+        // PUSH2 with one byte.
+        assert_eq!(
+            invalid(&[main], &["6100"]),
+            EofError::TruncatedImmediate(0, 0)
+        );
+        // RJUMP into its own immediate data.
+        assert_eq!(
+            invalid(&[main], &["e0ffff"]),
+            EofError::InvalidJumpTarget(0, 0)
+        );
+        // PUSH0 falling off the end of code.
+        assert_eq!(
+            invalid(&[(0, 0x80, 1)], &["5f"]),
+            EofError::MissingTerminator(0)
+        );
+        // ADD with an empty stack.
+        assert_eq!(invalid(&[main], &["0100"]), EofError::StackUnderflow(0, 0));
+        // RJUMP +1 skips over a STOP, which can never be reached.
+        assert_eq!(
+            invalid(&[main], &["e000010000"]),
+            EofError::UnreachableCode(0, 3)
+        );
+        // PUSH0 declared with no stack increase.
+        assert_eq!(
+            invalid(&[main], &["5f00"]),
+            EofError::InvalidMaxStackIncrease(0)
+        );
+        // The second section is never called.
+        assert_eq!(
+            invalid(&[main, main], &["00", "00"]),
+            EofError::UnreferencedSection(1)
+        );
+        // A loop pushing onto the stack: PUSH0 RJUMP -4.
+        assert_eq!(
+            invalid(&[(0, 0x80, 1)], &["5fe0fffc"]),
+            EofError::StackHeightMismatch(0, 1)
+        );
+        // A returning section without RETF.
+        assert_eq!(
+            invalid(&[main, (0, 0, 0)], &["e3000100", "00"]),
+            EofError::InvalidReturning(1)
+        );
+    }
+
+    #[test]
+    fn test_validate_branches() {
+        // PUSH0 RJUMPI +2 PUSH0 POP STOP: STOP is reached with heights 0 and 1.
+        let bytes = container(&[(0, 0x80, 1)], &["5fe100025f5000"], &[]);
+        assert!(validate(&bytes).is_ok());
+        // PUSH0 RJUMPV [0, 1] STOP PUSH0 STOP
+        let bytes = container(&[(0, 0x80, 1)], &["5fe20100000001005f00"], &[]);
+        assert!(validate(&bytes).is_ok());
+        // PUSH0 RJUMPV [2] STOP PUSH0, jumping into the immediate data of PUSH1.
+        let bytes = container(&[(0, 0x80, 1)], &["5fe200000200600000"], &[]);
+        assert_eq!(validate(&bytes), Err(EofError::InvalidJumpTarget(0, 1)));
+    }
+
+    #[test]
+    fn test_validate_subcontainers() {
+        let runtime = container(&[(0, 0x80, 0)], &["00"], &[]);
+        // PUSH0 PUSH0 RETURNCONTRACT 0
+        let initcode = EofContainer::new(
+            vec![CodeType::new(0, 0x80, 2)],
+            vec![hex::decode("5f5fee00").unwrap()],
+            vec![runtime.clone()],
+            Vec::new(),
+        )
+        .unwrap()
+        .encode();
+        assert!(EofContainer::validate(&initcode, ContainerKind::InitCode).is_ok());
+        assert_eq!(
+            validate(&initcode),
+            Err(EofError::IncompatibleContainerKind)
+        );
+
+        // PUSH0 PUSH0 PUSH0 PUSH0 EOFCREATE 0 POP STOP
+        let factory = EofContainer::new(
+            vec![CodeType::new(0, 0x80, 4)],
+            vec![hex::decode("5f5f5f5fec005000").unwrap()],
+            vec![initcode],
+            Vec::new(),
+        )
+        .unwrap();
+        assert!(validate(&factory.encode()).is_ok());
+
+        // The runtime container is not referenced.
+        let unreferenced = EofContainer::new(
+            vec![CodeType::new(0, 0x80, 0)],
+            vec![vec![0x00]],
+            vec![runtime],
+            Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            validate(&unreferenced.encode()),
+            Err(EofError::UnreferencedSection(0))
+        );
+    }
+
+    #[test]
+    fn test_new_too_large() {
+        let types = vec![CodeType::new(0, 0x80, 0)];
+        let large = vec![0; u16::MAX as usize + 1];
+        assert_eq!(
+            EofContainer::new(types.clone(), vec![large.clone()], Vec::new(), Vec::new()),
+            Err(EofError::InvalidSectionSize)
+        );
+        assert_eq!(
+            EofContainer::new(types, vec![vec![0x00]], Vec::new(), large),
+            Err(EofError::InvalidBodySize)
+        );
+    }
+}
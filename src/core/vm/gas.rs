@@ -0,0 +1,232 @@
+use crate::core::fork::Fork;
+
+/// Gas paid for instructions in the zero tier.
+pub const ZERO: u64 = 0;
+/// Gas paid for the `JUMPDEST` instruction.
+pub const JUMPDEST: u64 = 1;
+/// Gas paid for instructions in the base tier.
+pub const BASE: u64 = 2;
+/// Gas paid for instructions in the very low tier.
+pub const VERY_LOW: u64 = 3;
+/// Gas paid for instructions in the low tier.
+pub const LOW: u64 = 5;
+/// Gas paid for instructions in the mid tier.
+pub const MID: u64 = 8;
+/// Gas paid for instructions in the high tier.
+pub const HIGH: u64 = 10;
+/// Gas paid for a warm account or storage access (EIP-2929).
+pub const WARM_ACCESS: u64 = 100;
+/// Gas paid for a cold account access (EIP-2929).
+pub const COLD_ACCOUNT_ACCESS: u64 = 2600;
+/// Gas paid for a cold storage access (EIP-2929).
+pub const COLD_SLOAD: u64 = 2100;
+/// Gas paid for an `SSTORE` that sets a zero slot to a non-zero value.
+pub const SSTORE_SET: u64 = 20000;
+/// Gas paid for a contract creation.
+pub const CREATE: u64 = 32000;
+/// Gas paid per byte of code deposited by a contract creation.
+pub const CODE_DEPOSIT: u64 = 200;
+/// Gas paid for a non-zero value transfer as part of a message call.
+pub const CALL_VALUE: u64 = 9000;
+/// Gas stipend given to the callee of a non-zero value transfer.
+pub const CALL_STIPEND: u64 = 2300;
+/// Gas paid for a message call or self-destruct that creates an account.
+pub const NEW_ACCOUNT: u64 = 25000;
+/// Gas paid for the `SELFDESTRUCT` instruction since Tangerine Whistle (EIP-150).
+pub const SELF_DESTRUCT: u64 = 5000;
+/// Gas paid for the `EXP` instruction.
+pub const EXP: u64 = 10;
+/// Gas paid per word of memory.
+pub const MEMORY: u64 = 3;
+/// Gas paid for the `LOG` instructions.
+pub const LOG: u64 = 375;
+/// Gas paid per byte of data of the `LOG` instructions.
+pub const LOG_DATA: u64 = 8;
+/// Gas paid per topic of the `LOG` instructions.
+pub const LOG_TOPIC: u64 = 375;
+/// Gas paid for the `KECCAK256` instruction.
+pub const KECCAK256: u64 = 30;
+/// Gas paid per word of input to the `KECCAK256` instruction.
+pub const KECCAK256_WORD: u64 = 6;
+/// Gas paid per word copied by the copy instructions.
+pub const COPY: u64 = 3;
+/// Gas paid for the `BLOCKHASH` instruction.
+pub const BLOCKHASH: u64 = 20;
+/// Gas paid per word of initialization code since Shanghai (EIP-3860).
+pub const INIT_CODE_WORD: u64 = 2;
+/// Gas paid for the `RJUMPI` and `RJUMPV` instructions of EOF code (EIP-4200).
+pub const RJUMPI: u64 = 4;
+/// Gas paid for the `DATALOAD` instruction of EOF code (EIP-7480).
+pub const DATALOAD: u64 = 4;
+/// Gas an EOF message call leaves to its caller at least (EIP-7069).
+pub const MIN_RETAINED_GAS: u64 = 5000;
+/// Gas an EOF message call gives to its callee at least, or fails (EIP-7069).
+pub const MIN_CALLEE_GAS: u64 = 2300;
+
+/// Maximum size of deployed code since Spurious Dragon (EIP-170).
+pub const MAX_CODE_SIZE: usize = 24576;
+/// Maximum size of initialization code since Shanghai (EIP-3860).
+pub const MAX_INIT_CODE_SIZE: usize = 2 * MAX_CODE_SIZE;
+
+/// Gas metering rules for `SSTORE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SStoreMetering {
+    /// Metering by the current and new values of the slot.
+    Legacy,
+    /// Net gas metering (EIP-1283).
+    Net,
+    /// Net gas metering with a stipend check (EIP-2200).
+    NetWithStipend,
+}
+
+/// Fork-dependent gas costs.
+#[derive(Clone, Debug)]
+pub(crate) struct Schedule {
+    /// Whether accesses are priced by the accessed sets (EIP-2929).
+    pub(crate) warm_cold_access: bool,
+    pub(crate) sload: u64,
+    pub(crate) balance: u64,
+    pub(crate) ext_code: u64,
+    pub(crate) ext_code_hash: u64,
+    pub(crate) call: u64,
+    pub(crate) self_destruct: u64,
+    pub(crate) exp_byte: u64,
+    pub(crate) sstore_metering: SStoreMetering,
+    /// Gas paid for an `SSTORE` that modifies a non-zero slot.
+    pub(crate) sstore_reset: u64,
+    /// Refund for an `SSTORE` that clears a slot.
+    pub(crate) sstore_clears_refund: u64,
+    pub(crate) self_destruct_refund: u64,
+    /// Maximum portion of the gas used by a transaction that can be refunded is one over this
+    /// value.
+    pub(crate) max_refund_quotient: u64,
+}
+
+impl Schedule {
+    /// Returns the gas costs under the rules of `fork`.
+    pub(crate) fn new(fork: Fork) -> Self {
+        let mut schedule = Self {
+            warm_cold_access: false,
+            sload: 50,
+            balance: 20,
+            ext_code: 20,
+            ext_code_hash: 400,
+            call: 40,
+            self_destruct: 0,
+            exp_byte: 10,
+            sstore_metering: SStoreMetering::Legacy,
+            sstore_reset: 5000,
+            sstore_clears_refund: 15000,
+            self_destruct_refund: 24000,
+            max_refund_quotient: 2,
+        };
+
+        if fork >= Fork::TangerineWhistle {
+            schedule.sload = 200;
+            schedule.balance = 400;
+            schedule.ext_code = 700;
+            schedule.call = 700;
+            schedule.self_destruct = SELF_DESTRUCT;
+        }
+        if fork >= Fork::SpuriousDragon {
+            schedule.exp_byte = 50;
+        }
+        if fork == Fork::Constantinople {
+            schedule.sstore_metering = SStoreMetering::Net;
+        }
+        if fork >= Fork::Istanbul {
+            schedule.sload = 800;
+            schedule.balance = 700;
+            schedule.ext_code_hash = 700;
+            schedule.sstore_metering = SStoreMetering::NetWithStipend;
+        }
+        if fork >= Fork::Berlin {
+            schedule.warm_cold_access = true;
+            schedule.sload = WARM_ACCESS;
+            schedule.balance = WARM_ACCESS;
+            schedule.ext_code = WARM_ACCESS;
+            schedule.ext_code_hash = WARM_ACCESS;
+            schedule.call = WARM_ACCESS;
+            schedule.sstore_reset = 5000 - COLD_SLOAD;
+        }
+        if fork >= Fork::London {
+            schedule.sstore_clears_refund = schedule.sstore_reset + 1900;
+            schedule.self_destruct_refund = 0;
+            schedule.max_refund_quotient = 5;
+        }
+
+        schedule
+    }
+}
+
+/// Returns the number of words needed to hold `len` bytes.
+pub fn words(len: u64) -> u64 {
+    len / 32 + u64::from(!len.is_multiple_of(32))
+}
+
+/// Returns the total cost of `words` words of memory.
+pub fn memory_cost(words: u64) -> u64 {
+    MEMORY * words + words * words / 512
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule() {
+        let frontier = Schedule::new(Fork::Frontier);
+        assert_eq!(frontier.sload, 50);
+        assert_eq!(frontier.call, 40);
+        assert_eq!(frontier.self_destruct, 0);
+        assert_eq!(frontier.exp_byte, 10);
+        assert_eq!(frontier.sstore_metering, SStoreMetering::Legacy);
+
+        let tangerine_whistle = Schedule::new(Fork::TangerineWhistle);
+        assert_eq!(tangerine_whistle.sload, 200);
+        assert_eq!(tangerine_whistle.call, 700);
+        assert_eq!(tangerine_whistle.self_destruct, 5000);
+        assert_eq!(tangerine_whistle.exp_byte, 10);
+        assert_eq!(Schedule::new(Fork::SpuriousDragon).exp_byte, 50);
+
+        // Net metering was in Constantinople only and removed again in Petersburg.
+        let constantinople = Schedule::new(Fork::Constantinople);
+        assert_eq!(constantinople.sstore_metering, SStoreMetering::Net);
+        let petersburg = Schedule::new(Fork::Petersburg);
+        assert_eq!(petersburg.sstore_metering, SStoreMetering::Legacy);
+
+        let istanbul = Schedule::new(Fork::Istanbul);
+        assert_eq!(istanbul.sload, 800);
+        assert_eq!(istanbul.balance, 700);
+        assert_eq!(istanbul.ext_code_hash, 700);
+        assert_eq!(istanbul.sstore_metering, SStoreMetering::NetWithStipend);
+        assert!(!istanbul.warm_cold_access);
+
+        let berlin = Schedule::new(Fork::Berlin);
+        assert!(berlin.warm_cold_access);
+        assert_eq!(berlin.sload, WARM_ACCESS);
+        assert_eq!(berlin.call, WARM_ACCESS);
+        assert_eq!(berlin.sstore_reset, 2900);
+        assert_eq!(berlin.sstore_clears_refund, 15000);
+        assert_eq!(berlin.self_destruct_refund, 24000);
+        assert_eq!(berlin.max_refund_quotient, 2);
+
+        let london = Schedule::new(Fork::London);
+        assert_eq!(london.sstore_reset, 2900);
+        assert_eq!(london.sstore_clears_refund, 4800);
+        assert_eq!(london.self_destruct_refund, 0);
+        assert_eq!(london.max_refund_quotient, 5);
+    }
+
+    #[test]
+    fn test_memory_cost() {
+        assert_eq!(words(0), 0);
+        assert_eq!(words(1), 1);
+        assert_eq!(words(32), 1);
+        assert_eq!(words(33), 2);
+        assert_eq!(memory_cost(0), 0);
+        assert_eq!(memory_cost(1), 3);
+        assert_eq!(memory_cost(32), 98);
+        assert_eq!(memory_cost(1024), 3 * 1024 + 2048);
+    }
+}
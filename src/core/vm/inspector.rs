@@ -0,0 +1,67 @@
+use ethereum_types::{Address, U256};
+
+use super::env::ExecutionEnv;
+use super::interpreter::{ExceptionalHalt, Message, Outcome};
+use super::machine::MachineState;
+use super::substate::AccruedSubstate;
+use crate::core::types::log::Log;
+
+/// Callbacks on the execution of an `Interpreter`.
+///
+/// Every callback does nothing by default. Callbacks observe execution as it happens, and
+/// may alter it through the machine state they are given and the outcomes they return.
+pub trait Inspector {
+    /// Called before the instruction at the program counter of `machine` executes.
+    fn step(
+        &mut self,
+        _machine: &mut MachineState,
+        _env: &ExecutionEnv,
+        _substate: &AccruedSubstate,
+    ) {
+    }
+
+    /// Called after an instruction executes, with the condition that halted execution at the
+    /// instruction, if any. The program counter of `machine` is that of the next instruction.
+    fn step_end(
+        &mut self,
+        _machine: &mut MachineState,
+        _env: &ExecutionEnv,
+        _substate: &AccruedSubstate,
+        _error: Option<ExceptionalHalt>,
+    ) {
+    }
+
+    /// Called when a message call or contract creation starts, with the machine state and
+    /// environment of the frame starting it, which are `None` for the message of the
+    /// transaction. The target of a contract creation is the address of the created contract.
+    ///
+    /// Returning an outcome skips the message, which ends with that outcome instead.
+    fn call(
+        &mut self,
+        _machine: Option<&mut MachineState>,
+        _env: Option<&ExecutionEnv>,
+        _substate: &AccruedSubstate,
+        _message: &Message,
+    ) -> Option<Outcome> {
+        None
+    }
+
+    /// Called when the message call or contract creation most recently started ends, with its
+    /// outcome and the machine state and environment of the frame that started it, as for
+    /// `call`. Changes to `outcome` are seen by the caller, but do not undo or restore any
+    /// changes to the state.
+    fn call_end(
+        &mut self,
+        _machine: Option<&mut MachineState>,
+        _env: Option<&ExecutionEnv>,
+        _substate: &AccruedSubstate,
+        _outcome: &mut Outcome,
+    ) {
+    }
+
+    /// Called when the executing code emits `log`.
+    fn log(&mut self, _env: &ExecutionEnv, _log: &Log) {}
+
+    /// Called when the account `address` self-destructs, sending `balance` to `beneficiary`.
+    fn self_destruct(&mut self, _address: &Address, _beneficiary: &Address, _balance: U256) {}
+}
@@ -1,433 +1,593 @@
+use super::gas;
+use crate::core::fork::Fork;
+use std::fmt;
+use std::str::FromStr;
+
 /// An EVM instruction.
-#[derive(Debug)]
-enum Instruction {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Instruction {
     // Stop and arithmetic operations.
-    Stop,
-    Add,
-    Mul,
-    Sub,
-    Div,
-    SDiv,
-    Mod,
-    SMod,
-    AddMod,
-    MulMod,
-    Exp,
-    SignExtend,
+    Stop = 0x00,
+    Add = 0x01,
+    Mul = 0x02,
+    Sub = 0x03,
+    Div = 0x04,
+    SDiv = 0x05,
+    Mod = 0x06,
+    SMod = 0x07,
+    AddMod = 0x08,
+    MulMod = 0x09,
+    Exp = 0x0a,
+    SignExtend = 0x0b,
     // Comparison and bitwise logic operations.
-    Lt,
-    Gt,
-    SLt,
-    SGt,
-    Eq,
-    IsZero,
-    And,
-    Or,
-    Xor,
-    Not,
-    Byte,
-    ShL,
-    ShR,
-    SAR,
+    Lt = 0x10,
+    Gt = 0x11,
+    SLt = 0x12,
+    SGt = 0x13,
+    Eq = 0x14,
+    IsZero = 0x15,
+    And = 0x16,
+    Or = 0x17,
+    Xor = 0x18,
+    Not = 0x19,
+    Byte = 0x1a,
+    ShL = 0x1b,
+    ShR = 0x1c,
+    Sar = 0x1d,
     // Keccak 256.
-    Keccak256,
+    Keccak256 = 0x20,
     // Environmental information.
-    Address,
-    Balance,
-    Origin,
-    Caller,
-    CallValue,
-    CallDataLoad,
-    CallDataSize,
-    CallDataCopy,
-    CodeSize,
-    CodeCopy,
-    GasPrice,
-    ExtCodeSize,
-    ExtCodeCopy,
-    ReturnDataSize,
-    ReturnDataCopy,
-    ExtCodeHash,
+    Address = 0x30,
+    Balance = 0x31,
+    Origin = 0x32,
+    Caller = 0x33,
+    CallValue = 0x34,
+    CallDataLoad = 0x35,
+    CallDataSize = 0x36,
+    CallDataCopy = 0x37,
+    CodeSize = 0x38,
+    CodeCopy = 0x39,
+    GasPrice = 0x3a,
+    ExtCodeSize = 0x3b,
+    ExtCodeCopy = 0x3c,
+    ReturnDataSize = 0x3d,
+    ReturnDataCopy = 0x3e,
+    ExtCodeHash = 0x3f,
     // Block information.
-    BlockHash,
-    Coinbase,
-    Timestamp,
-    Number,
-    Difficulty,
-    GasLimit,
-    ChainId,
-    SelfBalance,
+    BlockHash = 0x40,
+    Coinbase = 0x41,
+    Timestamp = 0x42,
+    Number = 0x43,
+    Difficulty = 0x44,
+    GasLimit = 0x45,
+    ChainId = 0x46,
+    SelfBalance = 0x47,
+    BaseFee = 0x48,
+    BlobHash = 0x49,
+    BlobBaseFee = 0x4a,
     // Stack, memory, storage, and flow.
-    Pop,
-    MLoad,
-    MStore,
-    MStore8,
-    SLoad,
-    SStore,
-    Jump,
-    JumpI,
-    Pc,
-    MSize,
-    Gas,
-    JumpDest,
+    Pop = 0x50,
+    MLoad = 0x51,
+    MStore = 0x52,
+    MStore8 = 0x53,
+    SLoad = 0x54,
+    SStore = 0x55,
+    Jump = 0x56,
+    JumpI = 0x57,
+    Pc = 0x58,
+    MSize = 0x59,
+    Gas = 0x5a,
+    JumpDest = 0x5b,
+    TLoad = 0x5c,
+    TStore = 0x5d,
+    MCopy = 0x5e,
     // Push operations.
-    Push1,
-    Push2,
-    Push3,
-    Push4,
-    Push5,
-    Push6,
-    Push7,
-    Push8,
-    Push9,
-    Push10,
-    Push11,
-    Push12,
-    Push13,
-    Push14,
-    Push15,
-    Push16,
-    Push17,
-    Push18,
-    Push19,
-    Push20,
-    Push21,
-    Push22,
-    Push23,
-    Push24,
-    Push25,
-    Push26,
-    Push27,
-    Push28,
-    Push29,
-    Push30,
-    Push31,
-    Push32,
+    Push0 = 0x5f,
+    Push1 = 0x60,
+    Push2 = 0x61,
+    Push3 = 0x62,
+    Push4 = 0x63,
+    Push5 = 0x64,
+    Push6 = 0x65,
+    Push7 = 0x66,
+    Push8 = 0x67,
+    Push9 = 0x68,
+    Push10 = 0x69,
+    Push11 = 0x6a,
+    Push12 = 0x6b,
+    Push13 = 0x6c,
+    Push14 = 0x6d,
+    Push15 = 0x6e,
+    Push16 = 0x6f,
+    Push17 = 0x70,
+    Push18 = 0x71,
+    Push19 = 0x72,
+    Push20 = 0x73,
+    Push21 = 0x74,
+    Push22 = 0x75,
+    Push23 = 0x76,
+    Push24 = 0x77,
+    Push25 = 0x78,
+    Push26 = 0x79,
+    Push27 = 0x7a,
+    Push28 = 0x7b,
+    Push29 = 0x7c,
+    Push30 = 0x7d,
+    Push31 = 0x7e,
+    Push32 = 0x7f,
     // Duplication operations.
-    Dup1,
-    Dup2,
-    Dup3,
-    Dup4,
-    Dup5,
-    Dup6,
-    Dup7,
-    Dup8,
-    Dup9,
-    Dup10,
-    Dup11,
-    Dup12,
-    Dup13,
-    Dup14,
-    Dup15,
-    Dup16,
+    Dup1 = 0x80,
+    Dup2 = 0x81,
+    Dup3 = 0x82,
+    Dup4 = 0x83,
+    Dup5 = 0x84,
+    Dup6 = 0x85,
+    Dup7 = 0x86,
+    Dup8 = 0x87,
+    Dup9 = 0x88,
+    Dup10 = 0x89,
+    Dup11 = 0x8a,
+    Dup12 = 0x8b,
+    Dup13 = 0x8c,
+    Dup14 = 0x8d,
+    Dup15 = 0x8e,
+    Dup16 = 0x8f,
     // Exchange operations.
-    Swap1,
-    Swap2,
-    Swap3,
-    Swap4,
-    Swap5,
-    Swap6,
-    Swap7,
-    Swap8,
-    Swap9,
-    Swap10,
-    Swap11,
-    Swap12,
-    Swap13,
-    Swap14,
-    Swap15,
-    Swap16,
+    Swap1 = 0x90,
+    Swap2 = 0x91,
+    Swap3 = 0x92,
+    Swap4 = 0x93,
+    Swap5 = 0x94,
+    Swap6 = 0x95,
+    Swap7 = 0x96,
+    Swap8 = 0x97,
+    Swap9 = 0x98,
+    Swap10 = 0x99,
+    Swap11 = 0x9a,
+    Swap12 = 0x9b,
+    Swap13 = 0x9c,
+    Swap14 = 0x9d,
+    Swap15 = 0x9e,
+    Swap16 = 0x9f,
     // Logging operations.
-    Log0,
-    Log1,
-    Log2,
-    Log3,
-    Log4,
+    Log0 = 0xa0,
+    Log1 = 0xa1,
+    Log2 = 0xa2,
+    Log3 = 0xa3,
+    Log4 = 0xa4,
+    // EOF operations, only defined in EOF code (EIP-3540).
+    DataLoad = 0xd0,
+    DataLoadN = 0xd1,
+    DataSize = 0xd2,
+    DataCopy = 0xd3,
+    RJump = 0xe0,
+    RJumpI = 0xe1,
+    RJumpV = 0xe2,
+    CallF = 0xe3,
+    RetF = 0xe4,
+    JumpF = 0xe5,
+    DupN = 0xe6,
+    SwapN = 0xe7,
+    Exchange = 0xe8,
+    EofCreate = 0xec,
+    ReturnContract = 0xee,
     // System operations.
-    Create,
-    Call,
-    CallCode,
-    Return,
-    DelegateCall,
-    Create2,
-    StaticCall,
-    Revert,
-    Invalid,
-    SelfDestruct,
+    Create = 0xf0,
+    Call = 0xf1,
+    CallCode = 0xf2,
+    Return = 0xf3,
+    DelegateCall = 0xf4,
+    Create2 = 0xf5,
+    ReturnDataLoad = 0xf7,
+    ExtCall = 0xf8,
+    ExtDelegateCall = 0xf9,
+    StaticCall = 0xfa,
+    ExtStaticCall = 0xfb,
+    Revert = 0xfd,
+    Invalid = 0xfe,
+    SelfDestruct = 0xff,
+}
+
+/// Metadata of an opcode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    instruction: Instruction,
+    name: &'static str,
+    immediate_size: u8,
+    inputs: u8,
+    outputs: u8,
+    base_gas: u64,
+    /// The fork that introduced the opcode, or `None` for an EOF-only opcode.
+    fork: Option<Fork>,
+}
+
+impl OpcodeInfo {
+    /// Returns the instruction of the opcode.
+    pub fn instruction(&self) -> Instruction {
+        self.instruction
+    }
+
+    /// Returns the mnemonic, such as `PUSH1` or `CALLDATALOAD`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the number of bytes of immediate data following the opcode in code. That of
+    /// `RJUMPV` is its first byte, which is followed by its jump table.
+    pub fn immediate_size(&self) -> usize {
+        self.immediate_size as usize
+    }
+
+    /// Returns the number of items removed from the stack. Those of EOF instructions depending
+    /// on their immediate data or on code sections are computed during validation.
+    pub fn inputs(&self) -> usize {
+        self.inputs as usize
+    }
+
+    /// Returns the number of items added to the stack.
+    pub fn outputs(&self) -> usize {
+        self.outputs as usize
+    }
+
+    /// Returns the gas charged for every execution as of the latest fork, before any cost
+    /// depending on operands, memory expansion or accessed state. The charges that changed
+    /// between forks are in `Schedule`.
+    pub fn base_gas(&self) -> u64 {
+        self.base_gas
+    }
+
+    /// Returns the fork that introduced the opcode, or `None` for an EOF-only opcode, which no
+    /// fork enables yet.
+    pub fn fork(&self) -> Option<Fork> {
+        self.fork
+    }
+
+    /// Returns whether the opcode is only defined in EOF code, being undefined in legacy code.
+    pub fn is_eof_only(&self) -> bool {
+        self.fork.is_none()
+    }
+}
+
+const fn info(
+    instruction: Instruction,
+    name: &'static str,
+    immediate_size: u8,
+    inputs: u8,
+    outputs: u8,
+    base_gas: u64,
+    fork: Fork,
+) -> Option<OpcodeInfo> {
+    Some(OpcodeInfo {
+        instruction,
+        name,
+        immediate_size,
+        inputs,
+        outputs,
+        base_gas,
+        fork: Some(fork),
+    })
+}
+
+/// Returns the metadata of an opcode only defined in EOF code.
+const fn eof_info(
+    instruction: Instruction,
+    name: &'static str,
+    immediate_size: u8,
+    inputs: u8,
+    outputs: u8,
+    base_gas: u64,
+) -> Option<OpcodeInfo> {
+    Some(OpcodeInfo {
+        instruction,
+        name,
+        immediate_size,
+        inputs,
+        outputs,
+        base_gas,
+        fork: None,
+    })
+}
+
+/// The metadata of each opcode, or `None` for an undefined opcode.
+#[rustfmt::skip]
+pub const OPCODES: [Option<OpcodeInfo>; 256] = {
+    use Instruction::*;
+    let mut table = [None; 256];
+    table[0x00] = info(Stop, "STOP", 0, 0, 0, gas::ZERO, Fork::Frontier);
+    table[0x01] = info(Add, "ADD", 0, 2, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x02] = info(Mul, "MUL", 0, 2, 1, gas::LOW, Fork::Frontier);
+    table[0x03] = info(Sub, "SUB", 0, 2, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x04] = info(Div, "DIV", 0, 2, 1, gas::LOW, Fork::Frontier);
+    table[0x05] = info(SDiv, "SDIV", 0, 2, 1, gas::LOW, Fork::Frontier);
+    table[0x06] = info(Mod, "MOD", 0, 2, 1, gas::LOW, Fork::Frontier);
+    table[0x07] = info(SMod, "SMOD", 0, 2, 1, gas::LOW, Fork::Frontier);
+    table[0x08] = info(AddMod, "ADDMOD", 0, 3, 1, gas::MID, Fork::Frontier);
+    table[0x09] = info(MulMod, "MULMOD", 0, 3, 1, gas::MID, Fork::Frontier);
+    table[0x0a] = info(Exp, "EXP", 0, 2, 1, gas::HIGH, Fork::Frontier);
+    table[0x0b] = info(SignExtend, "SIGNEXTEND", 0, 2, 1, gas::LOW, Fork::Frontier);
+    table[0x10] = info(Lt, "LT", 0, 2, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x11] = info(Gt, "GT", 0, 2, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x12] = info(SLt, "SLT", 0, 2, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x13] = info(SGt, "SGT", 0, 2, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x14] = info(Eq, "EQ", 0, 2, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x15] = info(IsZero, "ISZERO", 0, 1, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x16] = info(And, "AND", 0, 2, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x17] = info(Or, "OR", 0, 2, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x18] = info(Xor, "XOR", 0, 2, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x19] = info(Not, "NOT", 0, 1, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x1a] = info(Byte, "BYTE", 0, 2, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x1b] = info(ShL, "SHL", 0, 2, 1, gas::VERY_LOW, Fork::Constantinople);
+    table[0x1c] = info(ShR, "SHR", 0, 2, 1, gas::VERY_LOW, Fork::Constantinople);
+    table[0x1d] = info(Sar, "SAR", 0, 2, 1, gas::VERY_LOW, Fork::Constantinople);
+    table[0x20] = info(
+        Keccak256, "KECCAK256", 0, 2, 1, gas::KECCAK256, Fork::Frontier);
+    table[0x30] = info(Address, "ADDRESS", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x31] = info(Balance, "BALANCE", 0, 1, 1, gas::WARM_ACCESS, Fork::Frontier);
+    table[0x32] = info(Origin, "ORIGIN", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x33] = info(Caller, "CALLER", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x34] = info(CallValue, "CALLVALUE", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x35] = info(CallDataLoad, "CALLDATALOAD", 0, 1, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x36] = info(CallDataSize, "CALLDATASIZE", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x37] = info(CallDataCopy, "CALLDATACOPY", 0, 3, 0, gas::VERY_LOW, Fork::Frontier);
+    table[0x38] = info(CodeSize, "CODESIZE", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x39] = info(CodeCopy, "CODECOPY", 0, 3, 0, gas::VERY_LOW, Fork::Frontier);
+    table[0x3a] = info(GasPrice, "GASPRICE", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x3b] = info(ExtCodeSize, "EXTCODESIZE", 0, 1, 1, gas::WARM_ACCESS, Fork::Frontier);
+    table[0x3c] = info(ExtCodeCopy, "EXTCODECOPY", 0, 4, 0, gas::WARM_ACCESS, Fork::Frontier);
+    table[0x3d] = info(ReturnDataSize, "RETURNDATASIZE", 0, 0, 1, gas::BASE, Fork::Byzantium);
+    table[0x3e] = info(ReturnDataCopy, "RETURNDATACOPY", 0, 3, 0, gas::VERY_LOW, Fork::Byzantium);
+    table[0x3f] = info(ExtCodeHash, "EXTCODEHASH", 0, 1, 1, gas::WARM_ACCESS, Fork::Constantinople);
+    table[0x40] = info(BlockHash, "BLOCKHASH", 0, 1, 1, gas::BLOCKHASH, Fork::Frontier);
+    table[0x41] = info(Coinbase, "COINBASE", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x42] = info(Timestamp, "TIMESTAMP", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x43] = info(Number, "NUMBER", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x44] = info(Difficulty, "DIFFICULTY", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x45] = info(GasLimit, "GASLIMIT", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x46] = info(ChainId, "CHAINID", 0, 0, 1, gas::BASE, Fork::Istanbul);
+    table[0x47] = info(SelfBalance, "SELFBALANCE", 0, 0, 1, gas::LOW, Fork::Istanbul);
+    table[0x48] = info(BaseFee, "BASEFEE", 0, 0, 1, gas::BASE, Fork::London);
+    table[0x49] = info(BlobHash, "BLOBHASH", 0, 1, 1, gas::VERY_LOW, Fork::Cancun);
+    table[0x4a] = info(BlobBaseFee, "BLOBBASEFEE", 0, 0, 1, gas::BASE, Fork::Cancun);
+    table[0x50] = info(Pop, "POP", 0, 1, 0, gas::BASE, Fork::Frontier);
+    table[0x51] = info(MLoad, "MLOAD", 0, 1, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x52] = info(MStore, "MSTORE", 0, 2, 0, gas::VERY_LOW, Fork::Frontier);
+    table[0x53] = info(MStore8, "MSTORE8", 0, 2, 0, gas::VERY_LOW, Fork::Frontier);
+    table[0x54] = info(SLoad, "SLOAD", 0, 1, 1, gas::WARM_ACCESS, Fork::Frontier);
+    table[0x55] = info(SStore, "SSTORE", 0, 2, 0, gas::ZERO, Fork::Frontier);
+    table[0x56] = info(Jump, "JUMP", 0, 1, 0, gas::MID, Fork::Frontier);
+    table[0x57] = info(JumpI, "JUMPI", 0, 2, 0, gas::HIGH, Fork::Frontier);
+    table[0x58] = info(Pc, "PC", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x59] = info(MSize, "MSIZE", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x5a] = info(Gas, "GAS", 0, 0, 1, gas::BASE, Fork::Frontier);
+    table[0x5b] = info(JumpDest, "JUMPDEST", 0, 0, 0, gas::JUMPDEST, Fork::Frontier);
+    table[0x5c] = info(TLoad, "TLOAD", 0, 1, 1, gas::WARM_ACCESS, Fork::Cancun);
+    table[0x5d] = info(TStore, "TSTORE", 0, 2, 0, gas::WARM_ACCESS, Fork::Cancun);
+    table[0x5e] = info(MCopy, "MCOPY", 0, 3, 0, gas::VERY_LOW, Fork::Cancun);
+    table[0x5f] = info(Push0, "PUSH0", 0, 0, 1, gas::BASE, Fork::Shanghai);
+    table[0x60] = info(Push1, "PUSH1", 1, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x61] = info(Push2, "PUSH2", 2, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x62] = info(Push3, "PUSH3", 3, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x63] = info(Push4, "PUSH4", 4, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x64] = info(Push5, "PUSH5", 5, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x65] = info(Push6, "PUSH6", 6, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x66] = info(Push7, "PUSH7", 7, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x67] = info(Push8, "PUSH8", 8, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x68] = info(Push9, "PUSH9", 9, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x69] = info(Push10, "PUSH10", 10, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x6a] = info(Push11, "PUSH11", 11, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x6b] = info(Push12, "PUSH12", 12, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x6c] = info(Push13, "PUSH13", 13, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x6d] = info(Push14, "PUSH14", 14, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x6e] = info(Push15, "PUSH15", 15, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x6f] = info(Push16, "PUSH16", 16, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x70] = info(Push17, "PUSH17", 17, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x71] = info(Push18, "PUSH18", 18, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x72] = info(Push19, "PUSH19", 19, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x73] = info(Push20, "PUSH20", 20, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x74] = info(Push21, "PUSH21", 21, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x75] = info(Push22, "PUSH22", 22, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x76] = info(Push23, "PUSH23", 23, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x77] = info(Push24, "PUSH24", 24, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x78] = info(Push25, "PUSH25", 25, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x79] = info(Push26, "PUSH26", 26, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x7a] = info(Push27, "PUSH27", 27, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x7b] = info(Push28, "PUSH28", 28, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x7c] = info(Push29, "PUSH29", 29, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x7d] = info(Push30, "PUSH30", 30, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x7e] = info(Push31, "PUSH31", 31, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x7f] = info(Push32, "PUSH32", 32, 0, 1, gas::VERY_LOW, Fork::Frontier);
+    table[0x80] = info(Dup1, "DUP1", 0, 1, 2, gas::VERY_LOW, Fork::Frontier);
+    table[0x81] = info(Dup2, "DUP2", 0, 2, 3, gas::VERY_LOW, Fork::Frontier);
+    table[0x82] = info(Dup3, "DUP3", 0, 3, 4, gas::VERY_LOW, Fork::Frontier);
+    table[0x83] = info(Dup4, "DUP4", 0, 4, 5, gas::VERY_LOW, Fork::Frontier);
+    table[0x84] = info(Dup5, "DUP5", 0, 5, 6, gas::VERY_LOW, Fork::Frontier);
+    table[0x85] = info(Dup6, "DUP6", 0, 6, 7, gas::VERY_LOW, Fork::Frontier);
+    table[0x86] = info(Dup7, "DUP7", 0, 7, 8, gas::VERY_LOW, Fork::Frontier);
+    table[0x87] = info(Dup8, "DUP8", 0, 8, 9, gas::VERY_LOW, Fork::Frontier);
+    table[0x88] = info(Dup9, "DUP9", 0, 9, 10, gas::VERY_LOW, Fork::Frontier);
+    table[0x89] = info(Dup10, "DUP10", 0, 10, 11, gas::VERY_LOW, Fork::Frontier);
+    table[0x8a] = info(Dup11, "DUP11", 0, 11, 12, gas::VERY_LOW, Fork::Frontier);
+    table[0x8b] = info(Dup12, "DUP12", 0, 12, 13, gas::VERY_LOW, Fork::Frontier);
+    table[0x8c] = info(Dup13, "DUP13", 0, 13, 14, gas::VERY_LOW, Fork::Frontier);
+    table[0x8d] = info(Dup14, "DUP14", 0, 14, 15, gas::VERY_LOW, Fork::Frontier);
+    table[0x8e] = info(Dup15, "DUP15", 0, 15, 16, gas::VERY_LOW, Fork::Frontier);
+    table[0x8f] = info(Dup16, "DUP16", 0, 16, 17, gas::VERY_LOW, Fork::Frontier);
+    table[0x90] = info(Swap1, "SWAP1", 0, 2, 2, gas::VERY_LOW, Fork::Frontier);
+    table[0x91] = info(Swap2, "SWAP2", 0, 3, 3, gas::VERY_LOW, Fork::Frontier);
+    table[0x92] = info(Swap3, "SWAP3", 0, 4, 4, gas::VERY_LOW, Fork::Frontier);
+    table[0x93] = info(Swap4, "SWAP4", 0, 5, 5, gas::VERY_LOW, Fork::Frontier);
+    table[0x94] = info(Swap5, "SWAP5", 0, 6, 6, gas::VERY_LOW, Fork::Frontier);
+    table[0x95] = info(Swap6, "SWAP6", 0, 7, 7, gas::VERY_LOW, Fork::Frontier);
+    table[0x96] = info(Swap7, "SWAP7", 0, 8, 8, gas::VERY_LOW, Fork::Frontier);
+    table[0x97] = info(Swap8, "SWAP8", 0, 9, 9, gas::VERY_LOW, Fork::Frontier);
+    table[0x98] = info(Swap9, "SWAP9", 0, 10, 10, gas::VERY_LOW, Fork::Frontier);
+    table[0x99] = info(Swap10, "SWAP10", 0, 11, 11, gas::VERY_LOW, Fork::Frontier);
+    table[0x9a] = info(Swap11, "SWAP11", 0, 12, 12, gas::VERY_LOW, Fork::Frontier);
+    table[0x9b] = info(Swap12, "SWAP12", 0, 13, 13, gas::VERY_LOW, Fork::Frontier);
+    table[0x9c] = info(Swap13, "SWAP13", 0, 14, 14, gas::VERY_LOW, Fork::Frontier);
+    table[0x9d] = info(Swap14, "SWAP14", 0, 15, 15, gas::VERY_LOW, Fork::Frontier);
+    table[0x9e] = info(Swap15, "SWAP15", 0, 16, 16, gas::VERY_LOW, Fork::Frontier);
+    table[0x9f] = info(Swap16, "SWAP16", 0, 17, 17, gas::VERY_LOW, Fork::Frontier);
+    table[0xa0] = info(Log0, "LOG0", 0, 2, 0, gas::LOG, Fork::Frontier);
+    table[0xa1] = info(Log1, "LOG1", 0, 3, 0, gas::LOG + gas::LOG_TOPIC, Fork::Frontier);
+    table[0xa2] = info(Log2, "LOG2", 0, 4, 0, gas::LOG + 2 * gas::LOG_TOPIC, Fork::Frontier);
+    table[0xa3] = info(Log3, "LOG3", 0, 5, 0, gas::LOG + 3 * gas::LOG_TOPIC, Fork::Frontier);
+    table[0xa4] = info(Log4, "LOG4", 0, 6, 0, gas::LOG + 4 * gas::LOG_TOPIC, Fork::Frontier);
+    table[0xd0] = eof_info(DataLoad, "DATALOAD", 0, 1, 1, gas::DATALOAD);
+    table[0xd1] = eof_info(DataLoadN, "DATALOADN", 2, 0, 1, gas::VERY_LOW);
+    table[0xd2] = eof_info(DataSize, "DATASIZE", 0, 0, 1, gas::BASE);
+    table[0xd3] = eof_info(DataCopy, "DATACOPY", 0, 3, 0, gas::VERY_LOW);
+    table[0xe0] = eof_info(RJump, "RJUMP", 2, 0, 0, gas::BASE);
+    table[0xe1] = eof_info(RJumpI, "RJUMPI", 2, 1, 0, gas::RJUMPI);
+    table[0xe2] = eof_info(RJumpV, "RJUMPV", 1, 1, 0, gas::RJUMPI);
+    table[0xe3] = eof_info(CallF, "CALLF", 2, 0, 0, gas::LOW);
+    table[0xe4] = eof_info(RetF, "RETF", 0, 0, 0, gas::VERY_LOW);
+    table[0xe5] = eof_info(JumpF, "JUMPF", 2, 0, 0, gas::LOW);
+    table[0xe6] = eof_info(DupN, "DUPN", 1, 0, 0, gas::VERY_LOW);
+    table[0xe7] = eof_info(SwapN, "SWAPN", 1, 0, 0, gas::VERY_LOW);
+    table[0xe8] = eof_info(Exchange, "EXCHANGE", 1, 0, 0, gas::VERY_LOW);
+    table[0xec] = eof_info(EofCreate, "EOFCREATE", 1, 4, 1, gas::CREATE);
+    table[0xee] = eof_info(ReturnContract, "RETURNCONTRACT", 1, 2, 0, gas::ZERO);
+    table[0xf0] = info(Create, "CREATE", 0, 3, 1, gas::CREATE, Fork::Frontier);
+    table[0xf1] = info(Call, "CALL", 0, 7, 1, gas::WARM_ACCESS, Fork::Frontier);
+    table[0xf2] = info(CallCode, "CALLCODE", 0, 7, 1, gas::WARM_ACCESS, Fork::Frontier);
+    table[0xf3] = info(Return, "RETURN", 0, 2, 0, gas::ZERO, Fork::Frontier);
+    table[0xf4] = info(DelegateCall, "DELEGATECALL", 0, 6, 1, gas::WARM_ACCESS, Fork::Homestead);
+    table[0xf5] = info(Create2, "CREATE2", 0, 4, 1, gas::CREATE, Fork::Constantinople);
+    table[0xf7] = eof_info(ReturnDataLoad, "RETURNDATALOAD", 0, 1, 1, gas::VERY_LOW);
+    table[0xf8] = eof_info(ExtCall, "EXTCALL", 0, 4, 1, gas::WARM_ACCESS);
+    table[0xf9] = eof_info(ExtDelegateCall, "EXTDELEGATECALL", 0, 3, 1, gas::WARM_ACCESS);
+    table[0xfa] = info(StaticCall, "STATICCALL", 0, 6, 1, gas::WARM_ACCESS, Fork::Byzantium);
+    table[0xfb] = eof_info(ExtStaticCall, "EXTSTATICCALL", 0, 3, 1, gas::WARM_ACCESS);
+    table[0xfd] = info(Revert, "REVERT", 0, 2, 0, gas::ZERO, Fork::Byzantium);
+    table[0xfe] = info(Invalid, "INVALID", 0, 0, 0, gas::ZERO, Fork::Frontier);
+    table[0xff] = info(SelfDestruct, "SELFDESTRUCT", 0, 1, 0, gas::SELF_DESTRUCT, Fork::Frontier);
+    table
+};
+
+/// Returns the metadata of `opcode` in legacy code, or `None` if it is undefined there.
+pub fn legacy_info(opcode: u8) -> Option<&'static OpcodeInfo> {
+    OPCODES[opcode as usize]
+        .as_ref()
+        .filter(|info| !info.is_eof_only())
 }
 
 impl From<u8> for Instruction {
     fn from(value: u8) -> Self {
-        use Instruction::*;
-        match value {
-            0x00 => Stop,
-            0x01 => Add,
-            0x02 => Mul,
-            0x03 => Sub,
-            0x04 => Div,
-            0x05 => SDiv,
-            0x06 => Mod,
-            0x07 => SMod,
-            0x08 => AddMod,
-            0x09 => MulMod,
-            0x0a => Exp,
-            0x0b => SignExtend,
-            0x10 => Lt,
-            0x11 => SLt,
-            0x12 => Gt,
-            0x13 => SGt,
-            0x14 => Eq,
-            0x15 => IsZero,
-            0x16 => And,
-            0x17 => Or,
-            0x18 => Xor,
-            0x19 => Not,
-            0x1a => Byte,
-            0x1b => ShL,
-            0x1c => ShR,
-            0x1d => SAR,
-            0x20 => Keccak256,
-            0x30 => Address,
-            0x31 => Balance,
-            0x32 => Origin,
-            0x33 => Caller,
-            0x34 => CallValue,
-            0x35 => CallDataLoad,
-            0x36 => CallDataSize,
-            0x37 => CallDataCopy,
-            0x38 => CodeSize,
-            0x39 => CodeCopy,
-            0x3a => GasPrice,
-            0x3b => ExtCodeSize,
-            0x3c => ExtCodeCopy,
-            0x3d => ReturnDataSize,
-            0x3e => ReturnDataCopy,
-            0x3f => ExtCodeHash,
-            0x40 => BlockHash,
-            0x41 => Coinbase,
-            0x42 => Timestamp,
-            0x43 => Number,
-            0x44 => Difficulty,
-            0x45 => GasLimit,
-            0x46 => ChainId,
-            0x47 => SelfBalance,
-            0x50 => Pop,
-            0x51 => MLoad,
-            0x52 => MStore,
-            0x53 => MStore8,
-            0x54 => SLoad,
-            0x55 => SStore,
-            0x56 => Jump,
-            0x57 => JumpI,
-            0x58 => Pc,
-            0x59 => MSize,
-            0x5a => Gas,
-            0x5b => JumpDest,
-            0x60 => Push1,
-            0x61 => Push2,
-            0x62 => Push3,
-            0x63 => Push4,
-            0x64 => Push5,
-            0x65 => Push6,
-            0x66 => Push7,
-            0x67 => Push8,
-            0x68 => Push9,
-            0x69 => Push10,
-            0x6a => Push11,
-            0x6b => Push12,
-            0x6c => Push13,
-            0x6d => Push14,
-            0x6e => Push15,
-            0x6f => Push16,
-            0x70 => Push17,
-            0x71 => Push18,
-            0x72 => Push19,
-            0x73 => Push20,
-            0x74 => Push21,
-            0x75 => Push22,
-            0x76 => Push23,
-            0x77 => Push24,
-            0x78 => Push25,
-            0x79 => Push26,
-            0x7a => Push27,
-            0x7b => Push28,
-            0x7c => Push29,
-            0x7d => Push30,
-            0x7e => Push31,
-            0x7f => Push32,
-            0x80 => Dup1,
-            0x81 => Dup2,
-            0x82 => Dup3,
-            0x83 => Dup4,
-            0x84 => Dup5,
-            0x85 => Dup6,
-            0x86 => Dup7,
-            0x87 => Dup8,
-            0x88 => Dup9,
-            0x89 => Dup10,
-            0x8a => Dup11,
-            0x8b => Dup12,
-            0x8c => Dup13,
-            0x8d => Dup14,
-            0x8e => Dup15,
-            0x8f => Dup16,
-            0x90 => Swap1,
-            0x91 => Swap2,
-            0x92 => Swap3,
-            0x93 => Swap4,
-            0x94 => Swap5,
-            0x95 => Swap6,
-            0x96 => Swap7,
-            0x97 => Swap8,
-            0x98 => Swap9,
-            0x99 => Swap10,
-            0x9a => Swap11,
-            0x9b => Swap12,
-            0x9c => Swap13,
-            0x9d => Swap14,
-            0x9e => Swap15,
-            0x9f => Swap16,
-            0xa0 => Log0,
-            0xa1 => Log1,
-            0xa2 => Log2,
-            0xa3 => Log3,
-            0xa4 => Log4,
-            0xf0 => Create,
-            0xf1 => Call,
-            0xf2 => CallCode,
-            0xf3 => Return,
-            0xf4 => DelegateCall,
-            0xf5 => Create2,
-            0xfa => StaticCall,
-            0xfd => Revert,
-            0xfe => Invalid,
-            0xff => SelfDestruct,
-            _ => Invalid,
+        match OPCODES[value as usize] {
+            Some(info) => info.instruction,
+            None => Instruction::Invalid,
         }
     }
 }
 
+/// An error parsing an instruction from its mnemonic.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownMnemonic(pub String);
+
+impl FromStr for Instruction {
+    type Err = UnknownMnemonic;
+
+    /// Parses an instruction from its case-insensitive mnemonic.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OPCODES
+            .iter()
+            .flatten()
+            .find(|info| info.name.eq_ignore_ascii_case(s))
+            .map(|info| info.instruction)
+            .ok_or_else(|| UnknownMnemonic(s.to_string()))
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 impl Instruction {
+    /// Returns the byte encoding the instruction in code.
+    pub fn opcode(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Returns the metadata of the instruction.
+    pub fn info(&self) -> &'static OpcodeInfo {
+        match &OPCODES[self.opcode() as usize] {
+            Some(info) => info,
+            None => unreachable!("every instruction has metadata"),
+        }
+    }
+
     /// Returns a tuple containing the number of items removed from the stack (delta) and the
     /// number of items added to the stack (alpha) for the instruction.
-    fn delta_alpha(&self) -> (u8, u8) {
-        use Instruction::*;
-        match self {
-            Stop => (0, 0),
-            Add => (2, 1),
-            Mul => (2, 1),
-            Sub => (2, 1),
-            Div => (2, 1),
-            SDiv => (2, 1),
-            Mod => (2, 1),
-            SMod => (2, 1),
-            AddMod => (3, 1),
-            MulMod => (3, 1),
-            Exp => (2, 1),
-            SignExtend => (2, 1),
-            Lt => (2, 1),
-            Gt => (2, 1),
-            SLt => (2, 1),
-            SGt => (2, 1),
-            Eq => (2, 1),
-            IsZero => (1, 1),
-            And => (2, 1),
-            Or => (2, 1),
-            Xor => (2, 1),
-            Not => (1, 1),
-            Byte => (2, 1),
-            ShL => (2, 1),
-            ShR => (2, 1),
-            SAR => (2, 1),
-            Keccak256 => (2, 1),
-            Address => (0, 1),
-            Balance => (1, 1),
-            Origin => (0, 1),
-            Caller => (0, 1),
-            CallValue => (0, 1),
-            CallDataLoad => (1, 1),
-            CallDataSize => (0, 1),
-            CallDataCopy => (3, 0),
-            CodeSize => (0, 1),
-            CodeCopy => (3, 0),
-            GasPrice => (0, 1),
-            ExtCodeSize => (1, 1),
-            ExtCodeCopy => (4, 0),
-            ReturnDataSize => (0, 1),
-            ReturnDataCopy => (3, 0),
-            ExtCodeHash => (1, 1),
-            BlockHash => (1, 1),
-            Coinbase => (0, 1),
-            Timestamp => (0, 1),
-            Number => (0, 1),
-            Difficulty => (0, 1),
-            GasLimit => (0, 1),
-            ChainId => (0, 1),
-            SelfBalance => (0, 1),
-            Pop => (1, 0),
-            MLoad => (1, 1),
-            MStore => (2, 0),
-            MStore8 => (2, 0),
-            SLoad => (1, 1),
-            SStore => (2, 0),
-            Jump => (1, 0),
-            JumpI => (2, 0),
-            Pc => (0, 1),
-            MSize => (0, 1),
-            Gas => (0, 1),
-            JumpDest => (0, 0),
-            Push1 | Push2 | Push3 | Push4 | Push5 | Push6 | Push7 | Push8 | Push9 | Push10
-            | Push11 | Push12 | Push13 | Push14 | Push15 | Push16 | Push17 | Push18 | Push19
-            | Push20 | Push21 | Push22 | Push23 | Push24 | Push25 | Push26 | Push27 | Push28
-            | Push29 | Push30 | Push31 | Push32 => (0, 1),
-            Dup1 => (1, 2),
-            Dup2 => (2, 3),
-            Dup3 => (3, 4),
-            Dup4 => (4, 5),
-            Dup5 => (5, 6),
-            Dup6 => (6, 7),
-            Dup7 => (7, 8),
-            Dup8 => (8, 9),
-            Dup9 => (9, 10),
-            Dup10 => (10, 11),
-            Dup11 => (11, 12),
-            Dup12 => (12, 13),
-            Dup13 => (13, 14),
-            Dup14 => (14, 15),
-            Dup15 => (15, 16),
-            Dup16 => (16, 17),
-            Swap1 => (2, 2),
-            Swap2 => (3, 3),
-            Swap3 => (4, 4),
-            Swap4 => (5, 5),
-            Swap5 => (6, 6),
-            Swap6 => (7, 7),
-            Swap7 => (8, 8),
-            Swap8 => (9, 9),
-            Swap9 => (10, 10),
-            Swap10 => (11, 11),
-            Swap11 => (12, 12),
-            Swap12 => (13, 13),
-            Swap13 => (14, 14),
-            Swap14 => (15, 15),
-            Swap15 => (16, 16),
-            Swap16 => (17, 17),
-            Log0 => (2, 0),
-            Log1 => (3, 0),
-            Log2 => (4, 0),
-            Log3 => (5, 0),
-            Log4 => (6, 0),
-            Create => (3, 1),
-            Call => (7, 1),
-            CallCode => (7, 1),
-            Return => (2, 0),
-            DelegateCall => (6, 1),
-            Create2 => (4, 1),
-            StaticCall => (6, 1),
-            Revert => (2, 0),
-            // Technically, delta and alpha are undefined for the invalid instruction.
-            Invalid => (0, 0),
-            SelfDestruct => (1, 0),
+    ///
+    /// Technically, delta and alpha are undefined for the invalid instruction, which has
+    /// neither.
+    pub fn delta_alpha(&self) -> (u8, u8) {
+        let info = self.info();
+        (info.inputs, info.outputs)
+    }
+
+    /// Returns the mnemonic of the instruction, such as `PUSH1` or `CALLDATALOAD`.
+    pub fn name(&self) -> &'static str {
+        self.info().name
+    }
+
+    /// Returns the number of bytes of immediate data following the instruction in code.
+    pub fn immediate_size(&self) -> usize {
+        self.info().immediate_size()
+    }
+
+    /// Returns the fork that introduced the instruction, or `None` for an EOF-only instruction.
+    pub fn fork(&self) -> Option<Fork> {
+        self.info().fork
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcode_table() {
+        for (opcode, info) in OPCODES.iter().enumerate() {
+            let info = match info {
+                Some(info) => info,
+                None => {
+                    assert_eq!(Instruction::from(opcode as u8), Instruction::Invalid);
+                    continue;
+                }
+            };
+            let instruction = info.instruction();
+            assert_eq!(instruction.opcode() as usize, opcode);
+            assert_eq!(Instruction::from(opcode as u8), instruction);
+            assert_eq!(instruction.to_string().parse(), Ok(instruction));
         }
+        assert_eq!(OPCODES.iter().flatten().count(), 168);
+        assert_eq!((0..=255).filter_map(legacy_info).count(), 149);
+    }
+
+    #[test]
+    fn test_instruction_metadata() {
+        let info = Instruction::Push20.info();
+        assert_eq!(info.name(), "PUSH20");
+        assert_eq!(info.immediate_size(), 20);
+        assert_eq!((info.inputs(), info.outputs()), (0, 1));
+        assert_eq!(info.base_gas(), gas::VERY_LOW);
+
+        assert_eq!(Instruction::Log2.info().base_gas(), 1125);
+        assert_eq!(Instruction::Create2.fork(), Some(Fork::Constantinople));
+        assert_eq!(Instruction::RJumpV.fork(), None);
+        assert!(Instruction::RJumpV.info().is_eof_only());
+        assert_eq!(Instruction::RJumpV.info().immediate_size(), 1);
+        assert_eq!(legacy_info(0xe2), None);
+        assert_eq!(legacy_info(0x60), Some(Instruction::Push1.info()));
+        assert_eq!("mcopy".parse(), Ok(Instruction::MCopy));
+        assert_eq!(
+            "PUSH33".parse::<Instruction>(),
+            Err(UnknownMnemonic(String::from("PUSH33")))
+        );
     }
 }
@@ -0,0 +1,2885 @@
+use crate::core::fork::Fork;
+use crate::core::spec::BlobParams;
+use crate::core::state::{Checkpoint, WorldState};
+use crate::core::types::block::BlockHeader;
+use crate::core::types::log::Log;
+use ethereum_types::{Address, BigEndianHash, H160, H256, U256, U512};
+use keccak_hash::{keccak, KECCAK_EMPTY};
+use rlp::RlpStream;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use super::env::{BlockHashes, ExecutionEnv};
+use super::eof::{self, ContainerKind, EofContainer};
+use super::gas::{self, SStoreMetering, Schedule};
+use super::inspector::Inspector;
+use super::instruction::Instruction;
+use super::jumpdest::{CodeAnalysisCache, JumpDests};
+use super::machine::MachineState;
+use super::precompile;
+use super::stack::MAX_LEN;
+use super::substate::AccruedSubstate;
+
+/// Maximum depth of message calls and contract creations.
+pub const MAX_CALL_DEPTH: usize = 1024;
+
+/// The kind of a message call or contract creation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallKind {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+    Create,
+    Create2,
+}
+
+impl CallKind {
+    /// Returns whether this is a contract creation.
+    pub fn is_create(&self) -> bool {
+        matches!(self, CallKind::Create | CallKind::Create2)
+    }
+}
+
+/// A condition that causes execution to halt, consuming all gas and reverting all state
+/// modifications.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExceptionalHalt {
+    OutOfGas,
+    InvalidInstruction,
+    StackUnderflow,
+    StackOverflow,
+    BadJumpDestination,
+    /// A state modification was attempted without write access.
+    StaticStateChange,
+    ReturnDataOutOfBounds,
+    /// The maximum call depth was exceeded. Gas is not consumed.
+    CallDepthExceeded,
+    /// The balance of the caller does not cover the value transferred. Gas is not consumed.
+    InsufficientBalance,
+    /// The nonce of the creator cannot be incremented (EIP-2681). Gas is not consumed.
+    NonceOverflow,
+    /// A contract already exists at the address of a contract creation.
+    CreateCollision,
+    /// Deployed code exceeds the size limit (EIP-170).
+    CodeSizeExceeded,
+    /// Initialization code exceeds the size limit (EIP-3860).
+    InitCodeSizeExceeded,
+    /// Deployed code starts with the 0xEF byte (EIP-3541).
+    InvalidCodePrefix,
+    PrecompileFailure,
+    /// An EOF message call was given an address with non-zero high bytes (EIP-7069).
+    AddressOutOfRange,
+    /// Deployed EOF code falls short of the data its header declares (EIP-7620).
+    InvalidContainer,
+}
+
+/// How an execution ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Revert,
+    Halt(ExceptionalHalt),
+}
+
+impl Status {
+    /// Returns whether the execution succeeded.
+    pub fn is_success(&self) -> bool {
+        *self == Status::Success
+    }
+}
+
+/// The parameters of a message call or contract creation.
+#[derive(Clone, Debug)]
+pub struct Message {
+    kind: CallKind,
+    /// Address of the account that caused the code to execute.
+    caller: Address,
+    /// Address of the account whose state the execution acts upon. For contract creations,
+    /// this is derived once the creation starts.
+    target: Address,
+    /// Address of the account whose code is executed.
+    code_address: Address,
+    /// Value (in Wei) transferred to `target`.
+    value: U256,
+    /// Value (in Wei) visible to the executing code. Differs from `value` for delegate calls.
+    apparent_value: U256,
+    /// Call data, or initialization code for contract creations.
+    input: Vec<u8>,
+    gas: U256,
+    depth: usize,
+    is_static: bool,
+    salt: Option<H256>,
+    /// Initialization code of an EOF contract creation, whose input is call data.
+    code: Option<Vec<u8>>,
+}
+
+impl Message {
+    /// Returns a message call from `caller` to `to`.
+    pub fn call(caller: Address, to: Address, value: U256, input: Vec<u8>, gas: U256) -> Self {
+        Self {
+            kind: CallKind::Call,
+            caller,
+            target: to,
+            code_address: to,
+            value,
+            apparent_value: value,
+            input,
+            gas,
+            depth: 0,
+            is_static: false,
+            salt: None,
+            code: None,
+        }
+    }
+
+    /// Returns a contract creation by `caller` with initialization code `init`.
+    pub fn create(caller: Address, value: U256, init: Vec<u8>, gas: U256) -> Self {
+        Self {
+            kind: CallKind::Create,
+            caller,
+            target: Address::zero(),
+            code_address: Address::zero(),
+            value,
+            apparent_value: value,
+            input: init,
+            gas,
+            depth: 0,
+            is_static: false,
+            salt: None,
+            code: None,
+        }
+    }
+
+    pub fn kind(&self) -> CallKind {
+        self.kind
+    }
+
+    pub fn caller(&self) -> &Address {
+        &self.caller
+    }
+
+    pub fn target(&self) -> &Address {
+        &self.target
+    }
+
+    pub fn code_address(&self) -> &Address {
+        &self.code_address
+    }
+
+    pub fn value(&self) -> U256 {
+        self.value
+    }
+
+    pub fn apparent_value(&self) -> U256 {
+        self.apparent_value
+    }
+
+    pub fn input(&self) -> &Vec<u8> {
+        &self.input
+    }
+
+    pub fn gas(&self) -> U256 {
+        self.gas
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.is_static
+    }
+
+    pub fn salt(&self) -> Option<&H256> {
+        self.salt.as_ref()
+    }
+}
+
+/// The result of a message call or contract creation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    status: Status,
+    gas_left: U256,
+    /// Returned data. Empty for successful contract creations, whose returned data is the
+    /// deployed code.
+    output: Vec<u8>,
+    created_address: Option<Address>,
+}
+
+impl Outcome {
+    /// Returns the outcome of a message call, or of a contract creation that created no
+    /// contract.
+    pub fn new(status: Status, gas_left: U256, output: Vec<u8>) -> Self {
+        Self {
+            status,
+            gas_left,
+            output,
+            created_address: None,
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn gas_left(&self) -> U256 {
+        self.gas_left
+    }
+
+    pub fn output(&self) -> &Vec<u8> {
+        &self.output
+    }
+
+    pub fn created_address(&self) -> Option<&Address> {
+        self.created_address.as_ref()
+    }
+
+    pub fn set_status(&mut self, status: Status) {
+        self.status = status;
+    }
+
+    pub fn set_gas_left(&mut self, gas_left: U256) {
+        self.gas_left = gas_left;
+    }
+
+    pub fn set_output(&mut self, output: Vec<u8>) {
+        self.output = output;
+    }
+
+    pub fn set_created_address(&mut self, address: Option<Address>) {
+        self.created_address = address;
+    }
+}
+
+/// Returns the address of a contract created by `sender` with `nonce`.
+pub fn create_address(sender: &Address, nonce: U256) -> Address {
+    let mut s = RlpStream::new_list(2);
+    s.append(sender);
+    s.append(&nonce);
+    Address::from_slice(&keccak(s.out())[12..])
+}
+
+/// Returns the address of a contract created by `sender` with `salt` and initialization code
+/// hashing to `init_code_hash` (EIP-1014).
+pub fn create2_address(sender: &Address, salt: &H256, init_code_hash: &H256) -> Address {
+    let mut bytes = Vec::with_capacity(85);
+    bytes.push(0xff);
+    bytes.extend_from_slice(sender.as_bytes());
+    bytes.extend_from_slice(salt.as_bytes());
+    bytes.extend_from_slice(init_code_hash.as_bytes());
+    Address::from_slice(&keccak(bytes)[12..])
+}
+
+/// An executing message call or contract creation.
+struct Frame {
+    kind: CallKind,
+    env: ExecutionEnv,
+    machine: MachineState,
+    jump_dests: Arc<JumpDests>,
+    /// State checkpoint taken on entry.
+    checkpoint: Checkpoint,
+    /// Substate as of entry.
+    substate: AccruedSubstate,
+    /// Memory region receiving the output of the pending message call, if any.
+    return_region: (usize, usize),
+    /// Whether the pending message is a contract creation.
+    pending_create: bool,
+    /// Whether the pending message is an EOF message call, which pushes its status.
+    pending_ext_call: bool,
+    /// The container of EOF code, with the executing code section.
+    eof: Option<EofFrame>,
+}
+
+/// The state of a frame executing EOF code.
+struct EofFrame {
+    container: Arc<EofContainer>,
+    /// Index of the executing code section.
+    section: usize,
+    /// The code section and offset each pending `CALLF` returns to.
+    return_stack: Vec<(usize, usize)>,
+}
+
+/// The effect of a single instruction on the frame executing it.
+enum Action {
+    Continue,
+    /// Start a message call or contract creation.
+    Message(Box<Message>),
+    /// Stop executing the frame.
+    Exit(Status, Vec<u8>),
+}
+
+/// Executes message calls and contract creations against a world state.
+pub struct Interpreter<'a> {
+    state: &'a mut WorldState,
+    substate: AccruedSubstate,
+    header: &'a BlockHeader,
+    block_hashes: &'a dyn BlockHashes,
+    fork: Fork,
+    schedule: Schedule,
+    chain_id: U256,
+    origin: Address,
+    gas_price: U256,
+    blob_hashes: Vec<H256>,
+    blob_params: Option<BlobParams>,
+    inspector: Option<&'a mut dyn Inspector>,
+    code_analysis_cache: Option<&'a mut CodeAnalysisCache>,
+    eof: bool,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Returns an interpreter for the transaction sent by `origin` at `gas_price`, carrying
+    /// blobs with `blob_hashes`, in the block with `header`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        state: &'a mut WorldState,
+        header: &'a BlockHeader,
+        block_hashes: &'a dyn BlockHashes,
+        fork: Fork,
+        chain_id: U256,
+        origin: Address,
+        gas_price: U256,
+        blob_hashes: Vec<H256>,
+    ) -> Self {
+        Self {
+            state,
+            substate: AccruedSubstate::empty(),
+            header,
+            block_hashes,
+            fork,
+            schedule: Schedule::new(fork),
+            chain_id,
+            origin,
+            gas_price,
+            blob_hashes,
+            blob_params: None,
+            inspector: None,
+            code_analysis_cache: None,
+            eof: false,
+        }
+    }
+
+    /// Sets the blob parameters of the chain, which the blob gas price follows from. Without
+    /// them, as before blobs, `BLOBBASEFEE` pushes zero.
+    pub fn set_blob_params(&mut self, params: Option<BlobParams>) {
+        self.blob_params = params;
+    }
+
+    /// Sets the inspector called back during execution.
+    pub fn set_inspector(&mut self, inspector: &'a mut dyn Inspector) {
+        self.inspector = Some(inspector);
+    }
+
+    /// Sets the cache of the analysis of deployed code: the jump destinations of legacy code
+    /// and the validated containers of EOF code. Without one, code is analyzed on every
+    /// message call.
+    pub fn set_code_analysis_cache(&mut self, cache: &'a mut CodeAnalysisCache) {
+        self.code_analysis_cache = Some(cache);
+    }
+
+    /// Sets whether code beginning with the EOF magic is executed as an EOF container
+    /// (EIP-3540), which no fork enables yet.
+    pub fn set_eof(&mut self, enabled: bool) {
+        self.eof = enabled;
+    }
+
+    /// Returns the world state.
+    pub fn state(&self) -> &WorldState {
+        self.state
+    }
+
+    /// Returns mutable world state.
+    pub fn state_mut(&mut self) -> &mut WorldState {
+        self.state
+    }
+
+    /// Returns the accrued substate.
+    pub fn substate(&self) -> &AccruedSubstate {
+        &self.substate
+    }
+
+    /// Returns the mutable accrued substate.
+    pub fn substate_mut(&mut self) -> &mut AccruedSubstate {
+        &mut self.substate
+    }
+
+    /// Consumes the interpreter, returning the accrued substate.
+    pub fn into_substate(self) -> AccruedSubstate {
+        self.substate
+    }
+
+    /// Executes `message` to completion.
+    pub fn execute(&mut self, message: Message) -> Outcome {
+        let mut frames = Vec::new();
+        let mut pending = match self.enter(None, message) {
+            Ok(frame) => {
+                frames.push(frame);
+                None
+            }
+            Err(outcome) => return self.call_end(None, outcome),
+        };
+
+        loop {
+            let frame = match frames.last_mut() {
+                Some(frame) => frame,
+                None => unreachable!("the outermost frame returns"),
+            };
+            if let Some(outcome) = pending.take() {
+                self.resume(frame, outcome);
+            }
+
+            match self.run(frame) {
+                Action::Message(message) => match self.enter(Some(frame), *message) {
+                    Ok(child) => frames.push(child),
+                    Err(outcome) => pending = Some(self.call_end(Some(frame), outcome)),
+                },
+                Action::Exit(status, output) => {
+                    let frame = frames.pop().expect("frame is executing");
+                    let outcome = self.exit(frame, status, output);
+                    let outcome = self.call_end(frames.last_mut(), outcome);
+                    if frames.is_empty() {
+                        return outcome;
+                    }
+                    pending = Some(outcome);
+                }
+                Action::Continue => unreachable!("run continues until the frame stops"),
+            }
+        }
+    }
+
+    /// Passes the outcome of the message call or contract creation most recently started to
+    /// the inspector, if any, along with the frame that started it.
+    fn call_end(&mut self, caller: Option<&mut Frame>, mut outcome: Outcome) -> Outcome {
+        if let Some(inspector) = self.inspector.as_mut() {
+            let (machine, env) = match caller {
+                Some(frame) => (Some(&mut frame.machine), Some(&frame.env)),
+                None => (None, None),
+            };
+            inspector.call_end(machine, env, &self.substate, &mut outcome);
+        }
+        outcome
+    }
+
+    /// Starts a message call or contract creation on behalf of the frame `caller`, which is
+    /// `None` for the message of the transaction. Returns the new frame, or the outcome if the
+    /// message completes without executing code.
+    fn enter(
+        &mut self,
+        caller: Option<&mut Frame>,
+        mut message: Message,
+    ) -> Result<Frame, Outcome> {
+        if message.kind.is_create() {
+            let address = self.create_target(&message);
+            message.target = address;
+            message.code_address = address;
+        }
+        if let Some(inspector) = self.inspector.as_mut() {
+            let (machine, env) = match caller {
+                Some(frame) => (Some(&mut frame.machine), Some(&frame.env)),
+                None => (None, None),
+            };
+            if let Some(outcome) = inspector.call(machine, env, &self.substate, &message) {
+                return Err(outcome);
+            }
+        }
+
+        let gas = message.gas;
+        let fail = |halt| Outcome::new(Status::Halt(halt), gas, Vec::new());
+
+        if message.depth > MAX_CALL_DEPTH {
+            return Err(fail(ExceptionalHalt::CallDepthExceeded));
+        }
+        let transfers_value =
+            matches!(message.kind, CallKind::Call | CallKind::CallCode) || message.kind.is_create();
+        if transfers_value && self.state.balance(&message.caller) < message.value {
+            return Err(fail(ExceptionalHalt::InsufficientBalance));
+        }
+
+        if message.kind.is_create() {
+            return self.enter_create(message);
+        }
+
+        let checkpoint = self.state.checkpoint();
+        let substate = self.substate.clone();
+
+        if message.kind == CallKind::Call {
+            let target = message.target;
+            let is_precompile = precompile::is_precompile(&message.code_address, self.fork);
+            if !self.state.exists(&target)
+                && !is_precompile
+                && self.fork >= Fork::SpuriousDragon
+                && message.value.is_zero()
+            {
+                return Err(Outcome::new(Status::Success, gas, Vec::new()));
+            }
+            if !self.state.exists(&target) {
+                self.state.create_account(&target);
+            }
+            self.state.transfer(&message.caller, &target, message.value);
+            self.substate.touch(target);
+        }
+
+        if precompile::is_precompile(&message.code_address, self.fork) {
+            let gas_u64 = gas.min(U256::from(u64::MAX)).low_u64();
+            return Err(
+                match precompile::execute(&message.code_address, &message.input, gas_u64, self.fork)
+                {
+                    Ok((cost, output)) => Outcome::new(Status::Success, gas - cost, output),
+                    Err(_) => {
+                        self.state.revert_to(checkpoint);
+                        self.substate = substate;
+                        // Mainnet block 2675119 left the RIPEMD-160 precompile touched despite
+                        // its call failing, which clients have preserved since (EIP-716).
+                        if message.code_address == Address::from_low_u64_be(3) {
+                            self.substate.touch(message.code_address);
+                        }
+                        Outcome::new(
+                            Status::Halt(ExceptionalHalt::PrecompileFailure),
+                            U256::zero(),
+                            Vec::new(),
+                        )
+                    }
+                },
+            );
+        }
+
+        let code = self.state.code(&message.code_address).to_vec();
+        if code.is_empty() {
+            return Err(Outcome::new(Status::Success, gas, Vec::new()));
+        }
+
+        let code_hash = self.state.code_hash(&message.code_address);
+        let container = if self.eof && eof::is_eof(&code) {
+            let container = match self.code_analysis_cache.as_mut() {
+                Some(cache) => cache.get_container(code_hash, &code),
+                None => EofContainer::validate(&code, ContainerKind::Runtime)
+                    .ok()
+                    .map(Arc::new),
+            };
+            match container {
+                Some(container) => Some(container),
+                None => {
+                    self.state.revert_to(checkpoint);
+                    self.substate = substate;
+                    return Err(Outcome::new(
+                        Status::Halt(ExceptionalHalt::InvalidInstruction),
+                        U256::zero(),
+                        Vec::new(),
+                    ));
+                }
+            }
+        } else {
+            None
+        };
+
+        Ok(self.frame(
+            message,
+            code,
+            Some(code_hash),
+            container,
+            checkpoint,
+            substate,
+        ))
+    }
+
+    /// Returns the address of the contract created by `message`.
+    fn create_target(&self, message: &Message) -> Address {
+        match message.salt {
+            Some(salt) => {
+                let init = message.code.as_ref().unwrap_or(&message.input);
+                create2_address(&message.caller, &salt, &keccak(init))
+            }
+            None => {
+                let nonce = self.state.nonce(&message.caller);
+                // The transaction has already incremented the nonce of its sender.
+                if message.depth == 0 {
+                    create_address(&message.caller, nonce.saturating_sub(U256::one()))
+                } else {
+                    create_address(&message.caller, nonce)
+                }
+            }
+        }
+    }
+
+    /// Starts a contract creation, whose target has been derived.
+    fn enter_create(&mut self, mut message: Message) -> Result<Frame, Outcome> {
+        let gas = message.gas;
+        if message.depth > 0 {
+            if self.state.nonce(&message.caller) >= U256::from(u64::MAX) {
+                return Err(Outcome::new(
+                    Status::Halt(ExceptionalHalt::NonceOverflow),
+                    gas,
+                    Vec::new(),
+                ));
+            }
+            self.state.increment_nonce(&message.caller);
+        }
+        let address = message.target;
+
+        if self.schedule.warm_cold_access {
+            self.substate.access_account(address);
+        }
+
+        if self.state.nonce(&address) > U256::zero()
+            || self.state.code_hash(&address) != KECCAK_EMPTY
+            || self.state.has_storage(&address)
+        {
+            return Err(Outcome::new(
+                Status::Halt(ExceptionalHalt::CreateCollision),
+                U256::zero(),
+                Vec::new(),
+            ));
+        }
+
+        let checkpoint = self.state.checkpoint();
+        let substate = self.substate.clone();
+
+        self.state.create_account(&address);
+        if self.fork >= Fork::SpuriousDragon {
+            self.state.set_nonce(&address, U256::one());
+        }
+        self.substate.add_created(address);
+        self.state
+            .transfer(&message.caller, &address, message.value);
+        self.substate.touch(address);
+
+        let (init, container) = match message.code.take() {
+            // The initialization code of `EOFCREATE` was validated with the code creating it.
+            Some(code) => {
+                let container = EofContainer::parse(&code).ok().map(Arc::new);
+                (code, container)
+            }
+            None => (std::mem::take(&mut message.input), None),
+        };
+        Ok(self.frame(message, init, None, container, checkpoint, substate))
+    }
+
+    /// Returns a frame executing `code` on behalf of `message`, starting at the first code
+    /// section of `container` for EOF code. The jump destinations of deployed legacy code,
+    /// which has `code_hash`, are cached.
+    fn frame(
+        &mut self,
+        message: Message,
+        code: Vec<u8>,
+        code_hash: Option<H256>,
+        container: Option<Arc<EofContainer>>,
+        checkpoint: Checkpoint,
+        substate: AccruedSubstate,
+    ) -> Frame {
+        let jump_dests = match (code_hash, self.code_analysis_cache.as_mut()) {
+            _ if container.is_some() => Arc::new(JumpDests::default()),
+            (Some(code_hash), Some(cache)) => cache.get(code_hash, &code),
+            _ => Arc::new(JumpDests::new(&code)),
+        };
+        let code = match &container {
+            Some(container) => container.code_sections()[0].clone(),
+            None => code,
+        };
+        let env = ExecutionEnv::new(
+            message.target,
+            self.origin,
+            self.gas_price,
+            message.input,
+            message.caller,
+            message.apparent_value,
+            code,
+            self.header.clone(),
+            U256::from(message.depth),
+            !message.is_static,
+        );
+
+        Frame {
+            kind: message.kind,
+            jump_dests,
+            env,
+            machine: MachineState::with_gas(message.gas),
+            checkpoint,
+            substate,
+            return_region: (0, 0),
+            pending_create: false,
+            pending_ext_call: false,
+            eof: container.map(|container| EofFrame {
+                container,
+                section: 0,
+                return_stack: Vec::new(),
+            }),
+        }
+    }
+
+    /// Finishes a frame, returning the outcome of its message call or contract creation.
+    fn exit(&mut self, frame: Frame, status: Status, output: Vec<u8>) -> Outcome {
+        let mut gas_left = *frame.machine.gas_available();
+        let mut status = status;
+        let mut output = output;
+        let mut created_address = None;
+
+        if frame.kind.is_create() && status == Status::Success {
+            let address = *frame.env.account();
+            let deposit = U256::from(gas::CODE_DEPOSIT) * output.len();
+
+            // EOF code deploys a validated container, which begins with the EOF magic.
+            if self.fork >= Fork::London && output.first() == Some(&0xef) && frame.eof.is_none() {
+                status = Status::Halt(ExceptionalHalt::InvalidCodePrefix);
+            } else if self.fork >= Fork::SpuriousDragon && output.len() > gas::MAX_CODE_SIZE {
+                status = Status::Halt(ExceptionalHalt::CodeSizeExceeded);
+            } else if deposit > gas_left {
+                if self.fork >= Fork::Homestead {
+                    status = Status::Halt(ExceptionalHalt::OutOfGas);
+                } else {
+                    // Frontier creations that cannot pay for their code deploy no code.
+                    created_address = Some(address);
+                    output = Vec::new();
+                }
+            } else {
+                gas_left -= deposit;
+                self.state.set_code(&address, std::mem::take(&mut output));
+                created_address = Some(address);
+            }
+        }
+
+        if status != Status::Success {
+            self.state.revert_to(frame.checkpoint);
+            self.substate = frame.substate;
+            if let Status::Halt(_) = status {
+                gas_left = U256::zero();
+                output = Vec::new();
+            }
+        }
+
+        Outcome {
+            status,
+            gas_left,
+            output,
+            created_address,
+        }
+    }
+
+    /// Resumes `frame` with the outcome of the message call or contract creation it started.
+    fn resume(&mut self, frame: &mut Frame, outcome: Outcome) {
+        let gas = *frame.machine.gas_available() + outcome.gas_left;
+        frame.machine.set_gas_available(gas);
+
+        let result = if frame.pending_create {
+            outcome
+                .created_address
+                .filter(|_| outcome.status.is_success())
+                .map_or_else(U256::zero, |address| H256::from(address).into_uint())
+        } else if frame.pending_ext_call {
+            U256::from(match outcome.status {
+                Status::Success => 0,
+                Status::Revert => 1,
+                Status::Halt(_) => 2,
+            })
+        } else {
+            let (offset, size) = frame.return_region;
+            let len = size.min(outcome.output.len());
+            frame.machine.memory_mut()[offset..offset + len]
+                .copy_from_slice(&outcome.output[..len]);
+            U256::from(outcome.status.is_success() as u8)
+        };
+        // The stack has room: the instruction that started the message popped its arguments.
+        let _ = frame.machine.stack_mut().push(result);
+        frame.machine.set_return_data(outcome.output);
+    }
+
+    /// Runs `frame` until it stops or starts a message call or contract creation.
+    fn run(&mut self, frame: &mut Frame) -> Action {
+        loop {
+            if let Some(inspector) = self.inspector.as_mut() {
+                inspector.step(&mut frame.machine, &frame.env, &self.substate);
+            }
+            let result = self.step(frame);
+            if let Some(inspector) = self.inspector.as_mut() {
+                let error = result.as_ref().err().copied();
+                inspector.step_end(&mut frame.machine, &frame.env, &self.substate, error);
+            }
+            match result {
+                Ok(Action::Continue) => {}
+                Ok(action) => return action,
+                Err(halt) => return Action::Exit(Status::Halt(halt), Vec::new()),
+            }
+        }
+    }
+
+    /// Executes the instruction at the program counter of `frame`.
+    fn step(&mut self, frame: &mut Frame) -> Result<Action, ExceptionalHalt> {
+        use ExceptionalHalt::*;
+        use Instruction::*;
+
+        let pc = frame.machine.program_counter().low_u64() as usize;
+        let opcode = match frame.env.machine_code().get(pc) {
+            Some(&opcode) => opcode,
+            None => return Ok(Action::Exit(Status::Success, Vec::new())),
+        };
+        if frame.eof.is_some() {
+            if let Some(action) = self.step_eof(frame, pc, opcode)? {
+                return Ok(action);
+            }
+        }
+        let instruction = Instruction::from(opcode);
+        // EOF-only instructions, which no fork enables, are undefined in legacy code.
+        let defined = matches!(instruction.fork(), Some(fork) if fork <= self.fork);
+        if instruction == Invalid || !defined {
+            return Err(InvalidInstruction);
+        }
+
+        let (delta, alpha) = instruction.delta_alpha();
+        let len = frame.machine.stack().len();
+        if len < delta as usize {
+            return Err(StackUnderflow);
+        }
+        if len - delta as usize + alpha as usize > MAX_LEN {
+            return Err(StackOverflow);
+        }
+
+        let mut next_pc = pc + 1;
+        let machine = &mut frame.machine;
+        let env = &frame.env;
+
+        match instruction {
+            Stop => return Ok(Action::Exit(Status::Success, Vec::new())),
+            Add => {
+                charge(machine, gas::VERY_LOW)?;
+                let (a, b) = (pop(machine), pop(machine));
+                push(machine, a.overflowing_add(b).0);
+            }
+            Mul => {
+                charge(machine, gas::LOW)?;
+                let (a, b) = (pop(machine), pop(machine));
+                push(machine, a.overflowing_mul(b).0);
+            }
+            Sub => {
+                charge(machine, gas::VERY_LOW)?;
+                let (a, b) = (pop(machine), pop(machine));
+                push(machine, a.overflowing_sub(b).0);
+            }
+            Div => {
+                charge(machine, gas::LOW)?;
+                let (a, b) = (pop(machine), pop(machine));
+                push(machine, if b.is_zero() { b } else { a / b });
+            }
+            SDiv => {
+                charge(machine, gas::LOW)?;
+                let (a, b) = (pop(machine), pop(machine));
+                push(machine, signed_div(a, b));
+            }
+            Mod => {
+                charge(machine, gas::LOW)?;
+                let (a, b) = (pop(machine), pop(machine));
+                push(machine, if b.is_zero() { b } else { a % b });
+            }
+            SMod => {
+                charge(machine, gas::LOW)?;
+                let (a, b) = (pop(machine), pop(machine));
+                push(machine, signed_mod(a, b));
+            }
+            AddMod => {
+                charge(machine, gas::MID)?;
+                let (a, b, n) = (pop(machine), pop(machine), pop(machine));
+                let result = if n.is_zero() {
+                    n
+                } else {
+                    narrow((U512::from(a) + U512::from(b)) % U512::from(n))
+                };
+                push(machine, result);
+            }
+            MulMod => {
+                charge(machine, gas::MID)?;
+                let (a, b, n) = (pop(machine), pop(machine), pop(machine));
+                let result = if n.is_zero() {
+                    n
+                } else {
+                    narrow(a.full_mul(b) % U512::from(n))
+                };
+                push(machine, result);
+            }
+            Exp => {
+                let (a, b) = (*peek(machine, 0), *peek(machine, 1));
+                let bytes = (b.bits() as u64).div_ceil(8);
+                charge(machine, gas::EXP + self.schedule.exp_byte * bytes)?;
+                pop(machine);
+                pop(machine);
+                push(machine, a.overflowing_pow(b).0);
+            }
+            SignExtend => {
+                charge(machine, gas::LOW)?;
+                let (b, x) = (pop(machine), pop(machine));
+                push(machine, sign_extend(b, x));
+            }
+            Lt | Gt | SLt | SGt | Eq => {
+                charge(machine, gas::VERY_LOW)?;
+                let (a, b) = (pop(machine), pop(machine));
+                let result = match instruction {
+                    Lt => a < b,
+                    Gt => a > b,
+                    SLt => signed_cmp(a, b) == Ordering::Less,
+                    SGt => signed_cmp(a, b) == Ordering::Greater,
+                    _ => a == b,
+                };
+                push(machine, U256::from(result as u8));
+            }
+            IsZero => {
+                charge(machine, gas::VERY_LOW)?;
+                let a = pop(machine);
+                push(machine, U256::from(a.is_zero() as u8));
+            }
+            And | Or | Xor => {
+                charge(machine, gas::VERY_LOW)?;
+                let (a, b) = (pop(machine), pop(machine));
+                let result = match instruction {
+                    And => a & b,
+                    Or => a | b,
+                    _ => a ^ b,
+                };
+                push(machine, result);
+            }
+            Not => {
+                charge(machine, gas::VERY_LOW)?;
+                let a = pop(machine);
+                push(machine, !a);
+            }
+            Byte => {
+                charge(machine, gas::VERY_LOW)?;
+                let (i, x) = (pop(machine), pop(machine));
+                let result = if i < U256::from(32) {
+                    U256::from(x.byte(31 - i.low_u64() as usize))
+                } else {
+                    U256::zero()
+                };
+                push(machine, result);
+            }
+            ShL | ShR => {
+                charge(machine, gas::VERY_LOW)?;
+                let (shift, value) = (pop(machine), pop(machine));
+                let result = if shift >= U256::from(256) {
+                    U256::zero()
+                } else if instruction == ShL {
+                    value << shift.low_u64() as usize
+                } else {
+                    value >> shift.low_u64() as usize
+                };
+                push(machine, result);
+            }
+            Sar => {
+                charge(machine, gas::VERY_LOW)?;
+                let (shift, value) = (pop(machine), pop(machine));
+                let negative = value.bit(255);
+                let result = if shift >= U256::from(256) {
+                    if negative {
+                        U256::MAX
+                    } else {
+                        U256::zero()
+                    }
+                } else if negative {
+                    !(!value >> shift.low_u64() as usize)
+                } else {
+                    value >> shift.low_u64() as usize
+                };
+                push(machine, result);
+            }
+            Keccak256 => {
+                let (offset, size) = (*peek(machine, 0), *peek(machine, 1));
+                charge_words(machine, gas::KECCAK256, gas::KECCAK256_WORD, size)?;
+                expand_memory(machine, offset, size)?;
+                pop(machine);
+                pop(machine);
+                let hash = keccak(memory_slice(machine, offset, size));
+                push(machine, hash.into_uint());
+            }
+            Address => {
+                charge(machine, gas::BASE)?;
+                push(machine, address_to_word(env.account()));
+            }
+            Balance => {
+                let address = word_to_address(peek(machine, 0));
+                let cost = self.account_access_cost(&address, self.schedule.balance);
+                charge(machine, cost)?;
+                pop(machine);
+                push(machine, self.state.balance(&address));
+            }
+            Origin => {
+                charge(machine, gas::BASE)?;
+                push(machine, address_to_word(env.origin()));
+            }
+            Caller => {
+                charge(machine, gas::BASE)?;
+                push(machine, address_to_word(env.sender()));
+            }
+            CallValue => {
+                charge(machine, gas::BASE)?;
+                push(machine, *env.value());
+            }
+            CallDataLoad => {
+                charge(machine, gas::VERY_LOW)?;
+                let offset = pop(machine);
+                let word = padded_slice(env.data(), offset, U256::from(32));
+                push(machine, U256::from_big_endian(&word));
+            }
+            CallDataSize => {
+                charge(machine, gas::BASE)?;
+                push(machine, U256::from(env.data().len()));
+            }
+            CallDataCopy | CodeCopy => {
+                let (dest, offset, size) =
+                    (*peek(machine, 0), *peek(machine, 1), *peek(machine, 2));
+                charge_words(machine, gas::VERY_LOW, gas::COPY, size)?;
+                expand_memory(machine, dest, size)?;
+                pop(machine);
+                pop(machine);
+                pop(machine);
+                let source = if instruction == CallDataCopy {
+                    env.data()
+                } else {
+                    env.machine_code()
+                };
+                let data = padded_slice(source, offset, size);
+                copy_to_memory(machine, dest, &data);
+            }
+            CodeSize => {
+                charge(machine, gas::BASE)?;
+                push(machine, U256::from(env.machine_code().len()));
+            }
+            GasPrice => {
+                charge(machine, gas::BASE)?;
+                push(machine, *env.gas_price());
+            }
+            ExtCodeSize => {
+                let address = word_to_address(peek(machine, 0));
+                let cost = self.account_access_cost(&address, self.schedule.ext_code);
+                charge(machine, cost)?;
+                pop(machine);
+                push(machine, U256::from(self.state.code(&address).len()));
+            }
+            ExtCodeCopy => {
+                let address = word_to_address(peek(machine, 0));
+                let (dest, offset, size) =
+                    (*peek(machine, 1), *peek(machine, 2), *peek(machine, 3));
+                let cost = self.account_access_cost(&address, self.schedule.ext_code);
+                charge_words(machine, cost, gas::COPY, size)?;
+                expand_memory(machine, dest, size)?;
+                for _ in 0..4 {
+                    pop(machine);
+                }
+                let data = padded_slice(self.state.code(&address), offset, size);
+                copy_to_memory(machine, dest, &data);
+            }
+            ReturnDataSize => {
+                charge(machine, gas::BASE)?;
+                let len = machine.return_data().len();
+                push(machine, U256::from(len));
+            }
+            ReturnDataCopy => {
+                let (dest, offset, size) =
+                    (*peek(machine, 0), *peek(machine, 1), *peek(machine, 2));
+                charge_words(machine, gas::VERY_LOW, gas::COPY, size)?;
+                let end = offset.overflowing_add(size);
+                if end.1 || end.0 > U256::from(machine.return_data().len()) {
+                    return Err(ReturnDataOutOfBounds);
+                }
+                expand_memory(machine, dest, size)?;
+                pop(machine);
+                pop(machine);
+                pop(machine);
+                let data = padded_slice(machine.return_data(), offset, size);
+                copy_to_memory(machine, dest, &data);
+            }
+            ExtCodeHash => {
+                let address = word_to_address(peek(machine, 0));
+                let cost = self.account_access_cost(&address, self.schedule.ext_code_hash);
+                charge(machine, cost)?;
+                pop(machine);
+                let hash = if self.state.is_dead(&address) {
+                    U256::zero()
+                } else {
+                    self.state.code_hash(&address).into_uint()
+                };
+                push(machine, hash);
+            }
+            BlockHash => {
+                charge(machine, gas::BLOCKHASH)?;
+                let number = pop(machine);
+                let current = env.header().number();
+                let hash = if number < current && current - number <= U256::from(256) {
+                    self.block_hashes.block_hash(number).unwrap_or_default()
+                } else {
+                    H256::zero()
+                };
+                push(machine, hash.into_uint());
+            }
+            Coinbase => {
+                charge(machine, gas::BASE)?;
+                push(machine, address_to_word(env.header().beneficiary()));
+            }
+            Timestamp => {
+                charge(machine, gas::BASE)?;
+                push(machine, env.header().timestamp());
+            }
+            Number => {
+                charge(machine, gas::BASE)?;
+                push(machine, env.header().number());
+            }
+            Difficulty => {
+                charge(machine, gas::BASE)?;
+                // Since Paris, the instruction returns the beacon chain randomness (EIP-4399).
+                let value = if self.fork >= Fork::Paris {
+                    env.header().mix_hash().into_uint()
+                } else {
+                    env.header().difficulty()
+                };
+                push(machine, value);
+            }
+            GasLimit => {
+                charge(machine, gas::BASE)?;
+                push(machine, env.header().gas_limit());
+            }
+            ChainId => {
+                charge(machine, gas::BASE)?;
+                push(machine, self.chain_id);
+            }
+            SelfBalance => {
+                charge(machine, gas::LOW)?;
+                push(machine, self.state.balance(env.account()));
+            }
+            BaseFee => {
+                charge(machine, gas::BASE)?;
+                push(machine, env.header().base_fee_per_gas().unwrap_or_default());
+            }
+            BlobHash => {
+                charge(machine, gas::VERY_LOW)?;
+                let index = pop(machine);
+                let hash = if index < U256::from(self.blob_hashes.len()) {
+                    self.blob_hashes[index.low_u64() as usize]
+                } else {
+                    H256::zero()
+                };
+                push(machine, hash.into_uint());
+            }
+            BlobBaseFee => {
+                charge(machine, gas::BASE)?;
+                let price = self
+                    .blob_params
+                    .and_then(|params| env.header().blob_gas_price(params.update_fraction()));
+                push(machine, price.unwrap_or_default());
+            }
+            Pop => {
+                charge(machine, gas::BASE)?;
+                pop(machine);
+            }
+            MLoad => {
+                charge(machine, gas::VERY_LOW)?;
+                let offset = *peek(machine, 0);
+                expand_memory(machine, offset, U256::from(32))?;
+                pop(machine);
+                let word = U256::from_big_endian(memory_slice(machine, offset, U256::from(32)));
+                push(machine, word);
+            }
+            MStore | MStore8 => {
+                charge(machine, gas::VERY_LOW)?;
+                let offset = *peek(machine, 0);
+                let size = if instruction == MStore { 32 } else { 1 };
+                expand_memory(machine, offset, U256::from(size))?;
+                pop(machine);
+                let value = pop(machine);
+                let mut word = [0u8; 32];
+                value.to_big_endian(&mut word);
+                copy_to_memory(machine, offset, &word[32 - size..]);
+            }
+            SLoad => {
+                let key = *peek(machine, 0);
+                let address = *env.account();
+                let cost = if self.schedule.warm_cold_access
+                    && !self.substate.access_storage(address, key)
+                {
+                    gas::COLD_SLOAD
+                } else {
+                    self.schedule.sload
+                };
+                charge(machine, cost)?;
+                pop(machine);
+                push(machine, self.state.storage(&address, &key));
+            }
+            SStore => {
+                if !env.write_access() {
+                    return Err(StaticStateChange);
+                }
+                let (key, value) = (*peek(machine, 0), *peek(machine, 1));
+                let address = *env.account();
+                self.sstore(machine, address, key, value)?;
+                pop(machine);
+                pop(machine);
+                self.state.set_storage(&address, key, value);
+            }
+            Jump => {
+                charge(machine, gas::MID)?;
+                let dest = pop(machine);
+                next_pc = jump_target(&frame.jump_dests, dest)?;
+            }
+            JumpI => {
+                charge(machine, gas::HIGH)?;
+                let (dest, condition) = (pop(machine), pop(machine));
+                if !condition.is_zero() {
+                    next_pc = jump_target(&frame.jump_dests, dest)?;
+                }
+            }
+            Pc => {
+                charge(machine, gas::BASE)?;
+                push(machine, U256::from(pc));
+            }
+            MSize => {
+                charge(machine, gas::BASE)?;
+                let size = *machine.words_in_memory() * 32;
+                push(machine, size);
+            }
+            Gas => {
+                charge(machine, gas::BASE)?;
+                let gas = *machine.gas_available();
+                push(machine, gas);
+            }
+            JumpDest => {
+                charge(machine, gas::JUMPDEST)?;
+            }
+            TLoad => {
+                charge(machine, gas::WARM_ACCESS)?;
+                let key = pop(machine);
+                push(machine, self.state.transient_storage(env.account(), &key));
+            }
+            TStore => {
+                if !env.write_access() {
+                    return Err(StaticStateChange);
+                }
+                charge(machine, gas::WARM_ACCESS)?;
+                let (key, value) = (pop(machine), pop(machine));
+                self.state.set_transient_storage(env.account(), key, value);
+            }
+            MCopy => {
+                let (dest, offset, size) =
+                    (*peek(machine, 0), *peek(machine, 1), *peek(machine, 2));
+                charge_words(machine, gas::VERY_LOW, gas::COPY, size)?;
+                expand_memory(machine, dest.max(offset), size)?;
+                pop(machine);
+                pop(machine);
+                pop(machine);
+                if !size.is_zero() {
+                    let (dest, offset, size) = (
+                        dest.low_u64() as usize,
+                        offset.low_u64() as usize,
+                        size.low_u64() as usize,
+                    );
+                    machine
+                        .memory_mut()
+                        .copy_within(offset..offset + size, dest);
+                }
+            }
+            Push0 => {
+                charge(machine, gas::BASE)?;
+                push(machine, U256::zero());
+            }
+            Push1 | Push2 | Push3 | Push4 | Push5 | Push6 | Push7 | Push8 | Push9 | Push10
+            | Push11 | Push12 | Push13 | Push14 | Push15 | Push16 | Push17 | Push18 | Push19
+            | Push20 | Push21 | Push22 | Push23 | Push24 | Push25 | Push26 | Push27 | Push28
+            | Push29 | Push30 | Push31 | Push32 => {
+                charge(machine, gas::VERY_LOW)?;
+                let n = (opcode - 0x5f) as usize;
+                let data = padded_slice(env.machine_code(), U256::from(pc + 1), U256::from(n));
+                push(machine, U256::from_big_endian(&data));
+                next_pc = pc + 1 + n;
+            }
+            Dup1 | Dup2 | Dup3 | Dup4 | Dup5 | Dup6 | Dup7 | Dup8 | Dup9 | Dup10 | Dup11
+            | Dup12 | Dup13 | Dup14 | Dup15 | Dup16 => {
+                charge(machine, gas::VERY_LOW)?;
+                let value = *peek(machine, (opcode - 0x80) as usize);
+                push(machine, value);
+            }
+            Swap1 | Swap2 | Swap3 | Swap4 | Swap5 | Swap6 | Swap7 | Swap8 | Swap9 | Swap10
+            | Swap11 | Swap12 | Swap13 | Swap14 | Swap15 | Swap16 => {
+                charge(machine, gas::VERY_LOW)?;
+                let _ = machine.stack_mut().swap_top((opcode - 0x8f) as usize);
+            }
+            Log0 | Log1 | Log2 | Log3 | Log4 => {
+                if !env.write_access() {
+                    return Err(StaticStateChange);
+                }
+                let topics = (opcode - 0xa0) as usize;
+                let (offset, size) = (*peek(machine, 0), *peek(machine, 1));
+                charge(machine, gas::LOG + gas::LOG_TOPIC * topics as u64)?;
+                if size > U256::from(u64::MAX) {
+                    return Err(OutOfGas);
+                }
+                charge(machine, size.low_u64().saturating_mul(gas::LOG_DATA))?;
+                expand_memory(machine, offset, size)?;
+                pop(machine);
+                pop(machine);
+                let topics = (0..topics)
+                    .map(|_| H256::from_uint(&pop(machine)))
+                    .collect();
+                let data = memory_slice(machine, offset, size).to_vec();
+                let log = Log::new(*env.account(), topics, data);
+                if let Some(inspector) = self.inspector.as_mut() {
+                    inspector.log(env, &log);
+                }
+                self.substate.add_log(log);
+            }
+            Create | Create2 => {
+                if !env.write_access() {
+                    return Err(StaticStateChange);
+                }
+                let (value, offset, size) =
+                    (*peek(machine, 0), *peek(machine, 1), *peek(machine, 2));
+                if self.fork >= Fork::Shanghai && size > U256::from(gas::MAX_INIT_CODE_SIZE) {
+                    return Err(InitCodeSizeExceeded);
+                }
+                let word_cost = if instruction == Create2 {
+                    gas::KECCAK256_WORD
+                } else {
+                    0
+                } + if self.fork >= Fork::Shanghai {
+                    gas::INIT_CODE_WORD
+                } else {
+                    0
+                };
+                charge_words(machine, gas::CREATE, word_cost, size)?;
+                expand_memory(machine, offset, size)?;
+                pop(machine);
+                pop(machine);
+                pop(machine);
+                let salt = if instruction == Create2 {
+                    Some(H256::from_uint(&pop(machine)))
+                } else {
+                    None
+                };
+                let init = memory_slice(machine, offset, size).to_vec();
+
+                let mut gas = *machine.gas_available();
+                if self.fork >= Fork::TangerineWhistle {
+                    gas = gas - gas / 64;
+                }
+                charge(machine, gas.low_u64())?;
+                machine.set_return_data(Vec::new());
+                machine.set_program_counter(U256::from(next_pc));
+                frame.return_region = (0, 0);
+                frame.pending_create = true;
+                frame.pending_ext_call = false;
+
+                return Ok(Action::Message(Box::new(Message {
+                    kind: if salt.is_some() {
+                        CallKind::Create2
+                    } else {
+                        CallKind::Create
+                    },
+                    caller: *env.account(),
+                    target: H160::zero(),
+                    code_address: H160::zero(),
+                    value,
+                    apparent_value: value,
+                    input: init,
+                    gas,
+                    depth: env.depth().low_u64() as usize + 1,
+                    is_static: false,
+                    salt,
+                    code: None,
+                })));
+            }
+            Call | CallCode | DelegateCall | StaticCall => {
+                let has_value = matches!(instruction, Call | CallCode);
+                let requested = *peek(machine, 0);
+                let address = word_to_address(peek(machine, 1));
+                let value = if has_value {
+                    *peek(machine, 2)
+                } else {
+                    U256::zero()
+                };
+                let i = if has_value { 3 } else { 2 };
+                let (in_offset, in_size) = (*peek(machine, i), *peek(machine, i + 1));
+                let (out_offset, out_size) = (*peek(machine, i + 2), *peek(machine, i + 3));
+
+                if instruction == Call && !value.is_zero() && !env.write_access() {
+                    return Err(StaticStateChange);
+                }
+
+                let mut cost = self.account_access_cost(&address, self.schedule.call);
+                if !value.is_zero() {
+                    cost += gas::CALL_VALUE;
+                }
+                if instruction == Call {
+                    let new_account = if self.fork >= Fork::SpuriousDragon {
+                        !value.is_zero() && self.state.is_dead(&address)
+                    } else {
+                        !self.state.exists(&address)
+                    };
+                    if new_account {
+                        cost += gas::NEW_ACCOUNT;
+                    }
+                }
+                charge(machine, cost)?;
+                expand_memory(machine, in_offset, in_size)?;
+                expand_memory(machine, out_offset, out_size)?;
+
+                let available = *machine.gas_available();
+                let mut gas = if self.fork >= Fork::TangerineWhistle {
+                    requested.min(available - available / 64)
+                } else {
+                    requested
+                };
+                if gas > U256::from(u64::MAX) {
+                    return Err(OutOfGas);
+                }
+                charge(machine, gas.low_u64())?;
+                if !value.is_zero() {
+                    gas += U256::from(gas::CALL_STIPEND);
+                }
+
+                for _ in 0..(i + 4) {
+                    pop(machine);
+                }
+                let input = memory_slice(machine, in_offset, in_size).to_vec();
+                machine.set_program_counter(U256::from(next_pc));
+                frame.pending_create = false;
+                frame.pending_ext_call = false;
+                frame.return_region = if out_size.is_zero() {
+                    (0, 0)
+                } else {
+                    (out_offset.low_u64() as usize, out_size.low_u64() as usize)
+                };
+
+                let (kind, caller, target, apparent_value) = match instruction {
+                    Call => (CallKind::Call, *env.account(), address, value),
+                    CallCode => (CallKind::CallCode, *env.account(), *env.account(), value),
+                    DelegateCall => (
+                        CallKind::DelegateCall,
+                        *env.sender(),
+                        *env.account(),
+                        *env.value(),
+                    ),
+                    _ => (CallKind::StaticCall, *env.account(), address, U256::zero()),
+                };
+
+                return Ok(Action::Message(Box::new(Message {
+                    kind,
+                    caller,
+                    target,
+                    code_address: address,
+                    value,
+                    apparent_value,
+                    input,
+                    gas,
+                    depth: env.depth().low_u64() as usize + 1,
+                    is_static: !env.write_access() || kind == CallKind::StaticCall,
+                    salt: None,
+                    code: None,
+                })));
+            }
+            Return | Revert => {
+                let (offset, size) = (*peek(machine, 0), *peek(machine, 1));
+                expand_memory(machine, offset, size)?;
+                let output = memory_slice(machine, offset, size).to_vec();
+                let status = if instruction == Return {
+                    Status::Success
+                } else {
+                    Status::Revert
+                };
+                return Ok(Action::Exit(status, output));
+            }
+            Invalid => return Err(InvalidInstruction),
+            SelfDestruct => {
+                if !env.write_access() {
+                    return Err(StaticStateChange);
+                }
+                let beneficiary = word_to_address(peek(machine, 0));
+                let account = *env.account();
+                let balance = self.state.balance(&account);
+
+                let mut cost = self.schedule.self_destruct;
+                if self.schedule.warm_cold_access && !self.substate.access_account(beneficiary) {
+                    cost += gas::COLD_ACCOUNT_ACCESS;
+                }
+                let new_account = if self.fork >= Fork::SpuriousDragon {
+                    self.state.is_dead(&beneficiary) && !balance.is_zero()
+                } else {
+                    self.fork >= Fork::TangerineWhistle && !self.state.exists(&beneficiary)
+                };
+                if new_account {
+                    cost += gas::NEW_ACCOUNT;
+                }
+                charge(machine, cost)?;
+                if let Some(inspector) = self.inspector.as_mut() {
+                    inspector.self_destruct(&account, &beneficiary, balance);
+                }
+
+                if !self.substate.self_destruct_accounts().contains(&account) {
+                    self.substate
+                        .add_refund(U256::from(self.schedule.self_destruct_refund));
+                }
+
+                if self.fork >= Fork::Cancun && !self.substate.created_accounts().contains(&account)
+                {
+                    // Since Cancun, only accounts created in the same transaction are deleted
+                    // (EIP-6780); others only transfer their balance.
+                    self.state.transfer(&account, &beneficiary, balance);
+                } else {
+                    self.state.add_balance(&beneficiary, balance);
+                    self.state.set_balance(&account, U256::zero());
+                    self.substate.add_self_destruct(account);
+                }
+                self.substate.touch(beneficiary);
+
+                return Ok(Action::Exit(Status::Success, Vec::new()));
+            }
+            // The EOF-only instructions, which are undefined in legacy code.
+            _ => return Err(InvalidInstruction),
+        }
+
+        machine.set_program_counter(U256::from(next_pc));
+        Ok(Action::Continue)
+    }
+
+    /// Executes the instruction `opcode` at `pc` of EOF code in `frame`, or returns `None` if it
+    /// executes as in legacy code. Validation of the code has ruled out undefined instructions,
+    /// truncated immediate data, invalid jumps and stack underflows.
+    fn step_eof(
+        &mut self,
+        frame: &mut Frame,
+        pc: usize,
+        opcode: u8,
+    ) -> Result<Option<Action>, ExceptionalHalt> {
+        use ExceptionalHalt::*;
+
+        let eof_frame = match frame.eof.as_mut() {
+            Some(eof_frame) => eof_frame,
+            None => return Ok(None),
+        };
+        let machine = &mut frame.machine;
+        let env = &frame.env;
+        let code = env.machine_code();
+        let size = match eof::immediate_size(code, pc) {
+            Some(size) if eof::eof_stack_io(opcode).is_some() => size,
+            _ => return Err(InvalidInstruction),
+        };
+        let immediate = &code[pc + 1..pc + 1 + size];
+        let immediate_u16 = || u16::from_be_bytes([immediate[0], immediate[1]]) as usize;
+        let mut next_pc = pc + 1 + size;
+        let mut section = None;
+
+        match opcode {
+            eof::RJUMP => {
+                charge(machine, gas::BASE)?;
+                next_pc = eof::relative_target(next_pc, immediate).ok_or(BadJumpDestination)?;
+            }
+            eof::RJUMPI => {
+                charge(machine, gas::RJUMPI)?;
+                if !pop(machine).is_zero() {
+                    next_pc = eof::relative_target(next_pc, immediate).ok_or(BadJumpDestination)?;
+                }
+            }
+            eof::RJUMPV => {
+                charge(machine, gas::RJUMPI)?;
+                let case = pop(machine);
+                if case <= U256::from(immediate[0]) {
+                    let i = 1 + 2 * case.low_u64() as usize;
+                    next_pc = eof::relative_target(next_pc, &immediate[i..i + 2])
+                        .ok_or(BadJumpDestination)?;
+                }
+            }
+            eof::CALLF | eof::JUMPF => {
+                charge(machine, gas::LOW)?;
+                let target = immediate_u16();
+                let target_type = eof_frame.container.types()[target];
+                if machine.stack().len() + target_type.max_stack_increase() > MAX_LEN {
+                    return Err(StackOverflow);
+                }
+                if opcode == eof::CALLF {
+                    if eof_frame.return_stack.len() >= MAX_CALL_DEPTH {
+                        return Err(StackOverflow);
+                    }
+                    eof_frame.return_stack.push((eof_frame.section, next_pc));
+                }
+                section = Some(target);
+                next_pc = 0;
+            }
+            eof::RETF => {
+                charge(machine, gas::VERY_LOW)?;
+                let (target, target_pc) = eof_frame.return_stack.pop().ok_or(StackUnderflow)?;
+                section = Some(target);
+                next_pc = target_pc;
+            }
+            eof::DUPN => {
+                charge(machine, gas::VERY_LOW)?;
+                if machine.stack().len() >= MAX_LEN {
+                    return Err(StackOverflow);
+                }
+                let value = *peek(machine, immediate[0] as usize);
+                push(machine, value);
+            }
+            eof::SWAPN => {
+                charge(machine, gas::VERY_LOW)?;
+                let _ = machine.stack_mut().swap_top(immediate[0] as usize + 1);
+            }
+            eof::EXCHANGE => {
+                charge(machine, gas::VERY_LOW)?;
+                let n = (immediate[0] >> 4) as usize + 1;
+                let m = (immediate[0] & 0x0f) as usize + 1;
+                let stack = machine.stack_mut();
+                let _ = stack.swap_top(n);
+                let _ = stack.swap_top(n + m);
+                let _ = stack.swap_top(n);
+            }
+            eof::DATALOAD | eof::DATALOADN => {
+                let offset = if opcode == eof::DATALOAD {
+                    charge(machine, gas::DATALOAD)?;
+                    pop(machine)
+                } else {
+                    charge(machine, gas::VERY_LOW)?;
+                    U256::from(immediate_u16())
+                };
+                let data = padded_slice(eof_frame.container.data(), offset, U256::from(32));
+                push(machine, U256::from_big_endian(&data));
+            }
+            eof::DATASIZE => {
+                charge(machine, gas::BASE)?;
+                push(machine, U256::from(eof_frame.container.data().len()));
+            }
+            eof::DATACOPY | 0x3e => {
+                // RETURNDATACOPY pads out-of-bounds return data with zeros in EOF code.
+                let (dest, offset, size) =
+                    (*peek(machine, 0), *peek(machine, 1), *peek(machine, 2));
+                charge_words(machine, gas::VERY_LOW, gas::COPY, size)?;
+                expand_memory(machine, dest, size)?;
+                pop(machine);
+                pop(machine);
+                pop(machine);
+                let source = if opcode == eof::DATACOPY {
+                    eof_frame.container.data()
+                } else {
+                    machine.return_data()
+                };
+                let data = padded_slice(source, offset, size);
+                copy_to_memory(machine, dest, &data);
+            }
+            eof::RETURNDATALOAD => {
+                charge(machine, gas::VERY_LOW)?;
+                let offset = pop(machine);
+                let data = padded_slice(machine.return_data(), offset, U256::from(32));
+                push(machine, U256::from_big_endian(&data));
+            }
+            eof::EOFCREATE => {
+                if !env.write_access() {
+                    return Err(StaticStateChange);
+                }
+                let init = eof_frame.container.container_sections()[immediate[0] as usize].clone();
+                let (value, salt) = (*peek(machine, 0), *peek(machine, 1));
+                let (in_offset, in_size) = (*peek(machine, 2), *peek(machine, 3));
+                charge_words(
+                    machine,
+                    gas::CREATE,
+                    gas::KECCAK256_WORD,
+                    U256::from(init.len()),
+                )?;
+                expand_memory(machine, in_offset, in_size)?;
+                for _ in 0..4 {
+                    pop(machine);
+                }
+                let input = memory_slice(machine, in_offset, in_size).to_vec();
+
+                let available = *machine.gas_available();
+                let gas = available - available / 64;
+                charge(machine, gas.low_u64())?;
+                machine.set_return_data(Vec::new());
+                machine.set_program_counter(U256::from(next_pc));
+                frame.return_region = (0, 0);
+                frame.pending_create = true;
+                frame.pending_ext_call = false;
+
+                return Ok(Some(Action::Message(Box::new(Message {
+                    kind: CallKind::Create2,
+                    caller: *env.account(),
+                    target: H160::zero(),
+                    code_address: H160::zero(),
+                    value,
+                    apparent_value: value,
+                    input,
+                    gas,
+                    depth: env.depth().low_u64() as usize + 1,
+                    is_static: false,
+                    salt: Some(H256::from_uint(&salt)),
+                    code: Some(init),
+                }))));
+            }
+            eof::RETURNCONTRACT => {
+                let (offset, size) = (*peek(machine, 0), *peek(machine, 1));
+                expand_memory(machine, offset, size)?;
+                let sections = eof_frame.container.container_sections();
+                let mut container =
+                    EofContainer::parse_undeployed(&sections[immediate[0] as usize])
+                        .map_err(|_| InvalidContainer)?;
+                container
+                    .append_data(memory_slice(machine, offset, size))
+                    .map_err(|_| InvalidContainer)?;
+                return Ok(Some(Action::Exit(Status::Success, container.encode())));
+            }
+            eof::EXTCALL | eof::EXTDELEGATECALL | eof::EXTSTATICCALL => {
+                let has_value = opcode == eof::EXTCALL;
+                let target = *peek(machine, 0);
+                let (in_offset, in_size) = (*peek(machine, 1), *peek(machine, 2));
+                let value = if has_value {
+                    *peek(machine, 3)
+                } else {
+                    U256::zero()
+                };
+                if target.bits() > 160 {
+                    return Err(AddressOutOfRange);
+                }
+                let address = word_to_address(&target);
+                if !value.is_zero() && !env.write_access() {
+                    return Err(StaticStateChange);
+                }
+
+                let mut cost = self.account_access_cost(&address, self.schedule.call);
+                if !value.is_zero() {
+                    cost += gas::CALL_VALUE;
+                    if self.state.is_dead(&address) {
+                        cost += gas::NEW_ACCOUNT;
+                    }
+                }
+                charge(machine, cost)?;
+                expand_memory(machine, in_offset, in_size)?;
+                for _ in 0..(if has_value { 4 } else { 3 }) {
+                    pop(machine);
+                }
+                machine.set_return_data(Vec::new());
+
+                let available = *machine.gas_available();
+                let retained = (available / 64).max(U256::from(gas::MIN_RETAINED_GAS));
+                let gas = available.saturating_sub(retained);
+                // A call that cannot start fails without consuming the gas it would be given.
+                if gas < U256::from(gas::MIN_CALLEE_GAS)
+                    || env.depth().low_u64() as usize >= MAX_CALL_DEPTH
+                    || self.state.balance(env.account()) < value
+                    || (opcode == eof::EXTDELEGATECALL && !eof::is_eof(self.state.code(&address)))
+                {
+                    push(machine, U256::one());
+                } else {
+                    charge(machine, gas.low_u64())?;
+                    let input = memory_slice(machine, in_offset, in_size).to_vec();
+                    machine.set_program_counter(U256::from(next_pc));
+                    frame.return_region = (0, 0);
+                    frame.pending_create = false;
+                    frame.pending_ext_call = true;
+
+                    let (kind, caller, target, apparent_value) = match opcode {
+                        eof::EXTCALL => (CallKind::Call, *env.account(), address, value),
+                        eof::EXTDELEGATECALL => (
+                            CallKind::DelegateCall,
+                            *env.sender(),
+                            *env.account(),
+                            *env.value(),
+                        ),
+                        _ => (CallKind::StaticCall, *env.account(), address, U256::zero()),
+                    };
+                    return Ok(Some(Action::Message(Box::new(Message {
+                        kind,
+                        caller,
+                        target,
+                        code_address: address,
+                        value,
+                        apparent_value,
+                        input,
+                        gas,
+                        depth: env.depth().low_u64() as usize + 1,
+                        is_static: !env.write_access() || kind == CallKind::StaticCall,
+                        salt: None,
+                        code: None,
+                    }))));
+                }
+            }
+            _ => return Ok(None),
+        }
+
+        if let Some(section) = section {
+            eof_frame.section = section;
+            let code = eof_frame.container.code_sections()[section].clone();
+            frame.env.set_machine_code(code);
+        }
+        frame.machine.set_program_counter(U256::from(next_pc));
+        Ok(Some(Action::Continue))
+    }
+
+    /// Returns the cost of accessing `address`: the warm or cold access cost since Berlin
+    /// (EIP-2929), and `cost` before.
+    fn account_access_cost(&mut self, address: &Address, cost: u64) -> u64 {
+        if !self.schedule.warm_cold_access {
+            cost
+        } else if self.substate.access_account(*address) {
+            gas::WARM_ACCESS
+        } else {
+            gas::COLD_ACCOUNT_ACCESS
+        }
+    }
+
+    /// Charges the gas for storing `value` at `key` of `address`, and adjusts the refund
+    /// balance accordingly.
+    fn sstore(
+        &mut self,
+        machine: &mut MachineState,
+        address: Address,
+        key: U256,
+        value: U256,
+    ) -> Result<(), ExceptionalHalt> {
+        let schedule = &self.schedule;
+        if schedule.sstore_metering == SStoreMetering::NetWithStipend
+            && *machine.gas_available() <= U256::from(gas::CALL_STIPEND)
+        {
+            return Err(ExceptionalHalt::OutOfGas);
+        }
+
+        let mut cost = 0;
+        if schedule.warm_cold_access && !self.substate.access_storage(address, key) {
+            cost += gas::COLD_SLOAD;
+        }
+
+        let current = self.state.storage(&address, &key);
+        let set = gas::SSTORE_SET;
+        let reset = schedule.sstore_reset;
+        let clears = U256::from(schedule.sstore_clears_refund);
+
+        if schedule.sstore_metering == SStoreMetering::Legacy {
+            if current.is_zero() && !value.is_zero() {
+                cost += set;
+            } else {
+                cost += reset;
+            }
+            charge(machine, cost)?;
+            if !current.is_zero() && value.is_zero() {
+                self.substate.add_refund(clears);
+            }
+            return Ok(());
+        }
+
+        let original = self.state.original_storage(&address, &key);
+        let sload = schedule.sload;
+        if value == current {
+            cost += sload;
+        } else if original == current {
+            if original.is_zero() {
+                cost += set;
+            } else {
+                cost += reset;
+                if value.is_zero() {
+                    self.substate.add_refund(clears);
+                }
+            }
+        } else {
+            cost += sload;
+            if !original.is_zero() {
+                if current.is_zero() {
+                    self.substate.sub_refund(clears);
+                } else if value.is_zero() {
+                    self.substate.add_refund(clears);
+                }
+            }
+            if original == value {
+                let restored = if original.is_zero() { set } else { reset };
+                self.substate.add_refund(U256::from(restored - sload));
+            }
+        }
+
+        charge(machine, cost)
+    }
+}
+
+/// Charges `cost` gas.
+fn charge(machine: &mut MachineState, cost: u64) -> Result<(), ExceptionalHalt> {
+    let gas = *machine.gas_available();
+    if gas < U256::from(cost) {
+        return Err(ExceptionalHalt::OutOfGas);
+    }
+    machine.set_gas_available(gas - cost);
+    Ok(())
+}
+
+/// Charges `cost` gas plus `word_cost` gas per word of `size` bytes.
+fn charge_words(
+    machine: &mut MachineState,
+    cost: u64,
+    word_cost: u64,
+    size: U256,
+) -> Result<(), ExceptionalHalt> {
+    if size > U256::from(u32::MAX) {
+        return Err(ExceptionalHalt::OutOfGas);
+    }
+    charge(machine, cost + word_cost * gas::words(size.low_u64()))
+}
+
+/// Expands memory to cover `size` bytes from `offset`, charging for the expansion.
+fn expand_memory(
+    machine: &mut MachineState,
+    offset: U256,
+    size: U256,
+) -> Result<(), ExceptionalHalt> {
+    if size.is_zero() {
+        return Ok(());
+    }
+    if offset > U256::from(u32::MAX) || size > U256::from(u32::MAX) {
+        return Err(ExceptionalHalt::OutOfGas);
+    }
+
+    let words = gas::words(offset.low_u64() + size.low_u64());
+    let current = machine.words_in_memory().low_u64();
+    if words > current {
+        charge(machine, gas::memory_cost(words) - gas::memory_cost(current))?;
+        machine.memory_mut().resize(words as usize * 32, 0);
+        machine.set_words_in_memory(U256::from(words));
+    }
+    Ok(())
+}
+
+/// Returns `size` bytes of memory from `offset`, which must already be allocated.
+fn memory_slice(machine: &MachineState, offset: U256, size: U256) -> &[u8] {
+    if size.is_zero() {
+        return &[];
+    }
+    let offset = offset.low_u64() as usize;
+    &machine.memory()[offset..offset + size.low_u64() as usize]
+}
+
+/// Copies `data` into memory from `offset`, which must already be allocated.
+fn copy_to_memory(machine: &mut MachineState, offset: U256, data: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+    let offset = offset.low_u64() as usize;
+    machine.memory_mut()[offset..offset + data.len()].copy_from_slice(data);
+}
+
+/// Returns `size` bytes of `source` from `offset`, padded with zeros.
+fn padded_slice(source: &[u8], offset: U256, size: U256) -> Vec<u8> {
+    let size = size.low_u64() as usize;
+    let mut out = vec![0u8; size];
+    if offset < U256::from(source.len()) {
+        let offset = offset.low_u64() as usize;
+        let end = source.len().min(offset + size);
+        out[..end - offset].copy_from_slice(&source[offset..end]);
+    }
+    out
+}
+
+/// Returns the destination of a jump, which must be a `JUMPDEST` instruction.
+fn jump_target(jump_dests: &JumpDests, dest: U256) -> Result<usize, ExceptionalHalt> {
+    if dest < U256::from(jump_dests.len()) && jump_dests.is_valid(dest.low_u64() as usize) {
+        Ok(dest.low_u64() as usize)
+    } else {
+        Err(ExceptionalHalt::BadJumpDestination)
+    }
+}
+
+/// Pops the top item of the stack, which must have been checked to exist.
+fn pop(machine: &mut MachineState) -> U256 {
+    machine.stack_mut().pop().unwrap_or_default()
+}
+
+/// Pushes an item onto the stack, which must have been checked to have room.
+fn push(machine: &mut MachineState, item: U256) {
+    let _ = machine.stack_mut().push(item);
+}
+
+/// Returns the item at position `pos` of the stack, which must have been checked to exist.
+fn peek(machine: &MachineState, pos: usize) -> &U256 {
+    machine
+        .stack()
+        .get(pos)
+        .expect("stack height is checked before execution")
+}
+
+fn address_to_word(address: &Address) -> U256 {
+    H256::from(*address).into_uint()
+}
+
+fn word_to_address(word: &U256) -> Address {
+    Address::from(H256::from_uint(word))
+}
+
+fn narrow(value: U512) -> U256 {
+    U256::try_from(value).expect("value is reduced modulo a 256-bit number")
+}
+
+/// Returns the two's complement negation of `value`.
+fn negate(value: U256) -> U256 {
+    (!value).overflowing_add(U256::one()).0
+}
+
+/// Returns the absolute value of `value` interpreted as a two's complement integer.
+fn abs(value: U256) -> U256 {
+    if value.bit(255) {
+        negate(value)
+    } else {
+        value
+    }
+}
+
+fn signed_div(a: U256, b: U256) -> U256 {
+    if b.is_zero() {
+        return b;
+    }
+    let quotient = abs(a) / abs(b);
+    if a.bit(255) != b.bit(255) {
+        negate(quotient)
+    } else {
+        quotient
+    }
+}
+
+fn signed_mod(a: U256, b: U256) -> U256 {
+    if b.is_zero() {
+        return b;
+    }
+    let remainder = abs(a) % abs(b);
+    if a.bit(255) {
+        negate(remainder)
+    } else {
+        remainder
+    }
+}
+
+fn signed_cmp(a: U256, b: U256) -> Ordering {
+    match (a.bit(255), b.bit(255)) {
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        _ => a.cmp(&b),
+    }
+}
+
+fn sign_extend(b: U256, x: U256) -> U256 {
+    if b >= U256::from(31) {
+        return x;
+    }
+    let bit = b.low_u64() as usize * 8 + 7;
+    let mask = (U256::one() << (bit + 1)) - 1;
+    if x.bit(bit) {
+        x | !mask
+    } else {
+        x & mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vm::env::ExecutionEnv;
+    use crate::core::vm::testing::{self, TestBlock};
+    use std::str::FromStr;
+
+    fn execute(state: &mut WorldState, fork: Fork, message: Message) -> Outcome {
+        testing::execute(state, fork, message).0
+    }
+
+    #[test]
+    fn test_create_address() {
+        let sender = Address::from_str("6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0").unwrap();
+        assert_eq!(
+            create_address(&sender, U256::zero()),
+            Address::from_str("cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_create2_address() {
+        // Example 5 from EIP-1014.
+        let sender = Address::from_str("00000000000000000000000000000000deadbeef").unwrap();
+        let salt = H256::from_low_u64_be(0xcafebabe);
+        let init = hex::decode("deadbeef").unwrap();
+        assert_eq!(
+            create2_address(&sender, &salt, &keccak(init)),
+            Address::from_str("60f3f640a8508fC6a86d45DF051962668E1e8AC7").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_sstore() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        // PUSH1 0x2a PUSH1 0x00 SSTORE STOP
+        let code = hex::decode("602a60005500").unwrap();
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+
+        let message = testing::call(contract, Vec::new(), 100000);
+        let outcome = execute(&mut state, Fork::Cancun, message);
+
+        assert_eq!(outcome.status(), Status::Success);
+        assert_eq!(
+            outcome.gas_left(),
+            U256::from(100000 - 3 - 3 - gas::COLD_SLOAD - gas::SSTORE_SET)
+        );
+        assert_eq!(state.storage(&contract, &U256::zero()), U256::from(0x2a));
+    }
+
+    #[test]
+    fn test_revert() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        // PUSH1 0x2a PUSH1 0x00 SSTORE PUSH1 0x2a PUSH1 0x00 MSTORE PUSH1 0x20 PUSH1 0x00 REVERT
+        let code = hex::decode("602a600055602a60005260206000fd").unwrap();
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+
+        let message = testing::call(contract, Vec::new(), 100000);
+        let outcome = execute(&mut state, Fork::Cancun, message);
+
+        assert_eq!(outcome.status(), Status::Revert);
+        assert!(outcome.gas_left() > U256::zero());
+        assert_eq!(U256::from_big_endian(outcome.output()), U256::from(0x2a));
+        assert_eq!(state.storage(&contract, &U256::zero()), U256::zero());
+    }
+
+    #[test]
+    fn test_invalid_instruction_before_fork() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        // PUSH0 STOP
+        state.insert_account(contract, U256::zero(), U256::zero(), vec![0x5f, 0x00], None);
+
+        let message = |gas| testing::call(contract, Vec::new(), gas);
+
+        let outcome = execute(&mut state, Fork::London, message(100));
+        assert_eq!(
+            outcome.status(),
+            Status::Halt(ExceptionalHalt::InvalidInstruction)
+        );
+        assert_eq!(outcome.gas_left(), U256::zero());
+
+        let outcome = execute(&mut state, Fork::Shanghai, message(100));
+        assert_eq!(outcome.status(), Status::Success);
+        assert_eq!(outcome.gas_left(), U256::from(98));
+    }
+
+    #[derive(Default)]
+    struct CountingInspector {
+        steps: usize,
+        step_ends: usize,
+        calls: Vec<Address>,
+        /// The account of the frame starting each message, and ending it.
+        callers: Vec<Option<Address>>,
+        call_end_callers: Vec<Option<Address>>,
+        call_ends: usize,
+        logs: usize,
+        /// Target whose message calls are skipped with a revert.
+        blocked: Option<Address>,
+    }
+
+    impl Inspector for CountingInspector {
+        fn step(&mut self, _: &mut MachineState, _: &ExecutionEnv, _: &AccruedSubstate) {
+            self.steps += 1;
+        }
+
+        fn step_end(
+            &mut self,
+            _: &mut MachineState,
+            _: &ExecutionEnv,
+            _: &AccruedSubstate,
+            _: Option<ExceptionalHalt>,
+        ) {
+            self.step_ends += 1;
+        }
+
+        fn call(
+            &mut self,
+            _: Option<&mut MachineState>,
+            env: Option<&ExecutionEnv>,
+            _: &AccruedSubstate,
+            message: &Message,
+        ) -> Option<Outcome> {
+            self.calls.push(*message.target());
+            self.callers.push(env.map(|env| *env.account()));
+            if Some(*message.target()) == self.blocked {
+                return Some(Outcome::new(Status::Revert, message.gas(), Vec::new()));
+            }
+            None
+        }
+
+        fn call_end(
+            &mut self,
+            _: Option<&mut MachineState>,
+            env: Option<&ExecutionEnv>,
+            _: &AccruedSubstate,
+            _: &mut Outcome,
+        ) {
+            self.call_ends += 1;
+            self.call_end_callers.push(env.map(|env| *env.account()));
+        }
+
+        fn log(&mut self, _: &ExecutionEnv, _: &Log) {
+            self.logs += 1;
+        }
+    }
+
+    #[test]
+    fn test_inspector() {
+        let contract = Address::repeat_byte(0xcc);
+        let callee = Address::repeat_byte(0xbb);
+        // Emits a log, calls the callee and stores the result of the call at slot 0.
+        //
+        // PUSH1 0 PUSH1 0 LOG0 PUSH1 0 DUP1 DUP1 DUP1 DUP1 PUSH20 callee GAS CALL PUSH1 0 SSTORE
+        // STOP
+        let code = hex::decode(format!(
+            "60006000a060008080808073{}5af160005500",
+            hex::encode(callee)
+        ))
+        .unwrap();
+
+        let run = |inspector: &mut CountingInspector| {
+            let mut state = WorldState::new();
+            state.insert_account(contract, U256::zero(), U256::zero(), code.clone(), None);
+            let block = TestBlock::default();
+            let mut interpreter = block.interpreter(&mut state, Fork::Cancun);
+            interpreter.set_inspector(inspector);
+            let message = testing::call(contract, Vec::new(), 100000);
+            assert!(interpreter.execute(message).status().is_success());
+            state.storage(&contract, &U256::zero())
+        };
+
+        let mut inspector = CountingInspector::default();
+        assert_eq!(run(&mut inspector), U256::one());
+        assert_eq!(inspector.steps, 14);
+        assert_eq!(inspector.step_ends, 14);
+        assert_eq!(inspector.calls, vec![contract, callee]);
+        assert_eq!(inspector.callers, vec![None, Some(contract)]);
+        assert_eq!(inspector.call_ends, 2);
+        assert_eq!(inspector.call_end_callers, vec![Some(contract), None]);
+        assert_eq!(inspector.logs, 1);
+
+        let mut inspector = CountingInspector {
+            blocked: Some(callee),
+            ..Default::default()
+        };
+        assert_eq!(run(&mut inspector), U256::zero());
+        assert_eq!(inspector.call_ends, 2);
+    }
+
+    #[test]
+    fn test_call_depth_limit() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        // Calls itself with all but 100 gas, then stores 1 at a slot keyed by the remaining
+        // gas, so every level writes a distinct slot.
+        //
+        // PUSH1 0 DUP1 DUP1 DUP1 DUP1 ADDRESS PUSH1 100 GAS SUB CALL POP PUSH1 1 GAS SSTORE STOP
+        let code = hex::decode("6000808080803060645a03f15060015a5500").unwrap();
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+
+        let message = testing::call(contract, Vec::new(), 100_000_000);
+        let outcome = execute(&mut state, Fork::Frontier, message);
+
+        assert_eq!(outcome.status(), Status::Success);
+        assert_eq!(state.storage_entries(&contract).count(), MAX_CALL_DEPTH + 1);
+    }
+
+    #[test]
+    fn test_code_analysis_cache() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        let callee = Address::repeat_byte(0xbb);
+        // Calls the callee twice.
+        //
+        // PUSH1 0 DUP1 DUP1 DUP1 DUP1 PUSH20 callee GAS CALL POP, twice, then STOP
+        let call = format!("60008080808073{}5af150", hex::encode(callee));
+        let code = hex::decode(format!("{}{}00", call, call)).unwrap();
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+        // PUSH1 4 JUMP INVALID JUMPDEST PUSH1 1 PUSH1 0 SSTORE STOP
+        let code = hex::decode("600456fe5b600160005500").unwrap();
+        state.insert_account(callee, U256::zero(), U256::zero(), code, None);
+
+        let mut cache = CodeAnalysisCache::new(16);
+        let block = TestBlock::default();
+        let mut interpreter = block.interpreter(&mut state, Fork::Cancun);
+        interpreter.set_code_analysis_cache(&mut cache);
+        let outcome = interpreter.execute(testing::call(contract, Vec::new(), 1_000_000));
+
+        assert_eq!(outcome.status(), Status::Success);
+        assert_eq!(state.storage(&callee, &U256::zero()), U256::one());
+        // The callee is analyzed on its first call only.
+        assert_eq!((cache.hits(), cache.misses()), (1, 2));
+        assert!(cache.contains(&state.code_hash(&callee)));
+    }
+
+    fn execute_eof(state: &mut WorldState, message: Message) -> Outcome {
+        let block = TestBlock::default();
+        let mut interpreter = block.interpreter(state, Fork::Cancun);
+        interpreter.set_eof(true);
+        interpreter.execute(message)
+    }
+
+    /// Returns an EOF container with one code section of each type.
+    fn eof_container(
+        types: &[(u8, u8, u16)],
+        code: &[&str],
+        containers: Vec<Vec<u8>>,
+        data: Vec<u8>,
+    ) -> Vec<u8> {
+        let types = types
+            .iter()
+            .map(|&(inputs, outputs, max)| eof::CodeType::new(inputs, outputs, max))
+            .collect();
+        let code = code.iter().map(|code| hex::decode(code).unwrap()).collect();
+        EofContainer::new(types, code, containers, data)
+            .unwrap()
+            .encode()
+    }
+
+    #[test]
+    fn test_eof_functions() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        // PUSH1 2 PUSH1 3 CALLF 1 DATALOADN 0 ADD PUSH0 SSTORE STOP, and ADD RETF.
+        let mut data = vec![0; 32];
+        data[31] = 0x10;
+        let code = eof_container(
+            &[(0, 0x80, 2), (2, 1, 0)],
+            &["60026003e30001d10000015f5500", "01e4"],
+            Vec::new(),
+            data,
+        );
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+        let message = testing::call(contract, Vec::new(), 100000);
+
+        // Without EOF, the magic is an invalid instruction.
+        let outcome = execute(&mut state, Fork::Cancun, message.clone());
+        assert_eq!(
+            outcome.status(),
+            Status::Halt(ExceptionalHalt::InvalidInstruction)
+        );
+
+        let outcome = execute_eof(&mut state, message);
+        assert_eq!(outcome.status(), Status::Success);
+        assert_eq!(state.storage(&contract, &U256::zero()), U256::from(0x15));
+    }
+
+    #[test]
+    fn test_eof_create() {
+        let mut state = WorldState::new();
+        let factory = Address::repeat_byte(0xcc);
+        // DATASIZE PUSH0 SSTORE STOP, with one byte of data to be appended on deployment.
+        let mut runtime = eof_container(&[(0, 0x80, 2)], &["d25f5500"], Vec::new(), vec![0]);
+        runtime.pop();
+        // PUSH1 0xaa PUSH0 MSTORE8 PUSH1 1 PUSH0 RETURNCONTRACT 0
+        let initcode = eof_container(
+            &[(0, 0x80, 2)],
+            &["60aa5f5360015fee00"],
+            vec![runtime],
+            Vec::new(),
+        );
+        // PUSH0 PUSH0 PUSH0 PUSH0 EOFCREATE 0 DUP1 PUSH0 SSTORE, then EXTCALL to the created
+        // contract: PUSH0 PUSH0 PUSH0 DUP4 EXTCALL STOP
+        let code = eof_container(
+            &[(0, 0x80, 5)],
+            &["5f5f5f5fec00805f555f5f5f83f800"],
+            vec![initcode.clone()],
+            Vec::new(),
+        );
+        state.insert_account(factory, U256::zero(), U256::zero(), code, None);
+
+        let outcome = execute_eof(&mut state, testing::call(factory, Vec::new(), 1_000_000));
+        assert_eq!(outcome.status(), Status::Success);
+
+        let created = create2_address(&factory, &H256::zero(), &keccak(&initcode));
+        assert_eq!(
+            state.storage(&factory, &U256::zero()),
+            address_to_word(&created)
+        );
+        let deployed = EofContainer::parse(state.code(&created)).unwrap();
+        assert_eq!(deployed.data(), &vec![0xaa]);
+        assert_eq!(state.storage(&created, &U256::zero()), U256::one());
+    }
+
+    /// Returns the word `operation` leaves on the stack, with `operands[0]` on top.
+    fn evaluate(operation: u8, operands: &[U256]) -> U256 {
+        let mut code = Vec::new();
+        for operand in operands.iter().rev() {
+            let mut word = [0; 32];
+            operand.to_big_endian(&mut word);
+            code.push(0x7f);
+            code.extend_from_slice(&word);
+        }
+        // PUSH0 MSTORE PUSH1 0x20 PUSH0 RETURN
+        code.push(operation);
+        code.extend(hex::decode("5f5260205ff3").unwrap());
+
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+        let message = testing::call(contract, Vec::new(), 100000);
+        let outcome = execute(&mut state, Fork::Cancun, message);
+        assert_eq!(outcome.status(), Status::Success);
+        U256::from_big_endian(outcome.output())
+    }
+
+    #[test]
+    fn test_signed_arithmetic() {
+        let negative = |value: u64| U256::zero().overflowing_sub(U256::from(value)).0;
+        let min = U256::one() << 255;
+        let (sdiv, smod, signextend, sar) = (0x05, 0x07, 0x0b, 0x1d);
+
+        assert_eq!(evaluate(sdiv, &[min, negative(1)]), min);
+        assert_eq!(evaluate(sdiv, &[negative(7), 2.into()]), negative(3));
+        assert_eq!(evaluate(sdiv, &[7.into(), negative(2)]), negative(3));
+        assert_eq!(evaluate(sdiv, &[negative(7), 0.into()]), U256::zero());
+
+        assert_eq!(evaluate(smod, &[negative(7), 2.into()]), negative(1));
+        assert_eq!(evaluate(smod, &[7.into(), negative(2)]), U256::one());
+        assert_eq!(evaluate(smod, &[min, negative(1)]), U256::zero());
+        assert_eq!(evaluate(smod, &[negative(7), 0.into()]), U256::zero());
+
+        assert_eq!(evaluate(signextend, &[0.into(), 0xff.into()]), U256::MAX);
+        assert_eq!(evaluate(signextend, &[0.into(), 0x7f.into()]), 0x7f.into());
+        assert_eq!(evaluate(signextend, &[1.into(), 0xff.into()]), 0xff.into());
+        assert_eq!(
+            evaluate(signextend, &[1.into(), 0x0180.into()]),
+            0x0180.into()
+        );
+        assert_eq!(evaluate(signextend, &[31.into(), min]), min);
+        assert_eq!(evaluate(signextend, &[U256::MAX, 0xff.into()]), 0xff.into());
+
+        assert_eq!(evaluate(sar, &[4.into(), negative(16)]), negative(1));
+        assert_eq!(evaluate(sar, &[1.into(), min]), U256::MAX << 254);
+        assert_eq!(evaluate(sar, &[255.into(), min]), U256::MAX);
+        assert_eq!(evaluate(sar, &[256.into(), min]), U256::MAX);
+        assert_eq!(evaluate(sar, &[U256::MAX, negative(1)]), U256::MAX);
+        assert_eq!(
+            evaluate(sar, &[256.into(), U256::one() << 254]),
+            U256::zero()
+        );
+        assert_eq!(evaluate(sar, &[4.into(), 0xff.into()]), 0x0f.into());
+    }
+
+    #[test]
+    fn test_sstore_gas_and_refunds() {
+        let contract = Address::repeat_byte(0xcc);
+        let forks = [
+            Fork::Petersburg,
+            Fork::Constantinople,
+            Fork::Istanbul,
+            Fork::Berlin,
+            Fork::London,
+        ];
+        // Original value, values stored in sequence, and the gas used and refund under each of
+        // the forks above.
+        #[allow(clippy::type_complexity)]
+        let cases: &[(u8, &[u8], [(u64, u64); 5])] = &[
+            (
+                0,
+                &[0, 0],
+                [(10012, 0), (412, 0), (1612, 0), (2312, 0), (2312, 0)],
+            ),
+            (
+                0,
+                &[1, 0],
+                [
+                    (25012, 15000),
+                    (20212, 19800),
+                    (20812, 19200),
+                    (22212, 19900),
+                    (22212, 19900),
+                ],
+            ),
+            (
+                1,
+                &[0, 0],
+                [
+                    (10012, 15000),
+                    (5212, 15000),
+                    (5812, 15000),
+                    (5112, 15000),
+                    (5112, 4800),
+                ],
+            ),
+            (
+                1,
+                &[0, 1],
+                [
+                    (25012, 15000),
+                    (5212, 4800),
+                    (5812, 4200),
+                    (5112, 2800),
+                    (5112, 2800),
+                ],
+            ),
+            (
+                1,
+                &[2, 0],
+                [
+                    (10012, 15000),
+                    (5212, 15000),
+                    (5812, 15000),
+                    (5112, 15000),
+                    (5112, 4800),
+                ],
+            ),
+            (
+                0,
+                &[1, 0, 1],
+                [
+                    (45018, 15000),
+                    (40218, 19800),
+                    (40818, 19200),
+                    (42218, 19900),
+                    (42218, 19900),
+                ],
+            ),
+            (
+                1,
+                &[1, 1],
+                [(10012, 0), (412, 0), (1612, 0), (2312, 0), (2312, 0)],
+            ),
+        ];
+
+        for (original, values, expected) in cases {
+            // PUSH1 value PUSH1 0x00 SSTORE for each value, then STOP.
+            let mut code = Vec::new();
+            for value in values.iter() {
+                code.extend_from_slice(&[0x60, *value, 0x60, 0x00, 0x55]);
+            }
+            code.push(0x00);
+
+            for (fork, (gas_used, refund)) in forks.iter().zip(expected.iter()) {
+                let mut state = WorldState::new();
+                let storage = vec![(U256::zero(), U256::from(*original))];
+                state.insert_account(contract, U256::zero(), U256::zero(), code.clone(), storage);
+                let message = testing::call(contract, Vec::new(), 100000);
+                let (outcome, substate) = testing::execute(&mut state, *fork, message);
+
+                let case = (original, values, fork);
+                assert_eq!(outcome.status(), Status::Success, "{:?}", case);
+                assert_eq!(
+                    U256::from(100000) - outcome.gas_left(),
+                    U256::from(*gas_used),
+                    "{:?}",
+                    case
+                );
+                assert_eq!(substate.refund_balance(), U256::from(*refund), "{:?}", case);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sstore_stipend() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        // PUSH1 0x00 PUSH1 0x00 SSTORE STOP
+        let code = hex::decode("600060005500").unwrap();
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+        let message = |gas| testing::call(contract, Vec::new(), gas);
+
+        // EIP-2200 fails an SSTORE left with no more than the call stipend, even if it could pay.
+        let outcome = execute(
+            &mut state,
+            Fork::Constantinople,
+            message(6 + gas::CALL_STIPEND),
+        );
+        assert_eq!(outcome.status(), Status::Success);
+        let outcome = execute(&mut state, Fork::Istanbul, message(6 + gas::CALL_STIPEND));
+        assert_eq!(outcome.status(), Status::Halt(ExceptionalHalt::OutOfGas));
+        let outcome = execute(&mut state, Fork::Istanbul, message(7 + gas::CALL_STIPEND));
+        assert_eq!(outcome.status(), Status::Success);
+    }
+
+    #[test]
+    fn test_call_gas() {
+        let mut state = WorldState::new();
+        let caller = Address::repeat_byte(0xcc);
+        let callee = Address::repeat_byte(0xdd);
+        let message = || testing::call(caller, Vec::new(), 100000);
+
+        // PUSH0 PUSH0 PUSH0 PUSH0 PUSH0 PUSH20 callee GAS CALL STOP
+        let code = hex::decode(format!("5f5f5f5f5f73{}5af100", hex::encode(callee))).unwrap();
+        state.insert_account(caller, U256::zero(), U256::zero(), code, None);
+        // GAS PUSH0 SSTORE STOP
+        let code = hex::decode("5a5f5500").unwrap();
+        state.insert_account(callee, U256::zero(), U256::zero(), code, None);
+
+        // All but one 64th of the gas left after the cold access is passed on (EIP-150).
+        let outcome = execute(&mut state, Fork::Cancun, message());
+        assert_eq!(outcome.status(), Status::Success);
+        let available = 100000 - 15 - gas::COLD_ACCOUNT_ACCESS;
+        assert_eq!(
+            state.storage(&callee, &U256::zero()),
+            U256::from(available - available / 64 - gas::BASE)
+        );
+
+        // PUSH1 0x20 PUSH0 PUSH0 PUSH0 PUSH1 0x01 PUSH20 callee PUSH0 CALL PUSH0 MLOAD PUSH0
+        // SSTORE STOP
+        let code = hex::decode(format!(
+            "60205f5f5f600173{}5ff15f515f5500",
+            hex::encode(callee)
+        ))
+        .unwrap();
+        state.insert_account(caller, U256::zero(), U256::one(), code, None);
+        // GAS PUSH0 MSTORE PUSH1 0x20 PUSH0 RETURN
+        let code = hex::decode("5a5f5260205ff3").unwrap();
+        state.insert_account(callee, U256::zero(), U256::zero(), code, None);
+
+        // A call transferring value gets the stipend on top of the zero gas requested.
+        let outcome = execute(&mut state, Fork::Cancun, message());
+        assert_eq!(outcome.status(), Status::Success);
+        assert_eq!(
+            state.storage(&caller, &U256::zero()),
+            U256::from(gas::CALL_STIPEND - gas::BASE)
+        );
+        assert_eq!(state.balance(&callee), U256::one());
+    }
+
+    #[test]
+    fn test_memory_expansion() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        // PUSH1 0x01 PUSH2 0x03ff MSTORE8 PUSH1 0x01 PUSH2 0x03ff MSTORE8 STOP
+        let code = hex::decode("60016103ff5360016103ff5300").unwrap();
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+        let message = testing::call(contract, Vec::new(), 100000);
+
+        // Only the first store expands the memory, to 32 words.
+        let outcome = execute(&mut state, Fork::Cancun, message);
+        assert_eq!(outcome.status(), Status::Success);
+        assert_eq!(
+            outcome.gas_left(),
+            U256::from(100000 - 6 * gas::VERY_LOW - gas::memory_cost(32))
+        );
+    }
+
+    #[test]
+    fn test_self_destruct() {
+        let contract = Address::repeat_byte(0xcc);
+        let beneficiary = Address::repeat_byte(0xbb);
+        // PUSH20 beneficiary SELFDESTRUCT
+        let code = hex::decode(format!("73{}ff", hex::encode(beneficiary))).unwrap();
+        let message = testing::call(contract, Vec::new(), 100000);
+
+        // Since Cancun, an existing contract only sends its balance (EIP-6780).
+        for (fork, destroyed) in [(Fork::Shanghai, true), (Fork::Cancun, false)].iter() {
+            let mut state = WorldState::new();
+            state.insert_account(contract, U256::zero(), 10.into(), code.clone(), None);
+            let (outcome, substate) = testing::execute(&mut state, *fork, message.clone());
+            assert_eq!(outcome.status(), Status::Success);
+            assert_eq!(
+                substate.self_destruct_accounts().contains(&contract),
+                *destroyed
+            );
+            assert_eq!(state.balance(&contract), U256::zero());
+            assert_eq!(state.balance(&beneficiary), U256::from(10));
+        }
+
+        // A contract created in the same transaction is still destroyed.
+        let mut state = WorldState::new();
+        let message = Message::create(testing::CALLER, U256::zero(), code, U256::from(100000));
+        let (outcome, substate) = testing::execute(&mut state, Fork::Cancun, message);
+        assert_eq!(outcome.status(), Status::Success);
+        let created = create_address(&testing::CALLER, U256::zero());
+        assert!(substate.self_destruct_accounts().contains(&created));
+    }
+
+    /// Returns an EOF container with one non-returning code section.
+    fn eof_code(code: &str, max_stack_increase: u16, data: Vec<u8>) -> Vec<u8> {
+        eof_container(&[(0, 0x80, max_stack_increase)], &[code], Vec::new(), data)
+    }
+
+    #[test]
+    fn test_eof_control_flow() {
+        let contract = Address::repeat_byte(0xcc);
+        // PUSH1 case RJUMPV [5, 10], then PUSH1 value PUSH0 SSTORE STOP for the fallthrough
+        // and each case, storing 10, 20 and 30.
+        for (case, value) in [(0, 20), (1, 30), (2, 10), (0xff, 10)].iter() {
+            let mut state = WorldState::new();
+            let code = format!("60{:02x}e2010005000a600a5f550060145f5500601e5f5500", case);
+            state.insert_account(
+                contract,
+                U256::zero(),
+                U256::zero(),
+                eof_code(&code, 2, Vec::new()),
+                None,
+            );
+            let outcome = execute_eof(&mut state, testing::call(contract, Vec::new(), 100000));
+            assert_eq!(outcome.status(), Status::Success);
+            assert_eq!(state.storage(&contract, &U256::zero()), U256::from(*value));
+        }
+
+        // PUSH1 7 JUMPF 1, and PUSH0 SSTORE STOP taking one input.
+        let mut state = WorldState::new();
+        let code = eof_container(
+            &[(0, 0x80, 1), (1, 0x80, 1)],
+            &["6007e50001", "5f5500"],
+            Vec::new(),
+            Vec::new(),
+        );
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+        let outcome = execute_eof(&mut state, testing::call(contract, Vec::new(), 100000));
+        assert_eq!(outcome.status(), Status::Success);
+        assert_eq!(state.storage(&contract, &U256::zero()), U256::from(7));
+    }
+
+    #[test]
+    fn test_eof_stack_instructions() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        // PUSH1 1 PUSH1 2 PUSH1 3 PUSH1 4 DUPN 3 SWAPN 1 EXCHANGE 0x01, leaving 3 2 1 4 1 from
+        // the top, then stores each in slots 0 to 4.
+        let code = eof_code(
+            "6001600260036004e603e701e8015f5560015560025560035560045500",
+            6,
+            Vec::new(),
+        );
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+        let outcome = execute_eof(&mut state, testing::call(contract, Vec::new(), 1_000_000));
+
+        assert_eq!(outcome.status(), Status::Success);
+        for (slot, value) in [3, 2, 1, 4, 1].iter().enumerate() {
+            assert_eq!(
+                state.storage(&contract, &U256::from(slot)),
+                U256::from(*value)
+            );
+        }
+    }
+
+    #[test]
+    fn test_eof_data_and_return_data() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        let callee = Address::repeat_byte(0xbb);
+        // PUSH1 0x2a PUSH0 MSTORE PUSH1 0x20 PUSH0 RETURN
+        let code = hex::decode("602a5f5260205ff3").unwrap();
+        state.insert_account(callee, U256::zero(), U256::zero(), code, None);
+        // PUSH1 4 PUSH1 2 PUSH0 DATACOPY PUSH0 MLOAD PUSH0 SSTORE, then PUSH0 PUSH0 PUSH20
+        // callee EXTSTATICCALL PUSH1 1 SSTORE PUSH1 1 RETURNDATALOAD PUSH1 2 SSTORE STOP
+        let code = format!(
+            "600460025fd35f515f555f5f73{}fb6001556001f760025500",
+            hex::encode(callee)
+        );
+        let data = hex::decode("aabbccdd").unwrap();
+        state.insert_account(
+            contract,
+            U256::zero(),
+            U256::zero(),
+            eof_code(&code, 3, data),
+            None,
+        );
+        let outcome = execute_eof(&mut state, testing::call(contract, Vec::new(), 1_000_000));
+
+        assert_eq!(outcome.status(), Status::Success);
+        // The copy past the end of the data is padded with zeros.
+        assert_eq!(
+            state.storage(&contract, &U256::zero()),
+            U256::from(0xccdd_0000u32) << 224
+        );
+        assert_eq!(state.storage(&contract, &U256::one()), U256::zero());
+        assert_eq!(state.storage(&contract, &U256::from(2)), U256::from(0x2a00));
+    }
+
+    #[test]
+    fn test_eof_static_and_delegate_calls() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        let (legacy, eof) = (Address::repeat_byte(0xbb), Address::repeat_byte(0xdd));
+        // PUSH1 1 PUSH0 SSTORE STOP
+        let code = hex::decode("60015f5500").unwrap();
+        state.insert_account(legacy, U256::zero(), U256::zero(), code, None);
+        // PUSH1 7 PUSH1 2 SSTORE STOP
+        let code = eof_code("600760025500", 2, Vec::new());
+        state.insert_account(eof, U256::zero(), U256::zero(), code, None);
+        // PUSH0 PUSH0 PUSH20 legacy EXTDELEGATECALL PUSH0 SSTORE, PUSH0 PUSH0 PUSH20 eof
+        // EXTDELEGATECALL PUSH1 1 SSTORE, PUSH0 PUSH0 PUSH20 legacy EXTSTATICCALL PUSH1 3
+        // SSTORE STOP
+        let code = format!(
+            "5f5f73{}f95f555f5f73{}f96001555f5f73{}fb60035500",
+            hex::encode(legacy),
+            hex::encode(eof),
+            hex::encode(legacy)
+        );
+        state.insert_account(
+            contract,
+            U256::zero(),
+            U256::zero(),
+            eof_code(&code, 3, Vec::new()),
+            None,
+        );
+        // The halting static call consumes all but one 64th of the gas.
+        let outcome = execute_eof(&mut state, testing::call(contract, Vec::new(), 10_000_000));
+
+        assert_eq!(outcome.status(), Status::Success);
+        // Delegating to legacy code fails lightly, while EOF code runs on the storage of the
+        // caller.
+        assert_eq!(state.storage(&contract, &U256::zero()), U256::one());
+        assert_eq!(state.storage(&contract, &U256::one()), U256::zero());
+        assert_eq!(state.storage(&contract, &U256::from(2)), U256::from(7));
+        assert_eq!(state.storage(&eof, &U256::from(2)), U256::zero());
+        // A static call that modifies state fails.
+        assert_eq!(state.storage(&contract, &U256::from(3)), U256::from(2));
+        assert_eq!(state.storage(&legacy, &U256::zero()), U256::zero());
+    }
+
+    #[test]
+    fn test_eof_light_failures() {
+        let contract = Address::repeat_byte(0xcc);
+        let callee = Address::repeat_byte(0xbb);
+        // PUSH1 value PUSH0 PUSH0 PUSH20 callee EXTCALL PUSH0 MSTORE PUSH1 0x20 PUSH0 RETURN
+        let code = |value: u8| {
+            let code = format!("60{:02x}5f5f73{}f85f5260205ff3", value, hex::encode(callee));
+            eof_code(&code, 4, Vec::new())
+        };
+        let status = |value, gas, depth| {
+            let mut state = WorldState::new();
+            state.insert_account(contract, U256::zero(), U256::zero(), code(value), None);
+            // PUSH1 1 PUSH0 SSTORE STOP
+            let callee_code = hex::decode("60015f5500").unwrap();
+            state.insert_account(callee, U256::zero(), U256::zero(), callee_code, None);
+            let mut message = testing::call(contract, Vec::new(), gas);
+            message.depth = depth;
+            let outcome = execute_eof(&mut state, message);
+            assert_eq!(outcome.status(), Status::Success);
+            let called = !state.storage(&callee, &U256::zero()).is_zero();
+            (
+                U256::from_big_endian(outcome.output()),
+                outcome.gas_left(),
+                called,
+            )
+        };
+
+        // Failing to start a call pushes 1 and consumes no more than the access cost.
+        let used = 10 + gas::COLD_ACCOUNT_ACCESS + 13;
+        assert_eq!(
+            status(0, 9000, 0),
+            (U256::one(), U256::from(9000 - used), false)
+        );
+        assert_eq!(
+            status(0, 100000, MAX_CALL_DEPTH),
+            (U256::one(), U256::from(100000 - used), false)
+        );
+        let used = used + gas::CALL_VALUE;
+        assert_eq!(
+            status(1, 100000, 0),
+            (U256::one(), U256::from(100000 - used), false)
+        );
+        assert_eq!(status(0, 100000, 0).0, U256::zero());
+        assert!(status(0, 100000, 0).2);
+    }
+
+    #[test]
+    fn test_eof_container_cache() {
+        let mut state = WorldState::new();
+        let contract = Address::repeat_byte(0xcc);
+        let callee = Address::repeat_byte(0xbb);
+        // PUSH0 PUSH0 PUSH20 callee EXTCALL POP, twice, then STOP
+        let call = format!("5f5f5f73{}f850", hex::encode(callee));
+        let code = eof_code(&format!("{}{}00", call, call), 4, Vec::new());
+        state.insert_account(contract, U256::zero(), U256::zero(), code, None);
+        // PUSH1 1 PUSH0 SSTORE STOP
+        let code = eof_code("60015f5500", 2, Vec::new());
+        state.insert_account(callee, U256::zero(), U256::zero(), code, None);
+
+        let mut cache = CodeAnalysisCache::new(16);
+        let block = TestBlock::default();
+        let mut interpreter = block.interpreter(&mut state, Fork::Cancun);
+        interpreter.set_eof(true);
+        interpreter.set_code_analysis_cache(&mut cache);
+        let outcome = interpreter.execute(testing::call(contract, Vec::new(), 1_000_000));
+
+        assert_eq!(outcome.status(), Status::Success);
+        assert_eq!(state.storage(&callee, &U256::zero()), U256::one());
+        // The callee is validated on its first call only.
+        assert_eq!((cache.hits(), cache.misses()), (1, 2));
+        assert!(cache.contains(&state.code_hash(&callee)));
+    }
+}
@@ -0,0 +1,244 @@
+use super::eof::{ContainerKind, EofContainer};
+use ethereum_types::H256;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// The valid jump destinations of code: its `JUMPDEST` instructions, excluding `0x5b` bytes in
+/// the immediate data of pushes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct JumpDests {
+    /// One bit per byte of code, set for a `JUMPDEST` instruction.
+    bitmap: Vec<u64>,
+    len: usize,
+}
+
+impl JumpDests {
+    /// Returns the jump destinations of `code`.
+    pub fn new(code: &[u8]) -> Self {
+        let mut bitmap = vec![0u64; code.len().div_ceil(64)];
+        let mut pc = 0;
+        while pc < code.len() {
+            let opcode = code[pc];
+            if opcode == 0x5b {
+                bitmap[pc / 64] |= 1 << (pc % 64);
+            } else if (0x60..=0x7f).contains(&opcode) {
+                pc += (opcode - 0x5f) as usize;
+            }
+            pc += 1;
+        }
+        Self {
+            bitmap,
+            len: code.len(),
+        }
+    }
+
+    /// Returns whether `pc` is a valid jump destination.
+    pub fn is_valid(&self, pc: usize) -> bool {
+        pc < self.len && self.bitmap[pc / 64] & (1 << (pc % 64)) != 0
+    }
+
+    /// Returns the length of the analyzed code.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the analyzed code is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// The cached analysis of code.
+#[derive(Debug)]
+enum Analysis {
+    JumpDests(Arc<JumpDests>),
+    /// The container of EOF code, or `None` if it failed validation (EIP-3540).
+    Container(Option<Arc<EofContainer>>),
+}
+
+/// A cache of the analysis of code, keyed by code hash, holding up to a fixed number of entries
+/// and evicting the least recently used. It holds the jump destinations of legacy code and the
+/// validated containers of EOF code.
+#[derive(Debug)]
+pub struct CodeAnalysisCache {
+    capacity: usize,
+    /// The analysis of each code hash, with the tick it was last used at.
+    entries: HashMap<H256, (Analysis, u64)>,
+    /// The code hash last used at each tick, ordered from the least recently used.
+    by_tick: BTreeMap<u64, H256>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl CodeAnalysisCache {
+    /// Returns an empty cache holding up to `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::with_capacity(capacity),
+            by_tick: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the jump destinations of `code` with `code_hash`, analyzing it if it is not
+    /// cached.
+    pub fn get(&mut self, code_hash: H256, code: &[u8]) -> Arc<JumpDests> {
+        if let Some(Analysis::JumpDests(dests)) = self.lookup(&code_hash) {
+            let dests = Arc::clone(dests);
+            self.hits += 1;
+            return dests;
+        }
+
+        let dests = Arc::new(JumpDests::new(code));
+        self.insert(code_hash, Analysis::JumpDests(Arc::clone(&dests)));
+        dests
+    }
+
+    /// Returns the container of the EOF runtime `code` with `code_hash`, or `None` if it is
+    /// invalid, validating it if it is not cached.
+    pub fn get_container(&mut self, code_hash: H256, code: &[u8]) -> Option<Arc<EofContainer>> {
+        if let Some(Analysis::Container(container)) = self.lookup(&code_hash) {
+            let container = container.clone();
+            self.hits += 1;
+            return container;
+        }
+
+        let container = EofContainer::validate(code, ContainerKind::Runtime)
+            .ok()
+            .map(Arc::new);
+        self.insert(code_hash, Analysis::Container(container.clone()));
+        container
+    }
+
+    /// Returns the cached analysis of the code with `code_hash`, marking it as used.
+    fn lookup(&mut self, code_hash: &H256) -> Option<&Analysis> {
+        self.tick += 1;
+        let (analysis, last_used) = self.entries.get_mut(code_hash)?;
+        self.by_tick.remove(last_used);
+        self.by_tick.insert(self.tick, *code_hash);
+        *last_used = self.tick;
+        Some(&*analysis)
+    }
+
+    /// Caches the analysis of the code with `code_hash`, which missed the cache.
+    fn insert(&mut self, code_hash: H256, analysis: Analysis) {
+        self.misses += 1;
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&code_hash) {
+            if let Some((_, oldest)) = self.by_tick.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        if let Some((_, last_used)) = self.entries.insert(code_hash, (analysis, self.tick)) {
+            self.by_tick.remove(&last_used);
+        }
+        self.by_tick.insert(self.tick, code_hash);
+    }
+
+    /// Returns whether the analysis of the code with `code_hash` is cached.
+    pub fn contains(&self, code_hash: &H256) -> bool {
+        self.entries.contains_key(code_hash)
+    }
+
+    /// Returns the number of cached entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether no analysis is cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of lookups answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns the number of lookups that analyzed code.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vm::eof::CodeType;
+
+    #[test]
+    fn test_jump_dests() {
+        // JUMPDEST PUSH2 0x5b5b JUMPDEST, followed by 64 more JUMPDESTs, and a truncated
+        // PUSH1.
+        let mut code = hex::decode("5b615b5b5b").unwrap();
+        code.extend(vec![0x5b; 64]);
+        code.push(0x60);
+        let dests = JumpDests::new(&code);
+        assert!(dests.is_valid(0));
+        assert!(!dests.is_valid(1));
+        assert!(!dests.is_valid(2));
+        assert!(!dests.is_valid(3));
+        assert!(dests.is_valid(4));
+        assert!(dests.is_valid(68));
+        assert!(!dests.is_valid(69));
+        assert!(!dests.is_valid(70));
+        assert_eq!(dests.len(), 70);
+    }
+
+    #[test]
+    fn test_code_analysis_cache() {
+        let mut cache = CodeAnalysisCache::new(2);
+        let (a, b, c) = (
+            H256::repeat_byte(0xaa),
+            H256::repeat_byte(0xbb),
+            H256::repeat_byte(0xcc),
+        );
+        cache.get(a, &[0x5b]);
+        cache.get(b, &[0x00]);
+        // The cached analysis is returned, whatever the code.
+        assert!(cache.get(a, &[]).is_valid(0));
+        // Evicts b, the least recently used.
+        cache.get(c, &[0x00]);
+        assert!(cache.contains(&a));
+        assert!(!cache.contains(&b));
+        assert!(cache.contains(&c));
+        assert_eq!((cache.hits(), cache.misses()), (1, 3));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_container_cache() {
+        let mut cache = CodeAnalysisCache::new(2);
+        let (a, b) = (H256::repeat_byte(0xaa), H256::repeat_byte(0xbb));
+        let container = |code| {
+            let types = vec![CodeType::new(0, 0x80, 0)];
+            EofContainer::new(types, vec![code], Vec::new(), Vec::new())
+                .unwrap()
+                .encode()
+        };
+        // STOP, and ADD underflowing the stack.
+        assert!(cache.get_container(a, &container(vec![0x00])).is_some());
+        assert!(cache.get_container(b, &container(vec![0x01])).is_none());
+        // The cached validation is returned, whatever the code, including a failed one.
+        assert!(cache.get_container(a, &[]).is_some());
+        assert!(cache.get_container(b, &container(vec![0x00])).is_none());
+        assert_eq!((cache.hits(), cache.misses()), (2, 2));
+
+        // Analyzing the code as legacy code replaces the entry.
+        assert!(cache.get(a, &[0x5b]).is_valid(0));
+        assert_eq!((cache.hits(), cache.misses()), (2, 3));
+        assert_eq!(cache.len(), 2);
+
+        // Evicts b, used before the replaced entry.
+        cache.get(H256::repeat_byte(0xcc), &[0x00]);
+        assert!(cache.contains(&a));
+        assert!(!cache.contains(&b));
+        assert_eq!(cache.by_tick.len(), 2);
+    }
+}
@@ -4,6 +4,7 @@ use super::memory::Memory;
 use super::stack::Stack;
 
 /// EVM machine state.
+#[derive(Clone, Default)]
 pub struct MachineState {
     /// Gas available.
     gas_available: U256,
@@ -15,6 +16,8 @@ pub struct MachineState {
     words_in_memory: U256,
     /// Stack contents.
     stack: Stack,
+    /// Output data of the most recent message call or contract creation.
+    return_data: Vec<u8>,
 }
 
 impl MachineState {
@@ -26,6 +29,15 @@ impl MachineState {
             memory: Memory::new(),
             words_in_memory: U256::from(0),
             stack: Stack::new(),
+            return_data: Vec::new(),
+        }
+    }
+
+    /// Returns a new machine state with `gas` available.
+    pub fn with_gas(gas: U256) -> Self {
+        Self {
+            gas_available: gas,
+            ..Self::new()
         }
     }
 
@@ -53,4 +65,39 @@ impl MachineState {
     pub fn stack(&self) -> &Stack {
         &self.stack
     }
+
+    /// Returns the output data of the most recent message call or contract creation.
+    pub fn return_data(&self) -> &Vec<u8> {
+        &self.return_data
+    }
+
+    /// Sets available gas.
+    pub fn set_gas_available(&mut self, gas: U256) {
+        self.gas_available = gas;
+    }
+
+    /// Sets program counter.
+    pub fn set_program_counter(&mut self, program_counter: U256) {
+        self.program_counter = program_counter;
+    }
+
+    /// Returns mutable memory.
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    /// Sets words in memory.
+    pub fn set_words_in_memory(&mut self, words: U256) {
+        self.words_in_memory = words;
+    }
+
+    /// Returns mutable stack.
+    pub fn stack_mut(&mut self) -> &mut Stack {
+        &mut self.stack
+    }
+
+    /// Sets the output data of the most recent message call or contract creation.
+    pub fn set_return_data(&mut self, return_data: Vec<u8>) {
+        self.return_data = return_data;
+    }
 }
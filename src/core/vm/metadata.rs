@@ -0,0 +1,361 @@
+use super::instruction::legacy_info;
+use ethereum_types::H256;
+use std::convert::TryFrom;
+use std::ops::Range;
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Metadata appended to code by the Solidity and Vyper compilers: a CBOR-encoded blob followed
+/// by its length as two big-endian bytes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Offset of the metadata in code.
+    offset: usize,
+    /// IPFS multihash of the metadata file.
+    ipfs: Option<Vec<u8>>,
+    /// Swarm hash of the metadata file.
+    swarm: Option<H256>,
+    compiler: Option<&'static str>,
+    compiler_version: Option<String>,
+    /// Whether experimental compiler features were enabled.
+    experimental: bool,
+}
+
+impl Metadata {
+    /// Returns the metadata at the end of `code`, if it has any.
+    pub fn decode(code: &[u8]) -> Option<Self> {
+        let (offset, map) = trailer(code)?;
+        let mut metadata = Self {
+            offset,
+            ..Self::default()
+        };
+        for (key, value) in map {
+            let key = match key {
+                Cbor::Text(key) => key,
+                _ => return None,
+            };
+            match (key.as_str(), value) {
+                ("ipfs", Cbor::Bytes(hash)) => metadata.ipfs = Some(hash),
+                ("bzzr0" | "bzzr1", Cbor::Bytes(hash)) if hash.len() == 32 => {
+                    metadata.swarm = Some(H256::from_slice(&hash))
+                }
+                ("solc", version) => {
+                    metadata.compiler = Some("solc");
+                    metadata.compiler_version = version_string(version);
+                }
+                ("vyper", version) => {
+                    metadata.compiler = Some("vyper");
+                    metadata.compiler_version = version_string(version);
+                }
+                ("experimental", Cbor::Bool(experimental)) => metadata.experimental = experimental,
+                _ => {}
+            }
+        }
+        Some(metadata)
+    }
+
+    /// Returns the offset of the metadata in code, which is the length of the code before it.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the IPFS multihash of the metadata file.
+    pub fn ipfs(&self) -> Option<&Vec<u8>> {
+        self.ipfs.as_ref()
+    }
+
+    /// Returns the IPFS content identifier of the metadata file, such as `Qm...`.
+    pub fn ipfs_cid(&self) -> Option<String> {
+        self.ipfs.as_deref().map(base58)
+    }
+
+    /// Returns the Swarm hash of the metadata file.
+    pub fn swarm(&self) -> Option<&H256> {
+        self.swarm.as_ref()
+    }
+
+    /// Returns the compiler, either `solc` or `vyper`.
+    pub fn compiler(&self) -> Option<&'static str> {
+        self.compiler
+    }
+
+    /// Returns the version of the compiler, such as `0.8.19`.
+    pub fn compiler_version(&self) -> Option<&str> {
+        self.compiler_version.as_deref()
+    }
+
+    /// Returns whether experimental compiler features were enabled.
+    pub fn experimental(&self) -> bool {
+        self.experimental
+    }
+}
+
+/// Returns `code` without the metadata at its end, if it has any.
+pub fn strip_metadata(code: &[u8]) -> &[u8] {
+    match trailer(code) {
+        Some((offset, _)) => &code[..offset],
+        None => code,
+    }
+}
+
+/// Returns the ranges of the immutables in `deployed` code, compiled as `compiled`, ignoring
+/// the metadata of each. Returns `None` if the code differs outside of immutables.
+///
+/// Solidity compiles a reference to an immutable as a `PUSH32` of zero, whose immediate data is
+/// filled in on deployment.
+pub fn find_immutables(compiled: &[u8], deployed: &[u8]) -> Option<Vec<Range<usize>>> {
+    let (compiled, deployed) = (strip_metadata(compiled), strip_metadata(deployed));
+    if compiled.len() != deployed.len() {
+        return None;
+    }
+
+    let mut immutables = Vec::new();
+    let mut pc = 0;
+    while pc < compiled.len() {
+        let opcode = compiled[pc];
+        let size = legacy_info(opcode).map_or(0, |info| info.immediate_size());
+        let end = (pc + 1 + size).min(compiled.len());
+        if compiled[pc..end] != deployed[pc..end] {
+            let placeholder = opcode == 0x7f && compiled[pc + 1..end].iter().all(|b| *b == 0);
+            if !placeholder || compiled[pc] != deployed[pc] || end - pc != 33 {
+                return None;
+            }
+            immutables.push(pc + 1..end);
+        }
+        pc = end;
+    }
+    Some(immutables)
+}
+
+/// Returns whether `deployed` code was compiled as `compiled`, ignoring metadata and
+/// immutables.
+pub fn matches(compiled: &[u8], deployed: &[u8]) -> bool {
+    find_immutables(compiled, deployed).is_some()
+}
+
+/// Returns the offset of the CBOR blob at the end of `code` and the map it encodes, if it is
+/// shaped as compiler metadata.
+fn trailer(code: &[u8]) -> Option<(usize, Vec<(Cbor, Cbor)>)> {
+    let end = code.len().checked_sub(2)?;
+    let len = u16::from_be_bytes([code[end], code[end + 1]]) as usize;
+    // Some versions of Vyper count the two bytes of the length in the length.
+    for len in [Some(len), len.checked_sub(2)].iter().flatten().copied() {
+        let offset = match end.checked_sub(len) {
+            Some(offset) if len > 0 => offset,
+            _ => continue,
+        };
+        let mut decoder = Decoder {
+            bytes: &code[offset..end],
+            pos: 0,
+        };
+        let map = match decoder.decode(0) {
+            Some(_) if decoder.pos != len => continue,
+            Some(Cbor::Map(map)) => map,
+            // Since 0.3.10, Vyper encodes an array ending with the map of the compiler version.
+            Some(Cbor::Array(mut items)) => match items.pop() {
+                Some(Cbor::Map(map)) => map,
+                _ => continue,
+            },
+            _ => continue,
+        };
+        return Some((offset, map));
+    }
+    None
+}
+
+/// Returns a compiler version, encoded as bytes or an array of its components, or as text for
+/// a prerelease.
+fn version_string(version: Cbor) -> Option<String> {
+    let components: Vec<String> = match version {
+        Cbor::Text(version) => return Some(version),
+        Cbor::Bytes(bytes) => bytes.iter().map(u8::to_string).collect(),
+        Cbor::Array(items) => items
+            .into_iter()
+            .map(|item| match item {
+                Cbor::Uint(n) => Some(n.to_string()),
+                _ => None,
+            })
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+    Some(components.join("."))
+}
+
+/// Returns `bytes` encoded in base58, as IPFS content identifiers are.
+fn base58(bytes: &[u8]) -> String {
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in bytes {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    std::iter::repeat_n(b'1', zeros)
+        .chain(digits.iter().rev().map(|d| BASE58_ALPHABET[*d as usize]))
+        .map(char::from)
+        .collect()
+}
+
+/// A CBOR value of the kinds compilers encode metadata with.
+#[derive(Debug)]
+enum Cbor {
+    Uint(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Bool(bool),
+}
+
+/// Maximum nesting of CBOR arrays and maps.
+const MAX_CBOR_DEPTH: usize = 8;
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    /// Returns the argument of an item with additional information `info`.
+    fn argument(&mut self, info: u8) -> Option<u64> {
+        let size = match info {
+            0..=23 => return Some(info as u64),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return None,
+        };
+        let bytes = self.take(size)?;
+        Some(bytes.iter().fold(0, |n, b| (n << 8) | *b as u64))
+    }
+
+    fn decode(&mut self, depth: usize) -> Option<Cbor> {
+        if depth > MAX_CBOR_DEPTH {
+            return None;
+        }
+        let initial = *self.take(1)?.first()?;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        if major == 7 {
+            return match info {
+                20 => Some(Cbor::Bool(false)),
+                21 => Some(Cbor::Bool(true)),
+                _ => None,
+            };
+        }
+
+        let argument = self.argument(info)?;
+        let len = usize::try_from(argument).ok()?;
+        match major {
+            0 => Some(Cbor::Uint(argument)),
+            2 => Some(Cbor::Bytes(self.take(len)?.to_vec())),
+            3 => String::from_utf8(self.take(len)?.to_vec())
+                .ok()
+                .map(Cbor::Text),
+            4 => {
+                let mut items = Vec::new();
+                for _ in 0..len {
+                    items.push(self.decode(depth + 1)?);
+                }
+                Some(Cbor::Array(items))
+            }
+            5 => {
+                let mut entries = Vec::new();
+                for _ in 0..len {
+                    entries.push((self.decode(depth + 1)?, self.decode(depth + 1)?));
+                }
+                Some(Cbor::Map(entries))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns Solidity metadata with an IPFS hash of `byte` repeated, for solc 0.8.19.
+    fn solidity_metadata(byte: u8) -> Vec<u8> {
+        let hash = hex::encode([byte; 32]);
+        hex::decode(format!(
+            "a2646970667358221220{}64736f6c63430008130033",
+            hash
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_decode_metadata() {
+        let mut code = hex::decode("6080604052").unwrap();
+        code.extend(solidity_metadata(0xab));
+        let metadata = Metadata::decode(&code).unwrap();
+        assert_eq!(metadata.offset(), 5);
+        assert_eq!(metadata.compiler(), Some("solc"));
+        assert_eq!(metadata.compiler_version(), Some("0.8.19"));
+        assert_eq!(
+            metadata.ipfs_cid().as_deref(),
+            Some("QmZtnFaddFtzGNT8BxdHVbQrhSFdq1pWxud5z4fA4kxfDt")
+        );
+        assert!(!metadata.experimental());
+        assert_eq!(strip_metadata(&code), &code[..5]);
+
+        // Swarm hash, experimental features and a prerelease compiler: {"bzzr1": h'00..',
+        // "experimental": true, "solc": "0.6.0-nightly"}
+        let blob = format!(
+            "a365627a7a72315820{}6c6578706572696d656e74616cf564736f6c636d302e362e302d6e696768746c79",
+            "00".repeat(32)
+        );
+        let code = hex::decode(format!("00{}{:04x}", blob, blob.len() / 2)).unwrap();
+        let metadata = Metadata::decode(&code).unwrap();
+        assert_eq!(metadata.swarm(), Some(&H256::zero()));
+        assert!(metadata.experimental());
+        assert_eq!(metadata.compiler_version(), Some("0.6.0-nightly"));
+
+        // Vyper 0.3.10: [size, [], 0, {"vyper": [0, 3, 10]}]
+        let code = hex::decode("0084182a8000a16576797065728300030a0010").unwrap();
+        let metadata = Metadata::decode(&code).unwrap();
+        assert_eq!(metadata.compiler(), Some("vyper"));
+        assert_eq!(metadata.compiler_version(), Some("0.3.10"));
+
+        assert_eq!(Metadata::decode(&hex::decode("6080604052").unwrap()), None);
+    }
+
+    #[test]
+    fn test_find_immutables() {
+        // PUSH32 0 POP, twice, then STOP, with the immutables filled in and a different metadata
+        // hash.
+        let placeholder = format!("7f{}50", "00".repeat(32));
+        let mut compiled = hex::decode(format!("{}{}00", placeholder, placeholder)).unwrap();
+        compiled.extend(solidity_metadata(0xab));
+        let filled = format!("7f{}50", "11".repeat(32));
+        let mut deployed = hex::decode(format!("{}{}00", filled, filled)).unwrap();
+        deployed.extend(solidity_metadata(0xcd));
+        assert_eq!(
+            find_immutables(&compiled, &deployed),
+            Some(vec![1..33, 35..67])
+        );
+        assert!(matches(&compiled, &compiled));
+
+        deployed[34] = 0x01;
+        assert!(!matches(&compiled, &deployed));
+
+        // A trailing byte that reads as a length, before a value that is not a map, is code.
+        let code = hex::decode("6001050001").unwrap();
+        assert_eq!(strip_metadata(&code), &code[..]);
+        assert!(!matches(&code, &hex::decode("6001070001").unwrap()));
+    }
+}
@@ -1,6 +1,20 @@
+pub mod analysis;
+pub mod assembler;
+pub mod cfg;
+pub mod disassembler;
 pub mod env;
+pub mod eof;
+pub mod gas;
+pub mod inspector;
 pub mod instruction;
+pub mod interpreter;
+pub mod jumpdest;
 pub mod machine;
 pub mod memory;
+pub mod metadata;
+pub mod precompile;
+pub mod proxy;
 pub mod stack;
 pub mod substate;
+#[cfg(test)]
+pub(crate) mod testing;
@@ -0,0 +1,490 @@
+use crate::core::crypto;
+use crate::core::fork::Fork;
+use ethereum_types::{Address, H256, U256};
+use num_bigint::BigUint;
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+/// An error that may occur when executing a precompiled contract. Either error consumes all gas
+/// passed to the contract.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PrecompileError {
+    OutOfGas,
+    InvalidInput,
+}
+
+/// Returns the addresses of the precompiled contracts available under the rules of `fork`.
+pub fn addresses(fork: Fork) -> impl Iterator<Item = Address> {
+    let count = if fork >= Fork::Cancun {
+        10
+    } else if fork >= Fork::Istanbul {
+        9
+    } else if fork >= Fork::Byzantium {
+        8
+    } else {
+        4
+    };
+    (1..=count).map(Address::from_low_u64_be)
+}
+
+/// Returns whether `address` holds a precompiled contract under the rules of `fork`.
+pub fn is_precompile(address: &Address, fork: Fork) -> bool {
+    addresses(fork).any(|precompile| precompile == *address)
+}
+
+/// Executes the precompiled contract at `address` with `input` and `gas` available, returning
+/// the gas used and the output.
+pub fn execute(
+    address: &Address,
+    input: &[u8],
+    gas: u64,
+    fork: Fork,
+) -> Result<(u64, Vec<u8>), PrecompileError> {
+    let charge = |cost: u64| {
+        if cost > gas {
+            Err(PrecompileError::OutOfGas)
+        } else {
+            Ok(cost)
+        }
+    };
+    let words = (input.len() as u64).div_ceil(32);
+
+    match address.to_low_u64_be() {
+        1 => {
+            let cost = charge(3000)?;
+            Ok((cost, ecrecover(input)))
+        }
+        2 => {
+            let cost = charge(60 + 12 * words)?;
+            Ok((cost, Sha256::digest(input).to_vec()))
+        }
+        3 => {
+            let cost = charge(600 + 120 * words)?;
+            let mut output = vec![0u8; 12];
+            output.extend_from_slice(&Ripemd160::digest(input));
+            Ok((cost, output))
+        }
+        4 => {
+            let cost = charge(15 + 3 * words)?;
+            Ok((cost, input.to_vec()))
+        }
+        5 => modexp(input, gas, fork),
+        6 => {
+            let cost = charge(if fork >= Fork::Istanbul { 150 } else { 500 })?;
+            Ok((cost, bn_add(input)?))
+        }
+        7 => {
+            let cost = charge(if fork >= Fork::Istanbul { 6000 } else { 40000 })?;
+            Ok((cost, bn_mul(input)?))
+        }
+        8 => {
+            let pairs = input.len() as u64 / 192;
+            let cost = if fork >= Fork::Istanbul {
+                45000 + 34000 * pairs
+            } else {
+                100000 + 80000 * pairs
+            };
+            let cost = charge(cost)?;
+            Ok((cost, bn_pairing(input)?))
+        }
+        9 => blake2f(input, gas),
+        10 => {
+            let cost = charge(50000)?;
+            Ok((cost, point_evaluation(input)?))
+        }
+        _ => Err(PrecompileError::InvalidInput),
+    }
+}
+
+/// Returns `len` bytes of `input` starting at `offset`, padded with zeros.
+fn padded(input: &[u8], offset: usize, len: usize) -> Vec<u8> {
+    let mut out = vec![0u8; len];
+    if offset < input.len() {
+        let end = input.len().min(offset.saturating_add(len));
+        out[..end - offset].copy_from_slice(&input[offset..end]);
+    }
+    out
+}
+
+/// Recovers the address that signed a hash. Returns no output if recovery fails.
+fn ecrecover(input: &[u8]) -> Vec<u8> {
+    let input = padded(input, 0, 128);
+    let hash = H256::from_slice(&input[..32]);
+    let v = U256::from_big_endian(&input[32..64]);
+    let r = U256::from_big_endian(&input[64..96]);
+    let s = U256::from_big_endian(&input[96..128]);
+
+    if v != U256::from(27) && v != U256::from(28) {
+        return Vec::new();
+    }
+
+    match crypto::recover(&hash, v.low_u32() as u8 - 27, &r, &s) {
+        Some(address) => H256::from(address).as_bytes().to_vec(),
+        None => Vec::new(),
+    }
+}
+
+/// Reads a big-endian length of at most `u64::MAX` from a 32-byte word.
+fn read_len(word: &[u8]) -> u64 {
+    let value = U256::from_big_endian(word);
+    if value > U256::from(u64::MAX) {
+        u64::MAX
+    } else {
+        value.low_u64()
+    }
+}
+
+/// Modular exponentiation (EIP-198), priced by EIP-2565 since Berlin.
+fn modexp(input: &[u8], gas: u64, fork: Fork) -> Result<(u64, Vec<u8>), PrecompileError> {
+    let header = padded(input, 0, 96);
+    let base_len = read_len(&header[..32]);
+    let exp_len = read_len(&header[32..64]);
+    let mod_len = read_len(&header[64..96]);
+
+    // The exponent head is the first (at most) 32 bytes of the exponent.
+    let exp_head = if base_len > input.len() as u64 {
+        U256::zero()
+    } else {
+        let head_len = exp_len.min(32) as usize;
+        let bytes = padded(input, 96 + base_len as usize, head_len);
+        U256::from_big_endian(&bytes)
+    };
+    let head_bits = exp_head.bits() as u64;
+    let iterations = if exp_len <= 32 {
+        head_bits.saturating_sub(1)
+    } else {
+        (exp_len - 32)
+            .saturating_mul(8)
+            .saturating_add(head_bits.saturating_sub(1))
+    };
+    let iterations = iterations.max(1) as u128;
+
+    let max_len = base_len.max(mod_len) as u128;
+    let cost = if fork >= Fork::Berlin {
+        let words = max_len.div_ceil(8);
+        let complexity = words.saturating_mul(words);
+        (complexity.saturating_mul(iterations) / 3).max(200)
+    } else {
+        let complexity = if max_len <= 64 {
+            max_len * max_len
+        } else if max_len <= 1024 {
+            max_len * max_len / 4 + 96 * max_len - 3072
+        } else {
+            (max_len.saturating_mul(max_len) / 16)
+                .saturating_add(480 * max_len)
+                .saturating_sub(199680)
+        };
+        complexity.saturating_mul(iterations) / 20
+    };
+    if cost > gas as u128 {
+        return Err(PrecompileError::OutOfGas);
+    }
+    let cost = cost as u64;
+
+    if mod_len == 0 {
+        return Ok((cost, Vec::new()));
+    }
+
+    let base_len = base_len as usize;
+    let exp_len = exp_len as usize;
+    let mod_len = mod_len as usize;
+    let base = BigUint::from_bytes_be(&padded(input, 96, base_len));
+    let exp = BigUint::from_bytes_be(&padded(input, 96 + base_len, exp_len));
+    let modulus = BigUint::from_bytes_be(&padded(input, 96 + base_len + exp_len, mod_len));
+
+    let mut output = vec![0u8; mod_len];
+    if modulus != BigUint::from(0u8) {
+        let result = base.modpow(&exp, &modulus).to_bytes_be();
+        output[mod_len - result.len()..].copy_from_slice(&result);
+    }
+
+    Ok((cost, output))
+}
+
+fn read_fq(input: &[u8], offset: usize) -> Result<bn::Fq, PrecompileError> {
+    bn::Fq::from_slice(&padded(input, offset, 32)).map_err(|_| PrecompileError::InvalidInput)
+}
+
+fn read_g1(input: &[u8], offset: usize) -> Result<bn::G1, PrecompileError> {
+    use bn::Group;
+
+    let x = read_fq(input, offset)?;
+    let y = read_fq(input, offset + 32)?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(bn::G1::zero());
+    }
+    bn::AffineG1::new(x, y)
+        .map(Into::into)
+        .map_err(|_| PrecompileError::InvalidInput)
+}
+
+fn read_g2(input: &[u8], offset: usize) -> Result<bn::G2, PrecompileError> {
+    use bn::Group;
+
+    // Elements of Fq2 are encoded with the imaginary part first (EIP-197).
+    let x = bn::Fq2::new(read_fq(input, offset + 32)?, read_fq(input, offset)?);
+    let y = bn::Fq2::new(read_fq(input, offset + 96)?, read_fq(input, offset + 64)?);
+    if x.is_zero() && y.is_zero() {
+        return Ok(bn::G2::zero());
+    }
+    bn::AffineG2::new(x, y)
+        .map(Into::into)
+        .map_err(|_| PrecompileError::InvalidInput)
+}
+
+fn encode_g1(point: bn::G1) -> Vec<u8> {
+    let mut output = vec![0u8; 64];
+    if let Some(affine) = bn::AffineG1::from_jacobian(point) {
+        // Both writes are into 32-byte slices, which cannot fail.
+        let _ = affine.x().to_big_endian(&mut output[..32]);
+        let _ = affine.y().to_big_endian(&mut output[32..]);
+    }
+    output
+}
+
+/// Point addition on the alt_bn128 curve (EIP-196).
+fn bn_add(input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+    let a = read_g1(input, 0)?;
+    let b = read_g1(input, 64)?;
+    Ok(encode_g1(a + b))
+}
+
+/// Scalar multiplication on the alt_bn128 curve (EIP-196).
+fn bn_mul(input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+    let point = read_g1(input, 0)?;
+    let scalar =
+        bn::Fr::from_slice(&padded(input, 64, 32)).map_err(|_| PrecompileError::InvalidInput)?;
+    Ok(encode_g1(point * scalar))
+}
+
+/// Pairing check on the alt_bn128 curve (EIP-197).
+fn bn_pairing(input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+    if !input.len().is_multiple_of(192) {
+        return Err(PrecompileError::InvalidInput);
+    }
+
+    let mut pairs = Vec::with_capacity(input.len() / 192);
+    for offset in (0..input.len()).step_by(192) {
+        pairs.push((read_g1(input, offset)?, read_g2(input, offset + 64)?));
+    }
+
+    let success = pairs.is_empty() || bn::pairing_batch(&pairs) == bn::Gt::one();
+    let mut output = vec![0u8; 32];
+    output[31] = success as u8;
+    Ok(output)
+}
+
+const BLAKE2B_IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const BLAKE2B_SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// The BLAKE2b compression function F (EIP-152).
+fn blake2f(input: &[u8], gas: u64) -> Result<(u64, Vec<u8>), PrecompileError> {
+    if input.len() != 213 || input[212] > 1 {
+        return Err(PrecompileError::InvalidInput);
+    }
+
+    let mut rounds = [0u8; 4];
+    rounds.copy_from_slice(&input[..4]);
+    let rounds = u32::from_be_bytes(rounds);
+    if u64::from(rounds) > gas {
+        return Err(PrecompileError::OutOfGas);
+    }
+
+    let word = |offset: usize| {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&input[offset..offset + 8]);
+        u64::from_le_bytes(bytes)
+    };
+    let mut h = [0u64; 8];
+    for (i, h) in h.iter_mut().enumerate() {
+        *h = word(4 + 8 * i);
+    }
+    let mut m = [0u64; 16];
+    for (i, m) in m.iter_mut().enumerate() {
+        *m = word(68 + 8 * i);
+    }
+    let t = [word(196), word(204)];
+    let last = input[212] == 1;
+
+    let mut v = [0u64; 16];
+    v[..8].copy_from_slice(&h);
+    v[8..].copy_from_slice(&BLAKE2B_IV);
+    v[12] ^= t[0];
+    v[13] ^= t[1];
+    if last {
+        v[14] = !v[14];
+    }
+
+    fn mix(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+        v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+        v[d] = (v[d] ^ v[a]).rotate_right(32);
+        v[c] = v[c].wrapping_add(v[d]);
+        v[b] = (v[b] ^ v[c]).rotate_right(24);
+        v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+        v[d] = (v[d] ^ v[a]).rotate_right(16);
+        v[c] = v[c].wrapping_add(v[d]);
+        v[b] = (v[b] ^ v[c]).rotate_right(63);
+    }
+
+    for round in 0..rounds as usize {
+        let s = &BLAKE2B_SIGMA[round % 10];
+        mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+
+    let mut output = Vec::with_capacity(64);
+    for i in 0..8 {
+        output.extend_from_slice(&(h[i] ^ v[i] ^ v[i + 8]).to_le_bytes());
+    }
+
+    Ok((u64::from(rounds), output))
+}
+
+/// Version byte of KZG commitment versioned hashes (EIP-4844).
+pub const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+
+/// Returns the versioned hash of a KZG commitment (EIP-4844).
+pub fn kzg_to_versioned_hash(commitment: &[u8]) -> H256 {
+    let mut hash = H256::from_slice(&Sha256::digest(commitment));
+    hash.0[0] = VERSIONED_HASH_VERSION_KZG;
+    hash
+}
+
+/// Verifies a KZG proof that a blob evaluates to a given value at a given point (EIP-4844).
+fn point_evaluation(input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+    if input.len() != 192 {
+        return Err(PrecompileError::InvalidInput);
+    }
+
+    let versioned_hash = &input[..32];
+    let commitment = &input[96..144];
+    if kzg_to_versioned_hash(commitment).as_bytes() != versioned_hash {
+        return Err(PrecompileError::InvalidInput);
+    }
+
+    let bytes32 =
+        |bytes: &[u8]| c_kzg::Bytes32::from_bytes(bytes).map_err(|_| PrecompileError::InvalidInput);
+    let bytes48 =
+        |bytes: &[u8]| c_kzg::Bytes48::from_bytes(bytes).map_err(|_| PrecompileError::InvalidInput);
+    let verified = c_kzg::ethereum_kzg_settings(0)
+        .verify_kzg_proof(
+            &bytes48(commitment)?,
+            &bytes32(&input[32..64])?,
+            &bytes32(&input[64..96])?,
+            &bytes48(&input[144..192])?,
+        )
+        .map_err(|_| PrecompileError::InvalidInput)?;
+    if !verified {
+        return Err(PrecompileError::InvalidInput);
+    }
+
+    // FIELD_ELEMENTS_PER_BLOB followed by BLS_MODULUS.
+    let mut output = vec![0u8; 64];
+    U256::from(c_kzg::FIELD_ELEMENTS_PER_BLOB).to_big_endian(&mut output[..32]);
+    U256::from_big_endian(&[
+        0x73, 0xed, 0xa7, 0x53, 0x29, 0x9d, 0x7d, 0x48, 0x33, 0x39, 0xd8, 0x08, 0x09, 0xa1, 0xd8,
+        0x05, 0x53, 0xbd, 0xa4, 0x02, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00,
+        0x00, 0x01,
+    ])
+    .to_big_endian(&mut output[32..]);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity() {
+        let address = Address::from_low_u64_be(4);
+        assert_eq!(
+            execute(&address, &[1, 2, 3], 18, Fork::Frontier),
+            Ok((18, vec![1, 2, 3]))
+        );
+        assert_eq!(
+            execute(&address, &[1, 2, 3], 17, Fork::Frontier),
+            Err(PrecompileError::OutOfGas)
+        );
+    }
+
+    #[test]
+    fn test_modexp() {
+        // 3 ** (2 ** 256 - 2 ** 32 - 978) % (2 ** 256 - 2 ** 32 - 977) = 1, from EIP-198.
+        let mut input = vec![0u8; 96];
+        input[31] = 1;
+        input[63] = 32;
+        input[95] = 32;
+        input.push(3);
+        let mut p = [0xffu8; 32];
+        p[27] = 0xfe;
+        p[30] = 0xfc;
+        p[31] = 0x2f;
+        let mut e = p;
+        e[31] = 0x2e;
+        input.extend_from_slice(&e);
+        input.extend_from_slice(&p);
+
+        let (_, output) = execute(
+            &Address::from_low_u64_be(5),
+            &input,
+            100000,
+            Fork::Byzantium,
+        )
+        .unwrap();
+        let mut expected = vec![0u8; 32];
+        expected[31] = 1;
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_blake2f() {
+        // Test vector 5 from EIP-152.
+        let mut input = Vec::new();
+        input.extend_from_slice(&12u32.to_be_bytes());
+        input.extend_from_slice(&hex::decode("48c9bdf267e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f3af54fa5d182e6ad7f520e511f6c3e2b8c68059b6bbd41fbabd9831f79217e1319cde05b").unwrap());
+        let mut m = vec![0u8; 128];
+        m[..3].copy_from_slice(b"abc");
+        input.extend_from_slice(&m);
+        input.extend_from_slice(&3u64.to_le_bytes());
+        input.extend_from_slice(&0u64.to_le_bytes());
+        input.push(1);
+
+        let (gas, output) = blake2f(&input, 12).unwrap();
+        assert_eq!(gas, 12);
+        assert_eq!(output, hex::decode("ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d17d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923").unwrap());
+    }
+
+    #[test]
+    fn test_bn_add_identity() {
+        let output = bn_add(&[]).unwrap();
+        assert_eq!(output, vec![0u8; 64]);
+    }
+}
@@ -0,0 +1,297 @@
+use crate::core::fork::Fork;
+use crate::core::state::WorldState;
+use crate::core::types::block::BlockHeader;
+use ethereum_types::{Address, U256};
+use keccak_hash::keccak;
+
+use super::env::BlockHashes;
+use super::interpreter::{Interpreter, Message};
+
+/// Code of an EIP-1167 minimal proxy before and after the address of its implementation.
+const MINIMAL_PROXY: (&[u8], &[u8]) = (
+    &[0x36, 0x3d, 0x3d, 0x37, 0x3d, 0x3d, 0x3d, 0x36, 0x3d, 0x73],
+    &[
+        0x5a, 0xf4, 0x3d, 0x82, 0x80, 0x3e, 0x90, 0x3d, 0x91, 0x60, 0x2b, 0x57, 0xfd, 0x5b, 0xf3,
+    ],
+);
+/// Code of a minimal proxy using `PUSH0` (ERC-7511) before and after the address of its
+/// implementation.
+const MINIMAL_PROXY_PUSH0: (&[u8], &[u8]) = (
+    &[0x36, 0x5f, 0x5f, 0x37, 0x5f, 0x5f, 0x36, 0x5f, 0x73],
+    &[
+        0x5a, 0xf4, 0x3d, 0x5f, 0x5f, 0x3e, 0x5f, 0x3d, 0x91, 0x60, 0x2a, 0x57, 0xfd, 0x5b, 0xf3,
+    ],
+);
+/// `PUSH4` of the selector of `masterCopy()`, which Gnosis Safe proxies answer.
+const MASTER_COPY: [u8; 5] = [0x63, 0xa6, 0x19, 0x48, 0x6e];
+/// Selectors of `implementation()` and `childImplementation()`, which beacons answer.
+const BEACON_SELECTORS: [[u8; 4]; 2] = [[0x5c, 0x60, 0xda, 0x1b], [0xda, 0x52, 0x57, 0x16]];
+/// Gas given to a beacon to answer for its implementation.
+pub const BEACON_CALL_GAS: u64 = 100_000;
+
+/// How a proxy finds the contract it delegates to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProxyKind {
+    /// A minimal proxy with the implementation in its code (EIP-1167).
+    Minimal,
+    /// A proxy with the implementation in the EIP-1967 slot.
+    Eip1967,
+    /// A proxy with a beacon in the EIP-1967 slot, which answers for the implementation.
+    Beacon,
+    /// A proxy with the implementation in the slot of `PROXIABLE` (EIP-1822).
+    Eip1822,
+    /// A Gnosis Safe proxy, with its master copy in the first slot.
+    GnosisSafe,
+}
+
+/// A proxy contract, resolved to its implementation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Proxy {
+    kind: ProxyKind,
+    implementation: Address,
+    beacon: Option<Address>,
+}
+
+impl Proxy {
+    /// Returns the pattern of the proxy, which tells how its implementation was found.
+    pub fn kind(&self) -> ProxyKind {
+        self.kind
+    }
+
+    /// Returns the address of the contract the proxy delegates to.
+    pub fn implementation(&self) -> &Address {
+        &self.implementation
+    }
+
+    /// Returns the address of the beacon of a beacon proxy.
+    pub fn beacon(&self) -> Option<&Address> {
+        self.beacon.as_ref()
+    }
+}
+
+/// Returns the proxy at `address` in `state`, if its code or storage is that of a known proxy
+/// pattern with an implementation set.
+///
+/// The beacon of a beacon proxy is called for the implementation in the block of `header`,
+/// and any change it makes to `state` is reverted.
+pub fn detect_proxy(
+    state: &mut WorldState,
+    header: &BlockHeader,
+    block_hashes: &dyn BlockHashes,
+    fork: Fork,
+    chain_id: U256,
+    address: &Address,
+) -> Option<Proxy> {
+    let code = state.code(address).to_vec();
+    let proxy = |kind, implementation| {
+        Some(Proxy {
+            kind,
+            implementation,
+            beacon: None,
+        })
+    };
+
+    for (prefix, suffix) in [MINIMAL_PROXY, MINIMAL_PROXY_PUSH0].iter() {
+        if code.len() == prefix.len() + 20 + suffix.len()
+            && code.starts_with(prefix)
+            && code.ends_with(suffix)
+        {
+            let implementation = Address::from_slice(&code[prefix.len()..prefix.len() + 20]);
+            return proxy(ProxyKind::Minimal, implementation);
+        }
+    }
+
+    let slot = |state: &WorldState, key| word_to_address(state.storage(address, &key));
+    if let Some(implementation) = slot(state, eip1967_slot("eip1967.proxy.implementation")) {
+        return proxy(ProxyKind::Eip1967, implementation);
+    }
+    if let Some(beacon) = slot(state, eip1967_slot("eip1967.proxy.beacon")) {
+        let implementation = BEACON_SELECTORS.iter().find_map(|selector| {
+            call_for_address(
+                state,
+                header,
+                block_hashes,
+                fork,
+                chain_id,
+                &beacon,
+                selector,
+            )
+        });
+        // A beacon that does not answer leaves the other patterns to check.
+        if let Some(implementation) = implementation {
+            return Some(Proxy {
+                kind: ProxyKind::Beacon,
+                implementation,
+                beacon: Some(beacon),
+            });
+        }
+    }
+    if let Some(implementation) = slot(state, U256::from(keccak(b"PROXIABLE").as_bytes())) {
+        return proxy(ProxyKind::Eip1822, implementation);
+    }
+    if code.windows(MASTER_COPY.len()).any(|w| w == MASTER_COPY) {
+        if let Some(implementation) = slot(state, U256::zero()) {
+            return proxy(ProxyKind::GnosisSafe, implementation);
+        }
+    }
+    None
+}
+
+/// Returns the slot EIP-1967 derives from `name`: its hash less one, which has no known
+/// preimage.
+fn eip1967_slot(name: &str) -> U256 {
+    U256::from(keccak(name.as_bytes()).as_bytes()) - U256::one()
+}
+
+/// Returns the address `word` holds, if it is non-zero and has no bits above an address.
+fn word_to_address(word: U256) -> Option<Address> {
+    if word.is_zero() || word.bits() > 160 {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    word.to_big_endian(&mut bytes);
+    Some(Address::from_slice(&bytes[12..]))
+}
+
+/// Returns the address returned by a call to `to` with `selector`, reverting any change the
+/// call makes to `state`.
+#[allow(clippy::too_many_arguments)]
+fn call_for_address(
+    state: &mut WorldState,
+    header: &BlockHeader,
+    block_hashes: &dyn BlockHashes,
+    fork: Fork,
+    chain_id: U256,
+    to: &Address,
+    selector: &[u8; 4],
+) -> Option<Address> {
+    let checkpoint = state.checkpoint();
+    let caller = Address::zero();
+    let mut interpreter = Interpreter::new(
+        state,
+        header,
+        block_hashes,
+        fork,
+        chain_id,
+        caller,
+        U256::zero(),
+        Vec::new(),
+    );
+    let outcome = interpreter.execute(Message::call(
+        caller,
+        *to,
+        U256::zero(),
+        selector.to_vec(),
+        U256::from(BEACON_CALL_GAS),
+    ));
+    state.revert_to(checkpoint);
+    if !outcome.status().is_success() || outcome.output().len() < 32 {
+        return None;
+    }
+    word_to_address(U256::from_big_endian(&outcome.output()[..32]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn detect(state: &mut WorldState, address: &Address) -> Option<Proxy> {
+        let header = BlockHeader::default();
+        let block_hashes = HashMap::new();
+        let (fork, chain_id) = (Fork::Cancun, U256::one());
+        detect_proxy(state, &header, &block_hashes, fork, chain_id, address)
+    }
+
+    #[test]
+    fn test_minimal_proxy() {
+        let mut state = WorldState::new();
+        let implementation = Address::repeat_byte(0xbb);
+        for (address, (prefix, suffix)) in [
+            (Address::repeat_byte(0xc1), MINIMAL_PROXY),
+            (Address::repeat_byte(0xc2), MINIMAL_PROXY_PUSH0),
+        ] {
+            let code = [prefix, implementation.as_bytes(), suffix].concat();
+            state.insert_account(address, U256::zero(), U256::zero(), code, None);
+            let proxy = detect(&mut state, &address).unwrap();
+            assert_eq!(proxy.kind(), ProxyKind::Minimal);
+            assert_eq!(proxy.implementation(), &implementation);
+        }
+
+        // A contract that is not a proxy.
+        let address = Address::repeat_byte(0xc3);
+        let code = hex::decode("6080604052").unwrap();
+        state.insert_account(address, U256::zero(), U256::zero(), code, None);
+        assert_eq!(detect(&mut state, &address), None);
+    }
+
+    #[test]
+    fn test_storage_proxies() {
+        let mut state = WorldState::new();
+        let implementation = Address::repeat_byte(0xbb);
+        let word = U256::from(implementation.as_bytes());
+        let code = hex::decode("6080604052").unwrap();
+
+        let transparent = Address::repeat_byte(0xc1);
+        state.insert_account(transparent, U256::zero(), U256::zero(), code.clone(), None);
+        let slot = eip1967_slot("eip1967.proxy.implementation");
+        assert_eq!(
+            slot,
+            U256::from("360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc")
+        );
+        state.set_storage(&transparent, slot, word);
+        assert_eq!(
+            detect(&mut state, &transparent).unwrap().kind(),
+            ProxyKind::Eip1967
+        );
+
+        let uups = Address::repeat_byte(0xc2);
+        state.insert_account(uups, U256::zero(), U256::zero(), code, None);
+        let slot = U256::from(keccak(b"PROXIABLE").as_bytes());
+        state.set_storage(&uups, slot, word);
+        assert_eq!(
+            detect(&mut state, &uups).unwrap().kind(),
+            ProxyKind::Eip1822
+        );
+
+        // A beacon answering every call with the implementation:
+        // PUSH20 implementation PUSH0 MSTORE PUSH1 32 PUSH0 RETURN
+        let beacon = Address::repeat_byte(0xbe);
+        let code = hex::decode(format!("73{}5f5260205ff3", hex::encode(implementation))).unwrap();
+        state.insert_account(beacon, U256::zero(), U256::zero(), code, None);
+        let proxy = Address::repeat_byte(0xc3);
+        state.insert_account(proxy, U256::zero(), U256::zero(), Vec::new(), None);
+        let slot = eip1967_slot("eip1967.proxy.beacon");
+        state.set_storage(&proxy, slot, U256::from(beacon.as_bytes()));
+        let resolved = detect(&mut state, &proxy).unwrap();
+        assert_eq!(resolved.kind(), ProxyKind::Beacon);
+        assert_eq!(resolved.implementation(), &implementation);
+        assert_eq!(resolved.beacon(), Some(&beacon));
+
+        // A beacon that stores to its first slot and answers with a word that is no address,
+        // with the implementation also in the slot of `PROXIABLE`:
+        // PUSH1 1 PUSH0 SSTORE PUSH0 NOT PUSH0 MSTORE PUSH1 32 PUSH0 RETURN
+        let failing = Address::repeat_byte(0xbf);
+        let code = hex::decode("60015f555f195f5260205ff3").unwrap();
+        state.insert_account(failing, U256::zero(), U256::zero(), code, None);
+        let proxy = Address::repeat_byte(0xc5);
+        state.insert_account(proxy, U256::zero(), U256::zero(), Vec::new(), None);
+        let slot = eip1967_slot("eip1967.proxy.beacon");
+        state.set_storage(&proxy, slot, U256::from(failing.as_bytes()));
+        let slot = U256::from(keccak(b"PROXIABLE").as_bytes());
+        state.set_storage(&proxy, slot, word);
+        let root = state.state_root();
+        let resolved = detect(&mut state, &proxy).unwrap();
+        assert_eq!(resolved.kind(), ProxyKind::Eip1822);
+        assert_eq!(state.state_root(), root);
+
+        // PUSH4 masterCopy() in the code, and the master copy in the first slot.
+        let safe = Address::repeat_byte(0xc4);
+        let code = hex::decode("608060405263a619486e00").unwrap();
+        state.insert_account(safe, U256::zero(), U256::zero(), code, None);
+        state.set_storage(&safe, U256::zero(), word);
+        let resolved = detect(&mut state, &safe).unwrap();
+        assert_eq!(resolved.kind(), ProxyKind::GnosisSafe);
+        assert_eq!(resolved.implementation(), &implementation);
+    }
+}
@@ -11,17 +11,16 @@ pub enum StackError {
 }
 
 /// EVM stack.
+#[derive(Clone)]
 pub struct Stack {
-    items: [U256; MAX_LEN],
-    len: usize,
+    items: Vec<U256>,
 }
 
 impl Stack {
     /// Creates a `Stack`.
     pub fn new() -> Self {
         Self {
-            items: [Default::default(); MAX_LEN],
-            len: 0,
+            items: Vec::with_capacity(MAX_LEN),
         }
     }
 
@@ -31,19 +30,12 @@ impl Stack {
             return None;
         }
 
-        return Some(&self.items[self.len() - 1 - pos]);
+        Some(&self.items[self.len() - 1 - pos])
     }
 
     /// Attempts to pop the top item from the stack.
     pub fn pop(&mut self) -> Result<U256, StackError> {
-        if self.len() == 0 {
-            return Err(StackError::Underflow);
-        }
-
-        let item = self.items[self.len() - 1];
-        self.len -= 1;
-
-        Ok(item)
+        self.items.pop().ok_or(StackError::Underflow)
     }
 
     /// Attempts to push an item onto the stack.
@@ -52,8 +44,7 @@ impl Stack {
             return Err(StackError::Overflow);
         }
 
-        self.items[self.len()] = item;
-        self.len += 1;
+        self.items.push(item);
 
         Ok(())
     }
@@ -74,13 +65,24 @@ impl Stack {
 
     /// Returns the number of items in the stack.
     pub fn len(&self) -> usize {
-        self.len
+        self.items.len()
     }
 
     /// Returns whether the stack is empty.
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// Returns the items in the stack, from bottom to top.
+    pub fn as_slice(&self) -> &[U256] {
+        &self.items
+    }
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
@@ -6,6 +6,7 @@ use crate::core::types::log::Log;
 /// Accrued transaction substate.
 ///
 /// Information acted upon immediately following a transaction.
+#[derive(Clone, Debug, Default)]
 pub struct AccruedSubstate {
     /// Self-destruct set. A set of accounts that will be discarded following the
     /// completion of the associated transaction.
@@ -22,11 +23,13 @@ pub struct AccruedSubstate {
     accessed_accounts: HashSet<Address>,
     /// Accessed storage set.
     accessed_storage: HashSet<(Address, U256)>,
+    /// Created account set. Accounts created by the associated transaction, which are the
+    /// only accounts a self-destruct may delete since Cancun (EIP-6780).
+    created_accounts: HashSet<Address>,
 }
 
 impl AccruedSubstate {
     /// Returns an empty accrued transaction substate.
-    // TODO: Add precompile accounts to accessed account set.
     pub fn empty() -> Self {
         AccruedSubstate {
             self_destruct_accounts: HashSet::new(),
@@ -35,6 +38,7 @@ impl AccruedSubstate {
             refund_balance: U256::zero(),
             accessed_accounts: HashSet::new(),
             accessed_storage: HashSet::new(),
+            created_accounts: HashSet::new(),
         }
     }
 
@@ -67,4 +71,56 @@ impl AccruedSubstate {
     pub fn accessed_storage(&self) -> &HashSet<(Address, U256)> {
         &self.accessed_storage
     }
+
+    /// Returns the created account set.
+    pub fn created_accounts(&self) -> &HashSet<Address> {
+        &self.created_accounts
+    }
+
+    /// Adds an account to the self-destruct set.
+    pub fn add_self_destruct(&mut self, address: Address) {
+        self.self_destruct_accounts.insert(address);
+    }
+
+    /// Appends a log to the log series.
+    pub fn add_log(&mut self, log: Log) {
+        self.log_series.push(log);
+    }
+
+    /// Adds an account to the touched account set.
+    pub fn touch(&mut self, address: Address) {
+        self.touched_accounts.insert(address);
+    }
+
+    /// Increases the refund balance by `amount`.
+    pub fn add_refund(&mut self, amount: U256) {
+        self.refund_balance = self.refund_balance.saturating_add(amount);
+    }
+
+    /// Decreases the refund balance by `amount`.
+    pub fn sub_refund(&mut self, amount: U256) {
+        self.refund_balance = self.refund_balance.saturating_sub(amount);
+    }
+
+    /// Adds an account to the accessed account set. Returns whether the account was already
+    /// present.
+    pub fn access_account(&mut self, address: Address) -> bool {
+        !self.accessed_accounts.insert(address)
+    }
+
+    /// Adds a storage slot to the accessed storage set. Returns whether the slot was already
+    /// present.
+    pub fn access_storage(&mut self, address: Address, key: U256) -> bool {
+        !self.accessed_storage.insert((address, key))
+    }
+
+    /// Adds an account to the created account set.
+    pub fn add_created(&mut self, address: Address) {
+        self.created_accounts.insert(address);
+    }
+
+    /// Takes the log series, leaving it empty.
+    pub fn take_logs(&mut self) -> Vec<Log> {
+        std::mem::take(&mut self.log_series)
+    }
 }
@@ -0,0 +1,58 @@
+use ethereum_types::{Address, H160, H256, U256};
+use std::collections::HashMap;
+
+use super::interpreter::{Interpreter, Message, Outcome};
+use super::substate::AccruedSubstate;
+use crate::core::fork::Fork;
+use crate::core::state::WorldState;
+use crate::core::types::block::BlockHeader;
+
+/// Sender of the messages of tests, and origin of their transactions.
+pub(crate) const CALLER: Address = H160([0xaa; 20]);
+
+/// The block tests execute messages in, which has a default header and no known block hashes.
+#[derive(Default)]
+pub(crate) struct TestBlock {
+    header: BlockHeader,
+    block_hashes: HashMap<U256, H256>,
+}
+
+impl TestBlock {
+    /// Returns an interpreter for a transaction sent by `CALLER` without fees or blobs, under
+    /// the rules of `fork`.
+    pub(crate) fn interpreter<'a>(
+        &'a self,
+        state: &'a mut WorldState,
+        fork: Fork,
+    ) -> Interpreter<'a> {
+        Interpreter::new(
+            state,
+            &self.header,
+            &self.block_hashes,
+            fork,
+            U256::one(),
+            CALLER,
+            U256::zero(),
+            Vec::new(),
+        )
+    }
+}
+
+/// Returns a message call from `CALLER` to `target` with `input` and `gas`, transferring no
+/// value.
+pub(crate) fn call(target: Address, input: Vec<u8>, gas: u64) -> Message {
+    Message::call(CALLER, target, U256::zero(), input, U256::from(gas))
+}
+
+/// Executes `message` against `state` under the rules of `fork`, returning its outcome and the
+/// accrued substate.
+pub(crate) fn execute(
+    state: &mut WorldState,
+    fork: Fork,
+    message: Message,
+) -> (Outcome, AccruedSubstate) {
+    let block = TestBlock::default();
+    let mut interpreter = block.interpreter(state, fork);
+    let outcome = interpreter.execute(message);
+    (outcome, interpreter.into_substate())
+}
@@ -0,0 +1,422 @@
+use super::{field, fixture_files, object_field, parse_network, read_fixture};
+use super::{CaseResult, CaseStatus, FixtureError};
+use crate::core::consensus::difficulty::validate_difficulty;
+use crate::core::consensus::header::validate_header;
+use crate::core::executor::block::{execute_block, validate_body};
+use crate::core::fork::Fork;
+use crate::core::genesis::{alloc_state, optional_hash, parse_alloc, parse_bytes};
+use crate::core::spec::ChainSpec;
+use crate::core::state::WorldState;
+use crate::core::types::block::{Block, BlockHeader};
+use ethereum_types::{H256, U256};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Number of generations back that an ommer may branch off the chain.
+const MAX_OMMER_DEPTH: usize = 6;
+
+/// A block imported into the chain of a test, with the state following it.
+struct ImportedBlock {
+    block: Block,
+    state: WorldState,
+    total_difficulty: U256,
+}
+
+/// The blocks of a test by hash.
+type Store = HashMap<H256, ImportedBlock>;
+
+/// Runs the blockchain test fixtures under `dir`, such as the `BlockchainTests` directory of
+/// ethereum/tests.
+pub fn run_blockchain_tests(dir: &Path) -> Result<Vec<CaseResult>, FixtureError> {
+    let mut results = Vec::new();
+    for path in fixture_files(dir)? {
+        results.extend(run_blockchain_test_file(&path)?);
+    }
+    Ok(results)
+}
+
+/// Runs the blockchain tests of the fixture at `path`.
+pub fn run_blockchain_test_file(path: &Path) -> Result<Vec<CaseResult>, FixtureError> {
+    let mut results = Vec::new();
+    for (name, test) in read_fixture(path)? {
+        results.push(run_blockchain_test(&name, &test)?);
+    }
+    Ok(results)
+}
+
+/// Runs the blockchain test with `name`: imports its blocks on top of its genesis block, and
+/// checks the resulting head and state.
+pub fn run_blockchain_test(name: &str, test: &Value) -> Result<CaseResult, FixtureError> {
+    let test = test
+        .as_object()
+        .ok_or_else(|| FixtureError::InvalidField(String::from(name)))?;
+    let status = run(test)?;
+    Ok(CaseResult::new(String::from(name), status))
+}
+
+fn run(test: &Map<String, Value>) -> Result<CaseStatus, FixtureError> {
+    let network = field(test, "network")?
+        .as_str()
+        .ok_or_else(|| FixtureError::InvalidField(String::from("network")))?;
+    let spec = match parse_network(network) {
+        Some(spec) => spec,
+        None => {
+            return Ok(CaseStatus::Skipped(format!(
+                "unsupported network {}",
+                network
+            )))
+        }
+    };
+    if spec.activation(Fork::Dao).is_some() {
+        let reason = String::from("the DAO fork state change is not implemented");
+        return Ok(CaseStatus::Skipped(reason));
+    }
+
+    let genesis = decode_block(field(test, "genesisRLP")?)?
+        .map_err(|_| FixtureError::InvalidField(String::from("genesisRLP")))?;
+    let genesis_header = object_field(test, "genesisBlockHeader")?;
+    if optional_hash(genesis_header, "hash")? != Some(genesis.hash()) {
+        return Ok(CaseStatus::Failed(String::from("genesis hash mismatch")));
+    }
+    let state = alloc_state(&parse_alloc(field(test, "pre")?)?);
+    if &state.state_root() != genesis.header().state_root() {
+        return Ok(CaseStatus::Failed(String::from(
+            "genesis state root mismatch",
+        )));
+    }
+
+    let mut head = genesis.hash();
+    let mut store = Store::new();
+    store.insert(
+        head,
+        ImportedBlock {
+            total_difficulty: genesis.header().difficulty(),
+            block: genesis,
+            state,
+        },
+    );
+
+    let blocks = field(test, "blocks")?
+        .as_array()
+        .ok_or_else(|| FixtureError::InvalidField(String::from("blocks")))?;
+    for (i, entry) in blocks.iter().enumerate() {
+        let entry = entry
+            .as_object()
+            .ok_or_else(|| FixtureError::InvalidField(String::from("blocks")))?;
+        let exception = entry.get("expectException").and_then(Value::as_str);
+
+        let imported = match decode_block(field(entry, "rlp")?)? {
+            Ok(block) => import(&store, &spec, block),
+            Err(err) => Err(err),
+        };
+        let imported = match (imported, exception) {
+            (Ok(_), Some(exception)) => {
+                let reason = format!("block {}: expected exception {}", i, exception);
+                return Ok(CaseStatus::Failed(reason));
+            }
+            (Err(err), None) => {
+                return Ok(CaseStatus::Failed(format!("block {}: {}", i, err)));
+            }
+            (Err(_), Some(_)) => continue,
+            (Ok(imported), None) => imported,
+        };
+
+        // Proof-of-work chains follow the most total difficulty, and proof-of-stake chains the
+        // latest block, as the fixtures do not model the beacon chain.
+        let hash = imported.block.hash();
+        let header = imported.block.header();
+        if spec.fork(header) >= Fork::Paris
+            || imported.total_difficulty > store[&head].total_difficulty
+        {
+            head = hash;
+        }
+        store.insert(hash, imported);
+    }
+
+    let expected_head = optional_hash(test, "lastblockhash")?
+        .ok_or_else(|| FixtureError::InvalidField(String::from("lastblockhash")))?;
+    if head != expected_head {
+        let reason = format!("head {:?}, expected {:?}", head, expected_head);
+        return Ok(CaseStatus::Failed(reason));
+    }
+
+    let expected_root = match test.get("postState") {
+        Some(post) => alloc_state(&parse_alloc(post)?).state_root(),
+        None => optional_hash(test, "postStateHash")?
+            .ok_or_else(|| FixtureError::InvalidField(String::from("postState")))?,
+    };
+    let root = store[&head].state.state_root();
+    if root != expected_root {
+        let reason = format!("state root {:?}, expected {:?}", root, expected_root);
+        return Ok(CaseStatus::Failed(reason));
+    }
+
+    Ok(CaseStatus::Passed)
+}
+
+/// Decodes the block given as a hexadecimal string of its RLP encoding. A block that cannot be
+/// decoded is invalid, which some fixtures expect.
+fn decode_block(value: &Value) -> Result<Result<Block, String>, FixtureError> {
+    let rlp = value
+        .as_str()
+        .ok_or_else(|| FixtureError::InvalidField(String::from("rlp")))?;
+    let bytes = parse_bytes(rlp, "rlp")?;
+    Ok(rlp::decode(&bytes).map_err(|err| format!("{:?}", err)))
+}
+
+/// Validates and executes `block` on top of its parent in `store`, returning the reason if it
+/// is invalid.
+fn import(store: &Store, spec: &ChainSpec, block: Block) -> Result<ImportedBlock, String> {
+    let header = block.header();
+    let parent = store
+        .get(header.parent_hash())
+        .ok_or_else(|| String::from("unknown parent"))?;
+    let fork = spec.fork(header);
+
+    validate(header, parent.block.header(), fork, spec)?;
+    validate_body(&block).map_err(|err| format!("{:?}", err))?;
+    validate_ommers(store, spec, &block)?;
+
+    let block_hashes = ancestor_hashes(store, header);
+    let mut state = parent.state.clone();
+    let outcome = execute_block(&mut state, &block, &block_hashes, spec)
+        .map_err(|err| format!("{:?}", err))?;
+    outcome
+        .validate(header)
+        .map_err(|err| format!("{:?}", err))?;
+
+    Ok(ImportedBlock {
+        total_difficulty: parent.total_difficulty + header.difficulty(),
+        block,
+        state,
+    })
+}
+
+/// Checks `header` against its `parent` under the rules of `fork` on the chain of `spec`,
+/// including its difficulty before the merge.
+fn validate(
+    header: &BlockHeader,
+    parent: &BlockHeader,
+    fork: Fork,
+    spec: &ChainSpec,
+) -> Result<(), String> {
+    validate_header(header, parent, fork, spec.blob_params(fork))
+        .map_err(|err| format!("{:?}", err))?;
+    if fork < Fork::Paris {
+        validate_difficulty(header, parent, fork).map_err(|err| format!("{:?}", err))?;
+    }
+    Ok(())
+}
+
+/// Checks that each ommer of `block` is a valid header branching off one of its recent
+/// ancestors, and is neither an ancestor nor an ommer of one.
+fn validate_ommers(store: &Store, spec: &ChainSpec, block: &Block) -> Result<(), String> {
+    if block.ommers().is_empty() {
+        return Ok(());
+    }
+
+    // The ancestors that an ommer may be a child of, and the blocks an ommer may not be.
+    let mut ancestors = HashMap::new();
+    let mut excluded = HashSet::new();
+    let mut hash = *block.header().parent_hash();
+    for _ in 0..=MAX_OMMER_DEPTH {
+        let ancestor = match store.get(&hash) {
+            Some(ancestor) => &ancestor.block,
+            None => break,
+        };
+        ancestors.insert(hash, ancestor.header());
+        excluded.insert(hash);
+        excluded.extend(ancestor.ommers().iter().map(BlockHeader::hash));
+        hash = *ancestor.header().parent_hash();
+    }
+
+    for ommer in block.ommers() {
+        let hash = ommer.hash();
+        if !excluded.insert(hash) {
+            return Err(String::from("ommer already included"));
+        }
+        let parent = match ancestors.get(ommer.parent_hash()) {
+            Some(parent) if ommer.parent_hash() != block.header().parent_hash() => parent,
+            _ => return Err(String::from("ommer is not a child of a recent ancestor")),
+        };
+        validate(ommer, parent, spec.fork(ommer), spec).map_err(|err| format!("ommer: {}", err))?;
+    }
+
+    Ok(())
+}
+
+/// Returns the hashes of the 256 most recent ancestors of `header` in `store`, by number.
+fn ancestor_hashes(store: &Store, header: &BlockHeader) -> HashMap<U256, H256> {
+    let mut hashes = HashMap::new();
+    let mut hash = *header.parent_hash();
+    while hashes.len() < 256 {
+        let ancestor = match store.get(&hash) {
+            Some(ancestor) => ancestor.block.header(),
+            None => break,
+        };
+        hashes.insert(ancestor.number(), hash);
+        hash = *ancestor.parent_hash();
+    }
+    hashes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::consensus::header::{calculate_base_fee, INITIAL_BASE_FEE};
+    use crate::core::crypto;
+    use crate::core::spec::Activation;
+    use crate::core::trie;
+    use crate::core::types::transaction::{Transaction, TransactionType};
+    use ethereum_types::Address;
+    use keccak_hash::{KECCAK_EMPTY_LIST_RLP, KECCAK_NULL_RLP};
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    fn secret() -> H256 {
+        H256::from_str("45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8").unwrap()
+    }
+
+    fn pre_state() -> WorldState {
+        let sender = crypto::secret_to_address(&secret()).unwrap();
+        let mut state = WorldState::new();
+        state.insert_account(sender, U256::zero(), U256::exp10(18), Vec::new(), None);
+        state
+    }
+
+    /// Returns a Shanghai genesis block and a child holding a transfer, sealed with the roots
+    /// that follow from executing it.
+    fn chain() -> (Block, Block) {
+        let mut header = BlockHeader::default();
+        header.set_gas_limit(U256::from(30_000_000));
+        header.set_base_fee_per_gas(Some(U256::from(INITIAL_BASE_FEE)));
+        header.set_withdrawals_root(Some(KECCAK_NULL_RLP));
+        header.set_ommers_hash(KECCAK_EMPTY_LIST_RLP);
+        header.set_transactions_root(KECCAK_NULL_RLP);
+        header.set_receipts_root(KECCAK_NULL_RLP);
+        header.set_state_root(pre_state().state_root());
+        let genesis = Block::new(header, Vec::new(), Vec::new(), Some(Vec::new()));
+
+        let mut tx = Transaction::new(
+            TransactionType::DynamicFee,
+            Some(U256::one()),
+            U256::zero(),
+            U256::from(21_000),
+            Some(Address::repeat_byte(0x22)),
+            U256::from(1000),
+            Vec::new(),
+        );
+        tx.set_gas_price(U256::from(2 * INITIAL_BASE_FEE));
+        tx.set_max_priority_fee_per_gas(U256::one());
+        tx.sign(&secret());
+
+        let mut header = genesis.header().clone();
+        header.set_parent_hash(genesis.hash());
+        header.set_number(U256::one());
+        header.set_timestamp(U256::from(12));
+        header.set_beneficiary(Address::repeat_byte(0xcc));
+        header.set_base_fee_per_gas(Some(calculate_base_fee(genesis.header()).unwrap()));
+        header.set_transactions_root(trie::ordered_trie_root([tx.encode()]));
+        let block = Block::new(header.clone(), Vec::new(), vec![tx], Some(Vec::new()));
+
+        let mut shanghai = ChainSpec::new(U256::one());
+        shanghai.set_fork(Fork::Shanghai, Activation::Timestamp(0));
+        let mut state = pre_state();
+        let outcome = execute_block(&mut state, &block, &HashMap::new(), &shanghai).unwrap();
+        header.set_gas_used(outcome.gas_used());
+        header.set_logs_bloom(*outcome.logs_bloom());
+        header.set_receipts_root(*outcome.receipts_root());
+        header.set_state_root(*outcome.state_root());
+        let block = Block::new(
+            header,
+            Vec::new(),
+            block.transactions().clone(),
+            Some(Vec::new()),
+        );
+
+        (genesis, block)
+    }
+
+    fn fixture(genesis: &Block, blocks: &[(&Block, bool)], head: H256, root: H256) -> Value {
+        let sender = crypto::secret_to_address(&secret()).unwrap();
+        let blocks: Vec<_> = blocks
+            .iter()
+            .map(|(block, valid)| {
+                let mut entry = serde_json::json!({
+                    "rlp": format!("0x{}", hex::encode(rlp::encode(*block))),
+                });
+                if !valid {
+                    entry["expectException"] = Value::from("InvalidStateRoot");
+                }
+                entry
+            })
+            .collect();
+        serde_json::json!({
+            "network": "Shanghai",
+            "genesisRLP": format!("0x{}", hex::encode(rlp::encode(genesis))),
+            "genesisBlockHeader": { "hash": format!("{:?}", genesis.hash()) },
+            "pre": { format!("{:?}", sender): { "balance": "0x0de0b6b3a7640000" } },
+            "blocks": blocks,
+            "lastblockhash": format!("{:?}", head),
+            "postStateHash": format!("{:?}", root),
+        })
+    }
+
+    #[test]
+    fn test_run_blockchain_test() {
+        let (genesis, block) = chain();
+        let test = fixture(
+            &genesis,
+            &[(&block, true)],
+            block.hash(),
+            *block.header().state_root(),
+        );
+        let result = run_blockchain_test("transfer", &test).unwrap();
+        assert_eq!(result.status(), &CaseStatus::Passed);
+
+        // The head must be the last block.
+        let test = fixture(
+            &genesis,
+            &[(&block, true)],
+            genesis.hash(),
+            *block.header().state_root(),
+        );
+        assert!(run_blockchain_test("transfer", &test).unwrap().is_failure());
+    }
+
+    #[test]
+    fn test_run_blockchain_test_invalid_block() {
+        let (genesis, block) = chain();
+        let mut header = block.header().clone();
+        header.set_state_root(H256::zero());
+        let invalid = Block::new(
+            header,
+            Vec::new(),
+            block.transactions().clone(),
+            Some(Vec::new()),
+        );
+
+        // An invalid block that is expected to be invalid leaves the head in place.
+        let root = *genesis.header().state_root();
+        let test = fixture(&genesis, &[(&invalid, false)], genesis.hash(), root);
+        let result = run_blockchain_test("invalid", &test).unwrap();
+        assert_eq!(result.status(), &CaseStatus::Passed);
+
+        let test = fixture(&genesis, &[(&invalid, true)], genesis.hash(), root);
+        assert!(run_blockchain_test("invalid", &test).unwrap().is_failure());
+    }
+
+    /// Runs the `BlockchainTests` of the ethereum/tests checkout at `$ETHEREUM_TESTS`.
+    #[test]
+    #[ignore = "requires the ethereum/tests fixtures"]
+    fn test_blockchain_tests() {
+        let dir = std::env::var("ETHEREUM_TESTS").expect("ETHEREUM_TESTS is not set");
+        let results = run_blockchain_tests(&PathBuf::from(dir).join("BlockchainTests")).unwrap();
+        let failures: Vec<_> = results
+            .iter()
+            .filter(|result| result.is_failure())
+            .collect();
+        assert!(failures.is_empty(), "{:#?}", failures);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::core::fork::Fork;
+use crate::core::genesis::GenesisError;
+use crate::core::spec::{Activation, ChainSpec};
+use crate::core::types::log::Log;
+use ethereum_types::{H256, U256};
+use keccak_hash::keccak;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub mod blockchain;
+pub mod rlp;
+pub mod state;
+pub mod transaction;
+pub mod trie;
+
+/// A reason for a fixture to be unreadable.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FixtureError {
+    /// The fixture could not be read from disk.
+    Io(String),
+    /// The fixture is not valid JSON.
+    Json(String),
+    /// The field with the given name is missing or has an invalid value.
+    InvalidField(String),
+}
+
+impl From<GenesisError> for FixtureError {
+    fn from(err: GenesisError) -> Self {
+        match err {
+            GenesisError::Json(msg) => FixtureError::Json(msg),
+            GenesisError::InvalidField(name) => FixtureError::InvalidField(name),
+        }
+    }
+}
+
+/// The status of a test case.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CaseStatus {
+    Passed,
+    /// The case failed for the given reason.
+    Failed(String),
+    /// The case was not run for the given reason, such as an unsupported fork.
+    Skipped(String),
+}
+
+/// The result of a test case of a fixture.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaseResult {
+    name: String,
+    status: CaseStatus,
+}
+
+impl CaseResult {
+    /// Returns a new result for the case with `name`.
+    pub fn new(name: String, status: CaseStatus) -> Self {
+        Self { name, status }
+    }
+
+    /// Returns the name of the case, qualified by the test and fork it belongs to.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn status(&self) -> &CaseStatus {
+        &self.status
+    }
+
+    /// Returns whether the case failed.
+    pub fn is_failure(&self) -> bool {
+        matches!(self.status, CaseStatus::Failed(_))
+    }
+}
+
+/// Returns the fork with `name` in the ethereum/tests fixtures, if supported.
+pub fn parse_fork(name: &str) -> Option<Fork> {
+    let fork = match name {
+        "Frontier" => Fork::Frontier,
+        "Homestead" => Fork::Homestead,
+        "EIP150" => Fork::TangerineWhistle,
+        "EIP158" => Fork::SpuriousDragon,
+        "Byzantium" => Fork::Byzantium,
+        "Constantinople" => Fork::Constantinople,
+        "ConstantinopleFix" | "Petersburg" => Fork::Petersburg,
+        "Istanbul" => Fork::Istanbul,
+        "MuirGlacier" => Fork::MuirGlacier,
+        "Berlin" => Fork::Berlin,
+        "London" => Fork::London,
+        "ArrowGlacier" => Fork::ArrowGlacier,
+        "GrayGlacier" => Fork::GrayGlacier,
+        "Merge" | "Paris" => Fork::Paris,
+        "Shanghai" => Fork::Shanghai,
+        "Cancun" => Fork::Cancun,
+        _ => return None,
+    };
+    Some(fork)
+}
+
+/// Forks supported by the fixtures, in order, excluding the DAO fork.
+const FORKS: [Fork; 16] = [
+    Fork::Frontier,
+    Fork::Homestead,
+    Fork::TangerineWhistle,
+    Fork::SpuriousDragon,
+    Fork::Byzantium,
+    Fork::Constantinople,
+    Fork::Petersburg,
+    Fork::Istanbul,
+    Fork::MuirGlacier,
+    Fork::Berlin,
+    Fork::London,
+    Fork::ArrowGlacier,
+    Fork::GrayGlacier,
+    Fork::Paris,
+    Fork::Shanghai,
+    Fork::Cancun,
+];
+
+/// Returns the chain specification of the `network` of a blockchain test fixture, if supported.
+///
+/// A network is either a fork, active from genesis, or a transition such as
+/// `BerlinToLondonAt5`, `ShanghaiToCancunAtTime15k` or `ArrowGlacierToMergeAtDiffC0000`.
+pub fn parse_network(network: &str) -> Option<ChainSpec> {
+    let mut spec = ChainSpec::new(U256::one());
+    let (base, transition) = match network.find("To") {
+        Some(i) => (&network[..i], Some(&network[i + 2..])),
+        None => (network, None),
+    };
+
+    let base = parse_fork(base)?;
+    for fork in FORKS.iter().copied().filter(|fork| *fork <= base) {
+        let activation = if fork >= Fork::Shanghai {
+            Activation::Timestamp(0)
+        } else {
+            Activation::Block(0)
+        };
+        spec.set_fork(fork, activation);
+    }
+    if base >= Fork::Paris {
+        spec.set_terminal_total_difficulty(Some(U256::zero()));
+    }
+
+    if let Some(transition) = transition {
+        let (name, condition) = transition.split_at(transition.find("At")?);
+        let fork = match name {
+            "Dao" => Fork::Dao,
+            name => parse_fork(name)?,
+        };
+        match condition {
+            "At5" => spec.set_fork(fork, Activation::Block(5)),
+            "AtTime15k" => spec.set_fork(fork, Activation::Timestamp(15_000)),
+            "AtDiffC0000" if fork == Fork::Paris => {
+                spec.set_terminal_total_difficulty(Some(U256::from(0xc0000)));
+            }
+            _ => return None,
+        }
+    }
+
+    Some(spec)
+}
+
+/// Returns the hash of the RLP encoding of `logs`, which the fixtures commit to.
+pub fn logs_hash(logs: &[Log]) -> H256 {
+    keccak(::rlp::encode_list::<Log, _>(logs))
+}
+
+/// Returns the paths of the JSON files under `dir`, recursively, in order.
+pub fn fixture_files(dir: &Path) -> Result<Vec<PathBuf>, FixtureError> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(&dir).map_err(|err| FixtureError::Io(err.to_string()))?;
+        for entry in entries {
+            let path = entry
+                .map_err(|err| FixtureError::Io(err.to_string()))?
+                .path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "json") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Returns the tests of the fixture at `path`, by name.
+pub(crate) fn read_fixture(path: &Path) -> Result<Map<String, Value>, FixtureError> {
+    let json = fs::read_to_string(path).map_err(|err| FixtureError::Io(err.to_string()))?;
+    match serde_json::from_str(&json) {
+        Ok(Value::Object(tests)) => Ok(tests),
+        Ok(_) => Err(FixtureError::Json(String::from("expected an object"))),
+        Err(err) => Err(FixtureError::Json(err.to_string())),
+    }
+}
+
+/// Returns the field of `object` with `name`.
+pub(crate) fn field<'a>(
+    object: &'a Map<String, Value>,
+    name: &str,
+) -> Result<&'a Value, FixtureError> {
+    object
+        .get(name)
+        .ok_or_else(|| FixtureError::InvalidField(String::from(name)))
+}
+
+/// Returns the object in the field of `object` with `name`.
+pub(crate) fn object_field<'a>(
+    object: &'a Map<String, Value>,
+    name: &str,
+) -> Result<&'a Map<String, Value>, FixtureError> {
+    field(object, name)?
+        .as_object()
+        .ok_or_else(|| FixtureError::InvalidField(String::from(name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::block::BlockHeader;
+
+    #[test]
+    fn test_parse_network() {
+        let spec = parse_network("BerlinToLondonAt5").unwrap();
+        let header = |number: u64| {
+            let mut header = BlockHeader::default();
+            header.set_number(U256::from(number));
+            header.set_difficulty(U256::one());
+            header
+        };
+        assert_eq!(spec.fork(&header(4)), Fork::Berlin);
+        assert_eq!(spec.fork(&header(5)), Fork::London);
+
+        let spec = parse_network("ShanghaiToCancunAtTime15k").unwrap();
+        let mut late = header(1);
+        late.set_timestamp(U256::from(15_000));
+        assert_eq!(spec.fork(&header(1)), Fork::Shanghai);
+        assert_eq!(spec.fork(&late), Fork::Cancun);
+
+        assert!(parse_network("Prague").is_none());
+    }
+}
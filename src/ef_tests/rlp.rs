@@ -0,0 +1,193 @@
+use super::{field, fixture_files, read_fixture, CaseResult, CaseStatus, FixtureError};
+use crate::core::genesis::parse_bytes;
+use num_bigint::BigUint;
+use rlp::{Rlp, RlpStream};
+use serde_json::Value;
+use std::path::Path;
+
+/// Runs the RLP test fixtures under `dir`, such as the `RLPTests` directory of ethereum/tests.
+pub fn run_rlp_tests(dir: &Path) -> Result<Vec<CaseResult>, FixtureError> {
+    let mut results = Vec::new();
+    for path in fixture_files(dir)? {
+        for (name, test) in read_fixture(&path)? {
+            results.push(run_rlp_test(&name, &test)?);
+        }
+    }
+    Ok(results)
+}
+
+/// Runs the RLP test with `name`.
+///
+/// The input of a valid case is encoded and compared with the expected output. The output of a
+/// case with input `"INVALID"` must fail to decode, and that of a case with input `"VALID"` must
+/// decode.
+pub fn run_rlp_test(name: &str, test: &Value) -> Result<CaseResult, FixtureError> {
+    let test = test
+        .as_object()
+        .ok_or_else(|| FixtureError::InvalidField(String::from(name)))?;
+    let input = field(test, "in")?;
+    let output = field(test, "out")?
+        .as_str()
+        .ok_or_else(|| FixtureError::InvalidField(String::from("out")))?;
+
+    let status = match (input.as_str(), parse_bytes(output, "out")) {
+        (Some("INVALID"), Ok(bytes)) => match validate(&bytes) {
+            Ok(()) => CaseStatus::Failed(String::from("decoded invalid RLP")),
+            Err(_) => CaseStatus::Passed,
+        },
+        // Some invalid outputs are not even valid hexadecimal.
+        (Some("INVALID"), Err(_)) => CaseStatus::Passed,
+        (Some("VALID"), bytes) => match validate(&bytes?) {
+            Ok(()) => CaseStatus::Passed,
+            Err(err) => CaseStatus::Failed(format!("failed to decode: {:?}", err)),
+        },
+        (_, bytes) => {
+            let expected = bytes?;
+            let mut s = RlpStream::new();
+            append(&mut s, input)?;
+            let encoded = s.out().to_vec();
+            if encoded != expected {
+                CaseStatus::Failed(format!("encoded {:02x?}", encoded))
+            } else if let Err(err) = validate(&encoded) {
+                CaseStatus::Failed(format!("failed to decode: {:?}", err))
+            } else {
+                CaseStatus::Passed
+            }
+        }
+    };
+    Ok(CaseResult::new(String::from(name), status))
+}
+
+/// Appends the RLP encoding of a fixture input to `s`: strings as their bytes, integers and
+/// `#`-prefixed decimal strings as big-endian integers, and arrays as lists.
+fn append(s: &mut RlpStream, input: &Value) -> Result<(), FixtureError> {
+    let invalid = || FixtureError::InvalidField(String::from("in"));
+    match input {
+        Value::String(string) => match string.strip_prefix('#') {
+            Some(digits) => {
+                let number = BigUint::parse_bytes(digits.as_bytes(), 10).ok_or_else(invalid)?;
+                append_integer(s, &number);
+            }
+            None => {
+                s.append(&string.as_bytes());
+            }
+        },
+        Value::Number(number) => {
+            let number =
+                BigUint::parse_bytes(number.to_string().as_bytes(), 10).ok_or_else(invalid)?;
+            append_integer(s, &number);
+        }
+        Value::Array(items) => {
+            s.begin_list(items.len());
+            for item in items {
+                append(s, item)?;
+            }
+        }
+        _ => return Err(invalid()),
+    }
+    Ok(())
+}
+
+/// Appends `number` as a big-endian byte string without leading zeros.
+fn append_integer(s: &mut RlpStream, number: &BigUint) {
+    let bytes = if number.bits() == 0 {
+        Vec::new()
+    } else {
+        number.to_bytes_be()
+    };
+    s.append(&bytes);
+}
+
+/// Checks that `bytes` is exactly one canonical RLP item, recursively.
+fn validate(bytes: &[u8]) -> Result<(), rlp::DecoderError> {
+    let rlp = Rlp::new(bytes);
+    let info = rlp.payload_info()?;
+    if info.header_len + info.value_len != bytes.len() {
+        return Err(rlp::DecoderError::RlpInconsistentLengthAndData);
+    }
+    validate_item(&rlp)
+}
+
+fn validate_item(rlp: &Rlp) -> Result<(), rlp::DecoderError> {
+    if rlp.is_list() {
+        for item in rlp.iter() {
+            validate_item(&item)?;
+        }
+        // Iteration stops at the first malformed item, so the items must fill the payload.
+        let info = rlp.payload_info()?;
+        let items: usize = rlp.iter().map(|item| item.as_raw().len()).sum();
+        if items != info.value_len {
+            return Err(rlp::DecoderError::RlpInconsistentLengthAndData);
+        }
+    } else {
+        // Unlike `data`, this rejects a single byte below 0x80 with a header.
+        rlp.decoder().decode_value(|_| Ok(()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn run(input: Value, output: &str) -> CaseStatus {
+        let test = serde_json::json!({ "in": input, "out": output });
+        run_rlp_test("test", &test).unwrap().status().clone()
+    }
+
+    #[test]
+    fn test_run_rlp_test() {
+        assert_eq!(run(Value::from(""), "0x80"), CaseStatus::Passed);
+        assert_eq!(run(Value::from("dog"), "0x83646f67"), CaseStatus::Passed);
+        assert_eq!(run(Value::from(1024), "0x820400"), CaseStatus::Passed);
+        assert_eq!(
+            run(
+                Value::from("#115792089237316195423570985008687907853269984665640564039457584007913129639936"),
+                "0xa1010000000000000000000000000000000000000000000000000000000000000000"
+            ),
+            CaseStatus::Passed
+        );
+        assert_eq!(
+            run(serde_json::json!([["cat"], []]), "0xc6c483636174c0"),
+            CaseStatus::Passed
+        );
+        assert!(matches!(
+            run(Value::from("dog"), "0x83646f68"),
+            CaseStatus::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn test_run_rlp_test_invalid() {
+        // A single byte below 0x80 must be encoded as itself.
+        assert_eq!(run(Value::from("INVALID"), "0x8100"), CaseStatus::Passed);
+        // The last item of the list overruns its payload.
+        assert_eq!(
+            run(Value::from("INVALID"), "0xc4826162ff"),
+            CaseStatus::Passed
+        );
+        // Trailing bytes follow the item.
+        assert_eq!(run(Value::from("INVALID"), "0x8000"), CaseStatus::Passed);
+        // The long form of the header is used for a short string.
+        assert_eq!(run(Value::from("INVALID"), "0xb80100"), CaseStatus::Passed);
+        assert!(matches!(
+            run(Value::from("INVALID"), "0x80"),
+            CaseStatus::Failed(_)
+        ));
+        assert_eq!(run(Value::from("VALID"), "0xc0"), CaseStatus::Passed);
+    }
+
+    /// Runs the `RLPTests` of the ethereum/tests checkout at `$ETHEREUM_TESTS`.
+    #[test]
+    #[ignore = "requires the ethereum/tests fixtures"]
+    fn test_rlp_tests() {
+        let dir = std::env::var("ETHEREUM_TESTS").expect("ETHEREUM_TESTS is not set");
+        let results = run_rlp_tests(&PathBuf::from(dir).join("RLPTests")).unwrap();
+        let failures: Vec<_> = results
+            .iter()
+            .filter(|result| result.is_failure())
+            .collect();
+        assert!(failures.is_empty(), "{:#?}", failures);
+    }
+}
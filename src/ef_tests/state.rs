@@ -0,0 +1,412 @@
+use super::{field, logs_hash, object_field, parse_fork, read_fixture};
+use super::{fixture_files, CaseResult, CaseStatus, FixtureError};
+use crate::core::executor::transaction::execute_transaction;
+use crate::core::fork::Fork;
+use crate::core::genesis::{
+    alloc_state, optional_bytes, optional_hash, optional_number, parse_address, parse_alloc,
+    parse_bytes, parse_number,
+};
+use crate::core::spec::ChainSpec;
+use crate::core::types::block::BlockHeader;
+use crate::core::types::transaction::{AccessListItem, Transaction, TransactionType};
+use crate::core::vm::env::BlockHashes;
+use ethereum_types::{BigEndianHash, H256, U256};
+use keccak_hash::keccak;
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// Chain identifier of the fixtures.
+pub const CHAIN_ID: u64 = 1;
+/// Base fee of the fixtures that activate London without giving one.
+pub const DEFAULT_BASE_FEE: u64 = 0x0a;
+
+/// The block hashes of the state test fixtures: the hash of the decimal representation of the
+/// block number.
+pub struct TestBlockHashes;
+
+impl BlockHashes for TestBlockHashes {
+    fn block_hash(&self, number: U256) -> Option<H256> {
+        Some(keccak(number.to_string()))
+    }
+}
+
+/// Runs the state test fixtures under `dir`, such as the `GeneralStateTests` directory of
+/// ethereum/tests.
+pub fn run_state_tests(dir: &Path) -> Result<Vec<CaseResult>, FixtureError> {
+    let mut results = Vec::new();
+    for path in fixture_files(dir)? {
+        results.extend(run_state_test_file(&path)?);
+    }
+    Ok(results)
+}
+
+/// Runs the state tests of the fixture at `path`.
+pub fn run_state_test_file(path: &Path) -> Result<Vec<CaseResult>, FixtureError> {
+    let mut results = Vec::new();
+    for (name, test) in read_fixture(path)? {
+        results.extend(run_state_test(&name, &test)?);
+    }
+    Ok(results)
+}
+
+/// Runs the state test with `name`: each post-state of each fork is a case.
+pub fn run_state_test(name: &str, test: &Value) -> Result<Vec<CaseResult>, FixtureError> {
+    let test = test
+        .as_object()
+        .ok_or_else(|| FixtureError::InvalidField(String::from(name)))?;
+    let pre = parse_alloc(field(test, "pre")?)?;
+    let env = object_field(test, "env")?;
+    let transaction = object_field(test, "transaction")?;
+
+    let mut results = Vec::new();
+    for (fork_name, posts) in object_field(test, "post")? {
+        let posts = posts
+            .as_array()
+            .ok_or_else(|| FixtureError::InvalidField(String::from("post")))?;
+        for post in posts {
+            let post = post
+                .as_object()
+                .ok_or_else(|| FixtureError::InvalidField(String::from("post")))?;
+            let indexes = object_field(post, "indexes")?;
+            let index = |name: &str| -> Result<usize, FixtureError> {
+                let value = field(indexes, name)?;
+                Ok(parse_number(value, name)?.low_u64() as usize)
+            };
+            let (data, gas, value) = (index("data")?, index("gas")?, index("value")?);
+            let case = format!("{}/{}/d{}g{}v{}", name, fork_name, data, gas, value);
+
+            let fork = match parse_fork(fork_name) {
+                Some(fork) => fork,
+                None => {
+                    let reason = format!("unsupported fork {}", fork_name);
+                    results.push(CaseResult::new(case, CaseStatus::Skipped(reason)));
+                    continue;
+                }
+            };
+
+            let header = parse_env(env, fork)?;
+            let tx = parse_transaction(transaction, data, gas, value)?;
+            let expected_root = optional_hash(post, "hash")?
+                .ok_or_else(|| FixtureError::InvalidField(String::from("hash")))?;
+            let expected_logs = optional_hash(post, "logs")?
+                .ok_or_else(|| FixtureError::InvalidField(String::from("logs")))?;
+            let expect_exception = post.get("expectException").and_then(Value::as_str);
+
+            let mut state = alloc_state(&pre);
+            let result = execute_transaction(
+                &mut state,
+                &header,
+                &TestBlockHashes,
+                fork,
+                U256::from(CHAIN_ID),
+                &tx,
+                header.gas_limit(),
+                ChainSpec::mainnet().blob_params(fork),
+            );
+
+            let status = match (result, expect_exception) {
+                (Ok(_), Some(exception)) => {
+                    CaseStatus::Failed(format!("expected exception {}", exception))
+                }
+                (Err(err), None) => CaseStatus::Failed(format!("unexpected exception {:?}", err)),
+                (result, _) => {
+                    let logs = result
+                        .map(|outcome| logs_hash(outcome.logs()))
+                        .unwrap_or_else(|_| logs_hash(&[]));
+                    let root = state.state_root();
+                    if root != expected_root {
+                        CaseStatus::Failed(format!(
+                            "state root {:?}, expected {:?}",
+                            root, expected_root
+                        ))
+                    } else if logs != expected_logs {
+                        CaseStatus::Failed(format!(
+                            "logs hash {:?}, expected {:?}",
+                            logs, expected_logs
+                        ))
+                    } else {
+                        CaseStatus::Passed
+                    }
+                }
+            };
+            results.push(CaseResult::new(case, status));
+        }
+    }
+
+    Ok(results)
+}
+
+/// Returns the header of the block described by the `env` of a fixture under `fork`.
+fn parse_env(env: &Map<String, Value>, fork: Fork) -> Result<BlockHeader, FixtureError> {
+    let number = |name: &str| -> Result<U256, FixtureError> {
+        Ok(optional_number(env, name)?.unwrap_or_default())
+    };
+
+    let mut header = BlockHeader::default();
+    let coinbase = field(env, "currentCoinbase")?
+        .as_str()
+        .ok_or_else(|| FixtureError::InvalidField(String::from("currentCoinbase")))?;
+    header.set_beneficiary(parse_address(coinbase, "currentCoinbase")?);
+    header.set_difficulty(number("currentDifficulty")?);
+    header.set_number(number("currentNumber")?);
+    header.set_gas_limit(number("currentGasLimit")?);
+    header.set_timestamp(number("currentTimestamp")?);
+    header.set_parent_hash(optional_hash(env, "previousHash")?.unwrap_or_default());
+
+    if fork >= Fork::London {
+        let base_fee = optional_number(env, "currentBaseFee")?;
+        header.set_base_fee_per_gas(Some(
+            base_fee.unwrap_or_else(|| U256::from(DEFAULT_BASE_FEE)),
+        ));
+    }
+    if fork >= Fork::Paris {
+        header.set_mix_hash(optional_hash(env, "currentRandom")?.unwrap_or_default());
+    }
+    if fork >= Fork::Cancun {
+        header.set_excess_blob_gas(Some(number("currentExcessBlobGas")?));
+        header.set_blob_gas_used(Some(U256::zero()));
+        header.set_parent_beacon_block_root(Some(
+            optional_hash(env, "currentBeaconRoot")?.unwrap_or_default(),
+        ));
+    }
+
+    Ok(header)
+}
+
+/// Returns the signed transaction of a fixture with the data, gas limit and value at the given
+/// indexes.
+fn parse_transaction(
+    transaction: &Map<String, Value>,
+    data: usize,
+    gas: usize,
+    value: usize,
+) -> Result<Transaction, FixtureError> {
+    let at = |name: &str, index: usize| -> Result<&Value, FixtureError> {
+        field(transaction, name)?
+            .as_array()
+            .and_then(|values| values.get(index))
+            .ok_or_else(|| FixtureError::InvalidField(String::from(name)))
+    };
+    let input = match at("data", data)? {
+        Value::String(s) => parse_bytes(s, "data")?,
+        _ => return Err(FixtureError::InvalidField(String::from("data"))),
+    };
+    let gas_limit = parse_number(at("gasLimit", gas)?, "gasLimit")?;
+    let value = parse_number(at("value", value)?, "value")?;
+
+    let to = match transaction.get("to").and_then(Value::as_str) {
+        None | Some("") => None,
+        Some(to) => Some(parse_address(to, "to")?),
+    };
+    let nonce = optional_number(transaction, "nonce")?.unwrap_or_default();
+
+    let access_list = match transaction.get("accessLists") {
+        Some(lists) => match lists.as_array().and_then(|lists| lists.get(data)) {
+            None | Some(Value::Null) => None,
+            Some(list) => Some(parse_access_list(list)?),
+        },
+        None => None,
+    };
+
+    let tx_type = if transaction.contains_key("blobVersionedHashes") {
+        TransactionType::Blob
+    } else if transaction.contains_key("maxFeePerGas") {
+        TransactionType::DynamicFee
+    } else if access_list.is_some() {
+        TransactionType::AccessList
+    } else {
+        TransactionType::Legacy
+    };
+    let chain_id = match tx_type {
+        TransactionType::Legacy => None,
+        _ => Some(U256::from(CHAIN_ID)),
+    };
+
+    let mut tx = Transaction::new(tx_type, chain_id, nonce, gas_limit, to, value, input);
+    match tx_type {
+        TransactionType::Legacy | TransactionType::AccessList => {
+            tx.set_gas_price(optional_number(transaction, "gasPrice")?.unwrap_or_default());
+        }
+        TransactionType::DynamicFee | TransactionType::Blob => {
+            tx.set_gas_price(optional_number(transaction, "maxFeePerGas")?.unwrap_or_default());
+            tx.set_max_priority_fee_per_gas(
+                optional_number(transaction, "maxPriorityFeePerGas")?.unwrap_or_default(),
+            );
+        }
+    }
+    if let Some(access_list) = access_list {
+        tx.set_access_list(access_list);
+    }
+    if tx_type == TransactionType::Blob {
+        tx.set_max_fee_per_blob_gas(
+            optional_number(transaction, "maxFeePerBlobGas")?.unwrap_or_default(),
+        );
+        let hashes = field(transaction, "blobVersionedHashes")?
+            .as_array()
+            .ok_or_else(|| FixtureError::InvalidField(String::from("blobVersionedHashes")))?;
+        tx.set_blob_versioned_hashes(parse_hashes(hashes, "blobVersionedHashes")?);
+    }
+
+    let secret = optional_bytes(transaction, "secretKey")?
+        .filter(|secret| secret.len() == H256::len_bytes())
+        .ok_or_else(|| FixtureError::InvalidField(String::from("secretKey")))?;
+    tx.sign(&H256::from_slice(&secret));
+    Ok(tx)
+}
+
+/// Returns the entries of an access list of a fixture.
+fn parse_access_list(list: &Value) -> Result<Vec<AccessListItem>, FixtureError> {
+    let invalid = || FixtureError::InvalidField(String::from("accessLists"));
+    let items = list.as_array().ok_or_else(invalid)?;
+    items
+        .iter()
+        .map(|item| {
+            let item = item.as_object().ok_or_else(invalid)?;
+            let address = field(item, "address")?.as_str().ok_or_else(invalid)?;
+            let keys = field(item, "storageKeys")?.as_array().ok_or_else(invalid)?;
+            Ok(AccessListItem::new(
+                parse_address(address, "address")?,
+                parse_hashes(keys, "storageKeys")?,
+            ))
+        })
+        .collect()
+}
+
+/// Returns the 32-byte values of `values`, given as numbers or hexadecimal strings.
+fn parse_hashes(values: &[Value], name: &str) -> Result<Vec<H256>, FixtureError> {
+    values
+        .iter()
+        .map(|value| {
+            let number = parse_number(value, name)?;
+            Ok(H256::from_uint(&number))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::state::WorldState;
+    use ethereum_types::Address;
+    use keccak_hash::KECCAK_EMPTY_LIST_RLP;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    fn fixture(fork: &str, hash: H256, exception: Option<&str>) -> Value {
+        let gas = exception.is_some() as u8;
+        let exception = exception
+            .map(|exception| format!(r#", "expectException": "{}""#, exception))
+            .unwrap_or_default();
+        let json = format!(
+            r#"{{
+                "env": {{
+                    "currentCoinbase": "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba",
+                    "currentDifficulty": "0x020000",
+                    "currentGasLimit": "0x05f5e100",
+                    "currentNumber": "0x01",
+                    "currentTimestamp": "0x03e8",
+                    "previousHash": "0x5e20a0453cecd065ea59c37ac63e079ee08998b6045136a8ce6635c7912ec0b6"
+                }},
+                "pre": {{
+                    "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b": {{
+                        "balance": "0x0de0b6b3a7640000",
+                        "code": "0x",
+                        "nonce": "0x00",
+                        "storage": {{}}
+                    }}
+                }},
+                "transaction": {{
+                    "data": ["0x"],
+                    "gasLimit": ["0x5208", "0x5207"],
+                    "gasPrice": "0x0a",
+                    "nonce": "0x00",
+                    "secretKey": "0x45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8",
+                    "to": "0x095e7baea6a6c7c4c2dfeb977efac326af552d87",
+                    "value": ["0x03e8"]
+                }},
+                "post": {{
+                    "{}": [{{
+                        "hash": "{:?}",
+                        "logs": "{:?}",
+                        "indexes": {{ "data": 0, "gas": {}, "value": 0 }}
+                        {}
+                    }}]
+                }}
+            }}"#,
+            fork, hash, KECCAK_EMPTY_LIST_RLP, gas, exception,
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn expected_root() -> H256 {
+        let sender = Address::from_str("a94f5374fce5edbc8e2a8697c15331677e6ebf0b").unwrap();
+        let mut state = WorldState::new();
+        let fee = U256::from(21_000 * 10);
+        state.insert_account(
+            sender,
+            U256::one(),
+            U256::exp10(18) - 1000 - fee,
+            Vec::new(),
+            None,
+        );
+        state.insert_account(
+            Address::from_str("095e7baea6a6c7c4c2dfeb977efac326af552d87").unwrap(),
+            U256::zero(),
+            U256::from(1000),
+            Vec::new(),
+            None,
+        );
+        state.insert_account(
+            Address::from_str("2adc25665018aa1fe0e6bc666dac8fc2697ff9ba").unwrap(),
+            U256::zero(),
+            fee,
+            Vec::new(),
+            None,
+        );
+        state.state_root()
+    }
+
+    #[test]
+    fn test_run_state_test() {
+        let results =
+            run_state_test("transfer", &fixture("Berlin", expected_root(), None)).unwrap();
+        assert_eq!(
+            results,
+            vec![CaseResult::new(
+                String::from("transfer/Berlin/d0g0v0"),
+                CaseStatus::Passed
+            )]
+        );
+
+        let results = run_state_test("transfer", &fixture("Berlin", H256::zero(), None)).unwrap();
+        assert!(results[0].is_failure());
+
+        let results = run_state_test("transfer", &fixture("Prague", H256::zero(), None)).unwrap();
+        assert!(matches!(results[0].status(), CaseStatus::Skipped(_)));
+    }
+
+    #[test]
+    fn test_run_state_test_exception() {
+        // The transaction at gas index 1 cannot cover its intrinsic gas, leaving the state as is.
+        let pre = fixture("Berlin", H256::zero(), None);
+        let pre = parse_alloc(&pre["pre"]).unwrap();
+        let root = alloc_state(&pre).state_root();
+
+        let test = fixture("Berlin", root, Some("TR_IntrinsicGas"));
+        let results = run_state_test("intrinsic", &test).unwrap();
+        assert_eq!(results[0].status(), &CaseStatus::Passed);
+    }
+
+    /// Runs the `GeneralStateTests` of the ethereum/tests checkout at `$ETHEREUM_TESTS`.
+    #[test]
+    #[ignore = "requires the ethereum/tests fixtures"]
+    fn test_general_state_tests() {
+        let dir = std::env::var("ETHEREUM_TESTS").expect("ETHEREUM_TESTS is not set");
+        let results = run_state_tests(&PathBuf::from(dir).join("GeneralStateTests")).unwrap();
+        let failures: Vec<_> = results
+            .iter()
+            .filter(|result| result.is_failure())
+            .collect();
+        assert!(failures.is_empty(), "{:#?}", failures);
+    }
+}
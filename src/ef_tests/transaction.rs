@@ -0,0 +1,218 @@
+use super::{field, fixture_files, object_field, parse_fork, read_fixture};
+use super::{CaseResult, CaseStatus, FixtureError};
+use crate::core::executor::transaction::validate_intrinsic;
+use crate::core::fork::Fork;
+use crate::core::genesis::{optional_hash, optional_number, parse_address, parse_bytes};
+use crate::core::spec::ChainSpec;
+use crate::core::types::transaction::Transaction;
+use ethereum_types::{Address, U256};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// Chain identifier of the fixtures.
+pub const CHAIN_ID: u64 = 1;
+
+/// Runs the transaction test fixtures under `dir`, such as the `TransactionTests` directory of
+/// ethereum/tests.
+pub fn run_transaction_tests(dir: &Path) -> Result<Vec<CaseResult>, FixtureError> {
+    let mut results = Vec::new();
+    for path in fixture_files(dir)? {
+        for (name, test) in read_fixture(&path)? {
+            results.extend(run_transaction_test(&name, &test)?);
+        }
+    }
+    Ok(results)
+}
+
+/// Runs the transaction test with `name`: the result expected under each fork is a case.
+///
+/// The transaction must decode, round-trip to the same bytes and pass the checks of
+/// `validate_intrinsic` unless the fork expects an exception, in which case it must fail any of
+/// them.
+pub fn run_transaction_test(name: &str, test: &Value) -> Result<Vec<CaseResult>, FixtureError> {
+    let test = test
+        .as_object()
+        .ok_or_else(|| FixtureError::InvalidField(String::from(name)))?;
+    let txbytes = field(test, "txbytes")?
+        .as_str()
+        .ok_or_else(|| FixtureError::InvalidField(String::from("txbytes")))?;
+    // Some invalid transactions are not even valid hexadecimal.
+    let tx = parse_bytes(txbytes, "txbytes")
+        .map_err(|err| format!("{:?}", err))
+        .and_then(|bytes| decode(&bytes));
+
+    let mut results = Vec::new();
+    for (fork_name, expected) in object_field(test, "result")? {
+        let case = format!("{}/{}", name, fork_name);
+        let fork = match parse_fork(fork_name) {
+            Some(fork) => fork,
+            None => {
+                let reason = format!("unsupported fork {}", fork_name);
+                results.push(CaseResult::new(case, CaseStatus::Skipped(reason)));
+                continue;
+            }
+        };
+        let expected = expected
+            .as_object()
+            .ok_or_else(|| FixtureError::InvalidField(String::from("result")))?;
+        let expect_exception = expected.get("exception").and_then(Value::as_str);
+
+        let result = tx.clone().and_then(|tx| {
+            let blob_params = ChainSpec::mainnet().blob_params(fork);
+            validate_intrinsic(&tx, fork, U256::from(CHAIN_ID), blob_params)
+                .map(|sender| (tx, sender))
+                .map_err(|err| format!("{:?}", err))
+        });
+        let status = match (result, expect_exception) {
+            (Ok(_), Some(exception)) => {
+                CaseStatus::Failed(format!("expected exception {}", exception))
+            }
+            (Err(err), None) => CaseStatus::Failed(format!("unexpected exception {}", err)),
+            (Err(_), Some(_)) => CaseStatus::Passed,
+            (Ok((tx, sender)), None) => check_result(expected, &tx, sender, fork)?,
+        };
+        results.push(CaseResult::new(case, status));
+    }
+
+    Ok(results)
+}
+
+/// Decodes a transaction, rejecting encodings other than its canonical one.
+fn decode(bytes: &[u8]) -> Result<Transaction, String> {
+    let tx = Transaction::decode(bytes).map_err(|err| format!("{:?}", err))?;
+    if tx.encode() != bytes {
+        return Err(String::from("non-canonical encoding"));
+    }
+    Ok(tx)
+}
+
+/// Compares the hash, sender and intrinsic gas of a valid transaction with the expectations.
+fn check_result(
+    expected: &Map<String, Value>,
+    tx: &Transaction,
+    sender: Address,
+    fork: Fork,
+) -> Result<CaseStatus, FixtureError> {
+    let expected_hash = optional_hash(expected, "hash")?;
+    let expected_sender = match expected.get("sender").and_then(Value::as_str) {
+        Some(address) => Some(parse_address(address, "sender")?),
+        None => None,
+    };
+    let expected_gas = optional_number(expected, "intrinsicGas")?;
+
+    let hash = tx.hash();
+    let gas = tx.intrinsic_gas(fork);
+    let status = if expected_hash.is_some_and(|expected| expected != hash) {
+        CaseStatus::Failed(format!("hash {:?}, expected {:?}", hash, expected_hash))
+    } else if expected_sender.is_some_and(|expected| expected != sender) {
+        CaseStatus::Failed(format!(
+            "sender {:?}, expected {:?}",
+            sender, expected_sender
+        ))
+    } else if expected_gas.is_some_and(|expected| expected != gas) {
+        CaseStatus::Failed(format!(
+            "intrinsic gas {}, expected {:?}",
+            gas, expected_gas
+        ))
+    } else {
+        CaseStatus::Passed
+    };
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::transaction::TransactionType;
+    use ethereum_types::H256;
+    use std::path::PathBuf;
+
+    fn signed_transfer() -> Transaction {
+        let mut tx = Transaction::new(
+            TransactionType::Legacy,
+            Some(U256::from(CHAIN_ID)),
+            U256::zero(),
+            U256::from(21_000),
+            Some(Address::repeat_byte(0x0b)),
+            U256::one(),
+            Vec::new(),
+        );
+        tx.set_gas_price(U256::from(10));
+        tx.sign(&H256::from_low_u64_be(1));
+        tx
+    }
+
+    fn statuses(test: &Value) -> Vec<(String, CaseStatus)> {
+        run_transaction_test("test", test)
+            .unwrap()
+            .into_iter()
+            .map(|result| (String::from(result.name()), result.status().clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_run_transaction_test() {
+        let tx = signed_transfer();
+        let test = serde_json::json!({
+            "txbytes": format!("0x{}", hex::encode(tx.encode())),
+            "result": {
+                // EIP-155 signatures are only valid since Spurious Dragon.
+                "Frontier": { "exception": "TR_ChainId" },
+                "Berlin": {
+                    "hash": format!("{:?}", tx.hash()),
+                    "intrinsicGas": "0x5208",
+                    "sender": format!("{:?}", tx.sender().unwrap()),
+                },
+                "Prague": { "intrinsicGas": "0x5208" },
+            },
+        });
+        assert_eq!(
+            statuses(&test),
+            vec![
+                (String::from("test/Berlin"), CaseStatus::Passed),
+                (String::from("test/Frontier"), CaseStatus::Passed),
+                (
+                    String::from("test/Prague"),
+                    CaseStatus::Skipped(String::from("unsupported fork Prague"))
+                ),
+            ]
+        );
+
+        let test = serde_json::json!({
+            "txbytes": format!("0x{}", hex::encode(tx.encode())),
+            "result": { "Berlin": { "intrinsicGas": "0x5209" } },
+        });
+        assert!(matches!(statuses(&test)[0].1, CaseStatus::Failed(_)));
+    }
+
+    #[test]
+    fn test_run_transaction_test_invalid() {
+        // Trailing bytes after the transaction.
+        let mut bytes = signed_transfer().encode();
+        bytes.push(0);
+        let test = serde_json::json!({
+            "txbytes": format!("0x{}", hex::encode(&bytes)),
+            "result": { "Berlin": { "exception": "TR_RLP" } },
+        });
+        assert_eq!(statuses(&test)[0].1, CaseStatus::Passed);
+
+        let test = serde_json::json!({
+            "txbytes": "0xzz",
+            "result": { "Berlin": { "hash": "0x00" } },
+        });
+        assert!(matches!(statuses(&test)[0].1, CaseStatus::Failed(_)));
+    }
+
+    /// Runs the `TransactionTests` of the ethereum/tests checkout at `$ETHEREUM_TESTS`.
+    #[test]
+    #[ignore = "requires the ethereum/tests fixtures"]
+    fn test_transaction_tests() {
+        let dir = std::env::var("ETHEREUM_TESTS").expect("ETHEREUM_TESTS is not set");
+        let results = run_transaction_tests(&PathBuf::from(dir).join("TransactionTests")).unwrap();
+        let failures: Vec<_> = results
+            .iter()
+            .filter(|result| result.is_failure())
+            .collect();
+        assert!(failures.is_empty(), "{:#?}", failures);
+    }
+}
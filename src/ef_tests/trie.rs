@@ -0,0 +1,166 @@
+use super::{field, fixture_files, read_fixture, CaseResult, CaseStatus, FixtureError};
+use crate::core::genesis::{optional_hash, parse_bytes};
+use crate::core::trie::{sec_trie_root, trie_root};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Runs the trie test fixtures under `dir`, such as the `TrieTests` directory of
+/// ethereum/tests. The keys of the fixtures with `secure` in their file name are hashed.
+pub fn run_trie_tests(dir: &Path) -> Result<Vec<CaseResult>, FixtureError> {
+    let mut results = Vec::new();
+    for path in fixture_files(dir)? {
+        let secure = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.to_lowercase().contains("secure"));
+        for (name, test) in read_fixture(&path)? {
+            results.push(run_trie_test(&name, &test, secure)?);
+        }
+    }
+    Ok(results)
+}
+
+/// Runs the trie test with `name`, hashing the keys if `secure`.
+///
+/// The input is either a list of key-value pairs, inserted in order, or an object mapping keys
+/// to values. A null or empty value deletes the key. Keys and values are hexadecimal if they
+/// start with `0x`, and are taken as raw bytes otherwise.
+pub fn run_trie_test(name: &str, test: &Value, secure: bool) -> Result<CaseResult, FixtureError> {
+    let test = test
+        .as_object()
+        .ok_or_else(|| FixtureError::InvalidField(String::from(name)))?;
+    let expected = optional_hash(test, "root")?
+        .ok_or_else(|| FixtureError::InvalidField(String::from("root")))?;
+
+    let invalid = || FixtureError::InvalidField(String::from("in"));
+    let pairs = match field(test, "in")? {
+        Value::Array(pairs) => pairs
+            .iter()
+            .map(|pair| match pair.as_array().map(Vec::as_slice) {
+                Some([key, value]) => {
+                    Ok((parse_item(key)?.ok_or_else(invalid)?, parse_item(value)?))
+                }
+                _ => Err(invalid()),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Value::Object(pairs) => pairs
+            .iter()
+            .map(|(key, value)| Ok((parse_string(key)?, parse_item(value)?)))
+            .collect::<Result<Vec<_>, FixtureError>>()?,
+        _ => return Err(invalid()),
+    };
+
+    let mut items = BTreeMap::new();
+    for (key, value) in pairs {
+        match value {
+            Some(value) if !value.is_empty() => {
+                items.insert(key, value);
+            }
+            _ => {
+                items.remove(&key);
+            }
+        }
+    }
+
+    let root = if secure {
+        sec_trie_root(items)
+    } else {
+        trie_root(items)
+    };
+    let status = if root == expected {
+        CaseStatus::Passed
+    } else {
+        CaseStatus::Failed(format!("root {:?}, expected {:?}", root, expected))
+    };
+    Ok(CaseResult::new(String::from(name), status))
+}
+
+/// Returns the bytes of a key or value of a trie fixture, or `None` if it is null.
+fn parse_item(value: &Value) -> Result<Option<Vec<u8>>, FixtureError> {
+    match value {
+        Value::Null => Ok(None),
+        Value::String(s) => parse_string(s).map(Some),
+        _ => Err(FixtureError::InvalidField(String::from("in"))),
+    }
+}
+
+/// Returns the bytes of a string of a trie fixture.
+fn parse_string(s: &str) -> Result<Vec<u8>, FixtureError> {
+    if s.starts_with("0x") {
+        Ok(parse_bytes(s, "in")?)
+    } else {
+        Ok(s.as_bytes().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keccak_hash::keccak;
+    use std::path::PathBuf;
+
+    fn run(test: Value, secure: bool) -> CaseStatus {
+        run_trie_test("test", &test, secure)
+            .unwrap()
+            .status()
+            .clone()
+    }
+
+    #[test]
+    fn test_run_trie_test() {
+        // The `emptyValues` test of `TrieTests/trietest.json`.
+        let expected = "0x5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84";
+        let test = serde_json::json!({
+            "in": [
+                ["do", "verb"],
+                ["ether", "wookiedoo"],
+                ["horse", "stallion"],
+                ["shaman", "horse"],
+                ["doge", "coin"],
+                ["ether", null],
+                ["dog", "puppy"],
+                ["shaman", null],
+            ],
+            "root": expected,
+        });
+        assert_eq!(run(test, false), CaseStatus::Passed);
+
+        let test = serde_json::json!({
+            "in": {
+                "0x646f67": "puppy",
+                "do": "0x76657262",
+                "doge": "coin",
+                "horse": "stallion",
+            },
+            "root": expected,
+        });
+        assert_eq!(run(test, false), CaseStatus::Passed);
+
+        let test = serde_json::json!({
+            "in": [["doe", "reindeer"], ["dog", "puppy"], ["dogglesworth", "cat"]],
+            "root": "0x8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3",
+        });
+        assert_eq!(run(test, false), CaseStatus::Passed);
+
+        let test = serde_json::json!({
+            "in": { "do": "verb", "dog": "puppy" },
+            "root": format!("{:?}", trie_root(vec![(keccak("do"), "verb"), (keccak("dog"), "puppy")])),
+        });
+        assert_eq!(run(test.clone(), true), CaseStatus::Passed);
+        assert!(matches!(run(test, false), CaseStatus::Failed(_)));
+    }
+
+    /// Runs the `TrieTests` of the ethereum/tests checkout at `$ETHEREUM_TESTS`.
+    #[test]
+    #[ignore = "requires the ethereum/tests fixtures"]
+    fn test_trie_tests() {
+        let dir = std::env::var("ETHEREUM_TESTS").expect("ETHEREUM_TESTS is not set");
+        let results = run_trie_tests(&PathBuf::from(dir).join("TrieTests")).unwrap();
+        let failures: Vec<_> = results
+            .iter()
+            .filter(|result| result.is_failure())
+            .collect();
+        assert!(failures.is_empty(), "{:#?}", failures);
+    }
+}
@@ -1 +1,2 @@
 pub mod core;
+pub mod ef_tests;